@@ -0,0 +1,42 @@
+// CPU cost of building pipelines and recording frames, to catch regressions
+// in either. Run with `cargo bench`; everything renders offscreen, so no
+// display is needed.
+use std::time::Duration;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use learn_wgpu::HeadlessRenderer;
+
+const WIDTH: u32 = 800;
+const HEIGHT: u32 = 600;
+
+fn headless() -> HeadlessRenderer {
+    HeadlessRenderer::new(WIDTH, HEIGHT)
+        .expect("No adapter for the benchmarks, not even a software one")
+}
+
+fn pipeline_creation(c: &mut Criterion) {
+    let headless = headless();
+    c.bench_function("create_scene_pipeline", |b| {
+        b.iter(|| headless.create_scene_pipeline())
+    });
+}
+
+// Only the time spent recording commands counts. Each frame is submitted
+// and waited on outside the measurement, so GPU execution (which is what a
+// software adapter is slowest at) doesn't leak into the numbers.
+fn frame_encoding(c: &mut Criterion) {
+    let mut headless = headless();
+    c.bench_function("encode_frame", |b| {
+        b.iter_custom(|iterations| {
+            let mut total = Duration::ZERO;
+            for _ in 0..iterations {
+                headless.update();
+                total += headless.render_frame().unwrap();
+            }
+            total
+        })
+    });
+}
+
+criterion_group!(benches, pipeline_creation, frame_encoding);
+criterion_main!(benches);
@@ -0,0 +1,30 @@
+// Writes assets/wide-circles.png, a 2:1 image of circles on a grid for
+// checking textures that aren't square show up unstretched: any stretching
+// turns the circles into ellipses. Point config.toml's diffuse_texture at it
+// to try it on the pentagon.
+//
+//     cargo run --example write_aspect_test_texture
+fn main() -> anyhow::Result<()> {
+    const PATH: &str = "assets/wide-circles.png";
+    const WIDTH: u32 = 512;
+    const HEIGHT: u32 = 256;
+    // Side of each grid cell, with a circle in the middle
+    const CELL: u32 = 64;
+
+    let image = image::RgbaImage::from_fn(WIDTH, HEIGHT, |x, y| {
+        let (column, row) = (x / CELL, y / CELL);
+        let dx = (x % CELL) as f32 + 0.5 - CELL as f32 / 2.0;
+        let dy = (y % CELL) as f32 + 0.5 - CELL as f32 / 2.0;
+        let inside = dx * dx + dy * dy < (CELL as f32 * 0.4).powi(2);
+        // Shade across the width so the crop is easy to see too
+        let red = (255 * x / WIDTH) as u8;
+        match (inside, (column + row).is_multiple_of(2)) {
+            (true, _) => image::Rgba([red, 200, 255 - red, 255]),
+            (false, true) => image::Rgba([240, 240, 240, 255]),
+            (false, false) => image::Rgba([200, 200, 200, 255]),
+        }
+    });
+    image.save(PATH)?;
+    println!("Wrote {} ({}x{})", PATH, WIDTH, HEIGHT);
+    Ok(())
+}
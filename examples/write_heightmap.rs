@@ -0,0 +1,34 @@
+// Writes assets/heightmap.png, a 16-bit grayscale heightmap of a few rounded
+// hills and a valley for heightmap::Terrain to build the Shift+T terrain
+// from. Any grayscale PNG can take its place.
+//
+//     cargo run --example write_heightmap
+fn main() -> anyhow::Result<()> {
+    const PATH: &str = "assets/heightmap.png";
+    const SIZE: u32 = 128;
+    // (center x, center y, radius, height), in 0..1 across the image
+    const HILLS: &[(f32, f32, f32, f32)] = &[
+        (0.3, 0.35, 0.25, 0.9),
+        (0.7, 0.3, 0.2, 0.6),
+        (0.55, 0.75, 0.3, 0.75),
+        (0.15, 0.8, 0.15, 0.4),
+    ];
+
+    let image = image::ImageBuffer::from_fn(SIZE, SIZE, |x, y| {
+        let u = x as f32 / (SIZE - 1) as f32;
+        let v = y as f32 / (SIZE - 1) as f32;
+        let height = HILLS
+            .iter()
+            .map(|&(cx, cy, radius, height)| {
+                let d = ((u - cx).powi(2) + (v - cy).powi(2)).sqrt() / radius;
+                // Smoothstep falloff, flat on top and at the foot
+                let t = (1.0 - d).clamp(0.0, 1.0);
+                height * t * t * (3.0 - 2.0 * t)
+            })
+            .fold(0.0f32, f32::max);
+        image::Luma([(height.min(1.0) * u16::MAX as f32) as u16])
+    });
+    image.save(PATH)?;
+    println!("Wrote {} ({}x{})", PATH, SIZE, SIZE);
+    Ok(())
+}
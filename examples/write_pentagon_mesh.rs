@@ -0,0 +1,24 @@
+// Regenerates assets/pentagon.mesh from the pentagon consts in geometry.rs
+// and reads it back to check it round-trips:
+//
+//     cargo run --example write_pentagon_mesh
+fn main() -> anyhow::Result<()> {
+    const PATH: &str = "assets/pentagon.mesh";
+
+    learn_wgpu::write_mesh(PATH, learn_wgpu::VERTICES, learn_wgpu::INDICES)?;
+
+    let mesh = learn_wgpu::load_mesh(PATH)?;
+    anyhow::ensure!(
+        mesh.vertices.len() == learn_wgpu::VERTICES.len()
+            && mesh.indices == learn_wgpu::INDICES,
+        "{} didn't read back the same as it was written",
+        PATH
+    );
+    println!(
+        "Wrote {} ({} vertices, {} indices)",
+        PATH,
+        mesh.vertices.len(),
+        mesh.indices.len()
+    );
+    Ok(())
+}
@@ -0,0 +1,230 @@
+// Writes src/font.png and src/font.json, the signed distance field atlas and
+// glyph metrics that sdf_text draws the overlay text and world labels with.
+// The glyphs are printable ASCII in Hack, the monospace font egui ships
+// with. The metrics follow msdf-atlas-gen's JSON layout, so an atlas made
+// with it (see sdf_text.rs) can replace these without code changes.
+//
+//     cargo run --release --example write_sdf_font
+use ab_glyph::{Font, FontRef, ScaleFont};
+
+const ATLAS_PATH: &str = "src/font.png";
+const METRICS_PATH: &str = "src/font.json";
+// Atlas pixels per em
+const EM_SIZE: f32 = 40.0;
+// Distance covered by the field's full 0..1 range, in atlas pixels. The
+// field is clamped beyond half of it either side of an edge.
+const DISTANCE_RANGE: f32 = 6.0;
+// Glyphs are rasterized this many times larger than the atlas, so distances
+// are measured to a finer edge than one atlas pixel
+const SUPERSAMPLE: u32 = 4;
+const ATLAS_WIDTH: u32 = 512;
+// Free atlas pixels around each glyph's field, so bilinear filtering never
+// reads a neighbour
+const GAP: u32 = 1;
+
+// A glyph's field, and where it sits relative to the pen in atlas pixels
+// (y down)
+struct Field {
+    unicode: u32,
+    advance: f32,
+    left: i32,
+    top: i32,
+    width: u32,
+    height: u32,
+    values: Vec<u8>,
+}
+
+fn main() -> anyhow::Result<()> {
+    let fonts = egui::FontDefinitions::default();
+    let data = &fonts.font_data["Hack"];
+    let font = FontRef::try_from_slice(&data.font)?;
+    let units_per_em = font.units_per_em().unwrap_or(1000.0);
+    let scale_for = |px_per_em: f32| {
+        ab_glyph::PxScale::from(
+            px_per_em * font.height_unscaled() / units_per_em,
+        )
+    };
+    let atlas_font = font.as_scaled(scale_for(EM_SIZE));
+    let fine_font = font.as_scaled(scale_for(EM_SIZE * SUPERSAMPLE as f32));
+
+    let fields: Vec<Field> = (' '..='~')
+        .map(|c| {
+            let id = font.glyph_id(c);
+            let advance = atlas_font.h_advance(id) / EM_SIZE;
+            distance_field(&fine_font, id, c as u32, advance)
+        })
+        .collect();
+
+    // Shelf packing in rows, left to right
+    let mut placements = vec![];
+    let (mut x, mut y, mut row_height) = (GAP, GAP, 0);
+    for field in &fields {
+        if x + field.width + GAP > ATLAS_WIDTH {
+            x = GAP;
+            y += row_height + GAP;
+            row_height = 0;
+        }
+        placements.push((x, y));
+        x += field.width + GAP;
+        row_height = row_height.max(field.height);
+    }
+    let atlas_height = y + row_height + GAP;
+
+    let mut atlas = image::GrayImage::new(ATLAS_WIDTH, atlas_height);
+    let mut glyphs = vec![];
+    for (field, &(x, y)) in fields.iter().zip(&placements) {
+        let mut glyph = serde_json::json!({
+            "unicode": field.unicode,
+            "advance": field.advance,
+        });
+        if field.width > 0 {
+            for (i, &value) in field.values.iter().enumerate() {
+                let i = i as u32;
+                atlas.put_pixel(
+                    x + i % field.width,
+                    y + i / field.width,
+                    image::Luma([value]),
+                );
+            }
+            let left = field.left as f32 / EM_SIZE;
+            let top = -field.top as f32 / EM_SIZE;
+            glyph["planeBounds"] = serde_json::json!({
+                "left": left,
+                "bottom": top - field.height as f32 / EM_SIZE,
+                "right": left + field.width as f32 / EM_SIZE,
+                "top": top,
+            });
+            // Counted from the atlas' bottom edge, see "yOrigin"
+            glyph["atlasBounds"] = serde_json::json!({
+                "left": x,
+                "bottom": atlas_height - y - field.height,
+                "right": x + field.width,
+                "top": atlas_height - y,
+            });
+        }
+        glyphs.push(glyph);
+    }
+
+    let metrics = serde_json::json!({
+        "atlas": {
+            "type": "sdf",
+            "distanceRange": DISTANCE_RANGE,
+            "size": EM_SIZE,
+            "width": ATLAS_WIDTH,
+            "height": atlas_height,
+            "yOrigin": "bottom",
+        },
+        "metrics": {
+            "emSize": 1,
+            "lineHeight": (atlas_font.height() + atlas_font.line_gap())
+                / EM_SIZE,
+            "ascender": atlas_font.ascent() / EM_SIZE,
+            "descender": atlas_font.descent() / EM_SIZE,
+        },
+        "glyphs": glyphs,
+    });
+
+    atlas.save(ATLAS_PATH)?;
+    std::fs::write(METRICS_PATH, serde_json::to_string_pretty(&metrics)?)?;
+    println!(
+        "Wrote {} ({}x{}) and {} ({} glyphs)",
+        ATLAS_PATH,
+        ATLAS_WIDTH,
+        atlas_height,
+        METRICS_PATH,
+        fields.len()
+    );
+    Ok(())
+}
+
+// Rasterizes the glyph at SUPERSAMPLE times the atlas size, then measures
+// each atlas pixel's distance to the nearest fine pixel on the other side of
+// the edge. Blank glyphs (space) get an advance and nothing else.
+fn distance_field(
+    font: &ab_glyph::PxScaleFont<&FontRef>,
+    id: ab_glyph::GlyphId,
+    unicode: u32,
+    advance: f32,
+) -> Field {
+    let blank = Field {
+        unicode,
+        advance,
+        left: 0,
+        top: 0,
+        width: 0,
+        height: 0,
+        values: vec![],
+    };
+    let Some(outline) = font.outline_glyph(id.with_scale(font.scale())) else {
+        return blank;
+    };
+
+    let ss = SUPERSAMPLE as i32;
+    let pad = (DISTANCE_RANGE / 2.0).ceil() as i32 + 1;
+    let bounds = outline.px_bounds();
+    // The field's corner in atlas pixels, snapped so fine pixels line up
+    // with atlas ones
+    let left = (bounds.min.x as i32).div_euclid(ss) - pad;
+    let top = (bounds.min.y as i32).div_euclid(ss) - pad;
+    let width = (bounds.max.x as i32 + ss - 1).div_euclid(ss) + pad - left;
+    let height = (bounds.max.y as i32 + ss - 1).div_euclid(ss) + pad - top;
+
+    let fine_width = (width * ss) as usize;
+    let fine_height = (height * ss) as usize;
+    let mut inside = vec![false; fine_width * fine_height];
+    let offset_x = bounds.min.x as i32 - left * ss;
+    let offset_y = bounds.min.y as i32 - top * ss;
+    outline.draw(|x, y, coverage| {
+        let x = x as i32 + offset_x;
+        let y = y as i32 + offset_y;
+        inside[y as usize * fine_width + x as usize] = coverage >= 0.5;
+    });
+    if !inside.contains(&true) {
+        return blank;
+    }
+
+    // Anything further than half the range is clamped anyway
+    let reach = (DISTANCE_RANGE / 2.0 * SUPERSAMPLE as f32).ceil() as i32;
+    let mut values = Vec::with_capacity((width * height) as usize);
+    for ay in 0..height {
+        for ax in 0..width {
+            // The atlas pixel's center, in fine pixels
+            let cx = (ax * ss + ss / 2) as f32;
+            let cy = (ay * ss + ss / 2) as f32;
+            let fine = |x: i32, y: i32| {
+                (0..fine_width as i32).contains(&x)
+                    && (0..fine_height as i32).contains(&y)
+                    && inside[y as usize * fine_width + x as usize]
+            };
+            let center_inside = fine(ax * ss + ss / 2, ay * ss + ss / 2);
+
+            let mut nearest = reach as f32;
+            for dy in -reach..=reach {
+                for dx in -reach..=reach {
+                    let x = ax * ss + ss / 2 + dx;
+                    let y = ay * ss + ss / 2 + dy;
+                    if fine(x, y) != center_inside {
+                        let d = ((x as f32 + 0.5 - cx).powi(2)
+                            + (y as f32 + 0.5 - cy).powi(2))
+                        .sqrt();
+                        nearest = nearest.min(d);
+                    }
+                }
+            }
+            let distance = nearest / SUPERSAMPLE as f32;
+            let signed = if center_inside { distance } else { -distance };
+            let value = (0.5 + signed / DISTANCE_RANGE).clamp(0.0, 1.0);
+            values.push((value * 255.0).round() as u8);
+        }
+    }
+
+    Field {
+        unicode,
+        advance,
+        left,
+        top,
+        width: width as u32,
+        height: height as u32,
+        values,
+    }
+}
@@ -0,0 +1,188 @@
+// Picks an internal render scale from recent frame times: when frames keep
+// running over budget the scale steps down, and once they're comfortably
+// under budget again it steps back up toward `max_scale`.
+//
+// Two things keep it from oscillating. There's a dead band between
+// `recover_fraction * target_frame_ms` and the budget where nothing happens,
+// and a change needs several frames in a row on the same side, with more
+// required to scale up than down so a brief dip doesn't immediately undo a
+// drop that was needed.
+//
+// Frame times are CPU side, so with a vsynced present mode they can never go
+// below the refresh interval. Pick a target above that (or a non-vsync
+// present mode) or the scale will never recover.
+pub struct AdaptiveResolution {
+    pub enabled: bool,
+    pub target_frame_ms: f32,
+    pub min_scale: f32,
+    pub max_scale: f32,
+    // How much each adjustment changes the scale by
+    pub step: f32,
+    // Frames must come in under this fraction of the budget to count toward
+    // scaling back up
+    pub recover_fraction: f32,
+    // Consecutive frames needed before scaling down / up
+    pub frames_to_drop: u32,
+    pub frames_to_recover: u32,
+    scale: f32,
+    over_budget_frames: u32,
+    under_budget_frames: u32,
+}
+
+impl AdaptiveResolution {
+    pub fn new(target_frame_ms: f32, min_scale: f32, max_scale: f32) -> Self {
+        Self {
+            enabled: false,
+            target_frame_ms,
+            min_scale,
+            max_scale,
+            step: 0.1,
+            recover_fraction: 0.8,
+            frames_to_drop: 10,
+            frames_to_recover: 60,
+            scale: max_scale,
+            over_budget_frames: 0,
+            under_budget_frames: 0,
+        }
+    }
+
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        self.over_budget_frames = 0;
+        self.under_budget_frames = 0;
+    }
+
+    // Feeds in the latest frame time. Returns the new scale when it changed,
+    // in which case the caller needs to reallocate its render targets.
+    // When disabled the scale snaps back to `max_scale`.
+    pub fn update(&mut self, frame_ms: f32) -> Option<f32> {
+        let scale = if !self.enabled {
+            self.max_scale
+        } else if frame_ms > self.target_frame_ms {
+            self.under_budget_frames = 0;
+            self.over_budget_frames += 1;
+            if self.over_budget_frames < self.frames_to_drop {
+                return None;
+            }
+            self.over_budget_frames = 0;
+            self.scale - self.step
+        } else if frame_ms < self.target_frame_ms * self.recover_fraction {
+            self.over_budget_frames = 0;
+            self.under_budget_frames += 1;
+            if self.under_budget_frames < self.frames_to_recover {
+                return None;
+            }
+            self.under_budget_frames = 0;
+            self.scale + self.step
+        } else {
+            self.over_budget_frames = 0;
+            self.under_budget_frames = 0;
+            return None;
+        };
+
+        let scale = scale.clamp(self.min_scale, self.max_scale);
+        if (scale - self.scale).abs() < f32::EPSILON {
+            return None;
+        }
+        self.scale = scale;
+        Some(scale)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TARGET_MS: f32 = 16.0;
+    const OVER_MS: f32 = 20.0;
+    // Under 0.8 of the budget
+    const UNDER_MS: f32 = 10.0;
+    // Between 0.8 of the budget and the budget
+    const DEAD_BAND_MS: f32 = 14.0;
+
+    // Steps of a quarter, so every scale is exact
+    fn enabled() -> AdaptiveResolution {
+        let mut resolution = AdaptiveResolution::new(TARGET_MS, 0.5, 1.0);
+        resolution.step = 0.25;
+        resolution.set_enabled(true);
+        resolution
+    }
+
+    // The scales `frames` frames of `frame_ms` changed it to, in order
+    fn feed(
+        resolution: &mut AdaptiveResolution,
+        frame_ms: f32,
+        frames: u32,
+    ) -> Vec<f32> {
+        (0..frames)
+            .filter_map(|_| resolution.update(frame_ms))
+            .collect()
+    }
+
+    #[test]
+    fn drops_after_a_run_of_slow_frames() {
+        let mut resolution = enabled();
+        assert!(feed(&mut resolution, OVER_MS, 9).is_empty());
+        assert_eq!(feed(&mut resolution, OVER_MS, 1), [0.75]);
+        // The run starts over after each drop
+        assert_eq!(feed(&mut resolution, OVER_MS, 10), [0.5]);
+    }
+
+    #[test]
+    fn needs_a_longer_run_to_recover() {
+        let mut resolution = enabled();
+        feed(&mut resolution, OVER_MS, 10);
+        assert!(feed(&mut resolution, UNDER_MS, 59).is_empty());
+        assert_eq!(feed(&mut resolution, UNDER_MS, 1), [1.0]);
+    }
+
+    #[test]
+    fn dead_band_and_the_other_side_break_a_run() {
+        let mut resolution = enabled();
+        for interruption in [DEAD_BAND_MS, UNDER_MS] {
+            feed(&mut resolution, OVER_MS, 9);
+            assert!(feed(&mut resolution, interruption, 1).is_empty());
+            assert!(feed(&mut resolution, OVER_MS, 9).is_empty());
+            feed(&mut resolution, DEAD_BAND_MS, 1);
+        }
+        // Frames in the dead band never change anything on their own
+        assert!(feed(&mut resolution, DEAD_BAND_MS, 1000).is_empty());
+        assert_eq!(resolution.scale(), 1.0);
+
+        let mut resolution = enabled();
+        feed(&mut resolution, OVER_MS, 10);
+        feed(&mut resolution, UNDER_MS, 59);
+        assert!(feed(&mut resolution, OVER_MS, 1).is_empty());
+        assert!(feed(&mut resolution, UNDER_MS, 59).is_empty());
+        assert_eq!(resolution.scale(), 0.75);
+    }
+
+    #[test]
+    fn stays_within_min_and_max() {
+        let mut resolution = enabled();
+        assert_eq!(feed(&mut resolution, OVER_MS, 100), [0.75, 0.5]);
+        assert_eq!(resolution.scale(), 0.5);
+        assert_eq!(feed(&mut resolution, UNDER_MS, 1000), [0.75, 1.0]);
+        assert_eq!(resolution.scale(), 1.0);
+    }
+
+    #[test]
+    fn snaps_back_to_max_when_disabled() {
+        let mut resolution = enabled();
+        feed(&mut resolution, OVER_MS, 20);
+        resolution.set_enabled(false);
+        assert_eq!(feed(&mut resolution, OVER_MS, 1), [1.0]);
+        assert!(feed(&mut resolution, OVER_MS, 100).is_empty());
+
+        // Switching back on starts every run over
+        let mut resolution = enabled();
+        feed(&mut resolution, OVER_MS, 9);
+        resolution.set_enabled(false);
+        resolution.set_enabled(true);
+        assert!(feed(&mut resolution, OVER_MS, 9).is_empty());
+    }
+}
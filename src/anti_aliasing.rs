@@ -0,0 +1,50 @@
+// How the scene's edges are smoothed. MSAA takes several coverage samples
+// per pixel while drawing, which is baked into the scene pipelines and
+// targets. FXAA draws with one and then blurs along the edges it finds in
+// the finished image, as the first step of the post chain (see
+// post::PostChain::set_fxaa). Only one is ever on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AaMode {
+    None,
+    // Samples per pixel, always more than 1
+    Msaa(u32),
+    Fxaa,
+}
+
+impl AaMode {
+    // What the scene has to be drawn with
+    pub fn sample_count(self) -> u32 {
+        match self {
+            AaMode::Msaa(samples) => samples,
+            AaMode::None | AaMode::Fxaa => 1,
+        }
+    }
+
+    // None, then each of `sample_counts` above 1 (in increasing order, as
+    // Renderer keeps them), then FXAA and back to None
+    pub fn next(self, sample_counts: &[u32]) -> Self {
+        let msaa_above = |samples: u32| {
+            sample_counts
+                .iter()
+                .copied()
+                .find(|&count| count > samples)
+                .map(AaMode::Msaa)
+        };
+        match self {
+            AaMode::None => msaa_above(1).unwrap_or(AaMode::Fxaa),
+            AaMode::Msaa(samples) => {
+                msaa_above(samples).unwrap_or(AaMode::Fxaa)
+            }
+            AaMode::Fxaa => AaMode::None,
+        }
+    }
+
+    // For the title bar, e.g. "MSAA x4"
+    pub fn name(self) -> String {
+        match self {
+            AaMode::None => "AA off".to_string(),
+            AaMode::Msaa(samples) => format!("MSAA x{}", samples),
+            AaMode::Fxaa => "FXAA".to_string(),
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use anyhow::*;
+
+use crate::ktx2::Ktx2;
+use crate::texture::{Texture, TextureKind};
+
+// Refers to a texture queued on an `AssetLoader`. Stays valid for the
+// loader's whole life, before and after the image arrives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextureHandle(usize);
+
+// A file's contents, ready to upload
+enum Image {
+    Rgba(image::RgbaImage),
+    // A whole KTX2 file the device can sample as is
+    Compressed(Vec<u8>),
+}
+
+// What a worker thread sends back once it's done with a file
+struct Decoded {
+    handle: TextureHandle,
+    result: Result<Image>,
+}
+
+struct Slot {
+    label: String,
+    kind: TextureKind,
+    // The 1x1 placeholder until the real image has been uploaded
+    texture: Texture,
+}
+
+// Streams textures in without blocking the event loop. Each file is read and
+// decoded on its own thread, then `poll` uploads whatever has finished. Until
+// then every handle resolves to a 1x1 placeholder, so anything using it can
+// be drawn straight away.
+pub struct AssetLoader {
+    sender: Sender<Decoded>,
+    receiver: Receiver<Decoded>,
+    slots: Vec<Slot>,
+    // Applied to every texture once it's uploaded, see
+    // `Texture::with_sampler`
+    anisotropy_clamp: u16,
+}
+
+impl AssetLoader {
+    pub fn new(anisotropy_clamp: u16) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self {
+            sender,
+            receiver,
+            slots: vec![],
+            anisotropy_clamp,
+        }
+    }
+
+    pub fn load_texture(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        path: impl Into<PathBuf>,
+        label: &str,
+        kind: TextureKind,
+    ) -> TextureHandle {
+        let path = path.into();
+        let handle = TextureHandle(self.slots.len());
+        self.slots.push(Slot {
+            label: label.to_string(),
+            kind,
+            texture: placeholder(device, queue, label, kind),
+        });
+
+        let sender = self.sender.clone();
+        let features = device.features();
+        std::thread::spawn(move || {
+            let result = read_image(&path, features);
+            // The loader is gone, so nobody wants the image any more
+            let _ = sender.send(Decoded { handle, result });
+        });
+
+        handle
+    }
+
+    pub fn texture(&self, handle: TextureHandle) -> &Texture {
+        &self.slots[handle.0].texture
+    }
+
+    // E.g. to change its sampler. `poll` replaces the placeholder once the
+    // real texture is in, changes and all.
+    pub fn texture_mut(&mut self, handle: TextureHandle) -> &mut Texture {
+        &mut self.slots[handle.0].texture
+    }
+
+    // Uploads every texture that finished decoding since the last call and
+    // returns how each one went. A texture that failed keeps its placeholder.
+    // Cheap enough to call every frame.
+    pub fn poll(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Vec<(TextureHandle, Result<()>)> {
+        let mut finished = vec![];
+        while let std::result::Result::Ok(Decoded { handle, result }) =
+            self.receiver.try_recv()
+        {
+            let slot = &mut self.slots[handle.0];
+            let result = result.and_then(|image| {
+                let texture = match image {
+                    Image::Rgba(rgba) => Texture::from_rgba(
+                        device,
+                        queue,
+                        &rgba,
+                        Some(&slot.label),
+                        slot.kind,
+                    ),
+                    Image::Compressed(bytes) => Texture::from_ktx2(
+                        device,
+                        queue,
+                        &bytes,
+                        Some(&slot.label),
+                    )?,
+                };
+                slot.texture = texture.with_sampler(
+                    device,
+                    wgpu::AddressMode::ClampToEdge,
+                    self.anisotropy_clamp,
+                )?;
+                Ok(())
+            });
+            finished.push((handle, result));
+        }
+        finished
+    }
+}
+
+// A .ktx2 file is kept block compressed if `features` cover its format.
+// Otherwise the PNG next to it (same name, .png extension) is decoded
+// instead, since plenty of GPUs only do one of BC and ETC2.
+fn read_image(path: &Path, features: wgpu::Features) -> Result<Image> {
+    let is_ktx2 = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("ktx2"));
+    if !is_ktx2 {
+        return Ok(Image::Rgba(Texture::decode_path(path)?.to_rgba8()));
+    }
+
+    let bytes = std::fs::read(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let format = Ktx2::parse(&bytes)
+        .with_context(|| format!("Failed to parse {}", path.display()))?
+        .format;
+    if features.contains(format.required_features()) {
+        return Ok(Image::Compressed(bytes));
+    }
+
+    let fallback = path.with_extension("png");
+    log::warn!(
+        "{} is {:?}, which this GPU can't sample, using {} instead",
+        path.display(),
+        format,
+        fallback.display()
+    );
+    Ok(Image::Rgba(Texture::decode_path(&fallback)?.to_rgba8()))
+}
+
+// White for color textures, and a normal pointing straight out of the
+// surface for data (normal maps), so a placeholder doesn't tint or bend
+// anything
+fn placeholder(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    label: &str,
+    kind: TextureKind,
+) -> Texture {
+    let pixel = match kind {
+        TextureKind::Color => [255, 255, 255, 255],
+        TextureKind::LinearData => [128, 128, 255, 255],
+    };
+    Texture::from_rgba(
+        device,
+        queue,
+        &image::RgbaImage::from_pixel(1, 1, image::Rgba(pixel)),
+        Some(&format!("{} (Placeholder)", label)),
+        kind,
+    )
+}
@@ -0,0 +1,88 @@
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+// Points at a directory of loose assets to use instead of any other
+const ASSETS_DIR_VAR: &str = "LEARN_WGPU_ASSETS";
+
+// Assets the binary has built in can also be shipped as loose files, and
+// edited without recompiling. They're looked for in one directory, the
+// first of these that exists:
+//
+// - whatever LEARN_WGPU_ASSETS says
+// - assets/ next to the executable
+// - the source tree's src/, where the built-in copies come from
+//
+// A file that isn't in that directory (or there being no directory at all)
+// just means the built-in copy is used.
+fn assets_dir() -> Option<&'static Path> {
+    static DIR: OnceLock<Option<PathBuf>> = OnceLock::new();
+    DIR.get_or_init(|| {
+        let from_env = std::env::var_os(ASSETS_DIR_VAR).map(PathBuf::from);
+        if let Some(dir) = from_env.as_ref().filter(|dir| !dir.is_dir()) {
+            log::warn!(
+                "{} is set to {}, which isn't a directory",
+                ASSETS_DIR_VAR,
+                dir.display()
+            );
+        }
+        let next_to_exe = std::env::current_exe()
+            .ok()
+            .and_then(|exe| Some(exe.parent()?.join("assets")));
+        let source_tree =
+            Some(Path::new(env!("CARGO_MANIFEST_DIR")).join("src"));
+
+        let dir = [from_env, next_to_exe, source_tree]
+            .into_iter()
+            .flatten()
+            .find(|dir| dir.is_dir());
+        match &dir {
+            Some(dir) => log::info!("Loose assets from {}", dir.display()),
+            None => log::info!("No assets directory, using built-in assets"),
+        }
+        dir
+    })
+    .as_deref()
+}
+
+// Where the loose copy of `name` is, if there is one
+pub fn find(name: &str) -> Option<PathBuf> {
+    assets_dir()
+        .map(|dir| dir.join(name))
+        .filter(|path| path.is_file())
+}
+
+// The loose copy of `name` if there is one that can be read, otherwise
+// `embedded`
+pub fn load(name: &str, embedded: &'static [u8]) -> Cow<'static, [u8]> {
+    match find(name).map(|path| (std::fs::read(&path), path)) {
+        Some((Ok(bytes), _)) => Cow::Owned(bytes),
+        Some((Err(e), path)) => {
+            log::warn!(
+                "Couldn't read {}: {}, using the built-in {}",
+                path.display(),
+                e,
+                name
+            );
+            Cow::Borrowed(embedded)
+        }
+        None => Cow::Borrowed(embedded),
+    }
+}
+
+// Same as `load` for text, e.g. shaders
+pub fn load_str(name: &str, embedded: &'static str) -> Cow<'static, str> {
+    match find(name).map(|path| (std::fs::read_to_string(&path), path)) {
+        Some((Ok(text), _)) => Cow::Owned(text),
+        Some((Err(e), path)) => {
+            log::warn!(
+                "Couldn't read {}: {}, using the built-in {}",
+                path.display(),
+                e,
+                name
+            );
+            Cow::Borrowed(embedded)
+        }
+        None => Cow::Borrowed(embedded),
+    }
+}
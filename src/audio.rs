@@ -0,0 +1,96 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+
+use anyhow::Context;
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{FromSample, SizedSample};
+
+// How far each buffer's RMS moves the amplitude towards itself. Lower is
+// smoother but slower to react; at a typical 10ms buffer this settles in
+// about a tenth of a second.
+const SMOOTHING: f32 = 0.2;
+
+// How loud the default input device (usually the microphone) is, for
+// shaders to react to. cpal calls back on its own audio thread with each
+// buffer of samples; the RMS amplitude is worked out and smoothed there,
+// and handed over through an atomic so reading it never waits on audio.
+pub struct AudioInput {
+    // Capture stops when this is dropped. None without a usable device.
+    _stream: Option<cpal::Stream>,
+    // The smoothed amplitude's f32 bits, see `amplitude`
+    amplitude: Arc<AtomicU32>,
+}
+
+impl AudioInput {
+    // Starts capturing straight away. Without an input device (or one in a
+    // format this doesn't read) it logs why and stays at zero amplitude.
+    pub fn start() -> Self {
+        let amplitude = Arc::new(AtomicU32::new(0.0f32.to_bits()));
+        let stream = open_stream(amplitude.clone())
+            .inspect_err(|e| log::warn!("Audio input disabled: {:#}", e))
+            .ok();
+        Self {
+            _stream: stream,
+            amplitude,
+        }
+    }
+
+    // The smoothed RMS of the latest samples, 0 (silence) to 1 (full scale)
+    pub fn amplitude(&self) -> f32 {
+        f32::from_bits(self.amplitude.load(Ordering::Relaxed))
+    }
+}
+
+fn open_stream(amplitude: Arc<AtomicU32>) -> anyhow::Result<cpal::Stream> {
+    let device = cpal::default_host()
+        .default_input_device()
+        .context("No input device")?;
+    let config = device.default_input_config()?;
+    let stream_config = config.config();
+    let stream = match config.sample_format() {
+        cpal::SampleFormat::F32 => {
+            build_stream::<f32>(&device, stream_config, amplitude)
+        }
+        cpal::SampleFormat::I16 => {
+            build_stream::<i16>(&device, stream_config, amplitude)
+        }
+        cpal::SampleFormat::U16 => {
+            build_stream::<u16>(&device, stream_config, amplitude)
+        }
+        format => anyhow::bail!("Unsupported sample format {}", format),
+    }?;
+    stream.play()?;
+    Ok(stream)
+}
+
+fn build_stream<T>(
+    device: &cpal::Device,
+    config: cpal::StreamConfig,
+    amplitude: Arc<AtomicU32>,
+) -> anyhow::Result<cpal::Stream>
+where
+    T: SizedSample,
+    f32: FromSample<T>,
+{
+    let stream = device.build_input_stream(
+        config,
+        move |data: &[T], _: &cpal::InputCallbackInfo| {
+            if data.is_empty() {
+                return;
+            }
+            // Channels are interleaved, which makes no difference to the
+            // RMS of all of them together
+            let sum_of_squares: f32 = data
+                .iter()
+                .map(|&sample| sample.to_sample::<f32>().powi(2))
+                .sum();
+            let rms = (sum_of_squares / data.len() as f32).sqrt();
+            let previous = f32::from_bits(amplitude.load(Ordering::Relaxed));
+            let smoothed = previous + (rms - previous) * SMOOTHING;
+            amplitude.store(smoothed.to_bits(), Ordering::Relaxed);
+        },
+        |e| log::warn!("Audio input error: {}", e),
+        None,
+    )?;
+    Ok(stream)
+}
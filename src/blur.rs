@@ -0,0 +1,364 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+
+use winit::dpi::PhysicalSize;
+
+use crate::post::{PostChain, PostEffect, ShaderEffect};
+
+// Where the blur's radius starts, and the most it can be, in pixels
+pub const DEFAULT_RADIUS: u32 = 4;
+pub const MAX_RADIUS: u32 = 32;
+
+// Pixels per workgroup along x and y, see blur.wgsl's cs_main
+const WORKGROUP_SIZE: u32 = 8;
+
+// blur.wgsl's override constants, for `BlurEffect::constants`
+pub const SAMPLE_SPACING: &str = "SAMPLE_SPACING";
+
+// The passes write this between them. Has to allow STORAGE_BINDING, see
+// `supported`.
+const INTERMEDIATE_FORMAT: wgpu::TextureFormat =
+    wgpu::TextureFormat::Rgba16Float;
+
+// see: blur.wgsl BlurUniform
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct BlurUniform {
+    radius: u32,
+    _padding: u32,
+    direction: [i32; 2],
+}
+
+// Whether `adapter` can write the blur's intermediate textures from a
+// compute shader
+pub fn supported(adapter: &wgpu::Adapter) -> bool {
+    adapter
+        .get_texture_format_features(INTERMEDIATE_FORMAT)
+        .allowed_usages
+        .contains(wgpu::TextureUsages::STORAGE_BINDING)
+}
+
+// A Gaussian blur done in compute rather than in a fragment shader: one
+// dispatch blurs the input horizontally into a storage texture, a second
+// blurs that vertically into another, and a fullscreen pass copies the
+// result into the output. Splitting it that way takes 2 * (2r + 1) reads
+// per pixel instead of (2r + 1)^2.
+//
+// The radius is shared through a `radius` handle, so it can be changed
+// while the effect is in the chain. So are the values of blur.wgsl's
+// override constants, through `constants`, but those are baked into the
+// pipeline when it's built, so changing them rebuilds it.
+pub struct BlurEffect {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    layout: wgpu::PipelineLayout,
+    pipeline: RefCell<wgpu::ComputePipeline>,
+    constants: Arc<Mutex<HashMap<String, f64>>>,
+    // What `pipeline` was built with
+    built_constants: RefCell<HashMap<String, f64>>,
+    output_layout: wgpu::BindGroupLayout,
+    input_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    // One uniform per pass, differing only in direction
+    uniform_buffers: [wgpu::Buffer; 2],
+    radius: Arc<AtomicU32>,
+    // What `uniform_buffers` were last written with
+    written_radius: RefCell<Option<u32>>,
+    // Made to fit the first input and again whenever its size changes
+    targets: RefCell<Option<BlurTargets>>,
+    composite: ShaderEffect,
+}
+
+struct BlurTargets {
+    size: PhysicalSize<u32>,
+    // Written by the horizontal pass, with its uniform
+    horizontal_output: wgpu::BindGroup,
+    // The horizontal pass's texture, as the vertical pass's input
+    vertical_input: wgpu::BindGroup,
+    // Written by the vertical pass, with its uniform
+    vertical_output: wgpu::BindGroup,
+    // The vertical pass's texture, for the composite to read
+    composite_input: wgpu::BindGroup,
+}
+
+impl BlurEffect {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        chain: &PostChain,
+    ) -> Self {
+        let output_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Blur Output Bind Group Layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::StorageTexture {
+                            access: wgpu::StorageTextureAccess::WriteOnly,
+                            format: INTERMEDIATE_FORMAT,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+
+        let layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Blur Pipeline Layout"),
+                bind_group_layouts: &[chain.input_layout(), &output_layout],
+                push_constant_ranges: &[],
+            });
+        let constants = HashMap::new();
+        let pipeline = create_pipeline(device, &layout, &constants);
+
+        let uniform_buffers = [0, 1].map(|_| {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Blur Uniform Buffer"),
+                size: std::mem::size_of::<BlurUniform>() as wgpu::BufferAddress,
+                usage: wgpu::BufferUsages::UNIFORM
+                    | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            })
+        });
+
+        // The intermediate textures are the same size as the input, so
+        // only the composite ever filters, when it scales up to the output
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Blur Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Self {
+            device: device.clone(),
+            queue: queue.clone(),
+            layout,
+            pipeline: RefCell::new(pipeline),
+            constants: Arc::new(Mutex::new(constants.clone())),
+            built_constants: RefCell::new(constants),
+            output_layout,
+            input_layout: chain.input_layout().clone(),
+            sampler,
+            uniform_buffers,
+            radius: Arc::new(AtomicU32::new(DEFAULT_RADIUS)),
+            written_radius: RefCell::new(None),
+            targets: RefCell::new(None),
+            composite: ShaderEffect::new(
+                device,
+                chain,
+                "Blur Composite",
+                "fs_copy",
+            ),
+        }
+    }
+
+    // Setting it takes effect the next time the blur runs, clamped to
+    // MAX_RADIUS
+    pub fn radius(&self) -> Arc<AtomicU32> {
+        self.radius.clone()
+    }
+
+    // Values for blur.wgsl's override constants by name, e.g.
+    // SAMPLE_SPACING. Left out ones keep the shader's defaults, and names
+    // the shader doesn't declare are ignored. The pipeline is rebuilt with
+    // them the next time the blur runs.
+    pub fn constants(&self) -> Arc<Mutex<HashMap<String, f64>>> {
+        self.constants.clone()
+    }
+
+    // Rebuilds the pipeline if the constants changed since it was built. If
+    // building fails validation with them the old pipeline is kept.
+    fn rebuild_pipeline(&self) {
+        let constants = self.constants.lock().unwrap().clone();
+        if *self.built_constants.borrow() == constants {
+            return;
+        }
+
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let pipeline = create_pipeline(&self.device, &self.layout, &constants);
+        match pollster::block_on(self.device.pop_error_scope()) {
+            None => *self.pipeline.borrow_mut() = pipeline,
+            Some(e) => log::error!(
+                "Keeping the blur pipeline, constants {:?} failed: {}",
+                constants,
+                e
+            ),
+        }
+        // Either way, so a failure isn't retried every frame
+        *self.built_constants.borrow_mut() = constants;
+    }
+
+    fn write_uniforms(&self, radius: u32) {
+        if *self.written_radius.borrow() == Some(radius) {
+            return;
+        }
+        for (buffer, direction) in
+            self.uniform_buffers.iter().zip([[1, 0], [0, 1]])
+        {
+            self.queue.write_buffer(
+                buffer,
+                0,
+                bytemuck::cast_slice(&[BlurUniform {
+                    radius,
+                    _padding: 0,
+                    direction,
+                }]),
+            );
+        }
+        *self.written_radius.borrow_mut() = Some(radius);
+    }
+
+    fn create_targets(&self, size: PhysicalSize<u32>) -> BlurTargets {
+        let [horizontal, vertical] = [0, 1].map(|_| {
+            self.device
+                .create_texture(&wgpu::TextureDescriptor {
+                    label: Some("Blur Target"),
+                    size: wgpu::Extent3d {
+                        width: size.width.max(1),
+                        height: size.height.max(1),
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: INTERMEDIATE_FORMAT,
+                    usage: wgpu::TextureUsages::STORAGE_BINDING
+                        | wgpu::TextureUsages::TEXTURE_BINDING,
+                    view_formats: &[],
+                })
+                .create_view(&wgpu::TextureViewDescriptor::default())
+        });
+        let output = |view: &wgpu::TextureView, uniforms: &wgpu::Buffer| {
+            self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Blur Output Bind Group"),
+                layout: &self.output_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: uniforms.as_entire_binding(),
+                    },
+                ],
+            })
+        };
+        let input = |view: &wgpu::TextureView| {
+            self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Blur Input Bind Group"),
+                layout: &self.input_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&self.sampler),
+                    },
+                ],
+            })
+        };
+        BlurTargets {
+            size,
+            horizontal_output: output(&horizontal, &self.uniform_buffers[0]),
+            vertical_input: input(&horizontal),
+            vertical_output: output(&vertical, &self.uniform_buffers[1]),
+            composite_input: input(&vertical),
+        }
+    }
+}
+
+impl PostEffect for BlurEffect {
+    fn name(&self) -> &str {
+        "Blur"
+    }
+
+    fn record(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        input: &wgpu::BindGroup,
+        output: &wgpu::TextureView,
+        uniforms: &wgpu::BindGroup,
+        size: PhysicalSize<u32>,
+    ) {
+        let radius = self.radius.load(Ordering::Relaxed).min(MAX_RADIUS);
+        self.write_uniforms(radius);
+        self.rebuild_pipeline();
+
+        let mut targets = self.targets.borrow_mut();
+        if targets.as_ref().is_none_or(|targets| targets.size != size) {
+            *targets = Some(self.create_targets(size));
+        }
+        let targets = targets.as_ref().unwrap();
+
+        {
+            let mut compute_pass =
+                encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                    label: Some("Blur Pass"),
+                    timestamp_writes: None,
+                });
+            compute_pass.set_pipeline(&self.pipeline.borrow());
+            for (input, output) in [
+                (input, &targets.horizontal_output),
+                (&targets.vertical_input, &targets.vertical_output),
+            ] {
+                compute_pass.set_bind_group(0, input, &[]);
+                compute_pass.set_bind_group(1, output, &[]);
+                compute_pass.dispatch_workgroups(
+                    size.width.div_ceil(WORKGROUP_SIZE),
+                    size.height.div_ceil(WORKGROUP_SIZE),
+                    1,
+                );
+            }
+        }
+
+        self.composite.record(
+            encoder,
+            &targets.composite_input,
+            output,
+            uniforms,
+            size,
+        );
+    }
+}
+
+// Each pipeline gets a shader module of its own. The GL backend caches
+// programs by module and entry point but not by constants, so a second
+// pipeline from the same module would quietly get the first one's values.
+fn create_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    constants: &HashMap<String, f64>,
+) -> wgpu::ComputePipeline {
+    let shader = device.create_shader_module(wgpu::include_wgsl!("blur.wgsl"));
+    device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: Some("Blur Pipeline"),
+        layout: Some(layout),
+        module: &shader,
+        entry_point: Some("cs_main"),
+        compilation_options: wgpu::PipelineCompilationOptions {
+            constants,
+            ..Default::default()
+        },
+        cache: None,
+    })
+}
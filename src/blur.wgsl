@@ -0,0 +1,53 @@
+// see: blur::BlurUniform
+struct BlurUniform {
+    // In pixels. 0 copies the input across unblurred.
+    radius: u32,
+    _padding: u32,
+    // (1, 0) for the horizontal pass, (0, 1) for the vertical one
+    direction: vec2<i32>,
+}
+
+// The previous step's output, see post::PostEffect. Only read with
+// textureLoad, so its sampler isn't declared.
+@group(0) @binding(0)
+var t_input: texture_2d<f32>;
+
+@group(1) @binding(0)
+var t_output: texture_storage_2d<rgba16float, write>;
+
+@group(1) @binding(1)
+var<uniform> blur: BlurUniform;
+
+// Pixels between the blur's taps, set when the pipeline is built (see
+// blur::SAMPLE_SPACING). 1 reads every pixel within the radius; 2 reads every
+// other one, half as many samples for a slightly coarser blur. The loop
+// below is fixed for the pipeline's lifetime, so the compiler can unroll
+// and fold it as if the value were written here.
+override SAMPLE_SPACING: i32 = 1;
+
+// see: blur::WORKGROUP_SIZE
+@compute @workgroup_size(8, 8)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = vec2<i32>(textureDimensions(t_input));
+    let pixel = vec2<i32>(id.xy);
+    // The dispatch is rounded up to whole workgroups
+    if (pixel.x >= size.x || pixel.y >= size.y) {
+        return;
+    }
+
+    // A Gaussian that's all but zero by `radius` pixels out
+    let radius = i32(blur.radius);
+    let sigma = max(f32(radius), 1.0) / 2.0;
+    // Taps at multiples of the spacing, so they're the same either side
+    let spacing = max(SAMPLE_SPACING, 1);
+    var color = vec4<f32>(0.0);
+    var total = 0.0;
+    for (var i = -(radius / spacing) * spacing; i <= radius; i += spacing) {
+        let weight = exp(-f32(i * i) / (2.0 * sigma * sigma));
+        // Clamped, so the edges don't darken
+        let sample_at = clamp(pixel + blur.direction * i, vec2<i32>(0), size - 1);
+        color += textureLoad(t_input, sample_at, 0) * weight;
+        total += weight;
+    }
+    textureStore(t_output, pixel, color / total);
+}
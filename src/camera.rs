@@ -0,0 +1,499 @@
+use cgmath::{InnerSpace, Point3, Vector2, Vector3};
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+use winit::event::{ElementState, MouseButton, MouseScrollDelta};
+use winit::keyboard::KeyCode;
+
+// cgmath's projections assume OpenGL's -1..1 clip space depth while wgpu uses
+// 0..1, so this remaps z (and leaves x/y alone).
+#[rustfmt::skip]
+pub const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
+    1.0, 0.0, 0.0, 0.0,
+    0.0, 1.0, 0.0, 0.0,
+    0.0, 0.0, 0.5, 0.0,
+    0.0, 0.0, 0.5, 1.0,
+);
+
+pub struct Camera {
+    pub eye: Point3<f32>,
+    pub target: Point3<f32>,
+    pub up: Vector3<f32>,
+    pub aspect: f32,
+    pub fovy: f32,
+    pub znear: f32,
+    pub zfar: f32,
+}
+
+impl Camera {
+    pub fn new(aspect: f32) -> Self {
+        Self {
+            eye: (0.0, 0.0, 2.4).into(),
+            target: (0.0, 0.0, 0.0).into(),
+            up: Vector3::unit_y(),
+            aspect,
+            fovy: 45.0,
+            znear: 0.1,
+            zfar: 100.0,
+        }
+    }
+
+    pub fn build_view_projection_matrix(&self) -> cgmath::Matrix4<f32> {
+        let view = cgmath::Matrix4::look_at_rh(self.eye, self.target, self.up);
+        let proj = cgmath::perspective(
+            cgmath::Deg(self.fovy),
+            self.aspect,
+            self.znear,
+            self.zfar,
+        );
+        OPENGL_TO_WGPU_MATRIX * proj * view
+    }
+}
+
+// We need this for Rust to store our data correctly for the shaders
+#[repr(C)]
+// This is so we can store this in a buffer
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct CameraUniform {
+    // We can't use cgmath with bytemuck directly, so we'll have to convert the
+    // Matrix4 into a 4x4 f32 array
+    view_proj: [[f32; 4]; 4],
+    // Takes clip space back to world space, e.g. to turn a pixel into the
+    // world space ray through it
+    inv_view_proj: [[f32; 4]; 4],
+    // Only xyz is used, w pads it to 16 bytes
+    view_position: [f32; 4],
+}
+
+impl CameraUniform {
+    pub fn new() -> Self {
+        use cgmath::SquareMatrix;
+        Self {
+            view_proj: cgmath::Matrix4::identity().into(),
+            inv_view_proj: cgmath::Matrix4::identity().into(),
+            view_position: [0.0; 4],
+        }
+    }
+
+    // Takes the matrix and eye of whichever camera is active, so shaders
+    // don't need to know if it's perspective or orthographic
+    pub fn update_view_proj(
+        &mut self,
+        view_proj: cgmath::Matrix4<f32>,
+        eye: Point3<f32>,
+    ) {
+        use cgmath::SquareMatrix;
+        self.view_proj = view_proj.into();
+        // A valid perspective or orthographic camera is always invertible
+        self.inv_view_proj = view_proj
+            .invert()
+            .unwrap_or_else(cgmath::Matrix4::identity)
+            .into();
+        self.view_position = eye.to_homogeneous().into();
+    }
+}
+
+// Keep pitch just shy of straight up/down, otherwise the view direction
+// becomes parallel to `up` and look_at flips
+const MAX_PITCH: f32 = std::f32::consts::FRAC_PI_2 - 0.01;
+
+#[derive(Clone)]
+pub struct OrbitCamera {
+    pub yaw: f32,
+    pub pitch: f32,
+    pub distance: f32,
+    pub target: Point3<f32>,
+    pub min_distance: f32,
+    pub max_distance: f32,
+    // Radians per pixel of drag
+    pub rotate_sensitivity: f32,
+    // Fraction of the current distance per scroll line
+    pub zoom_sensitivity: f32,
+    is_dragging: bool,
+    last_cursor_position: Option<PhysicalPosition<f64>>,
+}
+
+impl OrbitCamera {
+    pub fn new(distance: f32) -> Self {
+        Self {
+            yaw: 0.0,
+            pitch: 0.0,
+            distance,
+            target: (0.0, 0.0, 0.0).into(),
+            min_distance: 0.5,
+            max_distance: 20.0,
+            rotate_sensitivity: 0.005,
+            zoom_sensitivity: 0.1,
+            is_dragging: false,
+            last_cursor_position: None,
+        }
+    }
+
+    pub fn is_dragging(&self) -> bool {
+        self.is_dragging
+    }
+
+    pub fn process_mouse_input(
+        &mut self,
+        state: ElementState,
+        button: MouseButton,
+    ) -> bool {
+        if button != MouseButton::Left {
+            return false;
+        }
+        self.is_dragging = state == ElementState::Pressed;
+        true
+    }
+
+    // Always tracks the cursor so the first drag delta is relative to where
+    // the button went down rather than wherever the cursor last was when
+    // dragging ended. Returns whether the camera moved.
+    pub fn process_cursor_moved(
+        &mut self,
+        position: PhysicalPosition<f64>,
+    ) -> bool {
+        let last = self.last_cursor_position.replace(position);
+
+        match (self.is_dragging, last) {
+            (true, Some(last)) => {
+                let dx = (position.x - last.x) as f32;
+                let dy = (position.y - last.y) as f32;
+                self.rotate(
+                    -dx * self.rotate_sensitivity,
+                    dy * self.rotate_sensitivity,
+                );
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // In radians. Positive yaw swings the eye towards +X from its starting
+    // spot on +Z, positive pitch raises it.
+    pub fn rotate(&mut self, yaw: f32, pitch: f32) {
+        self.yaw += yaw;
+        self.pitch = (self.pitch + pitch).clamp(-MAX_PITCH, MAX_PITCH);
+    }
+
+    // Slides the target (and so the whole orbit) over the XZ plane, relative
+    // to where the camera is facing
+    pub fn pan(&mut self, right: f32, forward: f32) {
+        let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
+        let forward_dir = Vector3::new(-sin_yaw, 0.0, -cos_yaw);
+        let right_dir = Vector3::new(cos_yaw, 0.0, -sin_yaw);
+        self.target += right_dir * right + forward_dir * forward;
+    }
+
+    pub fn process_scroll(&mut self, delta: &MouseScrollDelta) {
+        let lines = match delta {
+            MouseScrollDelta::LineDelta(_, y) => *y,
+            // Roughly one "line" per 50 pixels on trackpads
+            MouseScrollDelta::PixelDelta(position) => position.y as f32 / 50.0,
+        };
+        self.distance = (self.distance * (1.0 - lines * self.zoom_sensitivity))
+            .clamp(self.min_distance, self.max_distance);
+    }
+
+    pub fn eye(&self) -> Point3<f32> {
+        let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
+        let (sin_pitch, cos_pitch) = self.pitch.sin_cos();
+        let direction =
+            Vector3::new(cos_pitch * sin_yaw, sin_pitch, cos_pitch * cos_yaw);
+        self.target + direction.normalize() * self.distance
+    }
+
+    // Orbits whatever is `distance` in front of `fly`, from where it is
+    pub fn look_from_fly(&mut self, fly: &FlyCamera) {
+        self.yaw = fly.yaw;
+        self.pitch = fly.pitch;
+        self.target = fly.position + fly.direction() * self.distance;
+    }
+
+    pub fn update_camera(&self, camera: &mut Camera) {
+        camera.eye = self.eye();
+        camera.target = self.target;
+    }
+}
+
+// The narrowest and widest vertical field of view FovZoom allows, in
+// degrees. Much narrower and depth precision runs out, and the projection
+// degenerates as it approaches 180.
+pub const MIN_FOVY: f32 = 10.0;
+pub const MAX_FOVY: f32 = 120.0;
+// Degrees per scroll line
+const FOV_SCROLL_STEP: f32 = 5.0;
+// How quickly the field of view closes in on its target: the gap shrinks by
+// a factor of e every 1 / FOV_SMOOTHING seconds
+const FOV_SMOOTHING: f32 = 12.0;
+
+// Zooms a perspective camera by changing its field of view rather than
+// moving it, which flattens the perspective when zooming in instead of
+// exaggerating it. Input only moves a target; `update` eases the camera's
+// fovy towards it a frame at a time so it doesn't snap.
+pub struct FovZoom {
+    target: f32,
+}
+
+impl FovZoom {
+    pub fn new(fovy: f32) -> Self {
+        Self {
+            target: fovy.clamp(MIN_FOVY, MAX_FOVY),
+        }
+    }
+
+    // Positive `degrees` narrow the field of view, zooming in
+    pub fn zoom(&mut self, degrees: f32) {
+        self.target = (self.target - degrees).clamp(MIN_FOVY, MAX_FOVY);
+    }
+
+    pub fn process_scroll(&mut self, delta: &MouseScrollDelta) {
+        let lines = match delta {
+            MouseScrollDelta::LineDelta(_, y) => *y,
+            // Same as OrbitCamera::process_scroll
+            MouseScrollDelta::PixelDelta(position) => position.y as f32 / 50.0,
+        };
+        self.zoom(lines * FOV_SCROLL_STEP);
+    }
+
+    // Framerate independent: the same fraction of the way there per second
+    // however that second is split up
+    pub fn update(&self, dt: f32, camera: &mut Camera) {
+        let t = 1.0 - (-FOV_SMOOTHING * dt).exp();
+        camera.fovy += (self.target - camera.fovy) * t;
+    }
+}
+
+// An FPS-style free camera. Mouse motion turns it and WASD moves it along
+// the view direction, with Q and E going straight down and up. Uses the same
+// yaw and pitch as OrbitCamera, looking the opposite way (from the eye
+// towards the target), so switching between the two doesn't jump.
+pub struct FlyCamera {
+    pub position: Point3<f32>,
+    pub yaw: f32,
+    pub pitch: f32,
+    // Radians per unit of raw mouse motion (roughly a pixel at default
+    // pointer speed)
+    pub sensitivity: f32,
+    // World units per second
+    pub speed: f32,
+    // Held movement keys, each 0 or 1
+    forward: f32,
+    backward: f32,
+    left: f32,
+    right: f32,
+    up: f32,
+    down: f32,
+}
+
+impl FlyCamera {
+    pub fn new() -> Self {
+        Self {
+            position: (0.0, 0.0, 2.4).into(),
+            yaw: 0.0,
+            pitch: 0.0,
+            sensitivity: 0.003,
+            speed: 2.0,
+            forward: 0.0,
+            backward: 0.0,
+            left: 0.0,
+            right: 0.0,
+            up: 0.0,
+            down: 0.0,
+        }
+    }
+
+    // Puts the camera where `orbit` has its eye, looking at its target
+    pub fn look_from_orbit(&mut self, orbit: &OrbitCamera) {
+        self.position = orbit.eye();
+        self.yaw = orbit.yaw;
+        self.pitch = orbit.pitch;
+    }
+
+    // Returns whether `key` is one of the movement keys
+    pub fn process_keyboard(
+        &mut self,
+        key: KeyCode,
+        state: ElementState,
+    ) -> bool {
+        let amount = if state == ElementState::Pressed {
+            1.0
+        } else {
+            0.0
+        };
+        match key {
+            KeyCode::KeyW => self.forward = amount,
+            KeyCode::KeyS => self.backward = amount,
+            KeyCode::KeyA => self.left = amount,
+            KeyCode::KeyD => self.right = amount,
+            KeyCode::KeyE => self.up = amount,
+            KeyCode::KeyQ => self.down = amount,
+            _ => return false,
+        }
+        true
+    }
+
+    // Keys released while the camera wasn't listening would otherwise stay
+    // held
+    pub fn release_keys(&mut self) {
+        self.forward = 0.0;
+        self.backward = 0.0;
+        self.left = 0.0;
+        self.right = 0.0;
+        self.up = 0.0;
+        self.down = 0.0;
+    }
+
+    // From `DeviceEvent::MouseMotion`, which isn't stopped by the window's
+    // edges or sped up by pointer acceleration the way cursor positions are
+    pub fn process_mouse_motion(&mut self, dx: f64, dy: f64) {
+        self.yaw -= dx as f32 * self.sensitivity;
+        self.pitch = (self.pitch + dy as f32 * self.sensitivity)
+            .clamp(-MAX_PITCH, MAX_PITCH);
+    }
+
+    pub fn direction(&self) -> Vector3<f32> {
+        let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
+        let (sin_pitch, cos_pitch) = self.pitch.sin_cos();
+        -Vector3::new(cos_pitch * sin_yaw, sin_pitch, cos_pitch * cos_yaw)
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
+        let right_dir = Vector3::new(cos_yaw, 0.0, -sin_yaw);
+        let step = self.speed * dt;
+        self.position +=
+            self.direction() * (self.forward - self.backward) * step
+                + right_dir * (self.right - self.left) * step
+                + Vector3::unit_y() * (self.up - self.down) * step;
+    }
+
+    pub fn update_camera(&self, camera: &mut Camera) {
+        camera.eye = self.position;
+        camera.target = self.position + self.direction();
+    }
+}
+
+// Where the orthographic camera sits along +Z looking down -Z, and how much
+// of the scene in front of and behind the XY plane it keeps
+const ORTHO_EYE_Z: f32 = 10.0;
+pub const ORTHO_ZNEAR: f32 = 0.1;
+pub const ORTHO_ZFAR: f32 = 100.0;
+
+// A flat view of the XY plane for 2D work. Middle-drag pans, scrolling zooms
+// in and out around the cursor.
+pub struct OrthoCamera {
+    // World space point in the middle of the screen
+    pub center: Vector2<f32>,
+    // 1.0 shows world y from -1 to 1, 2.0 half that
+    pub zoom: f32,
+    pub aspect: f32,
+    pub min_zoom: f32,
+    pub max_zoom: f32,
+    // Fraction of the current zoom per scroll line
+    pub zoom_sensitivity: f32,
+    is_dragging: bool,
+    last_cursor_position: Option<PhysicalPosition<f64>>,
+}
+
+impl OrthoCamera {
+    pub fn new(aspect: f32) -> Self {
+        Self {
+            center: Vector2::new(0.0, 0.0),
+            zoom: 1.0,
+            aspect,
+            min_zoom: 0.05,
+            max_zoom: 50.0,
+            zoom_sensitivity: 0.1,
+            is_dragging: false,
+            last_cursor_position: None,
+        }
+    }
+
+    // Half the visible width and height in world units
+    fn half_extent(&self) -> Vector2<f32> {
+        Vector2::new(self.aspect / self.zoom, 1.0 / self.zoom)
+    }
+
+    pub fn eye(&self) -> Point3<f32> {
+        Point3::new(self.center.x, self.center.y, ORTHO_EYE_Z)
+    }
+
+    pub fn build_view_projection_matrix(&self) -> cgmath::Matrix4<f32> {
+        let target = Point3::new(self.center.x, self.center.y, 0.0);
+        let view =
+            cgmath::Matrix4::look_at_rh(self.eye(), target, Vector3::unit_y());
+        let half = self.half_extent();
+        let proj = cgmath::ortho(
+            -half.x,
+            half.x,
+            -half.y,
+            half.y,
+            ORTHO_ZNEAR,
+            ORTHO_ZFAR,
+        );
+        OPENGL_TO_WGPU_MATRIX * proj * view
+    }
+
+    // The world space point under a pixel
+    fn cursor_to_world(
+        &self,
+        position: PhysicalPosition<f64>,
+        screen_size: PhysicalSize<u32>,
+    ) -> Vector2<f32> {
+        let ndc = Vector2::new(
+            position.x as f32 / screen_size.width.max(1) as f32 * 2.0 - 1.0,
+            1.0 - position.y as f32 / screen_size.height.max(1) as f32 * 2.0,
+        );
+        let half = self.half_extent();
+        self.center + Vector2::new(ndc.x * half.x, ndc.y * half.y)
+    }
+
+    pub fn process_mouse_input(
+        &mut self,
+        state: ElementState,
+        button: MouseButton,
+    ) -> bool {
+        if button != MouseButton::Middle {
+            return false;
+        }
+        self.is_dragging = state == ElementState::Pressed;
+        true
+    }
+
+    // Drags the scene along with the cursor. Like the orbit camera this
+    // always tracks the cursor, which zooming also relies on. Returns whether
+    // the camera moved.
+    pub fn process_cursor_moved(
+        &mut self,
+        position: PhysicalPosition<f64>,
+        screen_size: PhysicalSize<u32>,
+    ) -> bool {
+        let last = self.last_cursor_position.replace(position);
+
+        match (self.is_dragging, last) {
+            (true, Some(last)) => {
+                self.center += self.cursor_to_world(last, screen_size)
+                    - self.cursor_to_world(position, screen_size);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // Zooms around the cursor, so whatever is under it stays put
+    pub fn process_scroll(
+        &mut self,
+        delta: &MouseScrollDelta,
+        screen_size: PhysicalSize<u32>,
+    ) {
+        let lines = match delta {
+            MouseScrollDelta::LineDelta(_, y) => *y,
+            MouseScrollDelta::PixelDelta(position) => position.y as f32 / 50.0,
+        };
+        let cursor = self.last_cursor_position;
+        let before = cursor.map(|c| self.cursor_to_world(c, screen_size));
+        self.zoom = (self.zoom * (1.0 + lines * self.zoom_sensitivity))
+            .clamp(self.min_zoom, self.max_zoom);
+        if let (Some(cursor), Some(before)) = (cursor, before) {
+            self.center += before - self.cursor_to_world(cursor, screen_size);
+        }
+    }
+}
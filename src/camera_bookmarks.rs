@@ -0,0 +1,142 @@
+use std::f32::consts::{PI, TAU};
+use std::path::{Path, PathBuf};
+
+use anyhow::*;
+use cgmath::Point3;
+use serde::{Deserialize, Serialize};
+
+use crate::camera::OrbitCamera;
+
+// How many bookmarks there are, one per number key 1 to 9
+pub const N_BOOKMARKS: usize = 9;
+
+// An orbit camera's view, enough to put it back exactly where it was
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CameraBookmark {
+    pub yaw: f32,
+    pub pitch: f32,
+    pub distance: f32,
+    pub target: [f32; 3],
+}
+
+impl CameraBookmark {
+    pub fn from_camera(camera: &OrbitCamera) -> Self {
+        Self {
+            yaw: camera.yaw,
+            pitch: camera.pitch,
+            distance: camera.distance,
+            target: camera.target.into(),
+        }
+    }
+
+    pub fn apply(&self, camera: &mut OrbitCamera) {
+        camera.yaw = self.yaw;
+        camera.pitch = self.pitch;
+        camera.distance = self.distance;
+        camera.target = self.target.into();
+    }
+
+    // `t` of the way from `self` to `other`. Yaw goes the short way round,
+    // so a camera that's spun a few full turns doesn't unwind them all.
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        let yaw_delta = (other.yaw - self.yaw + PI).rem_euclid(TAU) - PI;
+        let lerp = |a: f32, b: f32| a + (b - a) * t;
+        let target = Point3::from(self.target)
+            + (Point3::from(other.target) - Point3::from(self.target)) * t;
+        Self {
+            yaw: self.yaw + yaw_delta * t,
+            pitch: lerp(self.pitch, other.pitch),
+            distance: lerp(self.distance, other.distance),
+            target: target.into(),
+        }
+    }
+}
+
+// The saved bookmarks, kept in a JSON file so they're still there next
+// time. The file is an array of N_BOOKMARKS entries, null where nothing's
+// been saved.
+pub struct CameraBookmarks {
+    path: PathBuf,
+    bookmarks: [Option<CameraBookmark>; N_BOOKMARKS],
+}
+
+impl CameraBookmarks {
+    // A missing file just means no bookmarks yet. One that can't be read is
+    // logged and ignored, and gets overwritten by the next save.
+    pub fn load(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let bookmarks = match read_bookmarks(&path) {
+            std::result::Result::Ok(bookmarks) => bookmarks,
+            Err(e) => {
+                log::warn!("{:#}, starting without camera bookmarks", e);
+                Default::default()
+            }
+        };
+        Self { path, bookmarks }
+    }
+
+    // `slot` is 0 based
+    pub fn get(&self, slot: usize) -> Option<CameraBookmark> {
+        self.bookmarks.get(slot).copied().flatten()
+    }
+
+    // Saves to disk straight away
+    pub fn set(&mut self, slot: usize, bookmark: CameraBookmark) -> Result<()> {
+        let Some(entry) = self.bookmarks.get_mut(slot) else {
+            bail!("No camera bookmark slot {}", slot + 1);
+        };
+        *entry = Some(bookmark);
+        let json = serde_json::to_string_pretty(&self.bookmarks)?;
+        std::fs::write(&self.path, json)
+            .with_context(|| format!("Writing {}", self.path.display()))
+    }
+}
+
+fn read_bookmarks(
+    path: &Path,
+) -> Result<[Option<CameraBookmark>; N_BOOKMARKS]> {
+    let json = match std::fs::read_to_string(path) {
+        std::result::Result::Ok(json) => json,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(Default::default())
+        }
+        Err(e) => return Err(e).context(format!("Reading {}", path.display())),
+    };
+    serde_json::from_str(&json)
+        .with_context(|| format!("Parsing {}", path.display()))
+}
+
+// Moves an orbit camera from where it was to a bookmark over `duration`
+// seconds, easing in and out. Driven by elapsed time rather than frames, so
+// it takes as long at any frame rate.
+pub struct CameraTransition {
+    from: CameraBookmark,
+    to: CameraBookmark,
+    elapsed: f32,
+    duration: f32,
+}
+
+impl CameraTransition {
+    pub fn new(
+        camera: &OrbitCamera,
+        to: CameraBookmark,
+        duration: f32,
+    ) -> Self {
+        Self {
+            from: CameraBookmark::from_camera(camera),
+            to,
+            elapsed: 0.0,
+            duration,
+        }
+    }
+
+    // Moves `camera` on by `dt` seconds. Returns false once it's arrived.
+    pub fn update(&mut self, dt: f32, camera: &mut OrbitCamera) -> bool {
+        self.elapsed += dt;
+        let t = (self.elapsed / self.duration.max(f32::EPSILON)).min(1.0);
+        // Smoothstep: starts and ends at zero speed
+        let eased = t * t * (3.0 - 2.0 * t);
+        self.from.lerp(&self.to, eased).apply(camera);
+        t < 1.0
+    }
+}
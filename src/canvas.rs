@@ -0,0 +1,412 @@
+use wgpu::util::DeviceExt;
+use winit::dpi::PhysicalSize;
+
+// How a canvas whose aspect doesn't match the window's gets shown
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FitMode {
+    // Show all of it, with black bars filling the leftover space
+    Letterbox,
+    // Fill the window and cut off whatever sticks out
+    Crop,
+    // Fill the window and distort the image
+    Stretch,
+}
+
+impl FitMode {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Letterbox => Self::Crop,
+            Self::Crop => Self::Stretch,
+            Self::Stretch => Self::Letterbox,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+struct BlitUniform {
+    uv_offset: [f32; 2],
+    uv_scale: [f32; 2],
+}
+
+// Where the canvas ends up for a given window size
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Layout {
+    canvas_size: PhysicalSize<u32>,
+    // x, y, width, height in window pixels
+    viewport: [f32; 4],
+    uv: BlitUniform,
+}
+
+impl Layout {
+    fn new(window: PhysicalSize<u32>, aspect: f32, fit: FitMode) -> Self {
+        let (window_width, window_height) =
+            (window.width as f32, window.height as f32);
+        let full_viewport = [0.0, 0.0, window_width, window_height];
+        let full_uv = BlitUniform {
+            uv_offset: [0.0, 0.0],
+            uv_scale: [1.0, 1.0],
+        };
+        let wider_than_canvas = window_width / window_height > aspect;
+
+        // Largest canvas that fits inside the window, so letterboxed
+        // canvas pixels map 1:1 onto window pixels
+        let (fit_width, fit_height) = if wider_than_canvas {
+            (window_height * aspect, window_height)
+        } else {
+            (window_width, window_width / aspect)
+        };
+
+        let (width, height, viewport, uv) = match fit {
+            FitMode::Letterbox => {
+                // Whole pixels keep the bars from bleeding into the image
+                let x = ((window_width - fit_width) * 0.5).floor();
+                let y = ((window_height - fit_height) * 0.5).floor();
+                (
+                    fit_width,
+                    fit_height,
+                    [x, y, fit_width.round(), fit_height.round()],
+                    full_uv,
+                )
+            }
+            FitMode::Crop => {
+                // Smallest canvas that covers the window. The overhang is
+                // cut off by sampling only the middle of the canvas.
+                let (width, height) = if wider_than_canvas {
+                    (window_width, window_width / aspect)
+                } else {
+                    (window_height * aspect, window_height)
+                };
+                let uv_scale = [window_width / width, window_height / height];
+                let uv = BlitUniform {
+                    uv_offset: [
+                        (1.0 - uv_scale[0]) * 0.5,
+                        (1.0 - uv_scale[1]) * 0.5,
+                    ],
+                    uv_scale,
+                };
+                (width, height, full_viewport, uv)
+            }
+            FitMode::Stretch => (fit_width, fit_height, full_viewport, full_uv),
+        };
+
+        Self {
+            canvas_size: PhysicalSize::new(
+                (width.round() as u32).max(1),
+                (height.round() as u32).max(1),
+            ),
+            viewport,
+            uv,
+        }
+    }
+}
+
+// An offscreen color target with a fixed aspect ratio that the scene renders
+// into instead of the surface, then gets fitted into the window. Whatever
+// shape the window is, the scene (and anything captured from `texture`) keeps
+// the same composition.
+pub struct Canvas {
+    aspect: f32,
+    fit: FitMode,
+    format: wgpu::TextureFormat,
+    window_size: PhysicalSize<u32>,
+    layout: Layout,
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    sampler: wgpu::Sampler,
+    uniform_buffer: wgpu::Buffer,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl Canvas {
+    // `aspect` is width / height. `format` is used for both the canvas and
+    // the surface it's blitted to, which also keeps captures of the canvas
+    // in the same channel order as captures of the surface.
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        aspect: f32,
+        fit: FitMode,
+        window_size: PhysicalSize<u32>,
+    ) -> Self {
+        let layout = Layout::new(window_size, aspect, fit);
+        let texture = Self::create_texture(device, format, layout.canvas_size);
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Canvas Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let uniform_buffer =
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Canvas Blit Uniform Buffer"),
+                contents: bytemuck::cast_slice(&[layout.uv]),
+                usage: wgpu::BufferUsages::UNIFORM
+                    | wgpu::BufferUsages::COPY_DST,
+            });
+
+        let bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Canvas Bind Group Layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Float {
+                                filterable: true,
+                            },
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(
+                            wgpu::SamplerBindingType::Filtering,
+                        ),
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+
+        let bind_group = Self::create_bind_group(
+            device,
+            &bind_group_layout,
+            &view,
+            &sampler,
+            &uniform_buffer,
+        );
+
+        let shader =
+            device.create_shader_module(wgpu::include_wgsl!("canvas.wgsl"));
+
+        let pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Canvas Pipeline Layout"),
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        let pipeline =
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Canvas Blit Pipeline"),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[],
+                    compilation_options:
+                        wgpu::PipelineCompilationOptions::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some("fs_main"),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format,
+                        blend: Some(wgpu::BlendState::REPLACE),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options:
+                        wgpu::PipelineCompilationOptions::default(),
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            });
+
+        Self {
+            aspect,
+            fit,
+            format,
+            window_size,
+            layout,
+            texture,
+            view,
+            sampler,
+            uniform_buffer,
+            bind_group_layout,
+            bind_group,
+            pipeline,
+        }
+    }
+
+    fn create_texture(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        size: PhysicalSize<u32>,
+    ) -> wgpu::Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Canvas Texture"),
+            size: wgpu::Extent3d {
+                width: size.width,
+                height: size.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        })
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        view: &wgpu::TextureView,
+        sampler: &wgpu::Sampler,
+        uniform_buffer: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Canvas Bind Group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    // Recomputes the fit, reallocating the canvas only if its size changed
+    fn relayout(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        window_size: PhysicalSize<u32>,
+    ) {
+        self.window_size = window_size;
+        let layout = Layout::new(window_size, self.aspect, self.fit);
+
+        if layout.canvas_size != self.layout.canvas_size {
+            self.texture =
+                Self::create_texture(device, self.format, layout.canvas_size);
+            self.view = self
+                .texture
+                .create_view(&wgpu::TextureViewDescriptor::default());
+            self.bind_group = Self::create_bind_group(
+                device,
+                &self.bind_group_layout,
+                &self.view,
+                &self.sampler,
+                &self.uniform_buffer,
+            );
+        }
+
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[layout.uv]),
+        );
+        self.layout = layout;
+    }
+
+    pub fn resize(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        window_size: PhysicalSize<u32>,
+    ) {
+        self.relayout(device, queue, window_size);
+    }
+
+    pub fn set_aspect(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        aspect: f32,
+    ) {
+        self.aspect = aspect;
+        self.relayout(device, queue, self.window_size);
+    }
+
+    pub fn set_fit(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        fit: FitMode,
+    ) {
+        self.fit = fit;
+        self.relayout(device, queue, self.window_size);
+    }
+
+    pub fn aspect(&self) -> f32 {
+        self.aspect
+    }
+
+    pub fn size(&self) -> PhysicalSize<u32> {
+        self.layout.canvas_size
+    }
+
+    pub fn texture(&self) -> &wgpu::Texture {
+        &self.texture
+    }
+
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    // Records a pass that clears `target` to black and draws the canvas into
+    // it according to the fit mode
+    pub fn blit(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+    ) {
+        let mut blit_pass =
+            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Canvas Blit Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: target,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+
+        let [x, y, width, height] = self.layout.viewport;
+        blit_pass.set_viewport(x, y, width, height, 0.0, 1.0);
+        blit_pass.set_pipeline(&self.pipeline);
+        blit_pass.set_bind_group(0, &self.bind_group, &[]);
+        blit_pass.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,39 @@
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+}
+
+// see: canvas::BlitUniform
+struct BlitUniform {
+    // Sub-rectangle of the canvas to show, in UVs
+    uv_offset: vec2<f32>,
+    uv_scale: vec2<f32>,
+}
+
+@group(0) @binding(0)
+var t_canvas: texture_2d<f32>;
+
+@group(0) @binding(1)
+var s_canvas: sampler;
+
+@group(0) @binding(2)
+var<uniform> blit: BlitUniform;
+
+// A single triangle that covers the whole viewport, no vertex buffer needed:
+// (-1, -1), (3, -1), (-1, 3)
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    let uv = vec2<f32>(
+        f32((vertex_index << 1u) & 2u),
+        f32(vertex_index & 2u),
+    );
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.tex_coords = blit.uv_offset + uv * blit.uv_scale;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(t_canvas, s_canvas, in.tex_coords);
+}
@@ -0,0 +1,165 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+use winit::dpi::PhysicalSize;
+
+use crate::config;
+
+// The window's size when only one of --width and --height is given, and
+// the frame's when --headless or --screenshot is given neither
+pub const DEFAULT_SIZE: PhysicalSize<u32> = PhysicalSize::new(800, 600);
+
+// What can be set on the command line. Anything left out falls back to
+// config.toml, then to the built-in defaults, and anything given here wins
+// over config.toml.
+#[derive(Debug, Clone, Default, Parser)]
+#[command(version, about = "The learn-wgpu demo scene")]
+pub struct Args {
+    #[arg(
+        long,
+        value_parser = clap::value_parser!(u32).range(1..),
+        help = "Width of the window, or of the frame with --headless"
+    )]
+    pub width: Option<u32>,
+    #[arg(
+        long,
+        value_parser = clap::value_parser!(u32).range(1..),
+        help = "Height of the window, or of the frame with --headless"
+    )]
+    pub height: Option<u32>,
+    #[arg(
+        long,
+        value_parser = parse_backends,
+        help = "Graphics backends to try, comma separated: vulkan, dx12, \
+                metal or gl. Overrides WGPU_BACKEND."
+    )]
+    pub backend: Option<wgpu::Backends>,
+    #[arg(
+        long,
+        value_parser = parse_present_mode_arg,
+        help = "auto, fifo, fifo_relaxed, mailbox, immediate, auto_vsync \
+                or auto_no_vsync"
+    )]
+    pub present_mode: Option<PresentModeArg>,
+    #[arg(
+        long,
+        value_parser = clap::value_parser!(u32).range(1..=16),
+        help = "MSAA samples, 1 for none. Lowered to what the adapter \
+                supports."
+    )]
+    pub msaa: Option<u32>,
+    #[arg(
+        long,
+        help = "Render one frame offscreen, without opening a window, and \
+                exit"
+    )]
+    pub headless: bool,
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Render one frame offscreen, save it as a PNG and exit"
+    )]
+    pub screenshot: Option<PathBuf>,
+    #[arg(long, help = "Use a software rasterizer even if there's a GPU")]
+    pub software: bool,
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Start from a render state saved with F6"
+    )]
+    pub load_state: Option<PathBuf>,
+}
+
+impl Args {
+    // The size asked for, if either side was
+    pub fn size(&self) -> Option<PhysicalSize<u32>> {
+        (self.width.is_some() || self.height.is_some()).then(|| {
+            PhysicalSize::new(
+                self.width.unwrap_or(DEFAULT_SIZE.width),
+                self.height.unwrap_or(DEFAULT_SIZE.height),
+            )
+        })
+    }
+
+    // Whether to render one frame offscreen instead of opening a window
+    pub fn offscreen(&self) -> bool {
+        self.headless || self.screenshot.is_some()
+    }
+}
+
+// A --present-mode, None being auto. Wrapped since clap takes an
+// `Option<Option<_>>` field to mean a flag with an optional value.
+#[derive(Debug, Clone, Copy)]
+pub struct PresentModeArg(pub Option<wgpu::PresentMode>);
+
+fn parse_present_mode_arg(name: &str) -> Result<PresentModeArg, String> {
+    config::parse_present_mode(name)
+        .map(PresentModeArg)
+        .map_err(|e| e.to_string())
+}
+
+// Stricter than `wgpu::Backends::from_comma_list`, which skips names it
+// doesn't know
+fn parse_backends(list: &str) -> Result<wgpu::Backends, String> {
+    list.split(',')
+        .try_fold(wgpu::Backends::empty(), |backends, name| {
+            match name.trim().to_lowercase().as_str() {
+                "vulkan" | "vk" => Ok(backends | wgpu::Backends::VULKAN),
+                "dx12" | "d3d12" => Ok(backends | wgpu::Backends::DX12),
+                "metal" | "mtl" => Ok(backends | wgpu::Backends::METAL),
+                "gl" | "gles" | "opengl" => Ok(backends | wgpu::Backends::GL),
+                other => Err(format!(
+                    "Unknown backend {:?}, expected vulkan, dx12, metal or gl",
+                    other
+                )),
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_every_option() {
+        let args = Args::try_parse_from([
+            "learn_wgpu",
+            "--width",
+            "640",
+            "--backend",
+            "vulkan,gl",
+            "--present-mode",
+            "mailbox",
+            "--msaa",
+            "1",
+            "--screenshot",
+            "out.png",
+        ])
+        .unwrap();
+        assert_eq!(args.size(), Some(PhysicalSize::new(640, 600)));
+        assert_eq!(
+            args.backend,
+            Some(wgpu::Backends::VULKAN | wgpu::Backends::GL)
+        );
+        assert_eq!(
+            args.present_mode.unwrap().0,
+            Some(wgpu::PresentMode::Mailbox)
+        );
+        assert_eq!(args.msaa, Some(1));
+        assert!(args.offscreen());
+    }
+
+    #[test]
+    fn rejects_bad_values_and_unknown_args() {
+        for bad in [
+            &["--backend", "vulkan,glide"][..],
+            &["--present-mode", "sometimes"],
+            &["--width", "0"],
+            &["--msaa", "32"],
+            &["--fullscreen"],
+        ] {
+            let args = std::iter::once("learn_wgpu").chain(bad.iter().copied());
+            assert!(Args::try_parse_from(args).is_err(), "{:?}", bad);
+        }
+    }
+}
@@ -0,0 +1,180 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::*;
+use serde::Deserialize;
+
+// Startup settings read from config.toml. Every field is optional in the
+// file; anything left out keeps the built-in default. For example:
+//
+//     title = "Demo"
+//     icon = "assets/icon.png"
+//     clear_color = [0.1, 0.2, 0.3, 1.0]
+//     present_mode = "mailbox"
+//     diffuse_texture = "assets/wide-circles.png"
+//     max_fps = 60
+//     anisotropy = 16
+//     model = "assets/scene.glb"
+//     render_scale = 0.5
+//     upscale_filter = "nearest"
+//     unclipped_depth = true
+//     background_gradient = [[0.0, 0.1, 0.3, 1.0], [0.3, 0.2, 0.1, 1.0]]
+//     grid_view = [3, 2]
+//
+//     [tile_atlas]
+//     path = "assets/tiles.png"
+//     cell_size = 16
+//     columns = 8
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    // RGBA. Setting this pins the background to it; otherwise it starts dark
+    // gray and follows the cursor.
+    pub clear_color: Option<[f64; 4]>,
+    // RGBA at the top of the window, then at the bottom. Setting this shows
+    // a gradient between them behind the scene instead of the skybox.
+    pub background_gradient: Option<[[f64; 4]; 2]>,
+    // One of auto, fifo, fifo_relaxed, mailbox, immediate, auto_vsync or
+    // auto_no_vsync. "auto" takes the first mode the surface lists.
+    pub present_mode: String,
+    pub title: String,
+    // Image file for the window icon. The built-in pentagon when left out.
+    pub icon: Option<PathBuf>,
+    // Image file to use in place of the built-in pentagon texture. Read at
+    // startup, so it can be swapped without recompiling. A block compressed
+    // .ktx2 file is uploaded as is if the GPU supports its format, and
+    // otherwise the .png of the same name is loaded instead. One that isn't
+    // square has its middle shown, cropped rather than stretched.
+    pub diffuse_texture: Option<PathBuf>,
+    // Caps the frame rate by sleeping between frames, even with a present
+    // mode that doesn't wait for vsync. Uncapped when left out.
+    pub max_fps: Option<u32>,
+    // Anisotropic filtering for the pentagon's textures, usually 4, 8 or 16.
+    // Off when left out.
+    pub anisotropy: Option<u16>,
+    // A glTF 2.0 model (.gltf or .glb) to show instead of the pentagon. Only
+    // its static meshes and base color textures are used.
+    pub model: Option<PathBuf>,
+    // Renders the scene at this fraction of the window's resolution and
+    // scales it up to fit. Full resolution when left out.
+    pub render_scale: Option<f32>,
+    // How the scene is scaled up when render_scale is below 1: nearest for
+    // crisp pixels or linear (the default) for a soft look
+    pub upscale_filter: String,
+    // Clamps the shadow map's depth instead of clipping it, where the GPU
+    // supports that
+    pub unclipped_depth: bool,
+    // The atlas the tile map demo draws from. A generated one is used when
+    // left out.
+    pub tile_atlas: Option<TileAtlas>,
+    // Columns and rows. Setting this starts with the render pipelines side
+    // by side in a grid of this size, one per cell, instead of just the
+    // active one.
+    pub grid_view: Option<[u32; 2]>,
+}
+
+// An image cut into square cells, numbered left to right and then top to
+// bottom
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TileAtlas {
+    pub path: PathBuf,
+    // Width and height of a cell in pixels
+    pub cell_size: u32,
+    // Cells per row
+    pub columns: u32,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            clear_color: None,
+            background_gradient: None,
+            present_mode: "auto".to_string(),
+            title: "Learn WGPU".to_string(),
+            icon: None,
+            diffuse_texture: None,
+            max_fps: None,
+            anisotropy: None,
+            model: None,
+            render_scale: None,
+            upscale_filter: "linear".to_string(),
+            unclipped_depth: false,
+            tile_atlas: None,
+            grid_view: None,
+        }
+    }
+}
+
+impl Config {
+    // A missing file isn't an error, it just means defaults
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = match std::fs::read_to_string(path) {
+            std::result::Result::Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                log::info!("No {}, using defaults", path.display());
+                return Ok(Self::default());
+            }
+            Err(e) => {
+                return Err(e).context(format!("Reading {}", path.display()))
+            }
+        };
+        toml::from_str(&contents)
+            .with_context(|| format!("Parsing {}", path.display()))
+    }
+
+    pub fn clear_color(&self) -> Option<wgpu::Color> {
+        self.clear_color.map(color)
+    }
+
+    // The top and bottom color
+    pub fn background_gradient(&self) -> Option<(wgpu::Color, wgpu::Color)> {
+        self.background_gradient
+            .map(|[top, bottom]| (color(top), color(bottom)))
+    }
+
+    // None means pick whatever the surface supports first. Unknown names are
+    // treated the same way, with a warning.
+    pub fn present_mode(&self) -> Option<wgpu::PresentMode> {
+        parse_present_mode(&self.present_mode).unwrap_or_else(|e| {
+            log::warn!("{}, using auto", e);
+            None
+        })
+    }
+
+    // None for an unknown name, with a warning
+    pub fn upscale_filter(&self) -> Option<wgpu::FilterMode> {
+        match self.upscale_filter.to_lowercase().as_str() {
+            "nearest" => Some(wgpu::FilterMode::Nearest),
+            "linear" => Some(wgpu::FilterMode::Linear),
+            other => {
+                log::warn!("Unknown upscale_filter {:?}, using linear", other);
+                None
+            }
+        }
+    }
+}
+
+// The present mode called `name` (in any case), as `present_mode` and
+// --present-mode take it. "auto" is None: whatever the surface supports
+// first.
+pub fn parse_present_mode(name: &str) -> Result<Option<wgpu::PresentMode>> {
+    Ok(match name.to_lowercase().as_str() {
+        "auto" => None,
+        "fifo" => Some(wgpu::PresentMode::Fifo),
+        "fifo_relaxed" => Some(wgpu::PresentMode::FifoRelaxed),
+        "mailbox" => Some(wgpu::PresentMode::Mailbox),
+        "immediate" => Some(wgpu::PresentMode::Immediate),
+        "auto_vsync" => Some(wgpu::PresentMode::AutoVsync),
+        "auto_no_vsync" => Some(wgpu::PresentMode::AutoNoVsync),
+        other => bail!(
+            "Unknown present mode {:?}, expected one of auto, fifo, \
+             fifo_relaxed, mailbox, immediate, auto_vsync or auto_no_vsync",
+            other
+        ),
+    })
+}
+
+fn color([r, g, b, a]: [f64; 4]) -> wgpu::Color {
+    wgpu::Color { r, g, b, a }
+}
@@ -0,0 +1,58 @@
+// see: instancing::InstanceRaw
+struct Instance {
+    model: mat4x4<f32>,
+}
+
+// Mirrors wgpu::util::DrawIndexedIndirectArgs. Only instance_count is
+// written here; everything else is set up on the CPU.
+struct DrawIndexedIndirectArgs {
+    index_count: u32,
+    instance_count: atomic<u32>,
+    first_index: u32,
+    base_vertex: i32,
+    first_instance: u32,
+}
+
+// see: instancing::CullUniform
+struct CullUniform {
+    // Left, right, bottom, top, near, far as (normal, d)
+    planes: array<vec4<f32>, 6>,
+    // Bounding sphere radius of the mesh in model space
+    radius: f32,
+    instance_count: u32,
+}
+
+@group(0) @binding(0)
+var<storage, read> instances: array<Instance>;
+
+@group(0) @binding(1)
+var<storage, read_write> visible_instances: array<Instance>;
+
+@group(0) @binding(2)
+var<storage, read_write> draw_args: DrawIndexedIndirectArgs;
+
+@group(0) @binding(3)
+var<uniform> cull: CullUniform;
+
+@compute @workgroup_size(64)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let index = id.x;
+    if index >= cull.instance_count {
+        return;
+    }
+
+    let instance = instances[index];
+    let center = instance.model[3].xyz;
+
+    for (var i = 0u; i < 6u; i++) {
+        let plane = cull.planes[i];
+        if dot(plane.xyz, center) + plane.w < -cull.radius {
+            return;
+        }
+    }
+
+    // Compact the survivors to the front of the output buffer. Order isn't
+    // stable between frames, which is fine for opaque geometry.
+    let slot = atomicAdd(&draw_args.instance_count, 1u);
+    visible_instances[slot] = instance;
+}
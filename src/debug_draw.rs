@@ -0,0 +1,238 @@
+use wgpu::util::DeviceExt;
+
+use crate::frustum::Aabb;
+use crate::growable_buffer::GrowableBuffer;
+use crate::pipeline::depth_stencil_state;
+
+// Grid lines run from -GRID_HALF_EXTENT to GRID_HALF_EXTENT along both x
+// and z, GRID_SPACING apart
+const GRID_HALF_EXTENT: i32 = 10;
+const GRID_SPACING: f32 = 1.0;
+const GRID_COLOR: [f32; 3] = [0.3, 0.3, 0.3];
+// The two grid lines through the origin
+const ORIGIN_LINE_COLOR: [f32; 3] = [0.7, 0.7, 0.7];
+// The positive axes, X red, Y green and Z blue
+const AXIS_LENGTH: f32 = 2.0;
+const AXES: [([f32; 3], [f32; 3]); 3] = [
+    ([1.0, 0.0, 0.0], [1.0, 0.2, 0.2]),
+    ([0.0, 1.0, 0.0], [0.2, 1.0, 0.2]),
+    ([0.0, 0.0, 1.0], [0.3, 0.4, 1.0]),
+];
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct LineVertex {
+    position: [f32; 3],
+    color: [f32; 3],
+}
+
+impl LineVertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 2] =
+        wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3];
+
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+// Room for this many lines before the first grow
+const INITIAL_LINE_CAPACITY: u64 = 256;
+
+// A ground grid on the y = 0 plane and colored XYZ axes at the origin, for
+// telling which way is which. Built once as a line list; the only thing it
+// needs each frame is the camera.
+//
+// Also lines (and boxes made of them) queued with `line` and `aabb` for just
+// the next frame, for showing rays, bounds and the like while debugging.
+// They're uploaded in one write by `prepare` and forgotten, so the cost
+// goes with how many were queued.
+pub struct DebugDraw {
+    vertex_buffer: wgpu::Buffer,
+    n_vertices: u32,
+    // Queued since the last `prepare`
+    lines: Vec<LineVertex>,
+    line_buffer: GrowableBuffer,
+    // What `line_buffer` holds for this frame
+    n_line_vertices: u32,
+}
+
+impl DebugDraw {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let vertices = grid_and_axes();
+        let vertex_buffer =
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Debug Draw Vertex Buffer"),
+                contents: bytemuck::cast_slice(&vertices),
+                usage: wgpu::BufferUsages::VERTEX,
+            });
+        let line_buffer = GrowableBuffer::new(
+            device,
+            "Debug Line Vertex Buffer",
+            wgpu::BufferUsages::VERTEX,
+            INITIAL_LINE_CAPACITY
+                * 2
+                * std::mem::size_of::<LineVertex>() as wgpu::BufferAddress,
+        );
+        Self {
+            vertex_buffer,
+            n_vertices: vertices.len() as u32,
+            lines: vec![],
+            line_buffer,
+            n_line_vertices: 0,
+        }
+    }
+
+    // Drawn in the next frame only, in world space
+    pub fn line(&mut self, from: [f32; 3], to: [f32; 3], color: [f32; 3]) {
+        self.lines.push(LineVertex {
+            position: from,
+            color,
+        });
+        self.lines.push(LineVertex {
+            position: to,
+            color,
+        });
+    }
+
+    // The box's 12 edges, drawn in the next frame only
+    pub fn aabb(&mut self, aabb: &Aabb, color: [f32; 3]) {
+        let (min, max) = (aabb.min, aabb.max);
+        let corner = |i: usize| {
+            [
+                if i & 1 == 0 { min.x } else { max.x },
+                if i & 2 == 0 { min.y } else { max.y },
+                if i & 4 == 0 { min.z } else { max.z },
+            ]
+        };
+        // Corners one bit apart share an edge
+        for i in 0..8 {
+            for bit in [1, 2, 4] {
+                if i & bit == 0 {
+                    self.line(corner(i), corner(i | bit), color);
+                }
+            }
+        }
+    }
+
+    // Uploads what's been queued for this frame and starts a new queue for
+    // the next
+    pub fn prepare(&mut self, queue: &wgpu::Queue) {
+        self.n_line_vertices = self.lines.len() as u32;
+        if !self.lines.is_empty() {
+            self.line_buffer.write(queue, &self.lines);
+            self.lines.clear();
+        }
+    }
+
+    // Expects the caller to have set a pipeline from `create_pipeline` and
+    // the camera at group 0
+    pub fn draw_grid(&self, render_pass: &mut wgpu::RenderPass) {
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.draw(0..self.n_vertices, 0..1);
+    }
+
+    // Whether `prepare` found anything for `draw_lines` to draw
+    pub fn has_lines(&self) -> bool {
+        self.n_line_vertices > 0
+    }
+
+    // Same expectations as `draw_grid`. Does nothing without any lines.
+    pub fn draw_lines(&self, render_pass: &mut wgpu::RenderPass) {
+        if self.has_lines() {
+            render_pass.set_vertex_buffer(0, self.line_buffer.slice());
+            render_pass.draw(0..self.n_line_vertices, 0..1);
+        }
+    }
+}
+
+// Lines go in the scene pass, so they're multisampled and depth tested like
+// everything else there, and hidden behind whatever's in front of them.
+// LessEqual lets the axes, drawn after the grid, win over the grid lines
+// they lie on.
+pub fn create_pipeline(
+    device: &wgpu::Device,
+    surface_configuration: &wgpu::SurfaceConfiguration,
+    camera_bind_group_layout: &wgpu::BindGroupLayout,
+    sample_count: u32,
+    depth_format: wgpu::TextureFormat,
+) -> wgpu::RenderPipeline {
+    let shader =
+        device.create_shader_module(wgpu::include_wgsl!("debug_draw.wgsl"));
+    let layout =
+        device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Debug Draw Pipeline Layout"),
+            bind_group_layouts: &[camera_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Debug Draw Pipeline"),
+        layout: Some(&layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: Some("vs_main"),
+            buffers: &[LineVertex::desc()],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: Some("fs_main"),
+            targets: &[Some(wgpu::ColorTargetState {
+                format: surface_configuration.format,
+                blend: Some(wgpu::BlendState::REPLACE),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        }),
+        // Lines have no facing, so nothing to cull
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::LineList,
+            ..Default::default()
+        },
+        depth_stencil: Some(depth_stencil_state(
+            depth_format,
+            wgpu::CompareFunction::LessEqual,
+            true,
+        )),
+        multisample: wgpu::MultisampleState {
+            count: sample_count,
+            ..Default::default()
+        },
+        multiview: None,
+        cache: None,
+    })
+}
+
+fn grid_and_axes() -> Vec<LineVertex> {
+    let mut vertices = vec![];
+    let mut line = |from: [f32; 3], to: [f32; 3], color: [f32; 3]| {
+        vertices.push(LineVertex {
+            position: from,
+            color,
+        });
+        vertices.push(LineVertex {
+            position: to,
+            color,
+        });
+    };
+
+    let extent = GRID_HALF_EXTENT as f32 * GRID_SPACING;
+    for i in -GRID_HALF_EXTENT..=GRID_HALF_EXTENT {
+        let offset = i as f32 * GRID_SPACING;
+        let color = if i == 0 {
+            ORIGIN_LINE_COLOR
+        } else {
+            GRID_COLOR
+        };
+        line([offset, 0.0, -extent], [offset, 0.0, extent], color);
+        line([-extent, 0.0, offset], [extent, 0.0, offset], color);
+    }
+    for (direction, color) in AXES {
+        line([0.0; 3], direction.map(|d| d * AXIS_LENGTH), color);
+    }
+    vertices
+}
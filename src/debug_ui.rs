@@ -0,0 +1,471 @@
+use std::time::Instant;
+
+use winit::event::{
+    ElementState, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent,
+};
+use winit::keyboard::{Key, NamedKey};
+
+use crate::renderer::{Overlay, Renderer};
+use crate::{gbuffer, material};
+
+// An egui panel drawn over the finished frame.
+//
+// egui-winit would normally turn window events into egui input, but the
+// releases that match egui-wgpu's wgpu version need winit 0.30. The handful
+// of events a debug panel needs are simple enough to translate here instead.
+pub struct DebugUi {
+    context: egui::Context,
+    renderer: egui_wgpu::Renderer,
+    start_time: Instant,
+    pixels_per_point: f32,
+    // Input gathered since the last frame
+    events: Vec<egui::Event>,
+    modifiers: egui::Modifiers,
+    pointer_position: egui::Pos2,
+    // Produced by `run`, consumed by `paint`
+    frame: Option<egui::FullOutput>,
+    pub visible: bool,
+}
+
+impl DebugUi {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        pixels_per_point: f32,
+    ) -> Self {
+        Self {
+            context: egui::Context::default(),
+            renderer: egui_wgpu::Renderer::new(device, format, None, 1, false),
+            start_time: Instant::now(),
+            pixels_per_point,
+            events: vec![],
+            modifiers: egui::Modifiers::default(),
+            pointer_position: egui::Pos2::ZERO,
+            frame: None,
+            visible: true,
+        }
+    }
+
+    // Kept up to date by the owner even while the panel is hidden
+    pub fn set_pixels_per_point(&mut self, pixels_per_point: f32) {
+        self.pixels_per_point = pixels_per_point;
+    }
+
+    // Returns true when egui wants the event for itself, e.g. a click on the
+    // panel or typing into a text field, in which case the app shouldn't also
+    // act on it. Escape is never claimed so it can always quit.
+    pub fn on_window_event(&mut self, event: &WindowEvent) -> bool {
+        if !self.visible {
+            return false;
+        }
+
+        match event {
+            WindowEvent::Focused(focused) => {
+                self.events.push(egui::Event::WindowFocused(*focused));
+                false
+            }
+            WindowEvent::ModifiersChanged(modifiers) => {
+                let state = modifiers.state();
+                self.modifiers = egui::Modifiers {
+                    alt: state.alt_key(),
+                    ctrl: state.control_key(),
+                    shift: state.shift_key(),
+                    mac_cmd: cfg!(target_os = "macos") && state.super_key(),
+                    command: if cfg!(target_os = "macos") {
+                        state.super_key()
+                    } else {
+                        state.control_key()
+                    },
+                };
+                false
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                self.pointer_position = egui::pos2(
+                    position.x as f32 / self.pixels_per_point,
+                    position.y as f32 / self.pixels_per_point,
+                );
+                self.events
+                    .push(egui::Event::PointerMoved(self.pointer_position));
+                self.context.wants_pointer_input()
+            }
+            WindowEvent::CursorLeft { .. } => {
+                self.events.push(egui::Event::PointerGone);
+                false
+            }
+            WindowEvent::MouseInput { state, button, .. } => {
+                let button = match button {
+                    MouseButton::Left => egui::PointerButton::Primary,
+                    MouseButton::Right => egui::PointerButton::Secondary,
+                    MouseButton::Middle => egui::PointerButton::Middle,
+                    _ => return false,
+                };
+                self.events.push(egui::Event::PointerButton {
+                    pos: self.pointer_position,
+                    button,
+                    pressed: *state == ElementState::Pressed,
+                    modifiers: self.modifiers,
+                });
+                // Releases always go through too so a camera drag that ends
+                // over the panel still ends
+                *state == ElementState::Pressed
+                    && self.context.wants_pointer_input()
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                let (unit, delta) = match delta {
+                    MouseScrollDelta::LineDelta(x, y) => {
+                        (egui::MouseWheelUnit::Line, egui::vec2(*x, *y))
+                    }
+                    MouseScrollDelta::PixelDelta(position) => (
+                        egui::MouseWheelUnit::Point,
+                        egui::vec2(position.x as f32, position.y as f32)
+                            / self.pixels_per_point,
+                    ),
+                };
+                self.events.push(egui::Event::MouseWheel {
+                    unit,
+                    delta,
+                    modifiers: self.modifiers,
+                });
+                self.context.is_pointer_over_area()
+            }
+            WindowEvent::KeyboardInput { event, .. } => {
+                self.on_key_event(event)
+            }
+            _ => false,
+        }
+    }
+
+    fn on_key_event(&mut self, event: &KeyEvent) -> bool {
+        let pressed = event.state == ElementState::Pressed;
+
+        let name = match &event.logical_key {
+            Key::Named(NamedKey::Escape) => return false,
+            Key::Named(named) => format!("{:?}", named),
+            Key::Character(c) => c.to_string(),
+            _ => return false,
+        };
+        if let Some(key) = egui::Key::from_name(&name) {
+            self.events.push(egui::Event::Key {
+                key,
+                physical_key: None,
+                pressed,
+                repeat: event.repeat,
+                modifiers: self.modifiers,
+            });
+        }
+
+        if let Some(text) = &event.text {
+            if pressed && !text.chars().any(char::is_control) {
+                self.events.push(egui::Event::Text(text.to_string()));
+            }
+        }
+
+        self.context.wants_keyboard_input()
+    }
+
+    // Lays out the UI for this frame with `build`. Call before `paint`.
+    pub fn run(
+        &mut self,
+        size: winit::dpi::PhysicalSize<u32>,
+        build: impl FnMut(&egui::Context),
+    ) {
+        if !self.visible {
+            self.events.clear();
+            self.frame = None;
+            return;
+        }
+
+        let mut raw_input = egui::RawInput {
+            screen_rect: Some(egui::Rect::from_min_size(
+                egui::Pos2::ZERO,
+                egui::vec2(size.width as f32, size.height as f32)
+                    / self.pixels_per_point,
+            )),
+            time: Some(self.start_time.elapsed().as_secs_f64()),
+            modifiers: self.modifiers,
+            events: std::mem::take(&mut self.events),
+            focused: true,
+            ..Default::default()
+        };
+        raw_input
+            .viewports
+            .entry(egui::ViewportId::ROOT)
+            .or_default()
+            .native_pixels_per_point = Some(self.pixels_per_point);
+
+        self.frame = Some(self.context.run(raw_input, build));
+    }
+
+    // Draws whatever the last `run` produced on top of the frame
+    pub fn paint(&mut self, overlay: Overlay) {
+        let Some(frame) = self.frame.take() else {
+            return;
+        };
+        let Overlay {
+            device,
+            queue,
+            encoder,
+            view,
+            size,
+        } = overlay;
+
+        let paint_jobs = self
+            .context
+            .tessellate(frame.shapes, frame.pixels_per_point);
+        let screen_descriptor = egui_wgpu::ScreenDescriptor {
+            size_in_pixels: [size.width, size.height],
+            pixels_per_point: frame.pixels_per_point,
+        };
+
+        for (id, image_delta) in &frame.textures_delta.set {
+            self.renderer
+                .update_texture(device, queue, *id, image_delta);
+        }
+        // Only paint callbacks produce extra command buffers and the panel
+        // doesn't use any
+        self.renderer.update_buffers(
+            device,
+            queue,
+            encoder,
+            &paint_jobs,
+            &screen_descriptor,
+        );
+
+        {
+            let render_pass =
+                encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("Debug UI Render Pass"),
+                    color_attachments: &[Some(
+                        wgpu::RenderPassColorAttachment {
+                            view,
+                            resolve_target: None,
+                            ops: wgpu::Operations {
+                                load: wgpu::LoadOp::Load,
+                                store: wgpu::StoreOp::Store,
+                            },
+                        },
+                    )],
+                    depth_stencil_attachment: None,
+                    occlusion_query_set: None,
+                    timestamp_writes: None,
+                });
+            // egui-wgpu wants a pass that isn't tied to the encoder's borrow
+            self.renderer.render(
+                &mut render_pass.forget_lifetime(),
+                &paint_jobs,
+                &screen_descriptor,
+            );
+        }
+
+        for id in &frame.textures_delta.free {
+            self.renderer.free_texture(id);
+        }
+    }
+}
+
+// Frame time, the clear color and which pentagon pipeline is active, plus the
+// main scene toggles
+pub fn panel(
+    context: &egui::Context,
+    renderer: &mut Renderer,
+    cursor_clear_color: &mut bool,
+) {
+    egui::Window::new("Debug")
+        .anchor(egui::Align2::RIGHT_TOP, [-16.0, 16.0])
+        .resizable(false)
+        .show(context, |ui| {
+            let frame_time_ms = renderer.frame_time_ms;
+            ui.label(format!(
+                "{:.1} fps ({:.2}ms)",
+                1000.0 / frame_time_ms.max(f32::EPSILON),
+                frame_time_ms
+            ));
+            let mut render_scale = renderer.render_scale();
+            let slider = egui::Slider::new(&mut render_scale, 0.1..=1.0)
+                .text("Render scale");
+            if ui.add(slider).changed() {
+                renderer.set_render_scale(render_scale);
+            }
+            let mut nearest =
+                renderer.upscale_filter() == wgpu::FilterMode::Nearest;
+            if ui.checkbox(&mut nearest, "Nearest upscaling").changed() {
+                renderer.toggle_upscale_filter();
+            }
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                let color = renderer.clear_color;
+                let mut rgb = [color.r as f32, color.g as f32, color.b as f32];
+                if ui.color_edit_button_rgb(&mut rgb).changed() {
+                    renderer.clear_color = wgpu::Color {
+                        r: rgb[0] as f64,
+                        g: rgb[1] as f64,
+                        b: rgb[2] as f64,
+                        a: color.a,
+                    };
+                    // Otherwise the next mouse move would undo it
+                    *cursor_clear_color = false;
+                }
+                ui.label("Clear color");
+            });
+            ui.checkbox(cursor_clear_color, "Clear color follows cursor");
+            ui.checkbox(&mut renderer.show_gradient, "Background gradient");
+            ui.horizontal(|ui| {
+                let (mut top, mut bottom) = renderer.gradient_colors();
+                let mut changed = false;
+                for color in [&mut top, &mut bottom] {
+                    let mut rgb =
+                        [color.r as f32, color.g as f32, color.b as f32];
+                    if ui.color_edit_button_rgb(&mut rgb).changed() {
+                        color.r = rgb[0] as f64;
+                        color.g = rgb[1] as f64;
+                        color.b = rgb[2] as f64;
+                        changed = true;
+                    }
+                }
+                if changed {
+                    renderer.set_gradient_colors(top, bottom);
+                }
+                ui.label("Gradient top and bottom");
+            });
+
+            ui.separator();
+            let mut active = renderer.active_render_pipeline_index();
+            for (index, name) in
+                renderer.render_pipeline_names().iter().copied().enumerate()
+            {
+                ui.radio_value(&mut active, index, name);
+            }
+            if active != renderer.active_render_pipeline_index() {
+                renderer.set_active_render_pipeline(active);
+            }
+
+            ui.separator();
+            ui.checkbox(&mut renderer.use_ortho_camera, "Orthographic camera");
+            ui.checkbox(&mut renderer.show_skybox, "Skybox");
+            ui.checkbox(&mut renderer.show_terrain, "Terrain");
+            ui.add_enabled(
+                renderer.has_heightmap(),
+                egui::Checkbox::new(
+                    &mut renderer.show_heightmap,
+                    "Heightmap terrain",
+                ),
+            );
+            ui.checkbox(&mut renderer.show_grid, "Grid and axes");
+            ui.checkbox(&mut renderer.show_instances, "Instance field");
+            ui.checkbox(&mut renderer.show_objects, "Separate objects");
+            ui.add_enabled(
+                renderer.has_model(),
+                egui::Checkbox::new(&mut renderer.show_model, "Loaded model"),
+            );
+            ui.checkbox(&mut renderer.show_shadows, "Shadows");
+            ui.checkbox(&mut renderer.show_mirror, "Mirror");
+            ui.checkbox(&mut renderer.show_morph, "Morph into a star");
+            ui.add_enabled(
+                renderer.has_linear_output(),
+                egui::Checkbox::new(
+                    &mut renderer.linear_view,
+                    "Linear output view (shader encodes sRGB)",
+                ),
+            );
+            let render_stats = renderer.render_stats();
+            ui.label(format!(
+                "Draw calls: {}  Triangles: {} submitted, {} after culling",
+                render_stats.draw_calls,
+                render_stats.triangles_submitted,
+                render_stats.triangles_after_culling
+            ));
+            ui.label(format!(
+                "Entity state changes: {} pipeline, {} material",
+                render_stats.pipeline_switches, render_stats.material_switches
+            ));
+            if !renderer.show_instances {
+                let stats = renderer.entity_stats();
+                ui.label(format!(
+                    "Entities: {} drawn, {} culled",
+                    stats.drawn, stats.culled
+                ));
+                ui.label(match renderer.triangle_limit {
+                    Some(limit) => format!("Triangles per entity: {}", limit),
+                    None => "Triangles per entity: all".to_string(),
+                });
+                ui.add_enabled(
+                    renderer.entity_sample_counts().is_some(),
+                    egui::Checkbox::new(
+                        &mut renderer.count_occlusion,
+                        "Count visible samples",
+                    ),
+                );
+                if let (true, Some(counts)) =
+                    (renderer.count_occlusion, renderer.entity_sample_counts())
+                {
+                    egui::CollapsingHeader::new("Samples per entity").show(
+                        ui,
+                        |ui| {
+                            for (index, count) in counts.iter().enumerate() {
+                                ui.label(format!("{}: {}", index, count));
+                            }
+                        },
+                    );
+                }
+            }
+            ui.checkbox(&mut renderer.show_sprites, "Sprites");
+            ui.checkbox(&mut renderer.show_tile_map, "Tile map");
+            ui.checkbox(&mut renderer.show_particles, "Particles");
+            ui.checkbox(&mut renderer.show_translucent, "Translucent quads");
+            ui.checkbox(&mut renderer.show_stencil_reveal, "Stencil reveal");
+            ui.checkbox(&mut renderer.show_normals, "Normals");
+            ui.add_enabled(
+                renderer.can_show_depth(),
+                egui::Checkbox::new(&mut renderer.show_depth, "Depth buffer"),
+            );
+            ui.add_enabled_ui(!renderer.show_instances, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("G-buffer");
+                    for view in gbuffer::GBufferView::ALL {
+                        ui.radio_value(
+                            &mut renderer.gbuffer_view,
+                            view,
+                            view.name(),
+                        );
+                    }
+                });
+            });
+            ui.checkbox(&mut renderer.show_labels, "Labels");
+            ui.add_enabled(
+                renderer.show_labels,
+                egui::Checkbox::new(
+                    &mut renderer.occlude_labels,
+                    "Hide labels behind geometry",
+                ),
+            );
+
+            ui.add(
+                egui::Slider::new(&mut renderer.detail_mix, 0.0..=1.0)
+                    .text("Detail mix"),
+            );
+
+            ui.checkbox(&mut renderer.show_specular, "Specular highlights");
+            let mut shininess = renderer.shininess();
+            let slider = egui::Slider::new(
+                &mut shininess,
+                material::MIN_SHININESS..=material::MAX_SHININESS,
+            )
+            .logarithmic(true)
+            .text("Shininess");
+            if ui.add_enabled(renderer.show_specular, slider).changed() {
+                renderer.set_shininess(shininess);
+            }
+
+            let mut reflectivity = renderer.reflectivity();
+            if ui
+                .add(
+                    egui::Slider::new(&mut reflectivity, 0.0..=1.0)
+                        .text("Reflectivity"),
+                )
+                .changed()
+            {
+                renderer.set_reflectivity(reflectivity);
+            }
+        });
+}
@@ -0,0 +1,250 @@
+// see: depth_view.wgsl DepthViewUniform
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DepthViewUniform {
+    near: f32,
+    far: f32,
+    orthographic: u32,
+    _padding: u32,
+}
+
+impl DepthViewUniform {
+    // The clip planes of the projection the depth buffer was drawn with
+    pub fn new(near: f32, far: f32, orthographic: bool) -> Self {
+        Self {
+            near,
+            far,
+            orthographic: orthographic as u32,
+            _padding: 0,
+        }
+    }
+}
+
+// A debug view of the depth buffer: a fullscreen pass that reads the scene's
+// depth and writes it out linearized as grayscale, see depth_view.wgsl.
+//
+// Reading depth differs from reading color in two ways. The binding's sample
+// type is `Depth` rather than `Float`, and the sampler can't filter: depth
+// only gets filtered through comparison samplers (like the shadow map's),
+// which return a pass/fail rather than the depth itself. A multisampled
+// depth buffer can't be sampled at all, so that case has its own layout and
+// entry point that loads one sample instead.
+//
+// GL is the exception: naga can only translate depth texture reads to GLSL
+// as comparisons. Depth textures may also be bound as unfilterable float
+// textures, with the depth in the red channel, so on GL they are. A
+// multisampled one can't be bound there at all, see the renderer's
+// depth_can_be_sampled.
+pub struct DepthView {
+    // Indexed by whether the depth buffer is multisampled
+    bind_group_layouts: [wgpu::BindGroupLayout; 2],
+    pipelines: [wgpu::RenderPipeline; 2],
+    sampler: wgpu::Sampler,
+    uniform_buffer: wgpu::Buffer,
+    uniform_bind_group: wgpu::BindGroup,
+}
+
+impl DepthView {
+    // `format` is what `draw` will write to, `backend` the device's
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        backend: wgpu::Backend,
+    ) -> Self {
+        let as_float = backend == wgpu::Backend::Gl;
+        let sample_type = if as_float {
+            wgpu::TextureSampleType::Float { filterable: false }
+        } else {
+            wgpu::TextureSampleType::Depth
+        };
+        // Same order as `bind_group_layouts`
+        let entry_points = if as_float {
+            ["fs_float", "fs_float_multisampled"]
+        } else {
+            ["fs_main", "fs_multisampled"]
+        };
+
+        let bind_group_layouts = [false, true].map(|multisampled| {
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Depth View Bind Group Layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(
+                            wgpu::SamplerBindingType::NonFiltering,
+                        ),
+                        count: None,
+                    },
+                ],
+            })
+        });
+
+        let uniform_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Depth View Uniform Bind Group Layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            });
+
+        let shader =
+            device.create_shader_module(wgpu::include_wgsl!("depth_view.wgsl"));
+
+        let pipelines = [0, 1].map(|layout_index| {
+            let layout = device.create_pipeline_layout(
+                &wgpu::PipelineLayoutDescriptor {
+                    label: Some("Depth View Pipeline Layout"),
+                    bind_group_layouts: &[
+                        &bind_group_layouts[layout_index],
+                        &uniform_layout,
+                    ],
+                    push_constant_ranges: &[],
+                },
+            );
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Depth View Pipeline"),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[],
+                    compilation_options:
+                        wgpu::PipelineCompilationOptions::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some(entry_points[layout_index]),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format,
+                        blend: Some(wgpu::BlendState::REPLACE),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options:
+                        wgpu::PipelineCompilationOptions::default(),
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            })
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Depth View Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            compare: None,
+            ..Default::default()
+        });
+
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Depth View Uniform Buffer"),
+            size: std::mem::size_of::<DepthViewUniform>()
+                as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let uniform_bind_group =
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Depth View Uniform Bind Group"),
+                layout: &uniform_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                }],
+            });
+
+        Self {
+            bind_group_layouts,
+            pipelines,
+            sampler,
+            uniform_buffer,
+            uniform_bind_group,
+        }
+    }
+
+    // Overwrites all of `output` with `depth_texture`, stretched to fit.
+    // The depth texture's bind group is made here each time rather than
+    // kept, since it's only drawn while debugging and the depth texture is
+    // replaced on every resize or sample count change.
+    pub fn draw(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        depth_texture: &wgpu::Texture,
+        output: &wgpu::TextureView,
+        uniform: DepthViewUniform,
+    ) {
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[uniform]),
+        );
+
+        let multisampled = depth_texture.sample_count() > 1;
+        // A depth-stencil texture can only be bound one aspect at a time
+        let depth_view =
+            depth_texture.create_view(&wgpu::TextureViewDescriptor {
+                label: Some("Depth View Texture View"),
+                aspect: wgpu::TextureAspect::DepthOnly,
+                ..Default::default()
+            });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Depth View Bind Group"),
+            layout: &self.bind_group_layouts[multisampled as usize],
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&depth_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        });
+
+        let mut render_pass =
+            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Depth View Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: output,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        // Every pixel gets overwritten
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+        render_pass.set_pipeline(&self.pipelines[multisampled as usize]);
+        render_pass.set_bind_group(0, &bind_group, &[]);
+        render_pass.set_bind_group(1, &self.uniform_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
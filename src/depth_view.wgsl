@@ -0,0 +1,101 @@
+// Draws the scene's depth buffer as grayscale, near black and far white
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+}
+
+// see: depth_view::DepthViewUniform
+struct DepthViewUniform {
+    near: f32,
+    far: f32,
+    // 1 when the depth came from an orthographic projection
+    orthographic: u32,
+}
+
+// Only one of these is bound at a time, matching the depth buffer's sample
+// count and the backend: each entry point below reads one. A multisampled
+// texture can't be sampled, only loaded from.
+@group(0) @binding(0)
+var t_depth: texture_depth_2d;
+
+@group(0) @binding(0)
+var t_depth_multisampled: texture_depth_multisampled_2d;
+
+// GL can't read depth textures other than through comparisons, so there
+// they're bound as plain unfilterable float textures instead, see
+// depth_view::DepthView
+@group(0) @binding(0)
+var t_depth_float: texture_2d<f32>;
+
+@group(0) @binding(0)
+var t_depth_float_multisampled: texture_multisampled_2d<f32>;
+
+// Depth textures can only be filtered through comparison samplers, so this
+// one is non-filtering (nearest)
+@group(0) @binding(1)
+var s_depth: sampler;
+
+@group(1) @binding(0)
+var<uniform> depth_view: DepthViewUniform;
+
+// A single triangle covering the screen: (-1, -1), (3, -1), (-1, 3)
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    let uv = vec2<f32>(
+        f32((vertex_index << 1u) & 2u),
+        f32(vertex_index & 2u),
+    );
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.tex_coords = uv;
+    return out;
+}
+
+// A perspective projection stores depth as roughly 1/z, so nearly all of
+// 0..1 is used up within a few units of the near plane and everything else
+// comes out white. Undoing it gives the distance from the camera, which is
+// then spread evenly over near..far. An orthographic projection's depth is
+// linear already.
+fn linearize(depth: f32) -> f32 {
+    let near = depth_view.near;
+    let far = depth_view.far;
+    if (depth_view.orthographic == 1u) {
+        return depth;
+    }
+    let z = near * far / (far - depth * (far - near));
+    return (z - near) / (far - near);
+}
+
+fn gray(depth: f32) -> vec4<f32> {
+    let value = linearize(depth);
+    return vec4<f32>(value, value, value, 1.0);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return gray(textureSample(t_depth, s_depth, in.tex_coords));
+}
+
+// The pixel under `tex_coords` in a texture of `size`
+fn texel(tex_coords: vec2<f32>, size: vec2<u32>) -> vec2<i32> {
+    return min(vec2<i32>(tex_coords * vec2<f32>(size)), vec2<i32>(size) - 1);
+}
+
+// Shows the first sample, there's no resolving depth
+@fragment
+fn fs_multisampled(in: VertexOutput) -> @location(0) vec4<f32> {
+    let pixel = texel(in.tex_coords, textureDimensions(t_depth_multisampled));
+    return gray(textureLoad(t_depth_multisampled, pixel, 0));
+}
+
+@fragment
+fn fs_float(in: VertexOutput) -> @location(0) vec4<f32> {
+    return gray(textureSample(t_depth_float, s_depth, in.tex_coords).r);
+}
+
+@fragment
+fn fs_float_multisampled(in: VertexOutput) -> @location(0) vec4<f32> {
+    let pixel = texel(in.tex_coords, textureDimensions(t_depth_float_multisampled));
+    return gray(textureLoad(t_depth_float_multisampled, pixel, 0).r);
+}
@@ -0,0 +1,81 @@
+// What changes from one scene draw to the next besides the model matrix:
+// for now just a tint the object's color is multiplied by. Small enough for
+// push constants, which are written straight into the command stream with
+// `set_push_constants` before each draw instead of going through a buffer,
+// the cheapest way there is to get a few bytes to a shader.
+//
+// Push constants aren't part of WebGPU, so they're a native-only feature.
+// Without it the tint goes in each object's slot of the dynamic offset
+// uniform buffer instead (see objects::ObjectUniform), which works
+// everywhere at the cost of a buffer write per frame. Which one is used is
+// hidden from fs_object behind `draw_tint()`, defined by one of the two
+// snippets below appended to the shader source.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DrawConstants {
+    pub tint: [f32; 4],
+}
+
+pub const SIZE: u32 = std::mem::size_of::<DrawConstants>() as u32;
+
+// Only fs_object reads them
+pub const STAGES: wgpu::ShaderStages = wgpu::ShaderStages::FRAGMENT;
+
+const PUSH_CONSTANT_RANGES: &[wgpu::PushConstantRange] =
+    &[wgpu::PushConstantRange {
+        stages: STAGES,
+        range: 0..SIZE,
+    }];
+
+const PUSH_CONSTANT_SOURCE: &str = "\
+// see: draw_constants::DrawConstants
+struct DrawConstants {
+    tint: vec4<f32>,
+}
+
+var<push_constant> draw_constants: DrawConstants;
+
+fn draw_tint() -> vec4<f32> {
+    return draw_constants.tint;
+}
+";
+
+// `object` is declared further up, in the shader this is appended to
+const UNIFORM_SOURCE: &str = "\
+fn draw_tint() -> vec4<f32> {
+    return object.tint;
+}
+";
+
+// Whether `device` was created with push constants and room for
+// DrawConstants in them. `Gpu::request_device` asks for both when the
+// adapter has them.
+pub fn push_constants_supported(device: &wgpu::Device) -> bool {
+    device.features().contains(wgpu::Features::PUSH_CONSTANTS)
+        && device.limits().max_push_constant_size >= SIZE
+}
+
+// The pipeline layout's push constant ranges for object pipelines
+pub fn push_constant_ranges(
+    push_constants: bool,
+) -> &'static [wgpu::PushConstantRange] {
+    if push_constants {
+        PUSH_CONSTANT_RANGES
+    } else {
+        &[]
+    }
+}
+
+// `source` (a shader with an fs_object calling `draw_tint()` and an
+// `object` uniform with a `tint`) with `draw_tint()` defined to read
+// whichever of the two holds it. Added at the end so line numbers in
+// errors still match `source`; WGSL doesn't care what order things are
+// declared in.
+pub fn shader_source(source: &str, push_constants: bool) -> String {
+    let definition = if push_constants {
+        PUSH_CONSTANT_SOURCE
+    } else {
+        UNIFORM_SOURCE
+    };
+    format!("{}\n{}", source, definition)
+}
@@ -0,0 +1,66 @@
+use std::time::{Duration, Instant};
+
+// Frame rate caps F steps through, None being uncapped
+pub const FRAME_RATE_CAPS: [Option<u32>; 4] =
+    [None, Some(30), Some(60), Some(120)];
+
+// OS timers can wake a thread late (Windows' default resolution is around
+// 15ms), so sleeping stops this far short of the deadline and the rest is
+// spent spinning
+const SPIN_TIME: Duration = Duration::from_millis(1);
+
+pub fn frame_time(fps: u32) -> Duration {
+    Duration::from_secs(1) / fps.max(1)
+}
+
+// Returns straight away if the deadline has already passed
+pub fn sleep_until(deadline: Instant) {
+    let Some(remaining) = deadline.checked_duration_since(Instant::now())
+    else {
+        return;
+    };
+    if remaining > SPIN_TIME {
+        std::thread::sleep(remaining - SPIN_TIME);
+    }
+    while Instant::now() < deadline {
+        std::hint::spin_loop();
+    }
+}
+
+// How far the simulation moves on per step, however often frames are drawn
+pub const FIXED_TIMESTEP: f32 = 1.0 / 60.0;
+// The most steps a single frame catches up on. After a long stall (dragging
+// the window, a breakpoint) the rest is dropped, instead of running so many
+// steps that the next frame stalls too and has even more to catch up on.
+const MAX_STEPS_PER_FRAME: u32 = 6;
+// The longest frame that counts in full, for anything else moved by frame
+// time rather than in steps
+pub const MAX_FRAME_TIME: f32 = MAX_STEPS_PER_FRAME as f32 * FIXED_TIMESTEP;
+
+// Runs a simulation in fixed steps of FIXED_TIMESTEP, decoupled from the
+// frame rate: each frame's time goes into an accumulator and comes back out
+// a whole step at a time, with whatever's left over carried on to the next
+// frame. `alpha` is how far that leftover is into the next step, for
+// drawing the frame in between the last two steps.
+#[derive(Debug, Default)]
+pub struct FixedTimestep {
+    // Seconds not yet stepped through, less than a step after `advance`
+    accumulator: f32,
+}
+
+impl FixedTimestep {
+    // Adds a frame `frame_time` seconds long and returns how many steps to
+    // run for it. Zero when frames come faster than steps.
+    pub fn advance(&mut self, frame_time: f32) -> u32 {
+        self.accumulator = (self.accumulator + frame_time).min(MAX_FRAME_TIME);
+        let steps = (self.accumulator / FIXED_TIMESTEP) as u32;
+        self.accumulator -= steps as f32 * FIXED_TIMESTEP;
+        steps
+    }
+
+    // From 0, drawn right at the last step, to just short of 1, a whole
+    // step on
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator / FIXED_TIMESTEP).clamp(0.0, 1.0)
+    }
+}
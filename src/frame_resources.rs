@@ -0,0 +1,43 @@
+// A ring of per-frame copies of whatever the CPU rewrites every frame, one
+// per frame that can be in flight (see `Renderer::set_frame_latency`).
+// `advance` moves on to the next slot at the start of each frame, so the
+// CPU writes into a copy the GPU finished reading frames ago rather than
+// the one a queued frame is still drawing with.
+pub struct FrameResources<T> {
+    slots: Vec<T>,
+    current: usize,
+}
+
+impl<T> FrameResources<T> {
+    // `count` slots made by `make`, at least one
+    pub fn new(count: usize, make: impl FnMut() -> T) -> Self {
+        let mut frames = Self {
+            slots: Vec::new(),
+            current: 0,
+        };
+        frames.resize(count, make);
+        frames
+    }
+
+    // Adds slots made by `make` or drops the extra ones. Starts over from
+    // the first slot, which is only as safe as the frames already in
+    // flight finishing first, so only resize between frames.
+    pub fn resize(&mut self, count: usize, make: impl FnMut() -> T) {
+        let count = count.max(1);
+        self.slots.truncate(count);
+        let missing = count - self.slots.len();
+        self.slots
+            .extend(std::iter::repeat_with(make).take(missing));
+        self.current = 0;
+    }
+
+    // The slot for the frame being prepared
+    pub fn current(&self) -> &T {
+        &self.slots[self.current]
+    }
+
+    // Call once at the start of each frame
+    pub fn advance(&mut self) {
+        self.current = (self.current + 1) % self.slots.len();
+    }
+}
@@ -0,0 +1,239 @@
+use cgmath::{Matrix, Matrix4, Point3, Vector4};
+
+// A plane stored as (normal.xyz, d) where points p with dot(normal, p) + d >= 0
+// are on the inside.
+pub type Plane = [f32; 4];
+
+#[derive(Debug, Clone, Copy)]
+pub struct Frustum {
+    // Left, right, bottom, top, near, far
+    pub planes: [Plane; 6],
+}
+
+impl Frustum {
+    // Gribb/Hartmann plane extraction. wgpu's clip space has 0 <= z <= w, so
+    // the near plane is just the third row rather than row4 + row3 as in
+    // OpenGL's -w <= z <= w.
+    pub fn from_view_proj(view_proj: &Matrix4<f32>) -> Self {
+        let row = |i: usize| -> Vector4<f32> { view_proj.row(i) };
+        let (r0, r1, r2, r3) = (row(0), row(1), row(2), row(3));
+
+        let planes =
+            [r3 + r0, r3 - r0, r3 + r1, r3 - r1, r2, r3 - r2].map(|p| {
+                let length = (p.x * p.x + p.y * p.y + p.z * p.z).sqrt();
+                (p / length).into()
+            });
+
+        Self { planes }
+    }
+
+    // False only when the box is entirely outside one of the planes. A box
+    // that's outside the frustum but straddles the extension of two planes,
+    // near a corner, still counts as intersecting, so this can keep
+    // something that's actually off screen but never drops something that's
+    // on it.
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|&[x, y, z, d]| {
+            // The corner furthest along the plane's normal. If even that one
+            // is outside, the whole box is.
+            let corner = [
+                if x >= 0.0 { aabb.max.x } else { aabb.min.x },
+                if y >= 0.0 { aabb.max.y } else { aabb.min.y },
+                if z >= 0.0 { aabb.max.z } else { aabb.min.z },
+            ];
+            x * corner[0] + y * corner[1] + z * corner[2] + d >= 0.0
+        })
+    }
+}
+
+// Axis-aligned bounding box
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Point3<f32>,
+    pub max: Point3<f32>,
+}
+
+impl Aabb {
+    // The smallest box around `points`, or a single point at the origin if
+    // there aren't any
+    pub fn from_points(points: impl IntoIterator<Item = [f32; 3]>) -> Self {
+        let mut points = points.into_iter();
+        let Some(first) = points.next() else {
+            let origin = Point3::new(0.0, 0.0, 0.0);
+            return Self {
+                min: origin,
+                max: origin,
+            };
+        };
+        points.fold(
+            Self {
+                min: first.into(),
+                max: first.into(),
+            },
+            |aabb, [x, y, z]| Self {
+                min: Point3::new(
+                    aabb.min.x.min(x),
+                    aabb.min.y.min(y),
+                    aabb.min.z.min(z),
+                ),
+                max: Point3::new(
+                    aabb.max.x.max(x),
+                    aabb.max.y.max(y),
+                    aabb.max.z.max(z),
+                ),
+            },
+        )
+    }
+
+    // The axis-aligned box around this one after `transform` (Arvo's
+    // method). Rotating a box makes its axis-aligned bounds grow, so this is
+    // loose but never too small.
+    pub fn transformed(&self, transform: &Matrix4<f32>) -> Self {
+        let min = [self.min.x, self.min.y, self.min.z];
+        let max = [self.max.x, self.max.y, self.max.z];
+        // Start from the translation and add each axis' contribution
+        let mut new_min = [transform.w.x, transform.w.y, transform.w.z];
+        let mut new_max = new_min;
+        for i in 0..3 {
+            for j in 0..3 {
+                // cgmath is column major, so this is row i, column j
+                let m = transform[j][i];
+                let a = m * min[j];
+                let b = m * max[j];
+                new_min[i] += a.min(b);
+                new_max[i] += a.max(b);
+            }
+        }
+        Self {
+            min: new_min.into(),
+            max: new_max.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{Deg, Rad, Vector3};
+
+    use super::*;
+    use crate::camera::OPENGL_TO_WGPU_MATRIX;
+
+    // Looking down -Z from the origin, near 0.1 and far 100
+    fn view_proj() -> Matrix4<f32> {
+        let view = Matrix4::look_at_rh(
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(0.0, 0.0, -1.0),
+            Vector3::unit_y(),
+        );
+        let proj = cgmath::perspective(Deg(90.0), 1.0, 0.1, 100.0);
+        OPENGL_TO_WGPU_MATRIX * proj * view
+    }
+
+    fn unit_box_at(x: f32, y: f32, z: f32) -> Aabb {
+        Aabb {
+            min: Point3::new(x - 0.5, y - 0.5, z - 0.5),
+            max: Point3::new(x + 0.5, y + 0.5, z + 0.5),
+        }
+    }
+
+    fn distance(plane: Plane, p: Point3<f32>) -> f32 {
+        plane[0] * p.x + plane[1] * p.y + plane[2] * p.z + plane[3]
+    }
+
+    #[test]
+    fn planes_are_normalized() {
+        for [x, y, z, _] in Frustum::from_view_proj(&view_proj()).planes {
+            let length = (x * x + y * y + z * z).sqrt();
+            assert!((length - 1.0).abs() < 1e-5, "length {}", length);
+        }
+    }
+
+    #[test]
+    fn near_and_far_planes_sit_at_the_clip_distances() {
+        let [.., near, far] = Frustum::from_view_proj(&view_proj()).planes;
+        // Points exactly on a plane are at distance 0 from it
+        assert!(distance(near, Point3::new(0.0, 0.0, -0.1)).abs() < 1e-4);
+        assert!(distance(far, Point3::new(0.0, 0.0, -100.0)).abs() < 1e-2);
+        // And the inside is towards the middle of the frustum
+        assert!(distance(near, Point3::new(0.0, 0.0, -1.0)) > 0.0);
+        assert!(distance(far, Point3::new(0.0, 0.0, -1.0)) > 0.0);
+    }
+
+    #[test]
+    fn side_planes_follow_the_field_of_view() {
+        let [left, right, bottom, top, ..] =
+            Frustum::from_view_proj(&view_proj()).planes;
+        // 90 degrees wide with an aspect of 1, so the sides are at 45
+        // degrees: x = +-z and y = +-z
+        let on_left = Point3::new(-5.0, 0.0, -5.0);
+        let on_right = Point3::new(5.0, 0.0, -5.0);
+        let on_bottom = Point3::new(0.0, -5.0, -5.0);
+        let on_top = Point3::new(0.0, 5.0, -5.0);
+        assert!(distance(left, on_left).abs() < 1e-4);
+        assert!(distance(right, on_right).abs() < 1e-4);
+        assert!(distance(bottom, on_bottom).abs() < 1e-4);
+        assert!(distance(top, on_top).abs() < 1e-4);
+    }
+
+    #[test]
+    fn boxes_in_front_are_kept() {
+        let frustum = Frustum::from_view_proj(&view_proj());
+        assert!(frustum.intersects_aabb(&unit_box_at(0.0, 0.0, -5.0)));
+        assert!(frustum.intersects_aabb(&unit_box_at(3.0, -3.0, -5.0)));
+    }
+
+    #[test]
+    fn boxes_outside_are_culled() {
+        let frustum = Frustum::from_view_proj(&view_proj());
+        // Behind the camera
+        assert!(!frustum.intersects_aabb(&unit_box_at(0.0, 0.0, 5.0)));
+        // Off to each side
+        assert!(!frustum.intersects_aabb(&unit_box_at(-10.0, 0.0, -5.0)));
+        assert!(!frustum.intersects_aabb(&unit_box_at(10.0, 0.0, -5.0)));
+        assert!(!frustum.intersects_aabb(&unit_box_at(0.0, -10.0, -5.0)));
+        assert!(!frustum.intersects_aabb(&unit_box_at(0.0, 10.0, -5.0)));
+        // Past the far plane
+        assert!(!frustum.intersects_aabb(&unit_box_at(0.0, 0.0, -200.0)));
+    }
+
+    #[test]
+    fn boxes_straddling_a_plane_are_kept() {
+        let frustum = Frustum::from_view_proj(&view_proj());
+        // Centered on the right plane
+        assert!(frustum.intersects_aabb(&unit_box_at(5.0, 0.0, -5.0)));
+        // Through the near plane
+        assert!(frustum.intersects_aabb(&unit_box_at(0.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn aabb_from_points() {
+        let aabb = Aabb::from_points([
+            [1.0, -2.0, 0.5],
+            [-1.0, 3.0, 0.0],
+            [0.0, 0.0, -4.0],
+        ]);
+        assert_eq!(aabb.min, Point3::new(-1.0, -2.0, -4.0));
+        assert_eq!(aabb.max, Point3::new(1.0, 3.0, 0.5));
+    }
+
+    #[test]
+    fn translated_aabb_moves() {
+        let aabb = unit_box_at(0.0, 0.0, 0.0).transformed(
+            &Matrix4::from_translation(Vector3::new(1.0, 2.0, 3.0)),
+        );
+        assert_eq!(aabb, unit_box_at(1.0, 2.0, 3.0));
+    }
+
+    #[test]
+    fn rotated_aabb_grows_to_cover_the_box() {
+        let aabb = unit_box_at(0.0, 0.0, 0.0).transformed(
+            &Matrix4::from_angle_z(Rad(std::f32::consts::FRAC_PI_4)),
+        );
+        // The corners of a unit square turned 45 degrees reach sqrt(2) / 2
+        let half_diagonal = std::f32::consts::SQRT_2 / 2.0;
+        assert!((aabb.max.x - half_diagonal).abs() < 1e-5);
+        assert!((aabb.min.y + half_diagonal).abs() < 1e-5);
+        // Z is untouched by a rotation around Z
+        assert!((aabb.max.z - 0.5).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,93 @@
+use std::time::Instant;
+
+use gilrs::{Axis, EventType, GamepadId, Gilrs};
+
+use crate::camera::OrbitCamera;
+
+// Drives the orbit camera from the first connected controller: the left stick
+// slides the orbit target around and the right stick orbits, alongside
+// whatever the mouse is doing.
+pub struct GamepadState {
+    // Stick deflection (0..1) below which input is ignored, since sticks
+    // rarely rest at exactly zero
+    pub deadzone: f32,
+    // World units per second at full deflection
+    pub move_sensitivity: f32,
+    // Radians per second at full deflection
+    pub look_sensitivity: f32,
+    active: Option<GamepadId>,
+    last_update: Option<Instant>,
+}
+
+impl GamepadState {
+    pub fn new() -> Self {
+        Self {
+            deadzone: 0.15,
+            move_sensitivity: 2.0,
+            look_sensitivity: 2.5,
+            active: None,
+            last_update: None,
+        }
+    }
+
+    // Drains pending gilrs events and applies the sticks to `camera`. Does
+    // nothing past the event loop while no controller is connected.
+    pub fn update(&mut self, gilrs: &mut Gilrs, camera: &mut OrbitCamera) {
+        while let Some(event) = gilrs.next_event() {
+            match event.event {
+                EventType::Connected if self.active.is_none() => {
+                    log::info!(
+                        "Gamepad connected: {}",
+                        gilrs.gamepad(event.id).name()
+                    );
+                    self.active = Some(event.id);
+                }
+                EventType::Disconnected if self.active == Some(event.id) => {
+                    log::info!("Gamepad disconnected");
+                    self.active = gilrs.gamepads().next().map(|(id, _)| id);
+                }
+                _ => {}
+            }
+        }
+
+        let Some(id) = self.active else {
+            self.last_update = None;
+            return;
+        };
+
+        // Scale by real elapsed time so speed doesn't depend on frame rate
+        let now = Instant::now();
+        let dt = self
+            .last_update
+            .replace(now)
+            .map_or(0.0, |last| (now - last).as_secs_f32());
+
+        let gamepad = gilrs.gamepad(id);
+        let (move_x, move_y) = self.stick(
+            gamepad.value(Axis::LeftStickX),
+            gamepad.value(Axis::LeftStickY),
+        );
+        let (look_x, look_y) = self.stick(
+            gamepad.value(Axis::RightStickX),
+            gamepad.value(Axis::RightStickY),
+        );
+
+        let step = self.move_sensitivity * dt;
+        camera.pan(move_x * step, move_y * step);
+        let turn = self.look_sensitivity * dt;
+        // Pushing up looks up, which lowers the eye
+        camera.rotate(-look_x * turn, -look_y * turn);
+    }
+
+    // Radial deadzone: anything inside it reads as centered and the rest is
+    // rescaled so output still ramps smoothly from 0 at the edge to 1
+    fn stick(&self, x: f32, y: f32) -> (f32, f32) {
+        let magnitude = (x * x + y * y).sqrt();
+        if magnitude <= self.deadzone {
+            return (0.0, 0.0);
+        }
+        let scaled =
+            ((magnitude - self.deadzone) / (1.0 - self.deadzone)).min(1.0);
+        (x / magnitude * scaled, y / magnitude * scaled)
+    }
+}
@@ -0,0 +1,162 @@
+use crate::texture;
+
+// Size of the G-buffer's targets. Fixed like the mirror's so resizing never
+// has to recreate them (or the sprite bind groups showing them). They're
+// drawn with the main camera and shown stretched over the whole window, so
+// the stretch cancels out and only the resolution is off.
+pub const GBUFFER_WIDTH: u32 = 640;
+pub const GBUFFER_HEIGHT: u32 = 360;
+
+pub const ALBEDO_FORMAT: wgpu::TextureFormat =
+    wgpu::TextureFormat::Rgba8UnormSrgb;
+// Normals are data, not color, so no sRGB curve
+pub const NORMAL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+
+// Which of the G-buffer's targets is shown over the scene, if any
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GBufferView {
+    #[default]
+    Off,
+    Albedo,
+    Normal,
+}
+
+impl GBufferView {
+    pub const ALL: [Self; 3] = [Self::Off, Self::Albedo, Self::Normal];
+
+    pub fn next(self) -> Self {
+        match self {
+            Self::Off => Self::Albedo,
+            Self::Albedo => Self::Normal,
+            Self::Normal => Self::Off,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Off => "off",
+            Self::Albedo => "albedo",
+            Self::Normal => "normals",
+        }
+    }
+}
+
+// Two color targets written by one pass (multiple render targets): the
+// scene's albedo and its world space normals, what a deferred renderer
+// would light afterwards. For now they're only drawn to be looked at, see
+// GBufferView. gbuffer.wgsl's fs_main writes one @location per target, in
+// the order of `targets` and `color_attachments`.
+pub struct GBuffer {
+    albedo: texture::Texture,
+    normal: texture::Texture,
+    depth_texture: texture::Texture,
+}
+
+impl GBuffer {
+    // `depth_format` has to match the G-buffer pipeline's
+    pub fn new(
+        device: &wgpu::Device,
+        depth_format: wgpu::TextureFormat,
+    ) -> Self {
+        let depth_texture = texture::Texture::create_depth_texture(
+            device,
+            &wgpu::SurfaceConfiguration {
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                format: ALBEDO_FORMAT,
+                width: GBUFFER_WIDTH,
+                height: GBUFFER_HEIGHT,
+                present_mode: wgpu::PresentMode::Fifo,
+                desired_maximum_frame_latency: 2,
+                alpha_mode: wgpu::CompositeAlphaMode::Auto,
+                view_formats: vec![],
+            },
+            1,
+            depth_format,
+            false,
+            "G-Buffer Depth Texture",
+        );
+        Self {
+            albedo: create_target(device, "G-Buffer Albedo", ALBEDO_FORMAT),
+            normal: create_target(device, "G-Buffer Normal", NORMAL_FORMAT),
+            depth_texture,
+        }
+    }
+
+    // For the pipeline, one per target
+    pub fn targets() -> [Option<wgpu::ColorTargetState>; 2] {
+        [
+            Some(wgpu::ColorTargetState {
+                format: ALBEDO_FORMAT,
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            }),
+            Some(wgpu::ColorTargetState {
+                format: NORMAL_FORMAT,
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            }),
+        ]
+    }
+
+    // Both targets, cleared to black
+    pub fn color_attachments(
+        &self,
+    ) -> [Option<wgpu::RenderPassColorAttachment<'_>>; 2] {
+        [&self.albedo, &self.normal].map(|target| {
+            Some(wgpu::RenderPassColorAttachment {
+                view: &target.view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })
+        })
+    }
+
+    pub fn depth_view(&self) -> &wgpu::TextureView {
+        &self.depth_texture.view
+    }
+
+    pub fn albedo(&self) -> &texture::Texture {
+        &self.albedo
+    }
+
+    pub fn normal(&self) -> &texture::Texture {
+        &self.normal
+    }
+}
+
+fn create_target(
+    device: &wgpu::Device,
+    label: &str,
+    format: wgpu::TextureFormat,
+) -> texture::Texture {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size: wgpu::Extent3d {
+            width: GBUFFER_WIDTH,
+            height: GBUFFER_HEIGHT,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+            | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+        label: Some(label),
+        mag_filter: wgpu::FilterMode::Linear,
+        min_filter: wgpu::FilterMode::Linear,
+        ..Default::default()
+    });
+    texture::Texture {
+        texture,
+        view,
+        sampler,
+    }
+}
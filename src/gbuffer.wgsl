@@ -0,0 +1,109 @@
+// The scene's entities drawn into two targets at once: albedo at
+// location 0 and world space normals at location 1. See gbuffer.rs.
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) normal: vec3<f32>,
+    @location(3) tangent: vec3<f32>,
+    @location(4) bitangent: vec3<f32>,
+    @location(5) color: vec3<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) tangent: vec3<f32>,
+    @location(3) bitangent: vec3<f32>,
+    @location(4) color: vec3<f32>,
+}
+
+// One per color attachment, in the order they're attached
+struct GBufferOutput {
+    @location(0) albedo: vec4<f32>,
+    @location(1) normal: vec4<f32>,
+}
+
+// see: camera::CameraUniform
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+    inv_view_proj: mat4x4<f32>,
+    view_position: vec4<f32>,
+}
+
+@group(1) @binding(0)
+var<uniform> camera: CameraUniform;
+
+// see: objects::ObjectUniform
+struct ObjectUniform {
+    model: mat4x4<f32>,
+    tint: vec4<f32>,
+}
+
+@group(3) @binding(0)
+var<uniform> object: ObjectUniform;
+
+// The same material bind group as shader.wgsl. The detail texture and
+// globals are in the layout too but go unused.
+@group(0) @binding(0)
+var t_diffuse: texture_2d<f32>;
+
+@group(0) @binding(1)
+var s_diffuse: sampler;
+
+@group(0) @binding(2)
+var t_normal: texture_2d<f32>;
+
+@group(0) @binding(3)
+var s_normal: sampler;
+
+// see: material::MaterialUniform
+struct MaterialUniform {
+    specular_color: vec3<f32>,
+    shininess: f32,
+    uv_scale: vec2<f32>,
+}
+
+@group(0) @binding(6)
+var<uniform> material: MaterialUniform;
+
+// Same as shader.wgsl's vs_object
+@vertex
+fn vs_main(model: VertexInput) -> VertexOutput {
+    let rotation = mat3x3<f32>(
+        object.model[0].xyz,
+        object.model[1].xyz,
+        object.model[2].xyz,
+    );
+    var out: VertexOutput;
+    out.tex_coords = model.tex_coords;
+    out.normal = rotation * model.normal;
+    out.tangent = rotation * model.tangent;
+    out.bitangent = rotation * model.bitangent;
+    out.color = model.color;
+    out.clip_position =
+        camera.view_proj * object.model * vec4<f32>(model.position, 1.0);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> GBufferOutput {
+    let tex_coords = (in.tex_coords - 0.5) * material.uv_scale + 0.5;
+
+    let tangent_normal =
+        textureSample(t_normal, s_normal, tex_coords).xyz * 2.0 - 1.0;
+    let tbn = mat3x3<f32>(
+        normalize(in.tangent),
+        normalize(in.bitangent),
+        normalize(in.normal),
+    );
+    let world_normal = normalize(tbn * tangent_normal);
+
+    var out: GBufferOutput;
+    let diffuse = textureSample(t_diffuse, s_diffuse, tex_coords);
+    out.albedo = vec4<f32>(diffuse.rgb * in.color * object.tint.rgb, 1.0);
+    // Packed into 0..1 like a normal map, since the target is unorm
+    out.normal = vec4<f32>(world_normal * 0.5 + 0.5, 1.0);
+    return out;
+}
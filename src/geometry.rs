@@ -0,0 +1,421 @@
+use wgpu::util::DeviceExt;
+
+use crate::frustum::Aabb;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Vertex {
+    pub position: [f32; 3],
+    pub tex_coords: [f32; 2],
+    pub normal: [f32; 3],
+    // Directions in which u and v increase across the surface. Together with
+    // the normal they take normal map samples from tangent space into model
+    // space.
+    pub tangent: [f32; 3],
+    pub bitangent: [f32; 3],
+    // Multiplied into the texture color by shader.wgsl
+    pub color: [f32; 3],
+}
+
+impl Vertex {
+    const POSITION_ATTRIBUTES: [wgpu::VertexAttribute; 1] =
+        wgpu::vertex_attr_array![0 => Float32x3];
+    const TEX_COORDS_ATTRIBUTES: [wgpu::VertexAttribute; 1] =
+        wgpu::vertex_attr_array![1 => Float32x2];
+    const SURFACE_ATTRIBUTES: [wgpu::VertexAttribute; 4] = wgpu::vertex_attr_array![
+        2 => Float32x3,
+        3 => Float32x3,
+        4 => Float32x3,
+        5 => Float32x3,
+    ];
+
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 3]>()
+                        as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 5]>()
+                        as wgpu::BufferAddress,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 8]>()
+                        as wgpu::BufferAddress,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 11]>()
+                        as wgpu::BufferAddress,
+                    shader_location: 4,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 14]>()
+                        as wgpu::BufferAddress,
+                    shader_location: 5,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+            ],
+        }
+    }
+
+    // The same attributes at the same shader locations as `desc`, but read
+    // from the three buffers `Mesh::new_split` makes: positions in slot 0,
+    // texture coordinates in slot 1, and the normal, tangent frame and
+    // color still interleaved in slot 2
+    pub fn split_desc() -> [wgpu::VertexBufferLayout<'static>; 3] {
+        let layout = |stride: usize, attributes| wgpu::VertexBufferLayout {
+            array_stride: stride as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes,
+        };
+        [
+            layout(std::mem::size_of::<[f32; 3]>(), &Self::POSITION_ATTRIBUTES),
+            layout(
+                std::mem::size_of::<[f32; 2]>(),
+                &Self::TEX_COORDS_ATTRIBUTES,
+            ),
+            layout(std::mem::size_of::<[f32; 12]>(), &Self::SURFACE_ATTRIBUTES),
+        ]
+    }
+
+    // The slot 2 half of `split_desc`
+    fn surface(&self) -> [f32; 12] {
+        let mut surface = [0.0; 12];
+        surface[0..3].copy_from_slice(&self.normal);
+        surface[3..6].copy_from_slice(&self.tangent);
+        surface[6..9].copy_from_slice(&self.bitangent);
+        surface[9..12].copy_from_slice(&self.color);
+        surface
+    }
+}
+
+// The pentagon lies flat in XY facing +Z with UVs that follow x and y, so
+// every vertex shares the same tangent frame. Each corner gets its own pastel
+// tint, which blends across the face.
+pub const VERTICES: &[Vertex] = &[
+    Vertex {
+        position: [-0.0868241, 0.49240386, 0.0],
+        tex_coords: [0.4131759, 0.99240386],
+        normal: [0.0, 0.0, 1.0],
+        tangent: [1.0, 0.0, 0.0],
+        bitangent: [0.0, 1.0, 0.0],
+        color: [1.0, 0.6, 0.6],
+    }, // A
+    Vertex {
+        position: [-0.49513406, 0.06958647, 0.0],
+        tex_coords: [0.0048659444, 0.56958647],
+        normal: [0.0, 0.0, 1.0],
+        tangent: [1.0, 0.0, 0.0],
+        bitangent: [0.0, 1.0, 0.0],
+        color: [1.0, 1.0, 0.6],
+    }, // B
+    Vertex {
+        position: [-0.21918549, -0.44939706, 0.0],
+        tex_coords: [0.28081453, 0.05060294],
+        normal: [0.0, 0.0, 1.0],
+        tangent: [1.0, 0.0, 0.0],
+        bitangent: [0.0, 1.0, 0.0],
+        color: [0.6, 1.0, 0.6],
+    }, // C
+    Vertex {
+        position: [0.35966998, -0.3473291, 0.0],
+        tex_coords: [0.85967, 0.1526709],
+        normal: [0.0, 0.0, 1.0],
+        tangent: [1.0, 0.0, 0.0],
+        bitangent: [0.0, 1.0, 0.0],
+        color: [0.6, 0.8, 1.0],
+    }, // D
+    Vertex {
+        position: [0.44147372, 0.2347359, 0.0],
+        tex_coords: [0.9414737, 0.7347359],
+        normal: [0.0, 0.0, 1.0],
+        tangent: [1.0, 0.0, 0.0],
+        bitangent: [0.0, 1.0, 0.0],
+        color: [0.9, 0.6, 1.0],
+    }, // E
+];
+
+pub const INDICES: &[u32] = &[
+    0, 1, 4, //
+    1, 2, 4, //
+    2, 3, 4, //
+];
+
+// The smallest index format that can address every one of `n_vertices`
+pub fn index_format_for(n_vertices: usize) -> wgpu::IndexFormat {
+    if n_vertices > u16::MAX as usize {
+        wgpu::IndexFormat::Uint32
+    } else {
+        wgpu::IndexFormat::Uint16
+    }
+}
+
+// Vertex and index buffers for one indexed mesh. Indices are stored as u16
+// whenever the vertex count allows it, which halves the index buffer for
+// anything under 65k vertices.
+pub struct Mesh {
+    // Bound to slots 0, 1 and so on. Just the one, of interleaved
+    // `Vertex`es, unless the mesh was made with `new_split`.
+    vertex_buffers: Vec<wgpu::Buffer>,
+    index_buffer: wgpu::Buffer,
+    index_format: wgpu::IndexFormat,
+    n_indices: u32,
+    n_vertices: u32,
+    // In the mesh's own space, for culling
+    bounds: Aabb,
+}
+
+impl Mesh {
+    pub fn new(
+        device: &wgpu::Device,
+        label: &str,
+        vertices: &[Vertex],
+        indices: &[u32],
+    ) -> Self {
+        Self::with_vertex_usage(
+            device,
+            label,
+            vertices,
+            indices,
+            wgpu::BufferUsages::VERTEX,
+        )
+    }
+
+    // A mesh whose vertices can be rewritten after it's created, see
+    // `write_vertices`. The indices stay fixed.
+    pub fn new_dynamic(
+        device: &wgpu::Device,
+        label: &str,
+        vertices: &[Vertex],
+        indices: &[u32],
+    ) -> Self {
+        Self::with_vertex_usage(
+            device,
+            label,
+            vertices,
+            indices,
+            wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        )
+    }
+
+    // The vertices in separate buffers per `Vertex::split_desc`, to be
+    // drawn with a pipeline made with those layouts rather than
+    // `Vertex::desc`
+    pub fn new_split(
+        device: &wgpu::Device,
+        label: &str,
+        vertices: &[Vertex],
+        indices: &[u32],
+    ) -> Self {
+        let positions: Vec<[f32; 3]> =
+            vertices.iter().map(|v| v.position).collect();
+        let tex_coords: Vec<[f32; 2]> =
+            vertices.iter().map(|v| v.tex_coords).collect();
+        let surfaces: Vec<[f32; 12]> =
+            vertices.iter().map(Vertex::surface).collect();
+        let usage = wgpu::BufferUsages::VERTEX;
+        let vertex_buffers = vec![
+            create_vertex_buffer(
+                device,
+                &format!("{} Position Buffer", label),
+                bytemuck::cast_slice(&positions),
+                usage,
+            ),
+            create_vertex_buffer(
+                device,
+                &format!("{} Tex Coords Buffer", label),
+                bytemuck::cast_slice(&tex_coords),
+                usage,
+            ),
+            create_vertex_buffer(
+                device,
+                &format!("{} Surface Buffer", label),
+                bytemuck::cast_slice(&surfaces),
+                usage,
+            ),
+        ];
+        Self::with_vertex_buffers(
+            device,
+            label,
+            vertex_buffers,
+            vertices,
+            indices,
+        )
+    }
+
+    fn with_vertex_usage(
+        device: &wgpu::Device,
+        label: &str,
+        vertices: &[Vertex],
+        indices: &[u32],
+        vertex_usage: wgpu::BufferUsages,
+    ) -> Self {
+        let vertex_buffer = create_vertex_buffer(
+            device,
+            &format!("{} Vertex Buffer", label),
+            bytemuck::cast_slice(vertices),
+            vertex_usage,
+        );
+        Self::with_vertex_buffers(
+            device,
+            label,
+            vec![vertex_buffer],
+            vertices,
+            indices,
+        )
+    }
+
+    // The index buffer and everything else that doesn't depend on how
+    // `vertex_buffers` lay the vertices out
+    fn with_vertex_buffers(
+        device: &wgpu::Device,
+        label: &str,
+        vertex_buffers: Vec<wgpu::Buffer>,
+        vertices: &[Vertex],
+        indices: &[u32],
+    ) -> Self {
+        let index_format = index_format_for(vertices.len());
+        let contents: Vec<u8> = match index_format {
+            wgpu::IndexFormat::Uint16 => {
+                let mut narrow: Vec<u16> =
+                    indices.iter().map(|&i| i as u16).collect();
+                // Buffer sizes have to be a multiple of COPY_BUFFER_ALIGNMENT
+                // (4 bytes), so an odd number of u16s gets one extra that's
+                // never drawn
+                if narrow.len() % 2 == 1 {
+                    narrow.push(0);
+                }
+                bytemuck::cast_slice(&narrow).to_vec()
+            }
+            wgpu::IndexFormat::Uint32 => bytemuck::cast_slice(indices).to_vec(),
+        };
+        let index_buffer =
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(&format!("{} Index Buffer", label)),
+                contents: &contents,
+                usage: wgpu::BufferUsages::INDEX,
+            });
+
+        Self {
+            vertex_buffers,
+            index_buffer,
+            n_vertices: vertices.len() as u32,
+            index_format,
+            n_indices: indices.len() as u32,
+            bounds: Aabb::from_points(vertices.iter().map(|v| v.position)),
+        }
+    }
+
+    // Overwrites the vertices of a mesh made with `new_dynamic`, starting
+    // from the first. The bounds used for culling aren't updated, so the new
+    // vertices should stay within the original ones.
+    pub fn write_vertices(&self, queue: &wgpu::Queue, vertices: &[Vertex]) {
+        debug_assert_eq!(self.vertex_buffers.len(), 1);
+        queue.write_buffer(
+            &self.vertex_buffers[0],
+            0,
+            bytemuck::cast_slice(vertices),
+        );
+    }
+
+    pub fn n_indices(&self) -> u32 {
+        self.n_indices
+    }
+
+    pub fn n_triangles(&self) -> u64 {
+        self.n_indices as u64 / 3
+    }
+
+    pub fn bounds(&self) -> &Aabb {
+        &self.bounds
+    }
+
+    // Binds the vertex buffers to slots 0 onwards and the index buffer with
+    // whichever format it was built with
+    pub fn bind(&self, render_pass: &mut wgpu::RenderPass) {
+        self.bind_vertex_buffers(render_pass);
+        render_pass
+            .set_index_buffer(self.index_buffer.slice(..), self.index_format);
+    }
+
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass) {
+        self.bind(render_pass);
+        render_pass.draw_indexed(0..self.n_indices, 0, 0..1);
+    }
+
+    // Every vertex once, in order, without the index buffer. For a
+    // PointList pipeline, where going through the indices would draw each
+    // shared vertex several times over.
+    pub fn draw_points(&self, render_pass: &mut wgpu::RenderPass) {
+        self.bind_vertex_buffers(render_pass);
+        render_pass.draw(0..self.n_vertices, 0..1);
+    }
+
+    fn bind_vertex_buffers(&self, render_pass: &mut wgpu::RenderPass) {
+        for (slot, buffer) in self.vertex_buffers.iter().enumerate() {
+            render_pass.set_vertex_buffer(slot as u32, buffer.slice(..));
+        }
+    }
+}
+
+fn create_vertex_buffer(
+    device: &wgpu::Device,
+    label: &str,
+    contents: &[u8],
+    usage: wgpu::BufferUsages,
+) -> wgpu::Buffer {
+    // To access the create_buffer_init method on wgpu::Device, we'll have
+    // to import the DeviceExt
+    // (https://docs.rs/wgpu/latest/wgpu/util/trait.DeviceExt.html#tymethod.create_buffer_init)
+    // extension trait. For more information on extension traits, check out
+    // this article: http://xion.io/post/code/rust-extension-traits.html.
+    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some(label),
+        contents,
+        usage,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gpu::test_gpu;
+    use crate::readback::read_buffer;
+
+    #[test]
+    fn pentagon_vertex_buffer_round_trips() {
+        let Some(gpu) = test_gpu() else { return };
+        // COPY_SRC on top of what `new` asks for, so read_buffer can copy
+        // the vertices out
+        let mesh = Mesh::with_vertex_usage(
+            &gpu.device,
+            "Pentagon",
+            VERTICES,
+            INDICES,
+            wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_SRC,
+        );
+
+        let vertices: Vec<Vertex> = read_buffer(
+            &gpu.device,
+            &gpu.queue,
+            &mesh.vertex_buffers[0],
+            VERTICES.len(),
+        );
+        assert_eq!(vertices, VERTICES);
+    }
+}
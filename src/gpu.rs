@@ -0,0 +1,731 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use winit::window::Window;
+
+use crate::draw_constants;
+
+#[derive(Debug)]
+pub enum StateError {
+    NoAdapter,
+    CreateWindow(winit::error::OsError),
+    CreateSurface(wgpu::CreateSurfaceError),
+    RequestDevice(wgpu::RequestDeviceError),
+    InvalidSize { width: u32, height: u32 },
+    Surface(wgpu::SurfaceError),
+    // The rendered frame couldn't be copied back from the GPU
+    Readback,
+    // The adapter picked for another window can't present to this one
+    SurfaceUnsupported,
+    // The device kept getting lost, even after being recreated
+    DeviceLost,
+    // A validation or internal error while drawing an offscreen frame,
+    // which would have come out blank or half drawn
+    Render(wgpu::Error),
+}
+
+impl std::fmt::Display for StateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoAdapter => write!(f, "No suitable GPU adapter found"),
+            Self::CreateWindow(e) => {
+                write!(f, "Failed to create window: {}", e)
+            }
+            Self::CreateSurface(e) => {
+                write!(f, "Failed to create surface: {}", e)
+            }
+            Self::RequestDevice(e) => {
+                write!(f, "Failed to create device: {}", e)
+            }
+            Self::InvalidSize { width, height } => {
+                write!(f, "Invalid render target size {}x{}", width, height)
+            }
+            Self::Surface(e) => write!(f, "Surface error: {}", e),
+            Self::Readback => write!(f, "Failed to read back the frame"),
+            Self::SurfaceUnsupported => {
+                write!(f, "The adapter in use can't present to this window")
+            }
+            Self::DeviceLost => {
+                write!(f, "The GPU device was lost too many times")
+            }
+            Self::Render(e) => write!(f, "Rendering the frame failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for StateError {}
+
+impl From<winit::error::OsError> for StateError {
+    fn from(e: winit::error::OsError) -> Self {
+        Self::CreateWindow(e)
+    }
+}
+
+impl From<wgpu::CreateSurfaceError> for StateError {
+    fn from(e: wgpu::CreateSurfaceError) -> Self {
+        Self::CreateSurface(e)
+    }
+}
+
+impl From<wgpu::RequestDeviceError> for StateError {
+    fn from(e: wgpu::RequestDeviceError) -> Self {
+        Self::RequestDevice(e)
+    }
+}
+
+impl From<wgpu::SurfaceError> for StateError {
+    fn from(e: wgpu::SurfaceError) -> Self {
+        Self::Surface(e)
+    }
+}
+
+// Why `run` stopped early
+#[derive(Debug)]
+pub enum RunError {
+    EventLoop(winit::error::EventLoopError),
+    State(StateError),
+    // --screenshot's frame couldn't be written to the path
+    SaveScreenshot(std::path::PathBuf, image::ImageError),
+}
+
+impl RunError {
+    // What the process exits with, so scripts can tell failures apart:
+    //
+    //     1  anything else
+    //     2  no GPU adapter
+    //     3  no display, or the window or its surface couldn't be made
+    //     4  the device couldn't be created, or kept getting lost
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Self::State(StateError::NoAdapter) => 2,
+            Self::EventLoop(_)
+            | Self::State(
+                StateError::CreateWindow(_)
+                | StateError::CreateSurface(_)
+                | StateError::SurfaceUnsupported,
+            ) => 3,
+            Self::State(
+                StateError::RequestDevice(_) | StateError::DeviceLost,
+            ) => 4,
+            Self::State(
+                StateError::InvalidSize { .. }
+                | StateError::Surface(_)
+                | StateError::Readback
+                | StateError::Render(_),
+            )
+            | Self::SaveScreenshot(..) => 1,
+        }
+    }
+}
+
+impl std::fmt::Display for RunError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::EventLoop(e) => write!(f, "Event loop error: {}", e),
+            Self::State(e) => e.fmt(f),
+            Self::SaveScreenshot(path, e) => {
+                write!(f, "Failed to save {}: {}", path.display(), e)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RunError {}
+
+impl From<winit::error::EventLoopError> for RunError {
+    fn from(e: winit::error::EventLoopError) -> Self {
+        Self::EventLoop(e)
+    }
+}
+
+impl From<StateError> for RunError {
+    fn from(e: StateError) -> Self {
+        Self::State(e)
+    }
+}
+
+// How many frames the CPU can queue up ahead of the one being shown. 1 is
+// the least input lag, more smooths over the odd slow frame. See
+// `Renderer::set_frame_latency` for changing it.
+pub const DEFAULT_FRAME_LATENCY: u32 = 2;
+
+// Headless rendering has no surface to pick a format from
+pub const OFFSCREEN_FORMAT: wgpu::TextureFormat =
+    wgpu::TextureFormat::Rgba8UnormSrgb;
+
+// Where finished frames end up
+pub enum RenderTarget<'a> {
+    // The surface is None only after `Renderer::recreate_surface` dropped
+    // it and couldn't make a new one
+    Surface {
+        surface: Option<wgpu::Surface<'a>>,
+        window: Arc<Window>,
+    },
+    // Sized and formatted according to `surface_configuration` even though
+    // there's no surface
+    Offscreen {
+        texture: wgpu::Texture,
+    },
+}
+
+// The texture render() draws the final image into this frame
+pub enum Frame {
+    Surface(wgpu::SurfaceTexture),
+    Offscreen(wgpu::Texture),
+}
+
+impl Frame {
+    pub fn texture(&self) -> &wgpu::Texture {
+        match self {
+            Frame::Surface(surface_texture) => &surface_texture.texture,
+            Frame::Offscreen(texture) => texture,
+        }
+    }
+
+    pub fn present(self) {
+        if let Frame::Surface(surface_texture) = self {
+            surface_texture.present();
+        }
+    }
+}
+
+pub fn create_offscreen_texture(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Offscreen Target"),
+        size: wgpu::Extent3d {
+            width: config.width,
+            height: config.height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: config.format,
+        usage: config.usage,
+        view_formats: &config.view_formats,
+    })
+}
+
+// The adapter, device and queue everything else gets created from. Shared by
+// the windowed and headless paths, which only differ in whether there's a
+// surface to be compatible with. Cloning only clones the handles, so every
+// window can draw with the same device.
+#[derive(Clone)]
+pub struct Gpu {
+    // Kept to create surfaces for more windows with
+    pub instance: wgpu::Instance,
+    pub adapter: wgpu::Adapter,
+    pub device: wgpu::Device,
+    pub queue: wgpu::Queue,
+    // Set from the device lost callback when the driver gives up on the
+    // device, e.g. after a GPU reset. Nothing made with it works afterwards.
+    pub device_lost: Arc<AtomicBool>,
+}
+
+// Backends to try first, overridable with WGPU_BACKEND as a comma separated
+// list, e.g. "vulkan" or "dx12,gl"
+fn backends_from_env() -> wgpu::Backends {
+    wgpu::Backends::from_env().unwrap_or(wgpu::Backends::PRIMARY)
+}
+
+// WGPU_POWER_PREF is one of "low", "high" or "none". Handy on dual GPU
+// laptops where the default lands on the integrated GPU.
+fn power_preference_from_env() -> wgpu::PowerPreference {
+    wgpu::PowerPreference::from_env().unwrap_or_else(|| {
+        if let Ok(value) = std::env::var("WGPU_POWER_PREF") {
+            log::warn!("Ignoring unknown WGPU_POWER_PREF {:?}", value);
+        }
+        wgpu::PowerPreference::default()
+    })
+}
+
+// WGPU_FORCE_FALLBACK_ADAPTER set to 1 (or true) asks for a software
+// adapter, for machines without a GPU like most CI runners
+fn force_fallback_from_env() -> bool {
+    std::env::var("WGPU_FORCE_FALLBACK_ADAPTER")
+        .is_ok_and(|value| value == "1" || value.eq_ignore_ascii_case("true"))
+}
+
+// The instance is a handle to our GPU
+// Backends::all => Vulkan + Metal + DX12 + GL + Browser WebGPU
+fn create_instance(backends: wgpu::Backends) -> wgpu::Instance {
+    wgpu::Instance::new(&wgpu::InstanceDescriptor {
+        backends,
+        ..Default::default()
+    })
+}
+
+// Looks for an adapter among `backends`, creating a surface for `window` (if
+// any) on the same instance since a surface can only be used with the
+// instance that made it
+async fn request_adapter(
+    backends: wgpu::Backends,
+    power_preference: wgpu::PowerPreference,
+    force_fallback_adapter: bool,
+    window: Option<Arc<Window>>,
+) -> Result<
+    Option<(
+        wgpu::Instance,
+        wgpu::Adapter,
+        Option<wgpu::Surface<'static>>,
+    )>,
+    StateError,
+> {
+    let instance = create_instance(backends);
+
+    // The surface is the part of the window that we draw to.
+    // We need it to draw directly to the screen. Holding on to the window
+    // keeps it alive for as long as the surface.
+    let surface = window
+        .map(|window| instance.create_surface(window))
+        .transpose()?;
+
+    let adapter = instance
+        .request_adapter(&wgpu::RequestAdapterOptions {
+            // power_preference has two variants: LowPower and
+            // HighPerformance. LowPower will pick an adapter that favors
+            // battery life, such as an integrated GPU. HighPerformance will
+            // pick an adapter for more power-hungry yet more performant
+            // GPU's, such as a dedicated graphics card. WGPU will favor
+            // LowPower if there is no adapter for the HighPerformance
+            // option.
+            power_preference,
+
+            // The compatible_surface field tells wgpu to find an adapter
+            // that can present to the supplied surface. None when there's
+            // nothing to present to.
+            compatible_surface: surface.as_ref(),
+
+            // The force_fallback_adapter forces wgpu to pick an adapter
+            // that will work on all hardware. This usually means that the
+            // rendering backend will use a "software" system instead of
+            // hardware such as a GPU.
+            force_fallback_adapter,
+        })
+        .await;
+
+    Ok(adapter.map(|adapter| (instance, adapter, surface)))
+}
+
+impl Gpu {
+    // Creating some of the wgpu types requires async code. Without
+    // `backends` they come from WGPU_BACKEND, and without a
+    // `power_preference` it comes from WGPU_POWER_PREF. `force_fallback`
+    // asks for a software adapter whatever WGPU_FORCE_FALLBACK_ADAPTER says.
+    pub async fn new_windowed(
+        window: Arc<Window>,
+        backends: Option<wgpu::Backends>,
+        power_preference: Option<wgpu::PowerPreference>,
+        force_fallback: bool,
+    ) -> Result<(Self, wgpu::Surface<'static>), StateError> {
+        let (gpu, surface) =
+            Self::new(Some(window), backends, power_preference, force_fallback)
+                .await?;
+        Ok((gpu, surface.expect("Surface requested for a window")))
+    }
+
+    // `new_windowed` without a window, for drawing offscreen
+    pub async fn new_headless(
+        backends: Option<wgpu::Backends>,
+        power_preference: Option<wgpu::PowerPreference>,
+        force_fallback: bool,
+    ) -> Result<Self, StateError> {
+        Self::new(None, backends, power_preference, force_fallback)
+            .await
+            .map(|(gpu, _)| gpu)
+    }
+
+    // A surface for another window, made with the same instance as the
+    // first. The adapter was picked to suit the first window, so this fails
+    // if it can't present to this one.
+    pub fn create_surface(
+        &self,
+        window: Arc<Window>,
+    ) -> Result<wgpu::Surface<'static>, StateError> {
+        let surface = self.instance.create_surface(window)?;
+        if !self.adapter.is_surface_supported(&surface) {
+            return Err(StateError::SurfaceUnsupported);
+        }
+        Ok(surface)
+    }
+
+    async fn new(
+        window: Option<Arc<Window>>,
+        backends: Option<wgpu::Backends>,
+        power_preference: Option<wgpu::PowerPreference>,
+        force_fallback: bool,
+    ) -> Result<(Self, Option<wgpu::Surface<'static>>), StateError> {
+        let force_fallback = force_fallback || force_fallback_from_env();
+        // Software rasterizers turn up under different backends on each
+        // platform (lavapipe on Vulkan, WARP on DX12, llvmpipe on GL), so
+        // there's no point in the usual short list
+        let backends = match backends {
+            Some(backends) => backends,
+            None if force_fallback => wgpu::Backends::all(),
+            None => backends_from_env(),
+        };
+        let power_preference =
+            power_preference.unwrap_or_else(power_preference_from_env);
+
+        let mut found = request_adapter(
+            backends,
+            power_preference,
+            force_fallback,
+            window.clone(),
+        )
+        .await?;
+        if found.is_none() && backends != wgpu::Backends::all() {
+            log::warn!(
+                "No adapter found for {:?}, falling back to all backends",
+                backends
+            );
+            found = request_adapter(
+                wgpu::Backends::all(),
+                power_preference,
+                false,
+                window,
+            )
+            .await?;
+        }
+        let (instance, adapter, surface) =
+            found.ok_or(StateError::NoAdapter)?;
+
+        let info = adapter.get_info();
+        log::info!(
+            "Using adapter {} ({:?}, {:?}, power preference {:?})",
+            info.name,
+            info.backend,
+            info.device_type,
+            power_preference
+        );
+        if force_fallback || info.device_type == wgpu::DeviceType::Cpu {
+            log::warn!(
+                "Running on the software adapter {}, expect it to be slow",
+                info.name
+            );
+        }
+
+        let (device, queue) = Self::request_device(&adapter).await?;
+
+        // Destroyed is what dropping the last handle reports, which is fine
+        let device_lost = Arc::new(AtomicBool::new(false));
+        let lost = device_lost.clone();
+        device.set_device_lost_callback(move |reason, message| {
+            if reason == wgpu::DeviceLostReason::Unknown {
+                log::error!("GPU device lost: {}", message);
+                lost.store(true, Ordering::Release);
+            }
+        });
+
+        Ok((
+            Self {
+                instance,
+                adapter,
+                device,
+                queue,
+                device_lost,
+            },
+            surface,
+        ))
+    }
+
+    async fn request_device(
+        adapter: &wgpu::Adapter,
+    ) -> Result<(wgpu::Device, wgpu::Queue), wgpu::RequestDeviceError> {
+        // Wireframe rendering, conservative rasterization and GPU timing are
+        // nice to have but not worth refusing to run over, so only ask for
+        // them when the adapter has them
+        let optional_features = wgpu::Features::POLYGON_MODE_LINE
+            | wgpu::Features::CONSERVATIVE_RASTERIZATION
+            | wgpu::Features::TIMESTAMP_QUERY
+            // For the per-draw tint, which goes through a uniform buffer
+            // instead without them
+            | wgpu::Features::PUSH_CONSTANTS
+            // Either is enough for KTX2 textures in that family of formats,
+            // otherwise the asset loader falls back to a PNG
+            | wgpu::Features::TEXTURE_COMPRESSION_BC
+            | wgpu::Features::TEXTURE_COMPRESSION_ETC2
+            // For clamping the shadow map's depth instead of clipping it
+            | wgpu::Features::DEPTH_CLIP_CONTROL;
+        let mut features = adapter.features() & optional_features;
+        if !features.contains(wgpu::Features::POLYGON_MODE_LINE) {
+            log::warn!(
+                "Adapter doesn't support POLYGON_MODE_LINE, wireframe \
+                 rendering is disabled"
+            );
+        }
+        if !features.contains(wgpu::Features::CONSERVATIVE_RASTERIZATION) {
+            log::warn!(
+                "Adapter doesn't support CONSERVATIVE_RASTERIZATION, the \
+                 conservative pipeline is disabled"
+            );
+        }
+
+        // The default limits allow no push constants at all, so room for the
+        // draw constants has to be asked for. Adapters with less than that
+        // are treated as not having them.
+        let mut required_limits = wgpu::Limits::default();
+        if features.contains(wgpu::Features::PUSH_CONSTANTS) {
+            let max = adapter.limits().max_push_constant_size;
+            if max >= draw_constants::SIZE {
+                required_limits.max_push_constant_size = draw_constants::SIZE;
+            } else {
+                features.remove(wgpu::Features::PUSH_CONSTANTS);
+            }
+        }
+
+        adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    // You can get a list of features supported by your device
+                    // using adapter.features() or device.features().
+                    // https://docs.rs/wgpu/latest/wgpu/struct.Features.html
+                    required_features: features,
+
+                    // https://docs.rs/wgpu/latest/wgpu/struct.Limits.html
+                    required_limits,
+
+                    label: None,
+
+                    // https://wgpu.rs/doc/wgpu/enum.MemoryHints.html
+                    memory_hints: Default::default(),
+                },
+                // Trace path
+                None,
+            )
+            .await
+    }
+
+    // Configures `surface` for drawing to at `size`, with `present_mode` if
+    // the surface supports it. Also returns whether the surface can be copied
+    // from, which screenshots rely on.
+    pub fn configure_surface(
+        &self,
+        surface: &wgpu::Surface,
+        size: winit::dpi::PhysicalSize<u32>,
+        present_mode: Option<wgpu::PresentMode>,
+    ) -> (wgpu::SurfaceConfiguration, bool) {
+        let surface_caps = surface.get_capabilities(&self.adapter);
+
+        let surface_format = surface_caps
+            .formats
+            .iter()
+            .find(|f| f.is_srgb())
+            .copied()
+            .unwrap_or(surface_caps.formats[0]);
+
+        // Screenshots copy straight out of the surface texture
+        let can_capture =
+            surface_caps.usages.contains(wgpu::TextureUsages::COPY_SRC);
+        if !can_capture {
+            log::warn!("Surface doesn't support COPY_SRC, captures disabled");
+        }
+
+        // The Auto modes fall back on their own so they're always accepted
+        let present_mode = match present_mode {
+            Some(
+                mode @ (wgpu::PresentMode::AutoVsync
+                | wgpu::PresentMode::AutoNoVsync),
+            ) => mode,
+            Some(mode) if surface_caps.present_modes.contains(&mode) => mode,
+            Some(mode) => {
+                log::warn!(
+                    "Present mode {:?} not supported, using {:?}",
+                    mode,
+                    surface_caps.present_modes[0]
+                );
+                surface_caps.present_modes[0]
+            }
+            None => surface_caps.present_modes[0],
+        };
+
+        // Lets the sRGB surface also be drawn to as linear, for presenting
+        // with the encoding done by hand, see `LinearOutput`. Not every
+        // backend can reinterpret surface formats (GL can't).
+        let linear_format = surface_format.remove_srgb_suffix();
+        let view_formats = if linear_format != surface_format
+            && self
+                .adapter
+                .get_downlevel_capabilities()
+                .flags
+                .contains(wgpu::DownlevelFlags::SURFACE_VIEW_FORMATS)
+        {
+            vec![linear_format]
+        } else {
+            vec![]
+        };
+
+        let surface_configuration = wgpu::SurfaceConfiguration {
+            usage: if can_capture {
+                wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::COPY_SRC
+            } else {
+                wgpu::TextureUsages::RENDER_ATTACHMENT
+            },
+            format: surface_format,
+            width: size.width,
+            height: size.height,
+
+            // present_mode uses wgpu::PresentMode enum, which determines
+            // how to sync the surface with the display.
+            // Unless config.toml asks for one, we select the first available
+            // option. If you do not want runtime selection,
+            // `PresentMode::Fifo` will cap the display rate at the display's
+            // framerate. This is essentially VSync.
+            // This mode is guaranteed to be supported on all platforms.
+            //
+            // There are other options, and you can see all of them in the docs:
+            // https://docs.rs/wgpu/latest/wgpu/enum.PresentMode.html
+            //
+            // `PresentMode::AutoVsync` and `PresentMode::AutoNoVsync` have
+            // fallback support and therefore will work on all platforms.
+            present_mode,
+
+            alpha_mode: surface_caps.alpha_modes[0],
+            view_formats,
+            desired_maximum_frame_latency: DEFAULT_FRAME_LATENCY,
+        };
+
+        surface.configure(&self.device, &surface_configuration);
+
+        (surface_configuration, can_capture)
+    }
+}
+
+// A headless Gpu for unit tests, or None (and the test passes without
+// checking anything) on machines without any adapter, not even a software one
+#[cfg(test)]
+pub(crate) fn test_gpu() -> Option<Gpu> {
+    pollster::block_on(Gpu::new_headless(None, None, false))
+        .inspect_err(|e| eprintln!("Skipping, no GPU: {}", e))
+        .ok()
+}
+
+// What wgpu has allocated right now, for tracking down leaks: how many
+// resources it's holding ids for (dropped ones linger until the device is
+// next polled), what the backend counts as alive and how much memory that
+// takes, and the allocator's totals where the backend has one. The backend
+// only counts with the memory-report feature on.
+pub fn log_memory_report(instance: &wgpu::Instance, device: &wgpu::Device) {
+    match instance.generate_report() {
+        Some(report) => {
+            let hub = &report.hub;
+            log::info!(
+                "Ids: {} buffers, {} textures, {} texture views, {} bind \
+                 groups, {} render pipelines",
+                hub.buffers.num_allocated,
+                hub.textures.num_allocated,
+                hub.texture_views.num_allocated,
+                hub.bind_groups.num_allocated,
+                hub.render_pipelines.num_allocated
+            );
+        }
+        None => log::info!("No id report from this instance"),
+    }
+
+    if cfg!(feature = "memory-report") {
+        let hal = device.get_internal_counters().hal;
+        log::info!(
+            "Backend: {} buffers ({}), {} textures ({}), {} allocations",
+            hal.buffers.read(),
+            format_bytes(hal.buffer_memory.read().max(0) as u64),
+            hal.textures.read(),
+            format_bytes(hal.texture_memory.read().max(0) as u64),
+            hal.memory_allocations.read()
+        );
+    } else {
+        log::info!("Backend counts need the memory-report feature");
+    }
+
+    match device.generate_allocator_report() {
+        Some(report) => log::info!(
+            "Allocator: {} allocated in {} allocations, {} reserved in {} \
+             blocks",
+            format_bytes(report.total_allocated_bytes),
+            report.allocations.len(),
+            format_bytes(report.total_reserved_bytes),
+            report.blocks.len()
+        ),
+        None => log::info!("No allocator report from this backend"),
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const MIB: f64 = 1024.0 * 1024.0;
+    if bytes as f64 >= MIB {
+        format!("{:.1} MiB", bytes as f64 / MIB)
+    } else {
+        format!("{:.1} KiB", bytes as f64 / 1024.0)
+    }
+}
+
+// Everything worth knowing about the GPU when triaging a bug report: what
+// the adapter is, what the device actually got, and for a window what its
+// surface can do and what was picked. Only logged at debug level (e.g.
+// RUST_LOG=learn_wgpu=debug) since it's long.
+pub fn log_gpu_diagnostics(
+    adapter: &wgpu::Adapter,
+    device: &wgpu::Device,
+    surface: Option<(&wgpu::Surface, &wgpu::SurfaceConfiguration)>,
+) {
+    if !log::log_enabled!(log::Level::Debug) {
+        return;
+    }
+
+    let info = adapter.get_info();
+    log::debug!(
+        "Adapter: {} (vendor {:#06x}, device {:#06x}, {:?}, {:?})",
+        info.name,
+        info.vendor,
+        info.device,
+        info.device_type,
+        info.backend
+    );
+    log::debug!("Driver: {} {}", info.driver, info.driver_info);
+    log::debug!("Adapter features: {:?}", adapter.features());
+    log::debug!("Device features: {:?}", device.features());
+    log::debug!("Device limits: {:#?}", device.limits());
+    log::debug!(
+        "Downlevel capabilities: {:?}",
+        adapter.get_downlevel_capabilities()
+    );
+
+    let Some((surface, configuration)) = surface else {
+        log::debug!("No surface, rendering offscreen");
+        return;
+    };
+    let caps = surface.get_capabilities(adapter);
+    log::debug!("Surface formats: {:?}", caps.formats);
+    log::debug!("Surface present modes: {:?}", caps.present_modes);
+    log::debug!("Surface alpha modes: {:?}", caps.alpha_modes);
+    log::debug!("Surface usages: {:?}", caps.usages);
+    log::debug!(
+        "Chose {:?}, {:?}, {:?}",
+        configuration.format,
+        configuration.present_mode,
+        configuration.alpha_mode
+    );
+}
+
+// Describes an offscreen target the same way a surface would be, so the rest
+// of the renderer doesn't have to care which one it's drawing into
+pub fn offscreen_configuration(
+    width: u32,
+    height: u32,
+) -> wgpu::SurfaceConfiguration {
+    wgpu::SurfaceConfiguration {
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+            | wgpu::TextureUsages::COPY_SRC,
+        format: OFFSCREEN_FORMAT,
+        width,
+        height,
+        // Never presented, so these don't matter
+        present_mode: wgpu::PresentMode::Fifo,
+        alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+        view_formats: vec![],
+        desired_maximum_frame_latency: DEFAULT_FRAME_LATENCY,
+    }
+}
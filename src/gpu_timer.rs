@@ -0,0 +1,228 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+
+// Written from the map_async callback
+const MAP_PENDING: u8 = 0;
+const MAP_OK: u8 = 1;
+const MAP_FAILED: u8 = 2;
+
+// Frames of results that can be waiting on the GPU at once. Any more and a
+// frame's measurement is just dropped.
+const N_SLOTS: usize = 3;
+
+// A begin and an end timestamp
+const QUERY_COUNT: u32 = 2;
+const RESULTS_SIZE: wgpu::BufferAddress = QUERY_COUNT as wgpu::BufferAddress
+    * wgpu::QUERY_SIZE as wgpu::BufferAddress;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SlotState {
+    Idle,
+    // Resolved results are being copied in but haven't been submitted yet
+    Copying,
+    Mapping,
+}
+
+struct Slot {
+    buffer: wgpu::Buffer,
+    state: SlotState,
+    map_status: Arc<AtomicU8>,
+}
+
+// Measures how long the GPU spends in a single pass with timestamp queries.
+// The pass writes a timestamp as it begins and ends, `resolve` copies them
+// into a staging buffer, and `poll` picks the result up once the GPU is done
+// with it, a frame or two later. Like readback::ReadbackPool it never waits.
+pub struct GpuTimer {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    slots: Vec<Slot>,
+    next: usize,
+    // Nanoseconds per timestamp tick
+    period: f32,
+    // Summed over the current logging window
+    total_ms: f32,
+    n_samples: u32,
+    // Samples per logged average
+    log_interval: u32,
+}
+
+impl GpuTimer {
+    // None unless the device was created with Features::TIMESTAMP_QUERY
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        log_interval: u32,
+    ) -> Option<Self> {
+        if !device.features().contains(wgpu::Features::TIMESTAMP_QUERY) {
+            log::info!("Timestamp queries not supported, GPU timing disabled");
+            return None;
+        }
+
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("GPU Timer Query Set"),
+            ty: wgpu::QueryType::Timestamp,
+            count: QUERY_COUNT,
+        });
+
+        let resolve_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("GPU Timer Resolve Buffer"),
+            size: RESULTS_SIZE,
+            usage: wgpu::BufferUsages::QUERY_RESOLVE
+                | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        let slots = (0..N_SLOTS)
+            .map(|_| Slot {
+                buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("GPU Timer Staging Buffer"),
+                    size: RESULTS_SIZE,
+                    usage: wgpu::BufferUsages::COPY_DST
+                        | wgpu::BufferUsages::MAP_READ,
+                    mapped_at_creation: false,
+                }),
+                state: SlotState::Idle,
+                map_status: Arc::new(AtomicU8::new(MAP_PENDING)),
+            })
+            .collect();
+
+        Some(Self {
+            query_set,
+            resolve_buffer,
+            slots,
+            next: 0,
+            period: queue.get_timestamp_period(),
+            total_ms: 0.0,
+            n_samples: 0,
+            log_interval: log_interval.max(1),
+        })
+    }
+
+    // Goes in the descriptor of the pass being measured
+    pub fn timestamp_writes(&self) -> wgpu::RenderPassTimestampWrites<'_> {
+        wgpu::RenderPassTimestampWrites {
+            query_set: &self.query_set,
+            beginning_of_pass_write_index: Some(0),
+            end_of_pass_write_index: Some(1),
+        }
+    }
+
+    // To measure a run of passes instead, these go in the first and last
+    // ones' descriptors, and everything recorded in between is counted too
+    pub fn begin_writes(&self) -> wgpu::RenderPassTimestampWrites<'_> {
+        wgpu::RenderPassTimestampWrites {
+            end_of_pass_write_index: None,
+            ..self.timestamp_writes()
+        }
+    }
+
+    pub fn end_writes(&self) -> wgpu::RenderPassTimestampWrites<'_> {
+        wgpu::RenderPassTimestampWrites {
+            beginning_of_pass_write_index: None,
+            ..self.timestamp_writes()
+        }
+    }
+
+    // Call after the measured pass has been recorded. Skips the frame if
+    // every staging buffer is still in flight.
+    pub fn resolve(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        let Some(index) = (0..N_SLOTS)
+            .map(|i| (self.next + i) % N_SLOTS)
+            .find(|&i| self.slots[i].state == SlotState::Idle)
+        else {
+            return;
+        };
+        self.next = (index + 1) % N_SLOTS;
+
+        encoder.resolve_query_set(
+            &self.query_set,
+            0..QUERY_COUNT,
+            &self.resolve_buffer,
+            0,
+        );
+        let slot = &mut self.slots[index];
+        encoder.copy_buffer_to_buffer(
+            &self.resolve_buffer,
+            0,
+            &slot.buffer,
+            0,
+            RESULTS_SIZE,
+        );
+        slot.state = SlotState::Copying;
+    }
+
+    // Call after the encoder passed to `resolve` has been submitted
+    pub fn after_submit(&mut self) {
+        for slot in &mut self.slots {
+            if slot.state != SlotState::Copying {
+                continue;
+            }
+            let map_status = slot.map_status.clone();
+            map_status.store(MAP_PENDING, Ordering::Release);
+            slot.buffer.slice(..).map_async(
+                wgpu::MapMode::Read,
+                move |result| {
+                    let status = match result {
+                        Ok(()) => MAP_OK,
+                        Err(e) => {
+                            log::error!("GPU timer map failed: {:?}", e);
+                            MAP_FAILED
+                        }
+                    };
+                    map_status.store(status, Ordering::Release);
+                },
+            );
+            slot.state = SlotState::Mapping;
+        }
+    }
+
+    // Returns the most recent pass duration in milliseconds that came back
+    // since the last call, if any. Every `log_interval` samples the average
+    // is logged under `label`.
+    pub fn poll(&mut self, device: &wgpu::Device, label: &str) -> Option<f32> {
+        device.poll(wgpu::Maintain::Poll);
+
+        let mut latest = None;
+        for slot in &mut self.slots {
+            if slot.state != SlotState::Mapping {
+                continue;
+            }
+            match slot.map_status.load(Ordering::Acquire) {
+                MAP_OK => {}
+                MAP_FAILED => {
+                    slot.state = SlotState::Idle;
+                    continue;
+                }
+                _ => continue,
+            }
+
+            let ticks = {
+                let data = slot.buffer.slice(..).get_mapped_range();
+                let timestamps: &[u64] = bytemuck::cast_slice(&data);
+                // Timestamps can come back out of order on some hardware
+                timestamps[1].saturating_sub(timestamps[0])
+            };
+            slot.buffer.unmap();
+            slot.state = SlotState::Idle;
+
+            let ms = ticks as f32 * self.period / 1_000_000.0;
+            self.total_ms += ms;
+            self.n_samples += 1;
+            latest = Some(ms);
+        }
+
+        if self.n_samples >= self.log_interval {
+            log::info!(
+                "GPU {}: {:.3}ms (average of {} frames)",
+                label,
+                self.total_ms / self.n_samples as f32,
+                self.n_samples
+            );
+            self.total_ms = 0.0;
+            self.n_samples = 0;
+        }
+
+        latest
+    }
+}
@@ -0,0 +1,117 @@
+use crate::uniform::Uniform;
+
+// The colors a new gradient starts with: a deep blue sky over a warm
+// horizon, in the same linear values as `Renderer::clear_color`
+pub const DEFAULT_TOP: wgpu::Color = wgpu::Color {
+    r: 0.02,
+    g: 0.05,
+    b: 0.2,
+    a: 1.0,
+};
+pub const DEFAULT_BOTTOM: wgpu::Color = wgpu::Color {
+    r: 0.4,
+    g: 0.2,
+    b: 0.1,
+    a: 1.0,
+};
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct GradientUniform {
+    top: [f32; 4],
+    bottom: [f32; 4],
+}
+
+impl GradientUniform {
+    fn new(top: wgpu::Color, bottom: wgpu::Color) -> Self {
+        let rgba = |c: wgpu::Color| [c.r, c.g, c.b, c.a].map(|v| v as f32);
+        Self {
+            top: rgba(top),
+            bottom: rgba(bottom),
+        }
+    }
+}
+
+// A vertical gradient behind the scene, in place of the flat clear color.
+// Like the skybox it's a single fullscreen triangle with no geometry of its
+// own; it goes first in the scene pass, writing no depth, so everything
+// else lands in front of it.
+pub struct BackgroundGradient {
+    uniform: Uniform<GradientUniform>,
+    top: wgpu::Color,
+    bottom: wgpu::Color,
+}
+
+impl BackgroundGradient {
+    pub fn new(
+        device: &wgpu::Device,
+        top: wgpu::Color,
+        bottom: wgpu::Color,
+    ) -> Self {
+        let layout = Uniform::<GradientUniform>::create_layout(
+            device,
+            "Gradient",
+            wgpu::ShaderStages::FRAGMENT,
+        );
+        let uniform = Uniform::with_layout(
+            device,
+            "Gradient",
+            &layout,
+            &GradientUniform::new(top, bottom),
+        );
+        Self {
+            uniform,
+            top,
+            bottom,
+        }
+    }
+
+    // Another gradient drawn through the same pipeline, sharing this one's
+    // bind group layout
+    pub fn sharing_layout(
+        &self,
+        device: &wgpu::Device,
+        label: &str,
+        top: wgpu::Color,
+        bottom: wgpu::Color,
+    ) -> Self {
+        let uniform = Uniform::with_layout(
+            device,
+            label,
+            self.uniform.layout(),
+            &GradientUniform::new(top, bottom),
+        );
+        Self {
+            uniform,
+            top,
+            bottom,
+        }
+    }
+
+    // The top and bottom color
+    pub fn colors(&self) -> (wgpu::Color, wgpu::Color) {
+        (self.top, self.bottom)
+    }
+
+    pub fn set_colors(
+        &mut self,
+        queue: &wgpu::Queue,
+        top: wgpu::Color,
+        bottom: wgpu::Color,
+    ) {
+        self.top = top;
+        self.bottom = bottom;
+        self.uniform
+            .update(queue, &GradientUniform::new(top, bottom));
+    }
+
+    // Group 0 of gradient.wgsl
+    pub fn bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        self.uniform.layout()
+    }
+
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass) {
+        render_pass.set_bind_group(0, self.uniform.bind_group(), &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
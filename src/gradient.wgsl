@@ -0,0 +1,34 @@
+// see: gradient::GradientUniform
+struct GradientUniform {
+    top: vec4<f32>,
+    bottom: vec4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> gradient: GradientUniform;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    // 0 at the bottom of the screen, 1 at the top
+    @location(0) height: f32,
+}
+
+// The same fullscreen triangle as skybox.wgsl:
+// (-1, -1), (3, -1), (-1, 3)
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    let ndc = vec2<f32>(
+        f32((vertex_index << 1u) & 2u) * 2.0 - 1.0,
+        f32(vertex_index & 2u) * 2.0 - 1.0,
+    );
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(ndc, 1.0, 1.0);
+    out.height = ndc.y * 0.5 + 0.5;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return mix(gradient.bottom, gradient.top, in.height);
+}
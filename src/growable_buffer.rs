@@ -0,0 +1,231 @@
+// A buffer for data that's rewritten every frame and changes size as it
+// goes, like the sprite batch's vertices. Writes go into the existing
+// buffer whenever they fit; only one that doesn't replaces it, with one at
+// least twice the size, so a batch that keeps growing reallocates a handful
+// of times rather than every frame and one that shrinks never does.
+//
+// Holds on to a handle to the device it was made with so growing doesn't
+// need one passed in.
+pub struct GrowableBuffer {
+    device: wgpu::Device,
+    buffer: wgpu::Buffer,
+    label: &'static str,
+    usage: wgpu::BufferUsages,
+    // Size of `buffer` in bytes
+    capacity: wgpu::BufferAddress,
+    // Bytes written by the last `write`
+    len: wgpu::BufferAddress,
+}
+
+impl GrowableBuffer {
+    // `capacity` is in bytes. COPY_DST is added to `usage`, `write` needs
+    // it.
+    pub fn new(
+        device: &wgpu::Device,
+        label: &'static str,
+        usage: wgpu::BufferUsages,
+        capacity: wgpu::BufferAddress,
+    ) -> Self {
+        let usage = usage | wgpu::BufferUsages::COPY_DST;
+        // Buffers can't be empty, and writes come in whole 4 byte words
+        let capacity = capacity
+            .max(wgpu::COPY_BUFFER_ALIGNMENT)
+            .next_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT);
+        Self {
+            device: device.clone(),
+            buffer: create_buffer(device, label, usage, capacity),
+            label,
+            usage,
+            capacity,
+            len: 0,
+        }
+    }
+
+    // Replaces the buffer's contents with `data`, growing it first if it's
+    // too small. Returns whether it grew, in which case anything that refers
+    // to the old buffer (like a bind group) has to be made again.
+    pub fn write<T: bytemuck::Pod>(
+        &mut self,
+        queue: &wgpu::Queue,
+        data: &[T],
+    ) -> bool {
+        let bytes: &[u8] = bytemuck::cast_slice(data);
+        self.len = bytes.len() as wgpu::BufferAddress;
+
+        let grew = self.len > self.capacity;
+        if grew {
+            let mut capacity = self.capacity;
+            while capacity < self.len {
+                capacity *= 2;
+            }
+            self.buffer =
+                create_buffer(&self.device, self.label, self.usage, capacity);
+            self.capacity = capacity;
+        }
+
+        if !bytes.is_empty() {
+            queue.write_buffer(&self.buffer, 0, bytes);
+        }
+        grew
+    }
+
+    // Overwrites just the bytes from `offset` on with `data`, leaving the
+    // rest alone, e.g. to move one instance out of thousands. Never grows
+    // the buffer: a range past its capacity is logged and skipped, as is one
+    // that isn't in whole 4 byte words, which write_buffer needs. Returns
+    // whether it was written. Bytes past what `write` last filled count as
+    // filled from then on.
+    pub fn update_range<T: bytemuck::Pod>(
+        &mut self,
+        queue: &wgpu::Queue,
+        offset: wgpu::BufferAddress,
+        data: &[T],
+    ) -> bool {
+        let bytes: &[u8] = bytemuck::cast_slice(data);
+        let size = bytes.len() as wgpu::BufferAddress;
+        let end = offset.checked_add(size);
+        if end.is_none_or(|end| end > self.capacity) {
+            log::error!(
+                "{}: can't update {} bytes at {}, it only holds {}",
+                self.label,
+                size,
+                offset,
+                self.capacity
+            );
+            return false;
+        }
+        if !offset.is_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT)
+            || !size.is_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT)
+        {
+            log::error!(
+                "{}: can't update {} bytes at {}, both need to be multiples \
+                 of {}",
+                self.label,
+                size,
+                offset,
+                wgpu::COPY_BUFFER_ALIGNMENT
+            );
+            return false;
+        }
+
+        if !bytes.is_empty() {
+            queue.write_buffer(&self.buffer, offset, bytes);
+        }
+        self.len = self.len.max(offset + size);
+        true
+    }
+
+    // Just what the last `write` filled, e.g. for set_vertex_buffer. Panics
+    // if that was nothing, as an empty slice would.
+    pub fn slice(&self) -> wgpu::BufferSlice<'_> {
+        self.buffer.slice(..self.len)
+    }
+
+    // The whole buffer, e.g. to bind as storage. A `write` that grows it
+    // replaces it.
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+}
+
+fn create_buffer(
+    device: &wgpu::Device,
+    label: &'static str,
+    usage: wgpu::BufferUsages,
+    size: wgpu::BufferAddress,
+) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some(label),
+        size,
+        usage,
+        mapped_at_creation: false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gpu::test_gpu;
+    use crate::readback::read_buffer;
+
+    #[test]
+    fn reuses_the_buffer_when_data_shrinks() {
+        let Some(gpu) = test_gpu() else { return };
+        let mut buffer = GrowableBuffer::new(
+            &gpu.device,
+            "Test Buffer",
+            wgpu::BufferUsages::VERTEX,
+            64,
+        );
+
+        for n in [16, 4, 0, 16] {
+            assert!(!buffer.write(&gpu.queue, &vec![0u32; n]));
+            assert_eq!(buffer.len, n as u64 * 4);
+        }
+        assert_eq!(buffer.capacity, 64);
+    }
+
+    #[test]
+    fn grows_once_when_crossing_the_capacity() {
+        let Some(gpu) = test_gpu() else { return };
+        let mut buffer = GrowableBuffer::new(
+            &gpu.device,
+            "Test Buffer",
+            wgpu::BufferUsages::VERTEX,
+            64,
+        );
+
+        // Growing a word at a time past the first capacity only reallocates
+        // at the word that crosses it, doubling the size
+        let grew: Vec<usize> = (1..=32)
+            .filter(|&n| buffer.write(&gpu.queue, &vec![0u32; n]))
+            .collect();
+        assert_eq!(grew, [17]);
+        assert_eq!(buffer.capacity, 128);
+        // Shrinking back down keeps the bigger buffer
+        assert!(!buffer.write(&gpu.queue, &[0u32; 1]));
+        assert_eq!(buffer.capacity, 128);
+    }
+
+    #[test]
+    fn update_range_only_changes_the_targeted_bytes() {
+        let Some(gpu) = test_gpu() else { return };
+        let mut buffer = GrowableBuffer::new(
+            &gpu.device,
+            "Test Buffer",
+            wgpu::BufferUsages::COPY_SRC,
+            64,
+        );
+        let original: Vec<u32> = (0..16).collect();
+        buffer.write(&gpu.queue, &original);
+
+        assert!(buffer.update_range(&gpu.queue, 20, &[100u32, 101]));
+        let read: Vec<u32> =
+            read_buffer(&gpu.device, &gpu.queue, &buffer.buffer, 16);
+        let mut expected = original;
+        expected[5..7].copy_from_slice(&[100, 101]);
+        assert_eq!(read, expected);
+    }
+
+    #[test]
+    fn update_range_rejects_ranges_it_cant_write() {
+        let Some(gpu) = test_gpu() else { return };
+        let mut buffer = GrowableBuffer::new(
+            &gpu.device,
+            "Test Buffer",
+            wgpu::BufferUsages::VERTEX,
+            64,
+        );
+        buffer.write(&gpu.queue, &[0u32; 4]);
+
+        // Past the capacity, even partly, and not in whole words
+        assert!(!buffer.update_range(&gpu.queue, 60, &[0u32; 2]));
+        assert!(!buffer.update_range(&gpu.queue, u64::MAX - 3, &[0u32]));
+        assert!(!buffer.update_range(&gpu.queue, 2, &[0u32]));
+        assert!(!buffer.update_range(&gpu.queue, 0, &[0u8; 3]));
+        assert_eq!((buffer.capacity, buffer.len), (64, 16));
+        // Past what was written but inside the capacity is fine, and counts
+        assert!(buffer.update_range(&gpu.queue, 32, &[0u32; 8]));
+        assert_eq!((buffer.capacity, buffer.len), (64, 64));
+    }
+}
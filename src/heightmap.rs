@@ -0,0 +1,202 @@
+use std::path::Path;
+
+use anyhow::*;
+use cgmath::{InnerSpace, Vector3};
+
+use crate::geometry::Vertex;
+
+// A grid of vertices on the XZ plane lifted by a heightmap, one vertex per
+// pixel, drawn like any other mesh. The image's top row ends up at -Z and
+// its left column at -X, so seen from above it looks like the image.
+pub struct Terrain {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+}
+
+impl Terrain {
+    // `path` is read as grayscale, black lowest and white `height_scale`
+    // higher. `scale` is how wide the terrain is along X, centered on the
+    // origin; Z gets the same spacing between vertices, so a heightmap that
+    // isn't square makes a terrain that isn't either.
+    pub fn from_heightmap(
+        path: impl AsRef<Path>,
+        scale: f32,
+        height_scale: f32,
+    ) -> Result<Self> {
+        let path = path.as_ref();
+        let image = image::open(path)
+            .with_context(|| format!("Reading heightmap {}", path.display()))?
+            .into_luma16();
+        let (columns, rows) = image.dimensions();
+        if columns < 2 || rows < 2 {
+            bail!(
+                "Heightmap {} is {}x{}, it needs at least 2x2 pixels",
+                path.display(),
+                columns,
+                rows
+            );
+        }
+        let heights: Vec<f32> = image
+            .pixels()
+            .map(|pixel| pixel.0[0] as f32 / u16::MAX as f32 * height_scale)
+            .collect();
+        Ok(Self::from_heights(
+            columns as usize,
+            rows as usize,
+            &heights,
+            scale,
+        ))
+    }
+
+    // `heights` are in world units, row by row from -Z
+    fn from_heights(
+        columns: usize,
+        rows: usize,
+        heights: &[f32],
+        scale: f32,
+    ) -> Self {
+        let spacing = scale / (columns - 1) as f32;
+        let depth = spacing * (rows - 1) as f32;
+        let height =
+            |column: usize, row: usize| heights[row * columns + column];
+
+        let mut vertices = Vec::with_capacity(columns * rows);
+        for row in 0..rows {
+            for column in 0..columns {
+                let (tangent, bitangent) = surface_tangents(
+                    column, row, columns, rows, spacing, height,
+                );
+                let normal = bitangent.cross(tangent).normalize();
+                let u = column as f32 / (columns - 1) as f32;
+                let v = row as f32 / (rows - 1) as f32;
+                vertices.push(Vertex {
+                    position: [
+                        u * scale - scale * 0.5,
+                        height(column, row),
+                        v * depth - depth * 0.5,
+                    ],
+                    tex_coords: [u, v],
+                    normal: normal.into(),
+                    tangent: tangent.normalize().into(),
+                    bitangent: bitangent.normalize().into(),
+                    color: [1.0, 1.0, 1.0],
+                });
+            }
+        }
+
+        let columns = columns as u32;
+        let indices = (0..rows as u32 - 1)
+            .flat_map(|row| {
+                (0..columns - 1).flat_map(move |column| {
+                    let top_left = row * columns + column;
+                    let top_right = top_left + 1;
+                    let bottom_left = top_left + columns;
+                    let bottom_right = bottom_left + 1;
+                    // Counter-clockwise when seen from above
+                    [
+                        top_left,
+                        bottom_left,
+                        top_right,
+                        top_right,
+                        bottom_left,
+                        bottom_right,
+                    ]
+                })
+            })
+            .collect();
+
+        Self { vertices, indices }
+    }
+}
+
+// Which way the surface goes from the vertex at `column`, `row` along +X
+// (the tangent, following u) and along +Z (the bitangent, following v), from
+// the difference between its neighbors' heights either side. Edge vertices
+// only have a neighbor on one side, so use themselves for the other.
+fn surface_tangents(
+    column: usize,
+    row: usize,
+    columns: usize,
+    rows: usize,
+    spacing: f32,
+    height: impl Fn(usize, usize) -> f32,
+) -> (Vector3<f32>, Vector3<f32>) {
+    let (left, right) =
+        (column.saturating_sub(1), (column + 1).min(columns - 1));
+    let (up, down) = (row.saturating_sub(1), (row + 1).min(rows - 1));
+    let tangent = Vector3::new(
+        (right - left) as f32 * spacing,
+        height(right, row) - height(left, row),
+        0.0,
+    );
+    let bitangent = Vector3::new(
+        0.0,
+        height(column, down) - height(column, up),
+        (down - up) as f32 * spacing,
+    );
+    (tangent, bitangent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: [f32; 3], b: Vector3<f32>) {
+        let a = Vector3::from(a);
+        assert!((a - b).magnitude() < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn flat_ground_faces_up() {
+        let terrain = Terrain::from_heights(3, 3, &[0.5; 9], 2.0);
+        for vertex in &terrain.vertices {
+            assert_close(vertex.normal, Vector3::unit_y());
+            assert_eq!(vertex.position[1], 0.5);
+        }
+    }
+
+    #[test]
+    fn ramp_normals_lean_away_from_the_slope() {
+        // Rising 1 unit over every 1 unit along +X: a 45 degree ramp whose
+        // normals all point up and back towards -X, edges included
+        let (columns, rows) = (4, 3);
+        let heights: Vec<f32> = (0..rows)
+            .flat_map(|_| (0..columns).map(|column| column as f32))
+            .collect();
+        let terrain = Terrain::from_heights(columns, rows, &heights, 3.0);
+
+        let expected = Vector3::new(-1.0, 1.0, 0.0).normalize();
+        for vertex in &terrain.vertices {
+            assert_close(vertex.normal, expected);
+            assert_close(
+                vertex.tangent,
+                Vector3::new(1.0, 1.0, 0.0).normalize(),
+            );
+            assert_close(vertex.bitangent, Vector3::unit_z());
+        }
+    }
+
+    #[test]
+    fn ramp_along_z_leans_towards_minus_z() {
+        let (columns, rows) = (3, 4);
+        let heights: Vec<f32> = (0..rows)
+            .flat_map(|row| (0..columns).map(move |_| row as f32 * 0.5))
+            .collect();
+        let terrain = Terrain::from_heights(columns, rows, &heights, 2.0);
+
+        let expected = Vector3::new(0.0, 1.0, -0.5).normalize();
+        for vertex in &terrain.vertices {
+            assert_close(vertex.normal, expected);
+        }
+    }
+
+    #[test]
+    fn grid_covers_the_heightmap() {
+        let terrain = Terrain::from_heights(4, 2, &[0.0; 8], 3.0);
+        assert_eq!(terrain.vertices.len(), 8);
+        // Two triangles per cell, 3 x 1 cells
+        assert_eq!(terrain.indices.len(), 3 * 6);
+        assert_eq!(terrain.vertices[0].position, [-1.5, 0.0, -0.5]);
+        assert_eq!(terrain.vertices[7].position, [1.5, 0.0, 0.5]);
+    }
+}
@@ -0,0 +1,96 @@
+use crate::geometry::Mesh;
+
+// Size of one wgpu::util::DrawIndexedIndirectArgs in the buffer
+const ARGS_SIZE: wgpu::BufferAddress = std::mem::size_of::<
+    wgpu::util::DrawIndexedIndirectArgs,
+>() as wgpu::BufferAddress;
+
+// The index and instance counts of each draw, read by the GPU from a buffer
+// when the draw executes rather than baked into the command. Rewriting the
+// buffer changes what's drawn without touching the recorded commands, and
+// since the buffer is also bound as storage, a compute pass can fill it in
+// with nothing coming back to the CPU (which is what `InstanceField` does
+// with its culling).
+//
+// Indirect draws need DownlevelFlags::INDIRECT_EXECUTION, which everything
+// but WebGL has. `first_instance` has to stay 0 without
+// Features::INDIRECT_FIRST_INSTANCE.
+pub struct IndirectDraws {
+    buffer: wgpu::Buffer,
+    capacity: usize,
+}
+
+impl IndirectDraws {
+    pub fn new(device: &wgpu::Device, capacity: usize) -> Self {
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Indirect Args Buffer"),
+            size: ARGS_SIZE * capacity as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::INDIRECT
+                | wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self { buffer, capacity }
+    }
+
+    // For compute passes that write the arguments themselves
+    #[allow(unused)]
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+
+    // Writes `args` into the first slots from the CPU. Panics if there are
+    // more than the buffer was created for.
+    pub fn set_args(
+        &self,
+        queue: &wgpu::Queue,
+        args: &[wgpu::util::DrawIndexedIndirectArgs],
+    ) {
+        assert!(
+            args.len() <= self.capacity,
+            "{} draws but room for {}",
+            args.len(),
+            self.capacity
+        );
+        let bytes: Vec<u8> = args
+            .iter()
+            .flat_map(|args| args.as_bytes().iter().copied())
+            .collect();
+        queue.write_buffer(&self.buffer, 0, &bytes);
+    }
+
+    // Binds `mesh` and draws it with the arguments in slot `index`. The
+    // pipeline and bind groups are left to the caller.
+    pub fn draw(
+        &self,
+        render_pass: &mut wgpu::RenderPass,
+        index: usize,
+        mesh: &Mesh,
+    ) {
+        debug_assert!(index < self.capacity);
+        mesh.bind(render_pass);
+        render_pass.draw_indexed_indirect(
+            &self.buffer,
+            index as wgpu::BufferAddress * ARGS_SIZE,
+        );
+    }
+}
+
+// All of `mesh` (or its first `max_triangles` triangles), once
+pub fn mesh_args(
+    mesh: &Mesh,
+    max_triangles: Option<u32>,
+) -> wgpu::util::DrawIndexedIndirectArgs {
+    let index_count = match max_triangles {
+        Some(triangles) => mesh.n_indices().min(triangles * 3),
+        None => mesh.n_indices(),
+    };
+    wgpu::util::DrawIndexedIndirectArgs {
+        index_count,
+        instance_count: 1,
+        first_index: 0,
+        base_vertex: 0,
+        first_instance: 0,
+    }
+}
@@ -0,0 +1,401 @@
+use std::collections::HashMap;
+
+use winit::event::{ElementState, KeyEvent};
+use winit::keyboard::{KeyCode, ModifiersState, PhysicalKey};
+
+// Everything the keyboard can do, independent of which key does it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    Quit,
+    ToggleFullscreen,
+    ToggleDebugUi,
+    Screenshot,
+    ToggleCaptureSequence,
+    // Held rather than pressed, see `InputMap::is_pressed`
+    MoveForward,
+    MoveBackward,
+    MoveLeft,
+    MoveRight,
+    CyclePipeline,
+    CyclePipelineBackward,
+    ToggleWireframe,
+    ToggleConservativeRasterization,
+    TogglePoints,
+    ToggleSplitStreams,
+    ToggleNormalsPipeline,
+    ToggleClipPlane,
+    // Held, see `InputMap::is_pressed`
+    AdvanceClipPlane,
+    RetreatClipPlane,
+    CycleCullMode,
+    ToggleFrontFace,
+    ToggleSplitScreen,
+    ToggleAspectLock,
+    ToggleGridView,
+    NarrowFov,
+    WidenFov,
+    CycleFrameRateCap,
+    CycleFrameLatency,
+    CycleAaMode,
+    ToggleLabels,
+    ToggleLabelOcclusion,
+    ToggleStencilReveal,
+    ToggleOrthoCamera,
+    ToggleFlyCamera,
+    ToggleInstances,
+    ToggleObjects,
+    ToggleShadows,
+    CycleTriangleLimit,
+    ToggleCulling,
+    ToggleDepthPrepass,
+    ToggleDiscardTransient,
+    ToggleDepthView,
+    CycleCanvasAspect,
+    CycleCanvasFit,
+    ToggleSprites,
+    CycleSpriteLayer,
+    ToggleTileMap,
+    ToggleTranslucent,
+    ToggleParticles,
+    ToggleSkybox,
+    ToggleGradient,
+    SwapGradientColors,
+    ToggleTerrain,
+    ToggleHeightmap,
+    ToggleGrid,
+    ToggleBounds,
+    ToggleNormals,
+    CycleGBufferView,
+    ToggleAdaptiveResolution,
+    CycleRenderScale,
+    ToggleUpscaleFilter,
+    CycleAddressMode,
+    ToggleUvTiling,
+    IncreaseDetailMix,
+    DecreaseDetailMix,
+    IncreaseShininess,
+    DecreaseShininess,
+    IncreaseReflectivity,
+    DecreaseReflectivity,
+    ToggleSpecular,
+    IncreaseBlurRadius,
+    DecreaseBlurRadius,
+    CycleBlurSampleSpacing,
+    IncreaseClearRed,
+    DecreaseClearRed,
+    IncreaseClearGreen,
+    DecreaseClearGreen,
+    IncreaseClearBlue,
+    DecreaseClearBlue,
+    ToggleCursorClearColor,
+    CyclePostPreset,
+    TogglePostHud,
+    SelectPreviousEffect,
+    SelectNextEffect,
+    ToggleSelectedEffect,
+    MoveEffectEarlier,
+    MoveEffectLater,
+    ToggleAnimationPaused,
+    ToggleRendering,
+    ToggleStepMode,
+    StepFrame,
+    ScrubBackward,
+    ScrubForward,
+    ResetTime,
+    ToggleMirror,
+    ToggleLinearView,
+    ToggleMorph,
+    OpenWindow,
+    ReloadShader,
+    SaveRenderState,
+    LogMemoryReport,
+}
+
+impl Action {
+    // Whether holding the key down keeps firing the action. Toggles only
+    // fire once per press so they don't flicker.
+    fn repeats(self) -> bool {
+        matches!(
+            self,
+            Action::CyclePipeline
+                | Action::CyclePipelineBackward
+                | Action::CycleTriangleLimit
+                | Action::IncreaseDetailMix
+                | Action::DecreaseDetailMix
+                | Action::IncreaseShininess
+                | Action::DecreaseShininess
+                | Action::StepFrame
+                | Action::ScrubBackward
+                | Action::ScrubForward
+                | Action::IncreaseReflectivity
+                | Action::DecreaseReflectivity
+                | Action::IncreaseBlurRadius
+                | Action::DecreaseBlurRadius
+                | Action::IncreaseClearRed
+                | Action::DecreaseClearRed
+                | Action::IncreaseClearGreen
+                | Action::DecreaseClearGreen
+                | Action::IncreaseClearBlue
+                | Action::DecreaseClearBlue
+                | Action::SelectPreviousEffect
+                | Action::SelectNextEffect
+                | Action::MoveEffectEarlier
+                | Action::MoveEffectLater
+        )
+    }
+}
+
+// A physical key, plus whether Shift has to be held with it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Binding {
+    pub key: KeyCode,
+    pub shift: bool,
+}
+
+impl Binding {
+    pub const fn key(key: KeyCode) -> Self {
+        Self { key, shift: false }
+    }
+
+    pub const fn shift(key: KeyCode) -> Self {
+        Self { key, shift: true }
+    }
+}
+
+// Keys are physical (a KeyCode is a position on a US layout, not the letter
+// printed on it), so bindings stay in the same place on other layouts
+const DEFAULT_BINDINGS: &[(Binding, Action)] = &[
+    (Binding::key(KeyCode::Escape), Action::Quit),
+    (Binding::key(KeyCode::F11), Action::ToggleFullscreen),
+    (Binding::key(KeyCode::F1), Action::ToggleDebugUi),
+    (Binding::key(KeyCode::F12), Action::Screenshot),
+    (Binding::key(KeyCode::F10), Action::ToggleCaptureSequence),
+    (Binding::key(KeyCode::F2), Action::OpenWindow),
+    (Binding::key(KeyCode::F5), Action::ReloadShader),
+    (Binding::key(KeyCode::F6), Action::SaveRenderState),
+    (Binding::key(KeyCode::F4), Action::LogMemoryReport),
+    (Binding::key(KeyCode::ArrowUp), Action::MoveForward),
+    (Binding::key(KeyCode::ArrowDown), Action::MoveBackward),
+    (Binding::key(KeyCode::ArrowLeft), Action::MoveLeft),
+    (Binding::key(KeyCode::ArrowRight), Action::MoveRight),
+    (Binding::key(KeyCode::Space), Action::CyclePipeline),
+    (
+        Binding::shift(KeyCode::Space),
+        Action::CyclePipelineBackward,
+    ),
+    (Binding::key(KeyCode::KeyL), Action::ToggleWireframe),
+    (Binding::key(KeyCode::Semicolon), Action::TogglePoints),
+    (Binding::key(KeyCode::Insert), Action::ToggleSplitStreams),
+    (
+        Binding::shift(KeyCode::Insert),
+        Action::ToggleNormalsPipeline,
+    ),
+    (Binding::key(KeyCode::Quote), Action::ToggleClipPlane),
+    (Binding::key(KeyCode::PageUp), Action::AdvanceClipPlane),
+    (Binding::key(KeyCode::PageDown), Action::RetreatClipPlane),
+    (
+        Binding::key(KeyCode::KeyH),
+        Action::ToggleConservativeRasterization,
+    ),
+    (Binding::key(KeyCode::Backslash), Action::CycleCullMode),
+    (Binding::shift(KeyCode::Backslash), Action::ToggleFrontFace),
+    (Binding::key(KeyCode::KeyF), Action::CycleFrameRateCap),
+    (Binding::key(KeyCode::F7), Action::CycleFrameLatency),
+    (Binding::key(KeyCode::F8), Action::ToggleSplitScreen),
+    (Binding::key(KeyCode::KeyK), Action::ToggleLabels),
+    (Binding::shift(KeyCode::KeyK), Action::ToggleLabelOcclusion),
+    (Binding::key(KeyCode::KeyM), Action::ToggleStencilReveal),
+    (Binding::key(KeyCode::KeyO), Action::ToggleOrthoCamera),
+    (Binding::key(KeyCode::F3), Action::ToggleFlyCamera),
+    (Binding::key(KeyCode::KeyI), Action::ToggleInstances),
+    (Binding::key(KeyCode::KeyD), Action::ToggleObjects),
+    (Binding::key(KeyCode::KeyJ), Action::ToggleShadows),
+    (Binding::key(KeyCode::KeyQ), Action::CycleTriangleLimit),
+    (Binding::key(KeyCode::KeyU), Action::ToggleCulling),
+    (Binding::key(KeyCode::KeyZ), Action::ToggleDepthPrepass),
+    (
+        Binding::shift(KeyCode::KeyD),
+        Action::ToggleDiscardTransient,
+    ),
+    (Binding::shift(KeyCode::KeyZ), Action::ToggleDepthView),
+    (Binding::key(KeyCode::KeyC), Action::CycleCanvasAspect),
+    (Binding::key(KeyCode::KeyV), Action::CycleCanvasFit),
+    (Binding::shift(KeyCode::KeyV), Action::ToggleAspectLock),
+    (Binding::shift(KeyCode::F8), Action::ToggleGridView),
+    (Binding::key(KeyCode::KeyS), Action::ToggleSprites),
+    (Binding::shift(KeyCode::KeyS), Action::ToggleTileMap),
+    (Binding::key(KeyCode::Backquote), Action::CycleSpriteLayer),
+    (Binding::key(KeyCode::KeyA), Action::ToggleTranslucent),
+    (Binding::shift(KeyCode::KeyA), Action::CycleAaMode),
+    (Binding::key(KeyCode::KeyE), Action::ToggleParticles),
+    (Binding::key(KeyCode::KeyB), Action::ToggleSkybox),
+    (Binding::key(KeyCode::Home), Action::ToggleGradient),
+    (Binding::key(KeyCode::End), Action::SwapGradientColors),
+    (Binding::shift(KeyCode::KeyB), Action::ToggleBounds),
+    (Binding::key(KeyCode::KeyT), Action::ToggleTerrain),
+    (Binding::shift(KeyCode::KeyT), Action::ToggleHeightmap),
+    (Binding::key(KeyCode::KeyN), Action::ToggleNormals),
+    (Binding::shift(KeyCode::KeyN), Action::CycleGBufferView),
+    (
+        Binding::key(KeyCode::KeyR),
+        Action::ToggleAdaptiveResolution,
+    ),
+    (Binding::shift(KeyCode::KeyR), Action::CycleRenderScale),
+    (Binding::shift(KeyCode::KeyF), Action::ToggleUpscaleFilter),
+    (Binding::key(KeyCode::Slash), Action::CycleAddressMode),
+    (Binding::shift(KeyCode::Slash), Action::ToggleUvTiling),
+    // + and - (Shift+= and Shift+- on most layouts)
+    (Binding::shift(KeyCode::Equal), Action::IncreaseDetailMix),
+    (Binding::shift(KeyCode::Minus), Action::DecreaseDetailMix),
+    // The keypad's + and -
+    (Binding::key(KeyCode::NumpadAdd), Action::IncreaseShininess),
+    (
+        Binding::key(KeyCode::NumpadSubtract),
+        Action::DecreaseShininess,
+    ),
+    (
+        Binding::key(KeyCode::NumpadMultiply),
+        Action::IncreaseReflectivity,
+    ),
+    (
+        Binding::key(KeyCode::NumpadDivide),
+        Action::DecreaseReflectivity,
+    ),
+    // Held, for as long as they're down
+    (Binding::shift(KeyCode::NumpadAdd), Action::NarrowFov),
+    (Binding::shift(KeyCode::NumpadSubtract), Action::WidenFov),
+    (Binding::shift(KeyCode::KeyL), Action::ToggleSpecular),
+    // < and > without Shift, for the blur preset
+    (Binding::key(KeyCode::Period), Action::IncreaseBlurRadius),
+    (Binding::key(KeyCode::Comma), Action::DecreaseBlurRadius),
+    // < on most layouts
+    (
+        Binding::shift(KeyCode::Comma),
+        Action::CycleBlurSampleSpacing,
+    ),
+    // The top row of the keypad, left to right, for the clear color's red,
+    // green and blue
+    (Binding::key(KeyCode::Numpad7), Action::IncreaseClearRed),
+    (Binding::shift(KeyCode::Numpad7), Action::DecreaseClearRed),
+    (Binding::key(KeyCode::Numpad8), Action::IncreaseClearGreen),
+    (Binding::shift(KeyCode::Numpad8), Action::DecreaseClearGreen),
+    (Binding::key(KeyCode::Numpad9), Action::IncreaseClearBlue),
+    (Binding::shift(KeyCode::Numpad9), Action::DecreaseClearBlue),
+    (
+        Binding::shift(KeyCode::KeyC),
+        Action::ToggleCursorClearColor,
+    ),
+    (Binding::key(KeyCode::KeyG), Action::CyclePostPreset),
+    (Binding::shift(KeyCode::KeyG), Action::ToggleGrid),
+    (Binding::key(KeyCode::KeyP), Action::TogglePostHud),
+    (Binding::shift(KeyCode::KeyP), Action::ToggleAnimationPaused),
+    (Binding::key(KeyCode::Pause), Action::ToggleRendering),
+    (Binding::key(KeyCode::F9), Action::ToggleStepMode),
+    // > on most layouts, next to . for the blur
+    (Binding::shift(KeyCode::Period), Action::StepFrame),
+    // Held Shift takes the arrows off the camera
+    (Binding::shift(KeyCode::ArrowLeft), Action::ScrubBackward),
+    (Binding::shift(KeyCode::ArrowRight), Action::ScrubForward),
+    (Binding::shift(KeyCode::Home), Action::ResetTime),
+    (Binding::key(KeyCode::KeyY), Action::ToggleMirror),
+    (Binding::key(KeyCode::KeyX), Action::ToggleLinearView),
+    (Binding::key(KeyCode::KeyW), Action::ToggleMorph),
+    (
+        Binding::key(KeyCode::BracketLeft),
+        Action::SelectPreviousEffect,
+    ),
+    (
+        Binding::key(KeyCode::BracketRight),
+        Action::SelectNextEffect,
+    ),
+    (Binding::key(KeyCode::Enter), Action::ToggleSelectedEffect),
+    (Binding::key(KeyCode::Minus), Action::MoveEffectEarlier),
+    (Binding::key(KeyCode::Equal), Action::MoveEffectLater),
+];
+
+// Turns key events into actions through a binding table, and keeps track of
+// which actions are held down right now
+pub struct InputMap {
+    bindings: HashMap<Binding, Action>,
+    // The action each held key started, so releasing the key ends the same
+    // action even if Shift changed in between
+    held: HashMap<KeyCode, Action>,
+}
+
+impl InputMap {
+    pub fn new() -> Self {
+        Self {
+            bindings: DEFAULT_BINDINGS.iter().copied().collect(),
+            held: HashMap::new(),
+        }
+    }
+
+    // Replaces whatever `binding` did before. An action can have any number
+    // of bindings.
+    #[allow(unused)]
+    pub fn bind(&mut self, binding: Binding, action: Action) {
+        self.bindings.insert(binding, action);
+    }
+
+    // Updates the held actions and returns the action the event fires, if
+    // any. A Shift binding wins over the plain one, and without one Shift is
+    // ignored.
+    pub fn process_key(
+        &mut self,
+        event: &KeyEvent,
+        modifiers: ModifiersState,
+    ) -> Option<Action> {
+        let PhysicalKey::Code(key) = event.physical_key else {
+            return None;
+        };
+
+        if event.state == ElementState::Released {
+            self.held.remove(&key);
+            return None;
+        }
+
+        let action = if modifiers.shift_key() {
+            self.bindings
+                .get(&Binding::shift(key))
+                .or_else(|| self.bindings.get(&Binding::key(key)))
+        } else {
+            self.bindings.get(&Binding::key(key))
+        }
+        .copied()?;
+
+        self.held.insert(key, action);
+        (!event.repeat || action.repeats()).then_some(action)
+    }
+
+    pub fn is_pressed(&self, action: Action) -> bool {
+        self.held.values().any(|&held| held == action)
+    }
+
+    // For when the window loses focus and won't see the releases
+    pub fn release_all(&mut self) {
+        self.held.clear();
+    }
+}
+
+// 1 to 9 for a fresh press of one of the number keys above the letters,
+// which are left unbound for camera bookmarks
+pub fn number_key(event: &KeyEvent) -> Option<usize> {
+    if event.state != ElementState::Pressed || event.repeat {
+        return None;
+    }
+    let PhysicalKey::Code(key) = event.physical_key else {
+        return None;
+    };
+    const KEYS: [KeyCode; 9] = [
+        KeyCode::Digit1,
+        KeyCode::Digit2,
+        KeyCode::Digit3,
+        KeyCode::Digit4,
+        KeyCode::Digit5,
+        KeyCode::Digit6,
+        KeyCode::Digit7,
+        KeyCode::Digit8,
+        KeyCode::Digit9,
+    ];
+    KEYS.iter().position(|&k| k == key).map(|i| i + 1)
+}
@@ -0,0 +1,102 @@
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) normal: vec3<f32>,
+    @location(3) tangent: vec3<f32>,
+    @location(4) bitangent: vec3<f32>,
+}
+
+// see: instancing::InstanceRaw
+struct InstanceInput {
+    @location(6) model_matrix_0: vec4<f32>,
+    @location(7) model_matrix_1: vec4<f32>,
+    @location(8) model_matrix_2: vec4<f32>,
+    @location(9) model_matrix_3: vec4<f32>,
+}
+
+struct VertexOutput {
+    // The depth prepass and the color pass must compute bit-identical
+    // positions for the Equal depth test to work
+    @builtin(position) @invariant clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    // World space tangent frame
+    @location(1) normal: vec3<f32>,
+    @location(2) tangent: vec3<f32>,
+    @location(3) bitangent: vec3<f32>,
+}
+
+// see: camera::CameraUniform
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+    inv_view_proj: mat4x4<f32>,
+    view_position: vec4<f32>,
+}
+
+@group(1) @binding(0)
+var<uniform> camera: CameraUniform;
+
+// see: GlobalsUniform
+struct GlobalsUniform {
+    time: f32,
+    show_normals: u32,
+}
+
+@group(2) @binding(0)
+var<uniform> globals: GlobalsUniform;
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput,
+) -> VertexOutput {
+    let model_matrix = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+    // Instances are only ever translated and rotated, so the upper 3x3 can
+    // transform directions as is, no inverse transpose needed
+    let rotation = mat3x3<f32>(
+        model_matrix[0].xyz,
+        model_matrix[1].xyz,
+        model_matrix[2].xyz,
+    );
+    var out: VertexOutput;
+    out.tex_coords = model.tex_coords;
+    out.normal = rotation * model.normal;
+    out.tangent = rotation * model.tangent;
+    out.bitangent = rotation * model.bitangent;
+    out.clip_position =
+        camera.view_proj * model_matrix * vec4<f32>(model.position, 1.0);
+    return out;
+}
+
+@group(0) @binding(0)
+var t_diffuse: texture_2d<f32>;
+
+@group(0) @binding(1)
+var s_diffuse: sampler;
+
+@group(0) @binding(2)
+var t_normal: texture_2d<f32>;
+
+@group(0) @binding(3)
+var s_normal: sampler;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    if globals.show_normals != 0u {
+        let tangent_normal =
+            textureSample(t_normal, s_normal, in.tex_coords).xyz * 2.0 - 1.0;
+        let tbn = mat3x3<f32>(
+            normalize(in.tangent),
+            normalize(in.bitangent),
+            normalize(in.normal),
+        );
+        let world_normal = normalize(tbn * tangent_normal);
+        return vec4<f32>(world_normal * 0.5 + 0.5, 1.0);
+    }
+
+    return textureSample(t_diffuse, s_diffuse, in.tex_coords);
+}
@@ -0,0 +1,379 @@
+use cgmath::{Matrix4, Quaternion, Rotation3, Vector3};
+use wgpu::util::DeviceExt;
+
+use crate::frustum::Frustum;
+use crate::growable_buffer::GrowableBuffer;
+
+const WORKGROUP_SIZE: u32 = 64;
+// How far the middle instance bobs above and below its place in the grid,
+// see `InstanceField::bob_middle`
+const BOB_HEIGHT: f32 = 0.5;
+
+pub struct Instance {
+    pub position: Vector3<f32>,
+    pub rotation: Quaternion<f32>,
+}
+
+impl Instance {
+    pub fn to_raw(&self) -> InstanceRaw {
+        InstanceRaw {
+            model: (Matrix4::from_translation(self.position)
+                * Matrix4::from(self.rotation))
+            .into(),
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct InstanceRaw {
+    model: [[f32; 4]; 4],
+}
+
+impl InstanceRaw {
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<InstanceRaw>()
+                as wgpu::BufferAddress,
+            // We need to switch from using a step mode of Vertex to Instance.
+            // This means that our shaders will only change to use the next
+            // instance when the shader starts processing a new instance
+            step_mode: wgpu::VertexStepMode::Instance,
+            // A mat4 takes up 4 vertex slots as it is technically 4 vec4s.
+            // Locations start after the last per-vertex attribute, see
+            // geometry::Vertex::desc.
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 6,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 4]>()
+                        as wgpu::BufferAddress,
+                    shader_location: 7,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 8]>()
+                        as wgpu::BufferAddress,
+                    shader_location: 8,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 12]>()
+                        as wgpu::BufferAddress,
+                    shader_location: 9,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+            ],
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct CullUniform {
+    planes: [[f32; 4]; 6],
+    radius: f32,
+    instance_count: u32,
+    _padding: [u32; 2],
+}
+
+// A large grid of mesh instances that can optionally be frustum culled on the
+// GPU. When culling is enabled a compute pass tests each instance's bounding
+// sphere against the camera frustum, compacts the survivors into
+// `visible_buffer` and bumps the instance count of an indirect draw, so the
+// CPU never needs to know how many instances are visible.
+pub struct InstanceField {
+    pub culling_enabled: bool,
+    n_instances: u32,
+    n_indices: u32,
+    radius: f32,
+    // Instances along each side of the grid, and how far apart they are
+    side: u32,
+    spacing: f32,
+    // Written whole once, then a single instance at a time, see
+    // `set_instance`
+    instance_buffer: GrowableBuffer,
+    visible_buffer: wgpu::Buffer,
+    indirect_buffer: wgpu::Buffer,
+    cull_buffer: wgpu::Buffer,
+    cull_pipeline: wgpu::ComputePipeline,
+    cull_bind_group: wgpu::BindGroup,
+}
+
+impl InstanceField {
+    // Lays out `side * side` instances on the XZ plane, `spacing` apart and
+    // centered on the origin. `radius` is the bounding sphere radius of the
+    // mesh each instance draws.
+    pub fn new_grid(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        side: u32,
+        spacing: f32,
+        n_indices: u32,
+        radius: f32,
+    ) -> Self {
+        let instances = (0..side)
+            .flat_map(|z| {
+                (0..side)
+                    .map(move |x| grid_instance(side, spacing, x, z).to_raw())
+            })
+            .collect::<Vec<_>>();
+
+        let n_instances = instances.len() as u32;
+
+        let instance_bytes: &[u8] = bytemuck::cast_slice(&instances);
+        let mut instance_buffer = GrowableBuffer::new(
+            device,
+            "Instance Buffer",
+            wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::STORAGE,
+            instance_bytes.len() as wgpu::BufferAddress,
+        );
+        instance_buffer.write(queue, &instances);
+
+        let visible_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Visible Instance Buffer"),
+            size: instance_buffer.buffer().size(),
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+
+        let indirect_buffer =
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Indirect Draw Buffer"),
+                contents: Self::draw_args(n_indices, 0).as_bytes(),
+                usage: wgpu::BufferUsages::INDIRECT
+                    | wgpu::BufferUsages::STORAGE
+                    | wgpu::BufferUsages::COPY_DST,
+            });
+
+        let cull_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Cull Uniform Buffer"),
+            size: std::mem::size_of::<CullUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let storage_entry = |binding, read_only| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+
+        let cull_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Cull Bind Group Layout"),
+                entries: &[
+                    storage_entry(0, true),
+                    storage_entry(1, false),
+                    storage_entry(2, false),
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+
+        let cull_bind_group =
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Cull Bind Group"),
+                layout: &cull_bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: instance_buffer.buffer().as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: visible_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: indirect_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: cull_buffer.as_entire_binding(),
+                    },
+                ],
+            });
+
+        let cull_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Cull Pipeline Layout"),
+                bind_group_layouts: &[&cull_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        let cull_pipeline =
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("Cull Pipeline"),
+                layout: Some(&cull_pipeline_layout),
+                module: &device
+                    .create_shader_module(wgpu::include_wgsl!("cull.wgsl")),
+                entry_point: Some("cs_main"),
+                compilation_options: wgpu::PipelineCompilationOptions::default(
+                ),
+                cache: None,
+            });
+
+        Self {
+            culling_enabled: true,
+            n_instances,
+            n_indices,
+            radius,
+            side,
+            spacing,
+            instance_buffer,
+            visible_buffer,
+            indirect_buffer,
+            cull_buffer,
+            cull_pipeline,
+            cull_bind_group,
+        }
+    }
+
+    fn draw_args(
+        index_count: u32,
+        instance_count: u32,
+    ) -> wgpu::util::DrawIndexedIndirectArgs {
+        wgpu::util::DrawIndexedIndirectArgs {
+            index_count,
+            instance_count,
+            first_index: 0,
+            base_vertex: 0,
+            first_instance: 0,
+        }
+    }
+
+    // Records the culling compute pass. Must be encoded before the render pass
+    // that calls `draw`. Does nothing when culling is disabled.
+    pub fn cull(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        queue: &wgpu::Queue,
+        view_proj: &Matrix4<f32>,
+    ) {
+        if !self.culling_enabled {
+            return;
+        }
+
+        let uniform = CullUniform {
+            planes: Frustum::from_view_proj(view_proj).planes,
+            radius: self.radius,
+            instance_count: self.n_instances,
+            _padding: [0; 2],
+        };
+        queue.write_buffer(
+            &self.cull_buffer,
+            0,
+            bytemuck::cast_slice(&[uniform]),
+        );
+
+        // The compute pass only ever increments instance_count, so it has to
+        // start from zero every frame
+        queue.write_buffer(
+            &self.indirect_buffer,
+            0,
+            Self::draw_args(self.n_indices, 0).as_bytes(),
+        );
+
+        let mut compute_pass =
+            encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Cull Pass"),
+                timestamp_writes: None,
+            });
+        compute_pass.set_pipeline(&self.cull_pipeline);
+        compute_pass.set_bind_group(0, &self.cull_bind_group, &[]);
+        compute_pass.dispatch_workgroups(
+            self.n_instances.div_ceil(WORKGROUP_SIZE),
+            1,
+            1,
+        );
+    }
+
+    // Expects the caller to have set an instanced pipeline along with its bind
+    // groups and the mesh's vertex buffer (slot 0) and index buffer.
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass) {
+        if self.culling_enabled {
+            render_pass.set_vertex_buffer(1, self.visible_buffer.slice(..));
+            render_pass.draw_indexed_indirect(&self.indirect_buffer, 0);
+        } else {
+            render_pass.set_vertex_buffer(1, self.instance_buffer.slice());
+            render_pass.draw_indexed(0..self.n_indices, 0, 0..self.n_instances);
+        }
+    }
+
+    // Replaces the instance at `index` (z * side + x) in place, uploading
+    // only its 64 bytes rather than the whole grid. Returns whether it was
+    // written, which it isn't for an index past the last instance.
+    pub fn set_instance(
+        &mut self,
+        queue: &wgpu::Queue,
+        index: u32,
+        instance: &Instance,
+    ) -> bool {
+        if index >= self.n_instances {
+            log::warn!(
+                "No instance {} to set, there are {}",
+                index,
+                self.n_instances
+            );
+            return false;
+        }
+        let stride = std::mem::size_of::<InstanceRaw>() as wgpu::BufferAddress;
+        self.instance_buffer.update_range(
+            queue,
+            index as wgpu::BufferAddress * stride,
+            &[instance.to_raw()],
+        )
+    }
+
+    // Bobs the instance in the middle of the grid up and down with `time`,
+    // through `set_instance`
+    pub fn bob_middle(&mut self, queue: &wgpu::Queue, time: f32) {
+        let middle = self.side / 2;
+        let mut instance =
+            grid_instance(self.side, self.spacing, middle, middle);
+        instance.position.y += time.sin() * BOB_HEIGHT;
+        let written =
+            self.set_instance(queue, middle * self.side + middle, &instance);
+        debug_assert!(written, "The middle instance is always in the grid");
+    }
+
+    pub fn n_instances(&self) -> u32 {
+        self.n_instances
+    }
+
+    // In every instance, culled or not
+    pub fn n_triangles(&self) -> u64 {
+        (self.n_indices / 3) as u64 * self.n_instances as u64
+    }
+}
+
+// Where `new_grid` puts the instance at `x`, `z`: `spacing` apart on the XZ
+// plane, centered on the origin, each turned a little differently
+fn grid_instance(side: u32, spacing: f32, x: u32, z: u32) -> Instance {
+    let offset = (side as f32 - 1.0) * spacing * 0.5;
+    let position = Vector3::new(
+        x as f32 * spacing - offset,
+        0.0,
+        z as f32 * spacing - offset,
+    );
+    let rotation =
+        Quaternion::from_angle_y(cgmath::Deg(((x * 7 + z * 13) % 360) as f32));
+    Instance { position, rotation }
+}
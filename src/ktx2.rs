@@ -0,0 +1,106 @@
+use anyhow::*;
+
+// Just enough of KTX2 (https://registry.khronos.org/KTX/specs/2.0/ktx20.html)
+// to upload block compressed 2D textures as is. The blocks have to already be
+// in a format the GPU samples directly (see `format_from_vk`), so files that
+// are supercompressed (Basis Universal, zstd) or hold arrays, cubemaps or 3D
+// textures are rejected.
+const IDENTIFIER: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+// Identifier, nine u32 header fields, then the index (four u32s, two u64s)
+const LEVEL_INDEX_OFFSET: usize = 12 + 9 * 4 + 4 * 4 + 2 * 8;
+// Offset, length and uncompressed length of each level, all u64
+const LEVEL_INDEX_ENTRY_SIZE: usize = 3 * 8;
+
+// A parsed file, borrowing the mip levels from the bytes it came from
+#[derive(Debug)]
+pub struct Ktx2<'a> {
+    pub format: wgpu::TextureFormat,
+    pub width: u32,
+    pub height: u32,
+    // Largest first
+    pub levels: Vec<&'a [u8]>,
+}
+
+impl<'a> Ktx2<'a> {
+    pub fn parse(bytes: &'a [u8]) -> Result<Self> {
+        ensure!(
+            bytes.len() >= LEVEL_INDEX_OFFSET && bytes[..12] == IDENTIFIER,
+            "Not a KTX2 file"
+        );
+
+        let u32_at = |offset: usize| {
+            u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+        };
+        let vk_format = u32_at(12);
+        let width = u32_at(20);
+        let height = u32_at(24);
+        let depth = u32_at(28);
+        let layer_count = u32_at(32);
+        let face_count = u32_at(36);
+        // 0 asks the loader to generate mipmaps, which we don't
+        let level_count = u32_at(40).max(1) as usize;
+        let supercompression = u32_at(44);
+
+        let format = format_from_vk(vk_format).with_context(|| {
+            format!("Unsupported KTX2 vkFormat {}", vk_format)
+        })?;
+        ensure!(
+            supercompression == 0,
+            "Supercompressed KTX2 files aren't supported"
+        );
+        ensure!(
+            depth == 0 && layer_count == 0 && face_count == 1,
+            "Only single 2D textures are supported"
+        );
+        ensure!(width > 0 && height > 0, "KTX2 file has no pixels");
+
+        let index_end =
+            LEVEL_INDEX_OFFSET + level_count * LEVEL_INDEX_ENTRY_SIZE;
+        ensure!(bytes.len() >= index_end, "KTX2 level index is truncated");
+
+        let u64_at = |offset: usize| {
+            u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+                as usize
+        };
+        let levels = (0..level_count)
+            .map(|level| {
+                let entry = LEVEL_INDEX_OFFSET + level * LEVEL_INDEX_ENTRY_SIZE;
+                let offset = u64_at(entry);
+                let length = u64_at(entry + 8);
+                bytes
+                    .get(offset..offset.saturating_add(length))
+                    .with_context(|| {
+                        format!("KTX2 mip level {} is truncated", level)
+                    })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            format,
+            width,
+            height,
+            levels,
+        })
+    }
+}
+
+// The Vulkan formats (VkFormat values) we can hand straight to wgpu
+fn format_from_vk(vk_format: u32) -> Option<wgpu::TextureFormat> {
+    use wgpu::TextureFormat::*;
+    Some(match vk_format {
+        133 => Bc1RgbaUnorm,
+        134 => Bc1RgbaUnormSrgb,
+        137 => Bc3RgbaUnorm,
+        138 => Bc3RgbaUnormSrgb,
+        141 => Bc5RgUnorm,
+        145 => Bc7RgbaUnorm,
+        146 => Bc7RgbaUnormSrgb,
+        147 => Etc2Rgb8Unorm,
+        148 => Etc2Rgb8UnormSrgb,
+        151 => Etc2Rgba8Unorm,
+        152 => Etc2Rgba8UnormSrgb,
+        _ => return None,
+    })
+}
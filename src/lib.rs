@@ -1,93 +1,594 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
 use env_logger::{Builder, Env};
-use wgpu::util::DeviceExt;
 use winit::window::Window;
 use winit::{
-    error::EventLoopError,
     event::*,
-    event_loop::EventLoop,
+    event_loop::{ControlFlow, EventLoop},
     keyboard::{KeyCode, PhysicalKey},
-    window::WindowBuilder,
+    window::{CursorGrabMode, Fullscreen, Icon, WindowBuilder, WindowId},
 };
 
+use input::Action;
+
+mod adaptive_resolution;
+mod anti_aliasing;
+mod asset_loader;
+mod assets;
+#[cfg(feature = "audio")]
+mod audio;
+mod blur;
+mod camera;
+mod camera_bookmarks;
+mod canvas;
+mod cli;
+mod config;
+mod debug_draw;
+mod debug_ui;
+mod depth_view;
+mod draw_constants;
+mod frame_pacing;
+mod frame_resources;
+mod frustum;
+#[cfg(feature = "gamepad")]
+mod gamepad;
+mod gbuffer;
+mod geometry;
+mod gpu;
+mod gpu_timer;
+mod gradient;
+mod growable_buffer;
+mod heightmap;
+mod indirect;
+mod input;
+mod instancing;
+mod ktx2;
+mod linear_output;
+mod lod;
+mod material;
+mod material_registry;
+mod mesh_file;
+mod mirror;
+mod model;
+mod morph;
+mod objects;
+mod occlusion;
+mod particles;
+mod pipeline;
+mod post;
+mod primitives;
+mod readback;
+mod render_config;
+mod render_graph;
+mod render_stats;
+mod renderer;
+mod scene;
+mod sdf_text;
+mod shader_validation;
+mod shadow;
+mod skybox;
+mod splat;
+mod sprite;
+mod sprite_array;
 mod texture;
+mod tilemap;
+mod uniform;
+mod viewport;
+mod world_labels;
+
+pub use cli::Args;
+pub use frustum::Aabb;
+pub use geometry::{Vertex, INDICES, VERTICES};
+pub use gpu::{RunError, StateError};
+pub use mesh_file::{load_mesh, write_mesh, MeshData};
+pub use readback::read_buffer;
+
+// Read from the working directory at startup. Optional.
+const CONFIG_PATH: &str = "config.toml";
+// Ctrl+1 to 9 save camera bookmarks here, in the working directory
+const CAMERA_BOOKMARKS_PATH: &str = "camera_bookmarks.json";
+// F6 saves a render_config::RenderConfig snapshot here, in the working
+// directory
+const RENDER_STATE_PATH: &str = "render_state.json";
+// How long jumping to a camera bookmark takes, in seconds
+const CAMERA_BOOKMARK_TRANSITION: f32 = 0.5;
+// How far one press of + or - moves the detail texture mix
+const DETAIL_MIX_STEP: f32 = 0.1;
+// What one press of keypad + multiplies the shininess by, and keypad -
+// divides it by
+const SHININESS_STEP: f32 = 1.25;
+// How far one press of keypad * or / moves the reflectivity
+const REFLECTIVITY_STEP: f32 = 0.1;
+// Pixels one press of . or , adds to or takes off the blur's radius
+const BLUR_RADIUS_STEP: i32 = 1;
+// What Shift+, cycles blur.wgsl's SAMPLE_SPACING through
+const BLUR_SAMPLE_SPACINGS: [f64; 3] = [1.0, 2.0, 4.0];
+// How far one press of keypad 7, 8 or 9 moves a clear color channel
+const CLEAR_COLOR_STEP: f64 = 0.05;
+// Shift+B's entity bounding boxes
+const BOUNDS_COLOR: [f32; 3] = [1.0, 0.9, 0.2];
+// How fast the arrow keys slide the orbit target, in world units per second
+const KEY_MOVE_SPEED: f32 = 2.0;
+// How fast they scroll the tile map instead while it's shown, in pixels per
+// second
+const TILE_SCROLL_SPEED: f32 = 240.0;
+// How fast holding Shift and the keypad's + or - changes the field of view,
+// in degrees per second
+const FOV_KEY_SPEED: f32 = 30.0;
+// How fast holding Page Up or Page Down moves the clip plane, in world units
+// per second
+const CLIP_PLANE_SPEED: f32 = 1.0;
+// What Shift+V locks the scene's aspect to
+const LOCKED_ASPECT: f32 = 16.0 / 9.0;
+// How often a window draws while it's in the background, on top of
+// whatever input it still gets. Slow enough to let the CPU and GPU idle,
+// fast enough that animations visibly keep going.
+const UNFOCUSED_FRAME_TIME: Duration = Duration::from_millis(250);
+// Reconfiguring normally fixes a lost or outdated surface straight away.
+// When it's still failing after this many frames in a row the surface is
+// made again from scratch, and if that doesn't help either the device itself
+// is assumed to be gone.
+const SURFACE_ERRORS_BEFORE_RESET: u32 = 3;
+// Surface recreations without a good frame in between before falling back
+// to a device reset
+const MAX_SURFACE_RECREATIONS: u32 = 2;
+// How many times in a row to try for a new device, and how long to wait
+// between tries for a GPU that's still coming back (e.g. from sleep)
+const DEVICE_RESET_ATTEMPTS: u32 = 3;
+const DEVICE_RESET_RETRY_DELAY: Duration = Duration::from_millis(500);
+// Device resets without a good frame in between before giving up, so a GPU
+// that's lost again as soon as it's recreated doesn't loop forever
+const MAX_DEVICE_RESETS: u32 = 3;
+// What `HeadlessRenderer::update` steps the animation by, for frames that
+// don't depend on how fast they were drawn
+const HEADLESS_FRAME_DELTA: f32 = 1.0 / 60.0;
+// How far each step moves the animation on in step mode, see
+// `State::toggle_step_mode`
+const STEP_FRAME_DELTA: f32 = 1.0 / 60.0;
+// How far each ScrubBackward and ScrubForward moves the animation
+const SCRUB_DELTA: f32 = 0.1;
 
-pub async fn run() -> Result<(), EventLoopError> {
+// Opens the window and runs until it's closed, or with --headless or
+// --screenshot draws a single frame offscreen and returns. Stops early with
+// the error if there's no window or GPU to run with.
+pub async fn run(args: Args) -> Result<(), RunError> {
     init_logger();
-    let event_loop = EventLoop::new().unwrap();
-    let window = WindowBuilder::new()
-        .with_title("Learn WGPU")
-        .build(&event_loop)
-        .unwrap();
-
-    let mut state = State::new(&window).await;
-
-    // Calling helps us avoid manually tracking if the surface is
-    // configured or not (it can become invalidated for example
-    // when changing windows - me thinks)
-    // Ummmmmm....or https://github.com/sotrh/learn-wgpu/issues/585
-    state.resize(state.size);
-
-    event_loop.run(move |event, control_flow| match event {
-        Event::WindowEvent { event, window_id } => {
-            if window_id != state.window().id() {
-                return;
+    let config = config::Config::load(CONFIG_PATH).unwrap_or_else(|e| {
+        log::error!("{:#}, using defaults", e);
+        config::Config::default()
+    });
+    let render_config = args.load_state.as_ref().and_then(|path| {
+        render_config::RenderConfig::load(path)
+            .inspect_err(|e| log::error!("{:#}, not loading it", e))
+            .ok()
+    });
+
+    let mut builder = StateBuilder::new().with_title(&config.title);
+    if let Some(present_mode) = config.present_mode() {
+        builder = builder.with_present_mode(present_mode);
+    }
+    if let Some(clear_color) = config.clear_color() {
+        builder = builder.with_clear_color(clear_color);
+    }
+    if let Some(path) = &config.diffuse_texture {
+        builder = builder.with_diffuse_texture(path);
+    }
+    if let Some(max_fps) = config.max_fps {
+        builder = builder.with_max_fps(max_fps);
+    }
+    if let Some(anisotropy) = config.anisotropy {
+        builder = builder.with_anisotropy(anisotropy);
+    }
+    if let Some(path) = &config.model {
+        builder = builder.with_model(path);
+    }
+    if let Some(render_scale) = config.render_scale {
+        builder = builder.with_render_scale(render_scale);
+    }
+    if let Some(filter) = config.upscale_filter() {
+        builder = builder.with_upscale_filter(filter);
+    }
+    if let Some((top, bottom)) = config.background_gradient() {
+        builder = builder.with_background_gradient(top, bottom);
+    }
+    if config.unclipped_depth {
+        builder = builder.with_unclipped_depth(true);
+    }
+    if let Some([columns, rows]) = config.grid_view {
+        builder = builder.with_grid_view(columns, rows);
+    }
+    if let Some(atlas) = &config.tile_atlas {
+        builder = builder.with_tile_atlas(
+            &atlas.path,
+            atlas.cell_size,
+            atlas.columns,
+        );
+    }
+    if args.software {
+        builder = builder.with_fallback_adapter(true);
+    }
+    // What can only be set up front goes through the builder; the rest is
+    // applied once there's a state
+    if let Some(render_config) = &render_config {
+        builder = builder
+            .with_present_mode(render_config.present_mode)
+            .with_clear_color(render_config.clear_color)
+            .with_msaa_samples(render_config.sample_count);
+    }
+    // The command line wins over both files
+    if let Some(backends) = args.backend {
+        builder = builder.with_backends(backends);
+    }
+    if let Some(cli::PresentModeArg(present_mode)) = args.present_mode {
+        // Set directly since auto (None) has no with_ method
+        builder.options.present_mode = present_mode;
+    }
+    if let Some(samples) = args.msaa {
+        builder = builder.with_msaa_samples(samples);
+    }
+
+    if args.offscreen() {
+        let size = args.size().unwrap_or(cli::DEFAULT_SIZE);
+        let rgba = builder.render_headless(size.width, size.height).await?;
+        if let Some(path) = &args.screenshot {
+            save_screenshot(path, size, rgba)?;
+            log::info!("Saved screenshot {}", path.display());
+        }
+        return Ok(());
+    }
+
+    let icon = window_icon(config.icon.as_deref());
+
+    let event_loop = EventLoop::new()?;
+    let mut window_builder = WindowBuilder::new()
+        .with_title(&config.title)
+        .with_window_icon(icon.clone());
+    if let Some(size) = args.size() {
+        window_builder = window_builder.with_inner_size(size);
+    }
+    let window = Arc::new(
+        window_builder
+            .build(&event_loop)
+            .map_err(StateError::CreateWindow)?,
+    );
+
+    let mut state = builder.clone().build(window.clone()).await?;
+    if let Some(render_config) = &render_config {
+        state.apply_config(render_config);
+    }
+
+    // Configure the surface for whatever size the window really has before
+    // the first frame. A window that starts out minimized reports zero,
+    // which pauses rendering until it's restored, see
+    // https://github.com/sotrh/learn-wgpu/issues/585
+    state.resize_now(window.inner_size());
+
+    // Every open window, each with its own surface and scene but all
+    // drawing with the first one's device. Dropping a state drops the last
+    // handle to its window, which closes it.
+    let mut states: HashMap<WindowId, State> =
+        HashMap::from([(window.id(), state)]);
+    drop(window);
+
+    // Set when a window's GPU can't be brought back, which ends the loop
+    let mut failure = None;
+    let failure_slot = &mut failure;
+
+    event_loop.run(move |event, control_flow| {
+        // Unfocused windows don't ask for their next frame, so the loop
+        // sleeps until the soonest one is due (or some event comes in)
+        if let Event::AboutToWait = event {
+            let next_redraw = states
+                .values_mut()
+                .filter_map(|state| state.request_idle_redraw())
+                .min();
+            control_flow.set_control_flow(match next_redraw {
+                Some(deadline) => ControlFlow::WaitUntil(deadline),
+                None => ControlFlow::Wait,
+            });
+            return;
+        }
+
+        // Raw mouse motion isn't tied to a window. Only one flying with the
+        // cursor grabbed does anything with it.
+        if let Event::DeviceEvent {
+            event: DeviceEvent::MouseMotion { delta },
+            ..
+        } = event
+        {
+            for state in states.values_mut() {
+                state.mouse_motion(delta);
             }
+            return;
+        }
+
+        if let Event::WindowEvent { event, window_id } = event {
+            let Some(state) = states.get_mut(&window_id) else {
+                return;
+            };
 
             if state.input(&event) {
+                if state.quit_requested() {
+                    control_flow.exit();
+                } else if state.take_new_window_request() {
+                    let title =
+                        format!("{} ({})", config.title, states.len() + 1);
+                    let other = &states[&window_id];
+                    match open_window(
+                        &builder,
+                        &title,
+                        icon.clone(),
+                        other,
+                        control_flow,
+                    ) {
+                        Ok(new_state) => {
+                            let id = new_state.window().unwrap().id();
+                            states.insert(id, new_state);
+                        }
+                        Err(e) => log::error!("Couldn't open a window: {}", e),
+                    }
+                }
                 return;
             }
 
             match event {
-                WindowEvent::CloseRequested => control_flow.exit(),
-                WindowEvent::KeyboardInput { event, .. } => {
-                    on_keyboard_input(&mut state, &event, control_flow);
+                // The app keeps running until the last window is closed
+                WindowEvent::CloseRequested => {
+                    states.remove(&window_id);
+                    if states.is_empty() {
+                        control_flow.exit();
+                    }
                 }
                 WindowEvent::Resized(physical_size) => {
                     state.resize(physical_size);
                 }
+                WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                    state.set_scale_factor(scale_factor);
+                }
                 WindowEvent::RedrawRequested => {
-                    on_redraw_requested(&mut state, control_flow);
+                    if !on_redraw_requested(state, control_flow) {
+                        return;
+                    }
+                    // The state is rebuilt around a new renderer, and the
+                    // old one has to be gone before the window can get a
+                    // new surface
+                    let state = states.remove(&window_id).unwrap();
+                    match state.reinitialize_device() {
+                        Ok(state) => {
+                            states.insert(window_id, state);
+                        }
+                        Err(e) => {
+                            log::error!("Couldn't recover the GPU: {}", e);
+                            *failure_slot = Some(e);
+                            control_flow.exit();
+                        }
+                    }
                 }
                 _ => {}
             }
         }
-        _ => {}
-    })
+    })?;
+
+    match failure {
+        Some(e) => Err(e.into()),
+        None => Ok(()),
+    }
 }
 
-fn on_keyboard_input<'a>(
-    _state: &mut State<'a>,
-    event: &KeyEvent,
+// Writes a frame from `render_headless` out as a PNG, whatever `path`'s
+// extension says
+fn save_screenshot(
+    path: &Path,
+    size: winit::dpi::PhysicalSize<u32>,
+    rgba: Vec<u8>,
+) -> Result<(), RunError> {
+    let image = image::RgbaImage::from_raw(size.width, size.height, rgba)
+        .ok_or(StateError::Readback)?;
+    image
+        .save_with_format(path, image::ImageFormat::Png)
+        .map_err(|e| RunError::SaveScreenshot(path.to_path_buf(), e))
+}
+
+// The icon in `path`, or the built-in one (or a loose assets/icon.png, see
+// assets.rs) without a path. One that can't be read or decoded is left off
+// with a warning rather than stopping the window from opening.
+fn window_icon(path: Option<&Path>) -> Option<Icon> {
+    use anyhow::Context;
+
+    let load = || -> anyhow::Result<Icon> {
+        let bytes = match path {
+            Some(path) => Cow::Owned(
+                std::fs::read(path)
+                    .with_context(|| format!("Reading {}", path.display()))?,
+            ),
+            None => assets::load("icon.png", include_bytes!("icon.png")),
+        };
+        let image = image::load_from_memory(&bytes)
+            .context("Decoding the icon")?
+            .into_rgba8();
+        let (width, height) = image.dimensions();
+        Ok(Icon::from_rgba(image.into_raw(), width, height)?)
+    };
+    load()
+        .inspect_err(|e| log::warn!("No window icon: {:#}", e))
+        .ok()
+}
+
+// Another window next to `other`, sharing its device. It starts on the next
+// render pipeline so the two are easy to tell apart.
+fn open_window(
+    builder: &StateBuilder,
+    title: &str,
+    icon: Option<Icon>,
+    other: &State,
     control_flow: &winit::event_loop::EventLoopWindowTarget<()>,
-) {
-    match event {
-        KeyEvent {
-            state: ElementState::Pressed,
-            physical_key: PhysicalKey::Code(code),
-            ..
-        } => match code {
-            KeyCode::Escape => control_flow.exit(),
-            // Handle other keys...
-            _ => {}
-        },
-        _ => {}
+) -> Result<State<'static>, StateError> {
+    let window = Arc::new(
+        WindowBuilder::new()
+            .with_title(title)
+            .with_window_icon(icon)
+            .build(control_flow)?,
+    );
+    let mut state = builder
+        .clone()
+        .with_title(title)
+        .build_sharing(window.clone(), other)?;
+    state.resize_now(window.inner_size());
+    state.renderer.cycle_render_pipeline(false);
+    Ok(state)
+}
+
+// Renders a single frame without a window and returns it as tightly packed
+// RGBA8 (sRGB) rows, e.g. for comparing against golden images in CI.
+pub fn run_headless(width: u32, height: u32) -> Result<Vec<u8>, StateError> {
+    pollster::block_on(StateBuilder::new().render_headless(width, height))
+}
+
+// The renderer drawing into an offscreen texture, for driving more than the
+// single frame `run_headless` gives, e.g. from benches/render.rs. Works with
+// a software adapter and no display.
+pub struct HeadlessRenderer {
+    renderer: renderer::Renderer<'static>,
+}
+
+impl HeadlessRenderer {
+    pub fn new(width: u32, height: u32) -> Result<Self, StateError> {
+        let renderer = pollster::block_on(renderer::Renderer::new_headless(
+            width,
+            height,
+            &renderer::RendererOptions::default(),
+        ))?;
+        Ok(Self { renderer })
+    }
+
+    // Steps the animation by a fixed 60th of a second
+    pub fn update(&mut self) {
+        self.update_by(HEADLESS_FRAME_DELTA);
+    }
+
+    // Steps the animation by `dt` seconds. 0 is a frame that doesn't move
+    // it on, like a redraw while paused or in step mode.
+    pub fn update_by(&mut self, dt: f32) {
+        self.renderer.update(dt);
+    }
+
+    // Draws a frame and waits for the GPU to finish it. Returns only the
+    // time spent recording the frame's commands on the CPU.
+    pub fn render_frame(&mut self) -> Result<Duration, wgpu::SurfaceError> {
+        let encode_time = self.renderer.render_timing_encode()?;
+        self.renderer.wait_idle();
+        Ok(encode_time)
+    }
+
+    // Builds (and throws away) a copy of the main scene pipeline
+    pub fn create_scene_pipeline(&self) -> wgpu::RenderPipeline {
+        self.renderer.create_scene_pipeline()
+    }
+
+    // MSAA samples per pixel, 1 without MSAA
+    pub fn sample_count(&self) -> u32 {
+        self.renderer.sample_count()
+    }
+
+    // 1 turns MSAA off, e.g. for golden images, whose edges would otherwise
+    // depend on where the adapter puts its samples. Unsupported counts are
+    // ignored with a warning.
+    pub fn set_sample_count(&mut self, sample_count: u32) {
+        self.renderer.set_sample_count(sample_count);
+    }
+
+    // Switches to the render pipeline called `name`, one of the names the
+    // debug panel lists (e.g. "split streams"). Unknown names are ignored
+    // with a warning.
+    pub fn set_render_pipeline(&mut self, name: &str) {
+        let names = self.renderer.render_pipeline_names();
+        match names.iter().position(|&n| n == name) {
+            Some(index) => self.renderer.set_active_render_pipeline(index),
+            None => log::warn!("No render pipeline called {:?}", name),
+        }
+    }
+
+    // The particles E toggles
+    pub fn set_show_particles(&mut self, show: bool) {
+        self.renderer.show_particles = show;
+    }
+
+    // The world space labels K toggles
+    pub fn set_show_labels(&mut self, show: bool) {
+        self.renderer.show_labels = show;
+    }
+
+    // Draws a frame and returns it the way `run_headless` does
+    pub fn render_to_rgba(&mut self) -> Result<Vec<u8>, StateError> {
+        self.renderer.render()?;
+        self.renderer.read_offscreen()
     }
 }
 
-fn on_redraw_requested<'a>(
-    state: &mut State<'a>,
+// Returns true when the device has been lost and `state` needs
+// reinitializing
+fn on_redraw_requested(
+    state: &mut State,
     control_flow: &winit::event_loop::EventLoopWindowTarget<()>,
-) {
-    // This tells winit that we want another frame after this one
-    state.window().request_redraw();
+) -> bool {
+    state.apply_pending_resize();
+    // The simulation moves on in fixed steps, as many as the time since the
+    // last frame adds up to (see frame_pacing::FixedTimestep), so it runs
+    // the same at any frame rate. In step mode each frame is one step.
+    // Input and the cameras move every frame instead, or they'd stand still
+    // on frames without a step and judder at refresh rates above the step
+    // rate.
+    let (steps, frame_time) = if state.step_mode {
+        (1, frame_pacing::FIXED_TIMESTEP)
+    } else {
+        let frame_time = state.frame_time();
+        (
+            state.timestep.advance(frame_time),
+            frame_time.min(frame_pacing::MAX_FRAME_TIME),
+        )
+    };
+    state.update(frame_time);
+    for _ in 0..steps {
+        state.step(frame_pacing::FIXED_TIMESTEP);
+    }
+    state.prepare_frame();
 
-    state.update();
+    let result = state.render();
+
+    // With a frame rate cap, hold off on the next frame until it's due. This
+    // tells winit that we want another frame after this one. With rendering
+    // off nothing asks for another, so the loop sleeps until a window event
+    // (or turning rendering back on) wakes it. In the background the next
+    // frame comes from `request_idle_redraw` instead, and in step mode from
+    // input.
+    if state.render_enabled && state.focused && !state.step_mode {
+        state.wait_for_next_frame();
+        if let Some(window) = state.window() {
+            window.request_redraw();
+        }
+    } else {
+        state.last_frame = Instant::now();
+    }
 
-    match state.render() {
-        Ok(_) => {}
-        // Reconfigure the surface if it's lost or outdated
+    match result {
+        Ok(_) => {
+            state.surface_errors = 0;
+            state.surface_recreations = 0;
+            state.device_resets = 0;
+        }
+        // Reconfigure the surface if it's lost or outdated, and recreate it
+        // if that keeps not working
         Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
-            state.resize(state.size)
+            state.surface_errors += 1;
+            if state.surface_errors < SURFACE_ERRORS_BEFORE_RESET {
+                state.resize_now(state.size())
+            } else if state.recreate_surface() {
+                state.surface_errors = 0;
+            }
         }
 
         // The system is out of memory, we should probably quit
@@ -101,386 +602,975 @@ fn on_redraw_requested<'a>(
             log::warn!("Surface timeout")
         }
     }
+
+    state.renderer.device_lost()
+        || state.surface_errors >= SURFACE_ERRORS_BEFORE_RESET
 }
 
-#[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-struct Vertex {
-    position: [f32; 3],
-    tex_coords: [f32; 2],
+// Configures a `State` before creating it. Anything left unset behaves the
+// same as running without a config file:
+//
+//     let state = StateBuilder::new()
+//         .with_present_mode(wgpu::PresentMode::Mailbox)
+//         .with_msaa_samples(1)
+//         .build(window.clone())
+//         .await?;
+#[derive(Debug, Clone, Default)]
+pub struct StateBuilder {
+    options: renderer::RendererOptions,
+    target_frame_time: Option<Duration>,
+    title: Option<String>,
+    grid_view: Option<(u32, u32)>,
 }
 
-impl Vertex {
-    fn desc() -> wgpu::VertexBufferLayout<'static> {
-        wgpu::VertexBufferLayout {
-            array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
-            step_mode: wgpu::VertexStepMode::Vertex,
-            attributes: &[
-                wgpu::VertexAttribute {
-                    offset: 0,
-                    shader_location: 0,
-                    format: wgpu::VertexFormat::Float32x3,
-                },
-                wgpu::VertexAttribute {
-                    offset: std::mem::size_of::<[f32; 2]>()
-                        as wgpu::BufferAddress,
-                    shader_location: 1,
-                    format: wgpu::VertexFormat::Float32x2,
-                },
-            ],
+impl StateBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Falls back to the first mode the surface supports (with a warning) if
+    // this one isn't
+    pub fn with_present_mode(
+        mut self,
+        present_mode: wgpu::PresentMode,
+    ) -> Self {
+        self.options.present_mode = Some(present_mode);
+        self
+    }
+
+    // Also stops the cursor from driving the clear color
+    pub fn with_clear_color(mut self, clear_color: wgpu::Color) -> Self {
+        self.options.clear_color = Some(clear_color);
+        self
+    }
+
+    // 1 turns MSAA off. Lowered to the nearest count the adapter supports.
+    pub fn with_msaa_samples(mut self, samples: u32) -> Self {
+        self.options.sample_count = samples.max(1);
+        self
+    }
+
+    // Overrides WGPU_BACKEND, e.g. wgpu::Backends::VULKAN
+    pub fn with_backends(mut self, backends: wgpu::Backends) -> Self {
+        self.options.backends = Some(backends);
+        self
+    }
+
+    // Overrides WGPU_POWER_PREF
+    pub fn with_power_preference(
+        mut self,
+        power_preference: wgpu::PowerPreference,
+    ) -> Self {
+        self.options.power_preference = Some(power_preference);
+        self
+    }
+
+    // Runs on a software rasterizer (lavapipe, llvmpipe or WARP) even when
+    // there's a GPU, like setting WGPU_FORCE_FALLBACK_ADAPTER=1. Slow, but
+    // works on CI machines without one.
+    pub fn with_fallback_adapter(mut self, enabled: bool) -> Self {
+        self.options.force_fallback_adapter = enabled;
+        self
+    }
+
+    // The window's title, which the anti-aliasing mode gets appended to.
+    // Without one the title is left alone.
+    pub fn with_title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    // Caps the frame rate by sleeping between frames, whatever the present
+    // mode. Without it frames go as fast as the present mode lets them.
+    pub fn with_max_fps(mut self, max_fps: u32) -> Self {
+        self.target_frame_time = Some(frame_pacing::frame_time(max_fps));
+        self
+    }
+
+    // Starts with every render pipeline drawn at once, in a grid `columns`
+    // by `rows`, see `State::grid_view`
+    pub fn with_grid_view(mut self, columns: u32, rows: u32) -> Self {
+        self.grid_view = Some((columns.max(1), rows.max(1)));
+        self
+    }
+
+    // Whether the depth buffer gets a stencil aspect (on by default). Turning
+    // it off keeps a pure depth buffer but disables the stencil reveal.
+    pub fn with_stencil(mut self, enabled: bool) -> Self {
+        self.options.stencil = enabled;
+        self
+    }
+
+    // Anisotropic filtering for the pentagon's textures, sharpening them at
+    // grazing angles. Usually 4, 8 or 16; 1 (the default) turns it off and
+    // anything over 16 is clamped.
+    pub fn with_anisotropy(mut self, anisotropy: u16) -> Self {
+        self.options.anisotropy = anisotropy.max(1);
+        self
+    }
+
+    // Image file for the pentagon's diffuse texture, loaded in the background
+    // after startup. If it can't be read the built-in texture is used
+    // instead.
+    pub fn with_diffuse_texture(mut self, path: impl Into<PathBuf>) -> Self {
+        self.options.diffuse_texture = Some(path.into());
+        self
+    }
+
+    // A .gltf or .glb file to show in place of the pentagon. Loaded before
+    // the first frame; if it can't be the pentagon is shown as usual.
+    pub fn with_model(mut self, path: impl Into<PathBuf>) -> Self {
+        self.options.model = Some(path.into());
+        self
+    }
+
+    // Renders the scene at this fraction of the window's resolution and
+    // scales it up to fill the window, e.g. 0.5 for a quarter of the pixels.
+    // Clamped to 0.05..=1.
+    pub fn with_render_scale(mut self, render_scale: f32) -> Self {
+        self.options.render_scale = render_scale;
+        self
+    }
+
+    // A vertical gradient from `top` to `bottom` behind the scene in place
+    // of the clear color. Starts with the skybox off so it shows; Home
+    // toggles it later either way.
+    pub fn with_background_gradient(
+        mut self,
+        top: wgpu::Color,
+        bottom: wgpu::Color,
+    ) -> Self {
+        self.options.background_gradient = Some((top, bottom));
+        self
+    }
+
+    // Clamps the shadow map's depth to the light's near and far planes
+    // instead of clipping it, so casters behind the light still cast
+    // shadows. Needs DEPTH_CLIP_CONTROL; without it this is ignored with a
+    // warning.
+    pub fn with_unclipped_depth(mut self, enabled: bool) -> Self {
+        self.options.unclipped_depth = enabled;
+        self
+    }
+
+    // Nearest gives crisp pixels when upscaling a reduced render scale,
+    // linear (the default) a soft look
+    pub fn with_upscale_filter(mut self, filter: wgpu::FilterMode) -> Self {
+        self.options.upscale_filter = filter;
+        self
+    }
+
+    // An image for the tile map to draw from, cut into square cells
+    // `cell_size` pixels across, `columns` to a row. If it can't be read a
+    // generated atlas is used instead.
+    pub fn with_tile_atlas(
+        mut self,
+        path: impl Into<PathBuf>,
+        cell_size: u32,
+        columns: u32,
+    ) -> Self {
+        let layout = tilemap::AtlasLayout {
+            cell_size: cell_size.max(1),
+            columns: columns.max(1),
+        };
+        self.options.tile_atlas = Some((path.into(), layout));
+        self
+    }
+
+    pub async fn build(
+        self,
+        window: Arc<Window>,
+    ) -> Result<State<'static>, StateError> {
+        let renderer =
+            renderer::Renderer::new(window.clone(), &self.options).await?;
+        Ok(self.into_state(renderer, &window))
+    }
+
+    // Draws a single frame into an offscreen texture `width` by `height`,
+    // without a window, and returns it the way `run_headless` does. Only
+    // the renderer options apply; there's no state to frame pace or title.
+    pub async fn render_headless(
+        self,
+        width: u32,
+        height: u32,
+    ) -> Result<Vec<u8>, StateError> {
+        let mut renderer =
+            renderer::Renderer::new_headless(width, height, &self.options)
+                .await?;
+        // Nobody's looking at the frame before it's saved, so anything going
+        // wrong while drawing it fails the capture instead
+        let device = renderer.device().clone();
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        device.push_error_scope(wgpu::ErrorFilter::Internal);
+        renderer.update(0.0);
+        let rgba = renderer
+            .render()
+            .map_err(StateError::from)
+            .and_then(|_| renderer.read_offscreen());
+        for _ in 0..2 {
+            if let Some(e) = device.pop_error_scope().await {
+                return Err(StateError::Render(e));
+            }
         }
+        rgba
+    }
+
+    // A state for another window that draws with `other`'s device and queue
+    pub fn build_sharing(
+        self,
+        window: Arc<Window>,
+        other: &State,
+    ) -> Result<State<'static>, StateError> {
+        let renderer = renderer::Renderer::new_sharing(
+            &other.renderer,
+            window.clone(),
+            &self.options,
+        )?;
+        Ok(self.into_state(renderer, &window))
+    }
+
+    fn into_state<'a>(
+        self,
+        renderer: renderer::Renderer<'a>,
+        window: &Window,
+    ) -> State<'a> {
+        let debug_ui = debug_ui::DebugUi::new(
+            renderer.device(),
+            renderer.format(),
+            window.scale_factor() as f32,
+        );
+        let state = State {
+            fov_zoom: camera::FovZoom::new(renderer.camera.fovy),
+            renderer,
+            title: self.title,
+            orbit_camera: camera::OrbitCamera::new(2.4),
+            fly_camera: camera::FlyCamera::new(),
+            flying: false,
+            camera_bookmarks: camera_bookmarks::CameraBookmarks::load(
+                CAMERA_BOOKMARKS_PATH,
+            ),
+            camera_transition: None,
+            // An explicitly configured color shouldn't get painted over
+            cursor_clear_color: self.options.clear_color.is_none(),
+            windowed_size: None,
+            pending_size: None,
+            scale_factor: window.scale_factor(),
+            modifiers: winit::keyboard::ModifiersState::empty(),
+            cursor_position: winit::dpi::PhysicalPosition::new(0.0, 0.0),
+            input_map: input::InputMap::new(),
+            aspect_lock: None,
+            split_screen: false,
+            grid_view: self.grid_view,
+            shader_constants: HashMap::new(),
+            quit_requested: false,
+            new_window_requested: false,
+            paused: false,
+            show_bounds: false,
+            render_enabled: true,
+            step_mode: false,
+            step_requested: false,
+            focused: true,
+            last_update: Instant::now(),
+            timestep: frame_pacing::FixedTimestep::default(),
+            debug_ui,
+            target_frame_time: self.target_frame_time,
+            last_frame: Instant::now(),
+            #[cfg(feature = "gamepad")]
+            gilrs: gilrs::Gilrs::new()
+                .inspect_err(|e| log::warn!("Gamepad input disabled: {}", e))
+                .ok(),
+            #[cfg(feature = "gamepad")]
+            gamepad: gamepad::GamepadState::new(),
+            #[cfg(feature = "audio")]
+            audio: audio::AudioInput::start(),
+            surface_errors: 0,
+            surface_recreations: 0,
+            device_resets: 0,
+            options: self.options,
+        };
+        state.update_title();
+        state
     }
 }
 
-const VERTICES: &[Vertex] = &[
-    Vertex {
-        position: [-0.0868241, 0.49240386, 0.0],
-        tex_coords: [0.4131759, 0.99240386],
-    }, // A
-    Vertex {
-        position: [-0.49513406, 0.06958647, 0.0],
-        tex_coords: [0.0048659444, 0.56958647],
-    }, // B
-    Vertex {
-        position: [-0.21918549, -0.44939706, 0.0],
-        tex_coords: [0.28081453, 0.05060294],
-    }, // C
-    Vertex {
-        position: [0.35966998, -0.3473291, 0.0],
-        tex_coords: [0.85967, 0.1526709],
-    }, // D
-    Vertex {
-        position: [0.44147372, 0.2347359, 0.0],
-        tex_coords: [0.9414737, 0.7347359],
-    }, // E
-];
-
-const INDICES: &[u16] = &[
-    0, 1, 4, //
-    1, 2, 4, //
-    2, 3, 4, /* padding */ 0,
-];
-
-struct State<'a> {
-    surface: wgpu::Surface<'a>,
-    device: wgpu::Device,
-    queue: wgpu::Queue,
-    surface_configuration: wgpu::SurfaceConfiguration,
-    size: winit::dpi::PhysicalSize<u32>,
-    window: &'a Window,
-    clear_color: wgpu::Color,
-    render_pipelines: Vec<wgpu::RenderPipeline>,
-    active_render_pipeline_index: usize,
-    vertex_buffer: wgpu::Buffer,
-    index_buffer: wgpu::Buffer,
-    n_indices: u32,
-    diffuse_bind_group: wgpu::BindGroup,
+// The windowed app: a renderer plus everything that reacts to input. Made
+// with a `StateBuilder`.
+pub struct State<'a> {
+    renderer: renderer::Renderer<'a>,
+    // The window's title without the anti-aliasing suffix, see `update_title`
+    title: Option<String>,
+    orbit_camera: camera::OrbitCamera,
+    fly_camera: camera::FlyCamera,
+    // Ctrl+scroll or Shift and the keypad's + and - narrow and widen the
+    // perspective camera's field of view, for either camera
+    fov_zoom: camera::FovZoom,
+    // Whether the fly camera is in charge, with the cursor grabbed and
+    // hidden. Right click or F3 to start, Escape or F3 to stop.
+    flying: bool,
+    // Number keys glide the orbit camera to these, Ctrl+number saves them
+    camera_bookmarks: camera_bookmarks::CameraBookmarks,
+    // Set while gliding to a bookmark
+    camera_transition: Option<camera_bookmarks::CameraTransition>,
+    // When true, moving the cursor (while not dragging the camera) drives the
+    // clear color
+    cursor_clear_color: bool,
+    // The size to go back to when leaving fullscreen. Some while fullscreen.
+    windowed_size: Option<winit::dpi::PhysicalSize<u32>>,
+    // The latest size from `resize`, applied at the start of the next frame
+    pending_size: Option<winit::dpi::PhysicalSize<u32>>,
+    // Physical pixels per logical pixel of the monitor the window is on
+    scale_factor: f64,
+    // Held modifier keys, for shortcuts like Shift+Space
+    modifiers: winit::keyboard::ModifiersState,
+    // Where the cursor last was, for Ctrl+click color picking
+    cursor_position: winit::dpi::PhysicalPosition<f64>,
+    // What each key does, and which actions are held down
+    input_map: input::InputMap,
+    // Confines the scene to the largest rect of this aspect that fits,
+    // with bars of `Renderer::bar_color` around it. Unlike the canvas this
+    // draws straight into the window through a viewport.
+    aspect_lock: Option<f32>,
+    // The left half through the main camera, the right from above. Inside
+    // the locked rect if there is one.
+    split_screen: bool,
+    // Values for the blur's override constants by name, pushed to the
+    // renderer on every change. Kept here so they survive a device reset.
+    shader_constants: HashMap<String, f64>,
+    // Columns and rows. Draws the render pipelines side by side, one per
+    // cell, for comparing them. Takes over from the split screen, and
+    // also stays inside the locked rect.
+    grid_view: Option<(u32, u32)>,
+    // Set by the Quit action for the event loop to act on
+    quit_requested: bool,
+    // Set by the OpenWindow action, the event loop creates the window
+    new_window_requested: bool,
+    // Freezes everything that moves on its own while frames keep being
+    // drawn. The camera can still be moved.
+    paused: bool,
+    // Outlines every entity's bounding box, as culled against
+    show_bounds: bool,
+    // Whether each frame asks for the next one. Off leaves the GPU idle,
+    // only drawing when the window needs it.
+    render_enabled: bool,
+    // Frames are only drawn on input, and the animation only moves on by
+    // STEP_FRAME_DELTA when asked to with the StepFrame action
+    step_mode: bool,
+    // Set by StepFrame, taken by the next frame's update
+    step_requested: bool,
+    // Whether the window has keyboard focus. Without it frames are only
+    // drawn every UNFOCUSED_FRAME_TIME or on input, see
+    // `request_idle_redraw`.
+    focused: bool,
+    // When the last frame's time was added to `timestep`
+    last_update: Instant,
+    // Turns the time between frames into fixed steps for `step`
+    timestep: frame_pacing::FixedTimestep,
+    // Toggled with F1
+    debug_ui: debug_ui::DebugUi,
+    // None is uncapped, F cycles through frame_pacing::FRAME_RATE_CAPS
+    target_frame_time: Option<Duration>,
+    // When the last frame was let through by `wait_for_next_frame`
+    last_frame: Instant,
+    // None if gilrs couldn't start, e.g. without udev access on Linux
+    #[cfg(feature = "gamepad")]
+    gilrs: Option<gilrs::Gilrs>,
+    #[cfg(feature = "gamepad")]
+    gamepad: gamepad::GamepadState,
+    // Microphone loudness for the shaders, silent without an input device
+    #[cfg(feature = "audio")]
+    audio: audio::AudioInput,
+    // Lost or outdated surfaces in a row, see SURFACE_ERRORS_BEFORE_RESET
+    surface_errors: u32,
+    // Surface recreations since the last frame that rendered fine, see
+    // MAX_SURFACE_RECREATIONS
+    surface_recreations: u32,
+    // Device resets since the last frame that rendered fine
+    device_resets: u32,
+    // What the renderer was made with, to make it again after a device loss
+    options: renderer::RendererOptions,
 }
 
 impl<'a> State<'a> {
-    // Creating some of the wgpu types requires async code
-    async fn new(window: &'a Window) -> State<'a> {
-        let size = window.inner_size();
-
-        // The instance is a handle to our GPU
-        // Backends::all => Vulkan + Metal + DX12 + Browser WebGPU
-        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::PRIMARY,
-            ..Default::default()
-        });
+    pub fn window(&self) -> Option<&Window> {
+        self.renderer.window()
+    }
 
-        // The surface is the part of the window that we draw to.
-        // We need it to draw directly to the screen
-        let surface = instance.create_surface(window).unwrap();
-
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                // power_preference has two variants: LowPower and
-                // HighPerformance. LowPower will pick an adapter that favors
-                // battery life, such as an integrated GPU. HighPerformance will
-                // pick an adapter for more power-hungry yet more performant
-                // GPU's, such as a dedicated graphics card. WGPU will favor
-                // LowPower if there is no adapter for the HighPerformance
-                // option.
-                power_preference: wgpu::PowerPreference::default(),
-
-                // The compatible_surface field tells wgpu to find an adapter
-                // that can present to the supplied surface.
-                compatible_surface: Some(&surface),
-
-                // The force_fallback_adapter forces wgpu to pick an adapter
-                // that will work on all hardware. This usually means that the
-                // rendering backend will use a "software" system instead of
-                // hardware such as a GPU.
-                force_fallback_adapter: false,
-            })
-            .await
-            .unwrap();
-
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    // You can get a list of features supported by your device
-                    // using adapter.features() or device.features().
-                    // https://docs.rs/wgpu/latest/wgpu/struct.Features.html
-                    required_features: wgpu::Features::empty(),
-
-                    // https://docs.rs/wgpu/latest/wgpu/struct.Limits.html
-                    required_limits: wgpu::Limits::default(),
-
-                    label: None,
-
-                    // https://wgpu.rs/doc/wgpu/enum.MemoryHints.html
-                    memory_hints: Default::default(),
-                },
-                // Trace path
-                None,
-            )
-            .await
-            .unwrap();
-
-        let surface_caps = surface.get_capabilities(&adapter);
-
-        let surface_format = surface_caps
-            .formats
+    // Starts over on a new adapter and device after the old device was lost
+    // (a driver reset, or a laptop's GPU resetting over sleep), keeping the
+    // window, cameras and input state. The renderer and debug UI are built
+    // from scratch, so anything toggled on the renderer besides the clear
+    // color goes back to how it was at startup. Other windows sharing the
+    // lost device notice and recover on their own.
+    //
+    // Fails after DEVICE_RESET_ATTEMPTS tries at getting a device, or after
+    // MAX_DEVICE_RESETS resets without a good frame in between.
+    pub fn reinitialize_device(self) -> Result<Self, StateError> {
+        if self.device_resets >= MAX_DEVICE_RESETS {
+            return Err(StateError::DeviceLost);
+        }
+        let Some(window) = self.renderer.shared_window() else {
+            return Ok(self);
+        };
+        let clear_color = self.renderer.clear_color;
+        log::warn!("GPU device lost, creating a new one");
+        drop(self.renderer);
+
+        let mut attempt = 1;
+        let mut renderer = loop {
+            match pollster::block_on(renderer::Renderer::new(
+                window.clone(),
+                &self.options,
+            )) {
+                Ok(renderer) => break renderer,
+                Err(e) if attempt < DEVICE_RESET_ATTEMPTS => {
+                    log::warn!(
+                        "Device reset attempt {} failed: {}",
+                        attempt,
+                        e
+                    );
+                    attempt += 1;
+                    std::thread::sleep(DEVICE_RESET_RETRY_DELAY);
+                }
+                Err(e) => return Err(e),
+            }
+        };
+        renderer.clear_color = clear_color;
+        let debug_ui = debug_ui::DebugUi::new(
+            renderer.device(),
+            renderer.format(),
+            window.scale_factor() as f32,
+        );
+
+        let mut state = State {
+            renderer,
+            debug_ui,
+            surface_errors: 0,
+            surface_recreations: 0,
+            device_resets: self.device_resets + 1,
+            ..self
+        };
+        state.renderer.set_blur_constants(&state.shader_constants);
+        state.resize_now(window.inner_size());
+        state.update_title();
+        log::info!("Recovered from device loss on a new device");
+        Ok(state)
+    }
+
+    // Makes the window's surface again on the same device, for a surface
+    // that reconfiguring can't bring back. Returns false once
+    // MAX_SURFACE_RECREATIONS tries haven't led to a good frame, or when a
+    // new surface can't be made, either of which takes a device reset.
+    pub fn recreate_surface(&mut self) -> bool {
+        if self.surface_recreations >= MAX_SURFACE_RECREATIONS {
+            return false;
+        }
+        self.surface_recreations += 1;
+        log::warn!(
+            "Surface still invalid after reconfiguring, recreating it \
+             (attempt {} of {})",
+            self.surface_recreations,
+            MAX_SURFACE_RECREATIONS
+        );
+        match self.renderer.recreate_surface() {
+            Ok(()) => true,
+            Err(e) => {
+                log::warn!("Couldn't recreate the surface: {}", e);
+                false
+            }
+        }
+    }
+
+    pub fn size(&self) -> winit::dpi::PhysicalSize<u32> {
+        self.renderer.size()
+    }
+
+    // Dragging the window's edge fires a stream of Resized events, often
+    // several per frame. Reconfiguring the surface and recreating the depth
+    // and MSAA targets for each one is wasted work, so this only records the
+    // size and `apply_pending_resize` acts on the latest one once per frame.
+    pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
+        self.pending_size = Some(new_size);
+    }
+
+    // Reconfigures straight away, for when the surface has to be right
+    // before the next frame is drawn
+    pub fn resize_now(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
+        self.pending_size = None;
+        self.renderer.resize(new_size);
+    }
+
+    fn apply_pending_resize(&mut self) {
+        if let Some(size) = self.pending_size.take() {
+            self.renderer.resize(size);
+        }
+    }
+
+    // The window moved to a monitor with a different DPI. Its physical size
+    // changes along with the scale factor and not every platform follows up
+    // with a Resized, so the surface is reconfigured here as well.
+    pub fn set_scale_factor(&mut self, scale_factor: f64) {
+        log::info!("Scale factor changed to {}", scale_factor);
+        self.scale_factor = scale_factor;
+        self.debug_ui.set_pixels_per_point(scale_factor as f32);
+        if let Some(window) = self.renderer.window() {
+            let size = window.inner_size();
+            self.resize(size);
+        }
+    }
+
+    // Sleeps off whatever is left of the frame time, if it's capped
+    fn wait_for_next_frame(&mut self) {
+        if let Some(target_frame_time) = self.target_frame_time {
+            frame_pacing::sleep_until(self.last_frame + target_frame_time);
+        }
+        self.last_frame = Instant::now();
+    }
+
+    fn cycle_frame_rate_cap(&mut self) {
+        let caps = frame_pacing::FRAME_RATE_CAPS;
+        let current = caps
             .iter()
-            .find(|f| f.is_srgb())
-            .copied()
-            .unwrap_or(surface_caps.formats[0]);
-
-        let surface_configuration = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: surface_format,
-            width: size.width,
-            height: size.height,
-
-            // present_mode uses wgpu::PresentMode enum, which determines
-            // how to sync the surface with the display.
-            // For the sake of simplicity, we select the first available
-            // option. If you do not want runtime selection,
-            // `PresentMode::Fifo` will cap the display rate at the display's
-            // framerate. This is essentially VSync.
-            // This mode is guaranteed to be supported on all platforms.
-            //
-            // There are other options, and you can see all of them in the docs:
-            // https://docs.rs/wgpu/latest/wgpu/enum.PresentMode.html
-            //
-            // `PresentMode::AutoVsync` and `PresentMode::AutoNoVsync` have
-            // fallback support and therefore will work on all platforms.
-            present_mode: surface_caps.present_modes[0],
-
-            alpha_mode: surface_caps.alpha_modes[0],
-            view_formats: vec![],
-            desired_maximum_frame_latency: 2,
+            .position(|&cap| {
+                cap.map(frame_pacing::frame_time) == self.target_frame_time
+            })
+            .unwrap_or(0);
+        let next = caps[(current + 1) % caps.len()];
+        self.target_frame_time = next.map(frame_pacing::frame_time);
+        match next {
+            Some(fps) => log::info!("Frame rate cap: {} fps", fps),
+            None => log::info!("Frame rate cap: off"),
+        }
+    }
+
+    // Everything render_config::RenderConfig records, as it is right now
+    pub fn snapshot(&self) -> render_config::RenderConfig {
+        let camera = if self.flying {
+            // Where the orbit camera ends up once flying stops
+            let mut orbit_camera = self.orbit_camera.clone();
+            orbit_camera.look_from_fly(&self.fly_camera);
+            camera_bookmarks::CameraBookmark::from_camera(&orbit_camera)
+        } else {
+            camera_bookmarks::CameraBookmark::from_camera(&self.orbit_camera)
         };
+        let pipeline = self.renderer.render_pipeline_names()
+            [self.renderer.active_render_pipeline_index()];
+        render_config::RenderConfig {
+            present_mode: self.renderer.present_mode(),
+            surface_format: self.renderer.format(),
+            clear_color: self.renderer.clear_color,
+            pipeline: pipeline.to_string(),
+            sample_count: self.renderer.sample_count(),
+            fxaa: self.renderer.aa_mode() == anti_aliasing::AaMode::Fxaa,
+            camera,
+            ortho_camera: self.renderer.use_ortho_camera,
+        }
+    }
 
-        surface.configure(&device, &surface_configuration);
+    // Puts back whatever of `config` can change while running. The present
+    // mode is only picked at startup (see `run`) and the surface format not
+    // at all, so differences in those are just logged.
+    pub fn apply_config(&mut self, config: &render_config::RenderConfig) {
+        if config.present_mode != self.renderer.present_mode() {
+            log::warn!(
+                "Saved present mode {:?} isn't in use, running with {:?}",
+                config.present_mode,
+                self.renderer.present_mode()
+            );
+        }
+        if config.surface_format != self.renderer.format() {
+            log::warn!(
+                "Saved surface format {:?} differs from this surface's {:?}",
+                config.surface_format,
+                self.renderer.format()
+            );
+        }
 
-        let diffuse_texture = texture::Texture::from_bytes(
-            &device,
-            &queue,
-            include_bytes!("g25.png"),
-            Some("Diffuse Texture"),
-        )
-        .unwrap();
-
-        let texture_bind_group_layout =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                entries: &[
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Texture {
-                            multisampled: false,
-                            view_dimension: wgpu::TextureViewDimension::D2,
-                            sample_type: wgpu::TextureSampleType::Float {
-                                filterable: true,
-                            },
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 1,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        // This should match the filterable field of the
-                        // corresponding Texture entry above.
-                        ty: wgpu::BindingType::Sampler(
-                            wgpu::SamplerBindingType::Filtering,
-                        ),
-                        count: None,
-                    },
-                ],
-                label: Some("Texture Bind Group Layour"),
-            });
+        self.renderer.clear_color = config.clear_color;
+        self.cursor_clear_color = false;
 
-        let diffuse_bind_group =
-            device.create_bind_group(&wgpu::BindGroupDescriptor {
-                layout: &texture_bind_group_layout,
-                entries: &[
-                    // @group(0) @binding(0)
-                    // var t_diffuse: texture_2d<f32>;
-                    wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: wgpu::BindingResource::TextureView(
-                            &diffuse_texture.view,
-                        ),
-                    },
-                    // @group(0) @binding(1)
-                    // var s_diffuse: sampler;
-                    wgpu::BindGroupEntry {
-                        binding: 1,
-                        resource: wgpu::BindingResource::Sampler(
-                            &diffuse_texture.sampler,
-                        ),
-                    },
-                ],
-                label: Some("diffuse_bind_group"),
-            });
+        let names = self.renderer.render_pipeline_names();
+        match names.iter().position(|&name| name == config.pipeline) {
+            Some(index) => self.renderer.set_active_render_pipeline(index),
+            None => log::warn!(
+                "No pipeline named {:?} on this adapter, keeping {:?}",
+                config.pipeline,
+                names[self.renderer.active_render_pipeline_index()]
+            ),
+        }
 
-        // To access the create_buffer_init method on wgpu::Device, we'll have
-        // to import the DeviceExt
-        // (https://docs.rs/wgpu/latest/wgpu/util/trait.DeviceExt.html#tymethod.create_buffer_init)
-        // extension trait. For more information on extension traits, check out
-        // this article: http://xion.io/post/code/rust-extension-traits.html.
-        let pentagon_vertex_buffer =
-            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Pentagon Vertex Buffer"),
-                contents: bytemuck::cast_slice(VERTICES),
-                usage: wgpu::BufferUsages::VERTEX,
-            });
+        self.renderer.set_aa_mode(if config.fxaa {
+            anti_aliasing::AaMode::Fxaa
+        } else if config.sample_count > 1 {
+            anti_aliasing::AaMode::Msaa(config.sample_count)
+        } else {
+            anti_aliasing::AaMode::None
+        });
+        self.update_title();
 
-        let index_buffer =
-            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Index Buffer"),
-                contents: bytemuck::cast_slice(INDICES),
-                usage: wgpu::BufferUsages::INDEX,
-            });
+        self.set_flying(false);
+        self.camera_transition = None;
+        config.camera.apply(&mut self.orbit_camera);
+        self.renderer.use_ortho_camera = config.ortho_camera;
+    }
 
-        let shader =
-            device.create_shader_module(wgpu::ShaderModuleDescriptor {
-                label: Some("Shader"),
-                source: wgpu::ShaderSource::Wgsl(
-                    include_str!("shader.wgsl").into(),
-                ),
-            });
+    // Logs what wgpu has allocated, see gpu::log_memory_report. Taking one
+    // before and after doing something that should free what it made (e.g.
+    // toggling a feature off and on) shows whether anything leaked.
+    pub fn log_memory_report(&self) {
+        self.renderer.log_memory_report();
+    }
 
-        let render_pipeline = Self::create_render_pipeline(
-            &device,
-            &surface_configuration,
-            &shader,
-            &texture_bind_group_layout,
+    fn save_render_state(&self) {
+        let path = std::path::Path::new(RENDER_STATE_PATH);
+        match self.snapshot().save(path) {
+            Ok(()) => log::info!("Saved render state to {}", path.display()),
+            Err(e) => log::error!("Couldn't save render state: {:#}", e),
+        }
+    }
+
+    // Moves one channel (0 red, 1 green, 2 blue) of the clear color a step
+    // in `direction`'s sign, and stops the cursor from driving it so the
+    // next mouse move doesn't undo that. Logs the whole color, ready to copy
+    // into config.toml's clear_color.
+    fn nudge_clear_color(&mut self, channel: usize, direction: f64) {
+        let color = &mut self.renderer.clear_color;
+        let value = match channel {
+            0 => &mut color.r,
+            1 => &mut color.g,
+            _ => &mut color.b,
+        };
+        *value = (*value + direction * CLEAR_COLOR_STEP).clamp(0.0, 1.0);
+        self.cursor_clear_color = false;
+        log::info!(
+            "Clear color: [{:.2}, {:.2}, {:.2}, {:.2}]",
+            color.r,
+            color.g,
+            color.b,
+            color.a
         );
+    }
 
-        let render_pipeline2 = Self::create_render_pipeline(
-            &device,
-            &surface_configuration,
-            &device.create_shader_module(wgpu::include_wgsl!("shader2.wgsl")),
-            &texture_bind_group_layout,
+    // "<title> - MSAA x4", "<title> - FXAA" or "<title> - AA off"
+    fn update_title(&self) {
+        let (Some(title), Some(window)) = (&self.title, self.renderer.window())
+        else {
+            return;
+        };
+        let aa_mode = self.renderer.aa_mode().name();
+        window.set_title(&format!("{} - {}", title, aa_mode));
+    }
+
+    // For an unfocused window: asks for a frame if one's due, and returns
+    // when the next one will be. None while focused (every frame asks for
+    // the next) or with rendering off.
+    fn request_idle_redraw(&mut self) -> Option<Instant> {
+        if self.focused || !self.render_enabled || self.step_mode {
+            return None;
+        }
+        let due = self.last_frame + UNFOCUSED_FRAME_TIME;
+        let now = Instant::now();
+        if now < due {
+            return Some(due);
+        }
+        self.request_redraw();
+        Some(now + UNFOCUSED_FRAME_TIME)
+    }
+
+    fn request_redraw(&self) {
+        if let Some(window) = self.renderer.window() {
+            window.request_redraw();
+        }
+    }
+
+    // Back in front, frames go back to asking for the next one. The clocks
+    // start over like in `toggle_rendering`, so the gap since the last idle
+    // frame doesn't come through as one long step.
+    fn set_focused(&mut self, focused: bool) {
+        if focused == self.focused {
+            return;
+        }
+        self.focused = focused;
+        log::debug!(
+            "Window {}",
+            if focused {
+                "focused"
+            } else {
+                "in the background"
+            }
         );
+        if focused {
+            self.last_update = Instant::now();
+            self.last_frame = Instant::now();
+            self.renderer.reset_frame_clock();
+            self.request_redraw();
+        }
+    }
 
-        Self {
-            surface,
-            device,
-            queue,
-            surface_configuration,
-            size,
-            window,
-            render_pipelines: vec![render_pipeline, render_pipeline2],
-            active_render_pipeline_index: 0,
-            vertex_buffer: pentagon_vertex_buffer,
-            index_buffer,
-            n_indices: INDICES.len() as u32,
-            diffuse_bind_group,
-            clear_color: wgpu::Color {
-                r: 0.03,
-                g: 0.03,
-                b: 0.03,
-                a: 1.0,
-            },
-        }
-    }
-
-    fn create_render_pipeline(
-        device: &wgpu::Device,
-        surface_configuration: &wgpu::SurfaceConfiguration,
-        shader: &wgpu::ShaderModule,
-        texture_bind_group_layout: &wgpu::BindGroupLayout,
-    ) -> wgpu::RenderPipeline {
-        let render_pipeline_layout =
-            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some("Render Pipeline Layout"),
-                bind_group_layouts: &[&texture_bind_group_layout],
-                push_constant_ranges: &[],
-            });
+    // Coming back on, the clocks start over so the time spent stopped isn't
+    // taken as one long frame
+    fn toggle_rendering(&mut self) {
+        self.render_enabled = !self.render_enabled;
+        log::info!(
+            "Rendering: {}",
+            if self.render_enabled { "on" } else { "off" }
+        );
+        if self.render_enabled {
+            self.last_update = Instant::now();
+            self.last_frame = Instant::now();
+            self.renderer.reset_frame_clock();
+            self.request_redraw();
+        }
+    }
 
-        let render_pipeline =
-            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                label: Some("Render Pipeline"),
-                layout: Some(&render_pipeline_layout),
-                vertex: wgpu::VertexState {
-                    module: &shader,
-                    entry_point: Some("vs_main"),
-                    buffers: &[Vertex::desc()],
-                    compilation_options:
-                        wgpu::PipelineCompilationOptions::default(),
-                },
-                fragment: Some(wgpu::FragmentState {
-                    module: &shader,
-                    entry_point: Some("fs_main"),
-                    targets: &[Some(wgpu::ColorTargetState {
-                        format: surface_configuration.format,
-                        blend: Some(wgpu::BlendState::REPLACE),
-                        write_mask: wgpu::ColorWrites::ALL,
-                    })],
-                    compilation_options:
-                        wgpu::PipelineCompilationOptions::default(),
-                }),
-                primitive: wgpu::PrimitiveState {
-                    topology: wgpu::PrimitiveTopology::TriangleList,
-                    strip_index_format: None,
-                    front_face: wgpu::FrontFace::Ccw,
-                    cull_mode: Some(wgpu::Face::Back),
-                    // Setting this to anything other than Fill requires
-                    // `Features::NON_FILL_POLYGON_MODE`
-                    polygon_mode: wgpu::PolygonMode::Fill,
-                    // Requires `Features::DEPTH_CLIP_CONTROL`
-                    unclipped_depth: false,
-                    // Requires `Features::CONSERVATIVE_RASTERIZATION`
-                    conservative: false,
-                },
-                depth_stencil: None,
-                multisample: wgpu::MultisampleState {
-                    count: 1,
-                    mask: !0,
-                    alpha_to_coverage_enabled: false,
-                },
-                multiview: None,
-                cache: None,
+    // For going through an animation frame by frame. Frames stop asking for
+    // the next one and the animation stands still, only moving on a step at
+    // a time with `step_frame`. Input (a resize included) still gets the
+    // window redrawn, without moving the animation on. Coming back out, the
+    // clocks start over like in `toggle_rendering`.
+    fn toggle_step_mode(&mut self) {
+        self.step_mode = !self.step_mode;
+        self.step_requested = false;
+        log::info!("Step mode: {}", if self.step_mode { "on" } else { "off" });
+        if !self.step_mode {
+            self.last_update = Instant::now();
+            self.last_frame = Instant::now();
+            self.renderer.reset_frame_clock();
+            self.request_redraw();
+        }
+    }
+
+    // Draws one more frame in step mode, with the animation moved on by
+    // STEP_FRAME_DELTA. Does nothing outside it.
+    fn step_frame(&mut self) {
+        if self.step_mode {
+            self.step_requested = true;
+            self.request_redraw();
+        }
+    }
+
+    // Shows the animation as it is `time` seconds in. Pauses it, or it would
+    // carry on from there straight away.
+    pub fn set_time(&mut self, time: f32) {
+        if !self.paused {
+            self.paused = true;
+            log::info!("Animation paused: on");
+        }
+        self.renderer.set_time(time);
+        log::info!("Animation time: {:.2}s", self.renderer.time());
+        self.request_redraw();
+    }
+
+    // Moves the animation `dt` seconds on, or back when negative, and pauses
+    // it. Time stops at zero.
+    pub fn advance_time(&mut self, dt: f32) {
+        self.set_time(self.renderer.time() + dt);
+    }
+
+    // Moves blur.wgsl's SAMPLE_SPACING on to the next of
+    // BLUR_SAMPLE_SPACINGS, rebuilding the blur's pipeline
+    fn cycle_blur_sample_spacing(&mut self) {
+        let spacing = self
+            .shader_constants
+            .get(blur::SAMPLE_SPACING)
+            .and_then(|spacing| {
+                BLUR_SAMPLE_SPACINGS.iter().position(|s| s == spacing)
+            })
+            // Unset is the shader's default of 1
+            .map_or(BLUR_SAMPLE_SPACINGS[1], |i| {
+                BLUR_SAMPLE_SPACINGS[(i + 1) % BLUR_SAMPLE_SPACINGS.len()]
             });
+        self.shader_constants
+            .insert(blur::SAMPLE_SPACING.to_string(), spacing);
+        self.renderer.set_blur_constants(&self.shader_constants);
+        log::info!("Blur sample spacing: {}px", spacing);
+    }
 
-        render_pipeline
+    // How far this frame moves the animation on, given `dt` since the last
+    fn animation_dt(&mut self, dt: f32) -> f32 {
+        if self.step_mode {
+            if std::mem::take(&mut self.step_requested) {
+                STEP_FRAME_DELTA
+            } else {
+                0.0
+            }
+        } else if self.paused {
+            0.0
+        } else {
+            dt
+        }
     }
 
-    fn window(&self) -> &Window {
-        &self.window
+    // Grabs and hides the cursor for the fly camera, or gives it back. The
+    // camera that takes over starts from where the other one left off.
+    fn set_flying(&mut self, flying: bool) {
+        if flying == self.flying {
+            return;
+        }
+        let Some(window) = self.renderer.window() else {
+            return;
+        };
+        if flying {
+            // Locked keeps the cursor where it is but X11 only has Confined,
+            // and macOS only Locked. MouseMotion keeps coming either way.
+            let grab = window
+                .set_cursor_grab(CursorGrabMode::Locked)
+                .or_else(|_| window.set_cursor_grab(CursorGrabMode::Confined));
+            if let Err(e) = grab {
+                log::warn!("Couldn't grab the cursor: {}", e);
+                return;
+            }
+            window.set_cursor_visible(false);
+            self.fly_camera.look_from_orbit(&self.orbit_camera);
+        } else {
+            if let Err(e) = window.set_cursor_grab(CursorGrabMode::None) {
+                log::warn!("Couldn't release the cursor: {}", e);
+            }
+            window.set_cursor_visible(true);
+            self.orbit_camera.look_from_fly(&self.fly_camera);
+        }
+        self.fly_camera.release_keys();
+        self.flying = flying;
+        log::info!("Fly camera: {}", if flying { "on" } else { "off" });
     }
 
-    fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
-        if new_size.width > 0 && new_size.height > 0 {
-            self.size = new_size;
-            self.surface_configuration.width = new_size.width;
-            self.surface_configuration.height = new_size.height;
-            self.surface
-                .configure(&self.device, &self.surface_configuration);
+    // Borderless on the current monitor. Either way the window sends a
+    // Resized with its new (nonzero) size, which reconfigures the surface.
+    fn toggle_fullscreen(&mut self) {
+        let Some(window) = self.renderer.window() else {
+            return;
+        };
+        match self.windowed_size.take() {
+            Some(windowed_size) => {
+                window.set_fullscreen(None);
+                // Some platforms remember the old size on their own, in
+                // which case this is a no-op
+                let _ = window.request_inner_size(windowed_size);
+                log::info!("Fullscreen: off");
+            }
+            None => {
+                self.windowed_size = Some(window.inner_size());
+                window.set_fullscreen(Some(Fullscreen::Borderless(None)));
+                log::info!("Fullscreen: on");
+            }
         }
     }
 
-    fn input(&mut self, event: &WindowEvent) -> bool {
+    pub fn input(&mut self, event: &WindowEvent) -> bool {
+        // In the background, input is what gets a window drawn between idle
+        // frames, so hovering or scrolling over it still shows straight away.
+        // Likewise in step mode, where there are no other frames.
+        if (!self.focused || self.step_mode)
+            && self.render_enabled
+            && !matches!(event, WindowEvent::RedrawRequested)
+        {
+            self.request_redraw();
+        }
+
+        // The panel gets first pick so clicking on it doesn't also spin the
+        // camera
+        if self.debug_ui.on_window_event(event) {
+            return true;
+        }
+
         match event {
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.modifiers = modifiers.state();
+                false
+            }
+            // Ctrl+click reports the color under the cursor instead of
+            // starting a drag
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Left,
+                ..
+            } if self.modifiers.control_key() => {
+                let position =
+                    self.cursor_position.to_logical::<f64>(self.scale_factor);
+                match self.pick_pixel(position.x, position.y) {
+                    Ok([r, g, b, a]) => log::info!(
+                        "Pixel at ({:.0}, {:.0}): rgba({}, {}, {}, {}), \
+                         #{:02x}{:02x}{:02x}",
+                        position.x,
+                        position.y,
+                        r,
+                        g,
+                        b,
+                        a,
+                        r,
+                        g,
+                        b
+                    ),
+                    Err(e) => log::warn!("Couldn't pick a color: {}", e),
+                }
+                true
+            }
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Right,
+                ..
+            } if !self.renderer.use_ortho_camera && !self.flying => {
+                self.set_flying(true);
+                true
+            }
+            // Mouse input goes to whichever camera is active
+            WindowEvent::MouseInput { state, button, .. } => {
+                if self.renderer.use_ortho_camera {
+                    self.renderer
+                        .ortho_camera
+                        .process_mouse_input(*state, *button)
+                } else {
+                    self.orbit_camera.process_mouse_input(*state, *button)
+                }
+            }
+            WindowEvent::MouseWheel { delta, .. }
+                if self.modifiers.control_key()
+                    && !self.renderer.use_ortho_camera =>
+            {
+                self.fov_zoom.process_scroll(delta);
+                true
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                if self.renderer.use_ortho_camera {
+                    let size = self.renderer.size();
+                    self.renderer.ortho_camera.process_scroll(delta, size);
+                } else {
+                    self.orbit_camera.process_scroll(delta);
+                }
+                true
+            }
             WindowEvent::CursorMoved { position, .. } => {
-                let x = position.x / self.size.width as f64;
-                let y = position.y / self.size.height as f64;
-                self.clear_color = wgpu::Color {
+                self.cursor_position = *position;
+                // Both cameras see every move so neither jumps on the first
+                // drag after switching
+                self.orbit_camera.process_cursor_moved(*position);
+                let size = self.renderer.size();
+                let panning = self
+                    .renderer
+                    .ortho_camera
+                    .process_cursor_moved(*position, size);
+                self.renderer.set_particle_emitter(*position);
+                // Dragging belongs to the camera; recoloring the background
+                // at the same time would just be noise
+                if !self.cursor_clear_color
+                    || self.flying
+                    || self.orbit_camera.is_dragging()
+                    || panning
+                {
+                    return true;
+                }
+                // In logical pixels so the gradient spans the window the
+                // same way on any display. With the aspect locked it spans
+                // the locked rect instead, the bars clamping to its edges.
+                let position = position.to_logical::<f64>(self.scale_factor);
+                let size =
+                    self.renderer.size().to_logical::<f64>(self.scale_factor);
+                let [left, top, width, height] =
+                    self.letterbox_rect().map(|fraction| fraction as f64);
+                let x =
+                    ((position.x / size.width - left) / width).clamp(0.0, 1.0);
+                let y =
+                    ((position.y / size.height - top) / height).clamp(0.0, 1.0);
+                self.renderer.clear_color = wgpu::Color {
                     r: x,
                     g: y,
                     b: (x + y) / 2.0,
@@ -488,85 +1578,604 @@ impl<'a> State<'a> {
                 };
                 true
             }
-            WindowEvent::KeyboardInput { event, .. } => match event {
-                KeyEvent {
-                    state: ElementState::Pressed,
-                    physical_key: PhysicalKey::Code(code),
-                    ..
-                } => match code {
-                    KeyCode::Space => {
-                        self.active_render_pipeline_index =
-                            (self.active_render_pipeline_index + 1) % 2;
+            // Keys held while the window loses focus never see a release
+            WindowEvent::Focused(false) => {
+                self.input_map.release_all();
+                self.set_flying(false);
+                self.set_focused(false);
+                false
+            }
+            WindowEvent::Focused(true) => {
+                self.set_focused(true);
+                false
+            }
+            // While flying, Escape lets go of the cursor instead of quitting
+            // and WASD/QE move instead of toggling things
+            WindowEvent::KeyboardInput { event, .. } if self.flying => {
+                let PhysicalKey::Code(key) = event.physical_key else {
+                    return false;
+                };
+                if key == KeyCode::Escape {
+                    if event.state == ElementState::Pressed {
+                        self.set_flying(false);
+                    }
+                    return true;
+                }
+                if self.fly_camera.process_keyboard(key, event.state) {
+                    return true;
+                }
+                match self.input_map.process_key(event, self.modifiers) {
+                    Some(action) => {
+                        self.on_action(action);
                         true
                     }
-                    _ => false,
-                },
-                _ => false,
-            },
+                    None => false,
+                }
+            }
+            WindowEvent::KeyboardInput { event, .. } => {
+                if let Some(number) = input::number_key(event) {
+                    self.on_bookmark_key(number - 1);
+                    return true;
+                }
+                match self.input_map.process_key(event, self.modifiers) {
+                    Some(action) => {
+                        self.on_action(action);
+                        true
+                    }
+                    None => false,
+                }
+            }
             _ => false,
         }
     }
 
-    fn update(&mut self) {}
+    // Ctrl saves the orbit camera's view into `slot`, otherwise the camera
+    // glides to what was saved there
+    fn on_bookmark_key(&mut self, slot: usize) {
+        if self.modifiers.control_key() {
+            let bookmark = camera_bookmarks::CameraBookmark::from_camera(
+                &self.orbit_camera,
+            );
+            match self.camera_bookmarks.set(slot, bookmark) {
+                Ok(()) => log::info!("Saved camera bookmark {}", slot + 1),
+                Err(e) => log::error!("Couldn't save camera bookmark: {:#}", e),
+            }
+            return;
+        }
+        match self.camera_bookmarks.get(slot) {
+            Some(bookmark) => {
+                self.camera_transition =
+                    Some(camera_bookmarks::CameraTransition::new(
+                        &self.orbit_camera,
+                        bookmark,
+                        CAMERA_BOOKMARK_TRANSITION,
+                    ));
+            }
+            None => log::info!("No camera bookmark {} yet", slot + 1),
+        }
+    }
+
+    fn on_action(&mut self, action: Action) {
+        // Flying needs all of `self`, which `renderer` below borrows
+        match action {
+            Action::ToggleFlyCamera if self.renderer.use_ortho_camera => {
+                log::info!("The fly camera needs the perspective camera");
+                return;
+            }
+            Action::ToggleFlyCamera => return self.set_flying(!self.flying),
+            Action::ToggleOrthoCamera => self.set_flying(false),
+            _ => {}
+        }
+        let renderer = &mut self.renderer;
+        let on_off = |on| if on { "on" } else { "off" };
+        match action {
+            Action::Quit => self.quit_requested = true,
+            Action::OpenWindow => self.new_window_requested = true,
+            Action::ToggleFullscreen => self.toggle_fullscreen(),
+            Action::ToggleDebugUi => {
+                self.debug_ui.visible = !self.debug_ui.visible;
+            }
+            Action::Screenshot => renderer.request_screenshot(),
+            Action::ToggleCaptureSequence => renderer.toggle_capture_sequence(),
+            // Read every frame in `update` instead
+            Action::MoveForward
+            | Action::MoveBackward
+            | Action::MoveLeft
+            | Action::MoveRight
+            | Action::NarrowFov
+            | Action::WidenFov
+            | Action::AdvanceClipPlane
+            | Action::RetreatClipPlane => {}
+            // Handled above, before `renderer` borrows `self`
+            Action::ToggleFlyCamera => {}
+            Action::CyclePipeline => renderer.cycle_render_pipeline(false),
+            Action::CyclePipelineBackward => {
+                renderer.cycle_render_pipeline(true)
+            }
+            Action::ToggleWireframe => renderer.toggle_wireframe(),
+            Action::CycleTriangleLimit => renderer.cycle_triangle_limit(),
+            Action::ToggleConservativeRasterization => {
+                renderer.toggle_conservative_rasterization()
+            }
+            Action::TogglePoints => renderer.toggle_points(),
+            Action::ToggleSplitStreams => renderer.toggle_split_streams(),
+            Action::ToggleNormalsPipeline => renderer.toggle_normals_pipeline(),
+            Action::ToggleClipPlane => renderer.toggle_clip_plane(),
+            Action::CycleFrameRateCap => self.cycle_frame_rate_cap(),
+            Action::CycleFrameLatency => renderer.cycle_frame_latency(),
+            Action::CycleAaMode => {
+                renderer.cycle_aa_mode();
+                self.update_title();
+            }
+            Action::ToggleLabels | Action::ToggleLabelOcclusion
+                if !renderer.has_labels() =>
+            {
+                log::info!("No labels, the SDF font couldn't be loaded");
+            }
+            Action::ToggleLabels => {
+                renderer.show_labels = !renderer.show_labels;
+                log::info!("Labels: {}", on_off(renderer.show_labels));
+            }
+            Action::ToggleLabelOcclusion => {
+                renderer.occlude_labels = !renderer.occlude_labels;
+                log::info!(
+                    "Label occlusion: {}",
+                    on_off(renderer.occlude_labels)
+                );
+            }
+            Action::ToggleStencilReveal => {
+                renderer.show_stencil_reveal = !renderer.show_stencil_reveal;
+                log::info!(
+                    "Stencil reveal: {}",
+                    on_off(renderer.show_stencil_reveal)
+                );
+            }
+            Action::ToggleOrthoCamera => {
+                renderer.use_ortho_camera = !renderer.use_ortho_camera;
+                log::info!(
+                    "Camera: {}",
+                    if renderer.use_ortho_camera {
+                        "orthographic"
+                    } else {
+                        "perspective"
+                    }
+                );
+            }
+            Action::ToggleInstances => {
+                renderer.show_instances = !renderer.show_instances;
+                log::info!(
+                    "Instance field: {}",
+                    on_off(renderer.show_instances)
+                );
+            }
+            Action::ToggleObjects => {
+                renderer.show_objects = !renderer.show_objects;
+                log::info!(
+                    "Separately drawn objects: {}",
+                    on_off(renderer.show_objects)
+                );
+            }
+            Action::ToggleShadows => {
+                renderer.show_shadows = !renderer.show_shadows;
+                log::info!("Shadows: {}", on_off(renderer.show_shadows));
+            }
+            Action::ToggleCulling => {
+                let field = &mut renderer.instance_field;
+                field.culling_enabled = !field.culling_enabled;
+                log::info!(
+                    "GPU culling: {} (last frame time {:.2}ms)",
+                    on_off(field.culling_enabled),
+                    renderer.frame_time_ms
+                );
+            }
+            Action::ToggleDepthPrepass => {
+                renderer.depth_prepass = !renderer.depth_prepass;
+                log::info!(
+                    "Depth prepass: {} (last frame time {:.2}ms)",
+                    on_off(renderer.depth_prepass),
+                    renderer.frame_time_ms
+                );
+            }
+            Action::ToggleDiscardTransient => {
+                renderer.discard_transient = !renderer.discard_transient;
+                log::info!(
+                    "Discard transient attachments: {} (last frame time \
+                     {:.2}ms)",
+                    on_off(renderer.discard_transient),
+                    renderer.frame_time_ms
+                );
+            }
+            Action::CycleCanvasAspect => renderer.cycle_canvas_aspect(),
+            Action::CycleCanvasFit => renderer.cycle_canvas_fit(),
+            Action::ToggleSprites => {
+                renderer.show_sprites = !renderer.show_sprites;
+                log::info!("Sprites: {}", on_off(renderer.show_sprites));
+            }
+            Action::CycleSpriteLayer => renderer.cycle_sprite_layer(),
+            Action::ToggleTileMap => {
+                renderer.show_tile_map = !renderer.show_tile_map;
+                log::info!("Tile map: {}", on_off(renderer.show_tile_map));
+            }
+            Action::ToggleTranslucent => {
+                renderer.show_translucent = !renderer.show_translucent;
+                log::info!(
+                    "Translucent quads: {}",
+                    on_off(renderer.show_translucent)
+                );
+            }
+            Action::ToggleParticles => {
+                renderer.show_particles = !renderer.show_particles;
+                log::info!("Particles: {}", on_off(renderer.show_particles));
+            }
+            Action::ToggleSkybox => {
+                renderer.show_skybox = !renderer.show_skybox;
+                log::info!("Skybox: {}", on_off(renderer.show_skybox));
+            }
+            Action::ToggleGradient => {
+                renderer.show_gradient = !renderer.show_gradient;
+                log::info!(
+                    "Background gradient: {}{}",
+                    on_off(renderer.show_gradient),
+                    if renderer.show_gradient && renderer.show_skybox {
+                        " (behind the skybox, B hides it)"
+                    } else {
+                        ""
+                    }
+                );
+            }
+            Action::SwapGradientColors => renderer.swap_gradient_colors(),
+            Action::ToggleTerrain => {
+                renderer.show_terrain = !renderer.show_terrain;
+                log::info!("Terrain: {}", on_off(renderer.show_terrain));
+            }
+            Action::ToggleHeightmap if !renderer.has_heightmap() => {
+                log::info!("No heightmap terrain, it couldn't be loaded");
+            }
+            Action::ToggleHeightmap => {
+                renderer.show_heightmap = !renderer.show_heightmap;
+                log::info!(
+                    "Heightmap terrain: {}",
+                    on_off(renderer.show_heightmap)
+                );
+            }
+            Action::ToggleBounds => {
+                self.show_bounds = !self.show_bounds;
+                log::info!("Entity bounds: {}", on_off(self.show_bounds));
+            }
+            Action::ToggleGrid => {
+                renderer.show_grid = !renderer.show_grid;
+                log::info!("Grid: {}", on_off(renderer.show_grid));
+            }
+            Action::CycleGBufferView => renderer.cycle_gbuffer_view(),
+            Action::CycleCullMode => renderer.cycle_cull_mode(),
+            Action::ToggleFrontFace => renderer.toggle_front_face(),
+            Action::ToggleSplitScreen => {
+                self.split_screen = !self.split_screen;
+                log::info!("Split screen: {}", on_off(self.split_screen));
+            }
+            Action::ToggleGridView => {
+                let n_pipelines = renderer.render_pipeline_names().len();
+                self.grid_view = match self.grid_view {
+                    Some(_) => None,
+                    None => Some(viewport::grid_size(n_pipelines)),
+                };
+                match self.grid_view {
+                    Some((columns, rows)) => {
+                        log::info!("Grid view: {}x{}", columns, rows)
+                    }
+                    None => log::info!("Grid view: off"),
+                }
+            }
+            Action::ToggleAspectLock => {
+                self.aspect_lock = match self.aspect_lock {
+                    Some(_) => None,
+                    None => Some(LOCKED_ASPECT),
+                };
+                match self.aspect_lock {
+                    Some(aspect) => log::info!("Aspect lock: {:.2}", aspect),
+                    None => log::info!("Aspect lock: off"),
+                }
+            }
+            Action::ToggleDepthView if !renderer.can_show_depth() => {
+                log::info!("No depth view with MSAA on the GL backend");
+            }
+            Action::ToggleDepthView => {
+                renderer.show_depth = !renderer.show_depth;
+                log::info!("Depth view: {}", on_off(renderer.show_depth));
+            }
+            Action::ToggleNormals => {
+                renderer.show_normals = !renderer.show_normals;
+                log::info!(
+                    "Normal visualization: {}",
+                    on_off(renderer.show_normals)
+                );
+            }
+            Action::ToggleAdaptiveResolution => {
+                let enabled = !renderer.adaptive_resolution.enabled;
+                renderer.adaptive_resolution.set_enabled(enabled);
+                log::info!(
+                    "Adaptive resolution: {} (target {:.2}ms)",
+                    on_off(enabled),
+                    renderer.adaptive_resolution.target_frame_ms
+                );
+            }
+            Action::ReloadShader => renderer.reload_scene_shader(),
+            Action::CycleRenderScale => renderer.cycle_render_scale(),
+            Action::ToggleUpscaleFilter => renderer.toggle_upscale_filter(),
+            Action::CycleAddressMode => renderer.cycle_address_mode(),
+            Action::ToggleUvTiling => {
+                renderer.toggle_uv_tiling();
+                log::info!("UV tiling: {}", on_off(renderer.tile_uvs()));
+            }
+            Action::IncreaseDetailMix => {
+                renderer.adjust_detail_mix(DETAIL_MIX_STEP)
+            }
+            Action::DecreaseDetailMix => {
+                renderer.adjust_detail_mix(-DETAIL_MIX_STEP)
+            }
+            Action::IncreaseShininess => {
+                renderer.scale_shininess(SHININESS_STEP)
+            }
+            Action::DecreaseShininess => {
+                renderer.scale_shininess(1.0 / SHININESS_STEP)
+            }
+            Action::IncreaseReflectivity => {
+                renderer.adjust_reflectivity(REFLECTIVITY_STEP)
+            }
+            Action::DecreaseReflectivity => {
+                renderer.adjust_reflectivity(-REFLECTIVITY_STEP)
+            }
+            Action::ToggleSpecular => renderer.toggle_specular(),
+            Action::IncreaseBlurRadius => {
+                renderer.adjust_blur_radius(BLUR_RADIUS_STEP)
+            }
+            Action::DecreaseBlurRadius => {
+                renderer.adjust_blur_radius(-BLUR_RADIUS_STEP)
+            }
+            Action::CycleBlurSampleSpacing => self.cycle_blur_sample_spacing(),
+            Action::IncreaseClearRed => self.nudge_clear_color(0, 1.0),
+            Action::DecreaseClearRed => self.nudge_clear_color(0, -1.0),
+            Action::IncreaseClearGreen => self.nudge_clear_color(1, 1.0),
+            Action::DecreaseClearGreen => self.nudge_clear_color(1, -1.0),
+            Action::IncreaseClearBlue => self.nudge_clear_color(2, 1.0),
+            Action::DecreaseClearBlue => self.nudge_clear_color(2, -1.0),
+            Action::ToggleCursorClearColor => {
+                self.cursor_clear_color = !self.cursor_clear_color;
+                log::info!(
+                    "Clear color follows cursor: {}",
+                    on_off(self.cursor_clear_color)
+                );
+            }
+            Action::CyclePostPreset => renderer.cycle_post_preset(),
+            Action::TogglePostHud => {
+                renderer.show_post_hud = !renderer.show_post_hud;
+            }
+            Action::ToggleAnimationPaused => {
+                self.paused = !self.paused;
+                log::info!("Animation paused: {}", on_off(self.paused));
+            }
+            Action::ToggleRendering => self.toggle_rendering(),
+            Action::ToggleStepMode => self.toggle_step_mode(),
+            Action::StepFrame => self.step_frame(),
+            Action::ScrubBackward => self.advance_time(-SCRUB_DELTA),
+            Action::ScrubForward => self.advance_time(SCRUB_DELTA),
+            Action::ResetTime => self.set_time(0.0),
+            Action::SaveRenderState => self.save_render_state(),
+            Action::LogMemoryReport => self.log_memory_report(),
+            Action::ToggleLinearView => renderer.toggle_linear_view(),
+            Action::ToggleMorph => {
+                renderer.show_morph = !renderer.show_morph;
+                log::info!("Morph: {}", on_off(renderer.show_morph));
+            }
+            Action::ToggleMirror => {
+                renderer.show_mirror = !renderer.show_mirror;
+                log::info!("Mirror: {}", on_off(renderer.show_mirror));
+            }
+            Action::SelectPreviousEffect
+            | Action::SelectNextEffect
+            | Action::ToggleSelectedEffect
+            | Action::MoveEffectEarlier
+            | Action::MoveEffectLater => {
+                let post_chain = &mut renderer.post_chain;
+                match action {
+                    Action::SelectPreviousEffect => {
+                        post_chain.select_previous()
+                    }
+                    Action::SelectNextEffect => post_chain.select_next(),
+                    Action::ToggleSelectedEffect => {
+                        post_chain.toggle_selected()
+                    }
+                    Action::MoveEffectEarlier => post_chain.move_selected(-1),
+                    _ => post_chain.move_selected(1),
+                }
+                log::info!("Post chain: {}", post_chain.describe().join(" |"));
+            }
+        }
+    }
+
+    // The RGBA color of the pixel at (x, y) in logical pixels, the units
+    // the debug panel and most UI code work in. Draws a frame to read it
+    // from, see `Renderer::pick_pixel`.
+    pub fn pick_pixel(
+        &mut self,
+        x: f64,
+        y: f64,
+    ) -> Result<[u8; 4], StateError> {
+        let position = winit::dpi::LogicalPosition::new(x, y)
+            .to_physical::<f64>(self.scale_factor);
+        // Past the right or bottom edge is clamped to the last pixel
+        self.renderer.pick_pixel(
+            position.x.max(0.0).floor() as u32,
+            position.y.max(0.0).floor() as u32,
+        )
+    }
 
-    fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
-        let output = self.surface.get_current_texture()?;
+    // Raw mouse motion from DeviceEvent::MouseMotion
+    pub fn mouse_motion(&mut self, delta: (f64, f64)) {
+        if self.flying {
+            self.fly_camera.process_mouse_motion(delta.0, delta.1);
+        }
+    }
 
-        let view = output
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
+    pub fn quit_requested(&self) -> bool {
+        self.quit_requested
+    }
 
-        let mut encoder = self.device.create_command_encoder(
-            &wgpu::CommandEncoderDescriptor {
-                label: Some("Render Encoder"),
-            },
-        );
+    // Whether a new window was asked for since the last call
+    pub fn take_new_window_request(&mut self) -> bool {
+        std::mem::take(&mut self.new_window_requested)
+    }
+
+    // Seconds since the last call, or since the clocks started over
+    fn frame_time(&mut self) -> f32 {
+        let now = Instant::now();
+        let frame_time = (now - self.last_update).as_secs_f32();
+        self.last_update = now;
+        frame_time
+    }
 
+    // Once per frame, `dt` seconds after the last one: held keys and the
+    // cameras
+    pub fn update(&mut self, dt: f32) {
+        let axis = |positive, negative| {
+            let pressed = |action| self.input_map.is_pressed(action) as i8;
+            (pressed(positive) - pressed(negative)) as f32
+        };
+        let zoom = axis(Action::NarrowFov, Action::WidenFov);
+        self.fov_zoom.zoom(zoom * FOV_KEY_SPEED * dt);
+        self.fov_zoom.update(dt, &mut self.renderer.camera);
+        let clip = axis(Action::AdvanceClipPlane, Action::RetreatClipPlane);
+        self.renderer.move_clip_plane(clip * CLIP_PLANE_SPEED * dt);
+        #[cfg(feature = "audio")]
         {
-            // Begin_render_pass() borrows encoder mutably (aka &mut self). We
-            // can't call encoder.finish() until we release that mutable borrow.
-            // The block tells Rust to drop any variables within it when the
-            // code leaves that scope, thus releasing the mutable borrow on
-            // encoder and allowing us to finish() it.
-            //
-            // If you don't like the {}, you can also use drop(render_pass) to
-            // achieve the same effect.
-            let mut render_pass =
-                encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                    label: Some("Render Pass"),
-                    color_attachments: &[Some(
-                        wgpu::RenderPassColorAttachment {
-                            view: &view,
-                            resolve_target: None,
-                            ops: wgpu::Operations {
-                                load: wgpu::LoadOp::Clear(self.clear_color),
-                                store: wgpu::StoreOp::Store,
-                            },
-                        },
-                    )],
-                    depth_stencil_attachment: None,
-                    occlusion_query_set: None,
-                    timestamp_writes: None,
-                });
-
-            let active_render_pipeline =
-                &self.render_pipelines[self.active_render_pipeline_index];
-
-            render_pass.set_pipeline(&active_render_pipeline);
-            render_pass.set_bind_group(0, &self.diffuse_bind_group, &[]);
-            render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-            render_pass.set_index_buffer(
-                self.index_buffer.slice(..),
-                wgpu::IndexFormat::Uint16,
-            );
-            render_pass.draw_indexed(0..self.n_indices, 0, 0..1);
+            self.renderer.audio_amplitude = self.audio.amplitude();
+        }
+
+        if self.flying {
+            self.fly_camera.update(dt);
+            self.fly_camera.update_camera(&mut self.renderer.camera);
+            return;
+        }
+
+        let right = axis(Action::MoveRight, Action::MoveLeft);
+        let forward = axis(Action::MoveForward, Action::MoveBackward);
+        if (right != 0.0 || forward != 0.0) && self.renderer.show_tile_map {
+            let step = TILE_SCROLL_SPEED * dt;
+            self.renderer.scroll_tile_map(right * step, -forward * step);
+        } else if right != 0.0 || forward != 0.0 {
+            let step = KEY_MOVE_SPEED * dt;
+            self.orbit_camera.pan(right * step, forward * step);
         }
 
-        // Submit will accept anything that implements `IntoIter`
-        self.queue.submit(std::iter::once(encoder.finish()));
-        output.present();
+        #[cfg(feature = "gamepad")]
+        if let Some(gilrs) = &mut self.gilrs {
+            self.gamepad.update(gilrs, &mut self.orbit_camera);
+        }
+
+        // Wins over anything else moving the camera until it arrives
+        if let Some(transition) = &mut self.camera_transition {
+            if !transition.update(dt, &mut self.orbit_camera) {
+                self.camera_transition = None;
+            }
+        }
+
+        self.orbit_camera.update_camera(&mut self.renderer.camera);
+    }
+
+    // One step of the simulation, `dt` seconds long: the animation and the
+    // particles
+    pub fn step(&mut self, dt: f32) {
+        let animation_dt = self.animation_dt(dt);
+        self.renderer.step(animation_dt);
+    }
+
+    // Once per frame, after its steps. In step mode the frame shows where
+    // the step it just took ended up rather than catching up to it.
+    pub fn prepare_frame(&mut self) {
+        let alpha = if self.step_mode {
+            1.0
+        } else {
+            self.timestep.alpha()
+        };
+        // Before the renderer writes a camera per viewport
+        self.update_viewports();
+        self.renderer.prepare_frame(alpha);
+        self.queue_debug_shapes();
+    }
+
+    // A line drawn over the scene for the next frame only, e.g. a ray or a
+    // velocity. Queue it again every frame to keep it around.
+    pub fn debug_line(
+        &mut self,
+        from: cgmath::Point3<f32>,
+        to: cgmath::Point3<f32>,
+        color: [f32; 3],
+    ) {
+        self.renderer.debug_line(from, to, color);
+    }
+
+    // The same for the edges of a box
+    pub fn debug_box(&mut self, aabb: &Aabb, color: [f32; 3]) {
+        self.renderer.debug_box(aabb, color);
+    }
+
+    // After the renderer's update so the shapes follow this frame's
+    // transforms
+    fn queue_debug_shapes(&mut self) {
+        if self.show_bounds {
+            for bounds in self.renderer.entity_bounds() {
+                self.debug_box(&bounds, BOUNDS_COLOR);
+            }
+        }
+    }
+
+    // Locks the scene to `aspect` (width over height), or lets it fill the
+    // window again with None
+    pub fn set_aspect_lock(&mut self, aspect: Option<f32>) {
+        self.aspect_lock = aspect;
+    }
+
+    // Where the scene goes with the aspect locked, in fractions of what
+    // it's drawn into. The whole of it without a lock.
+    fn letterbox_rect(&self) -> [f32; 4] {
+        match self.aspect_lock {
+            Some(aspect) => {
+                viewport::letterbox(self.renderer.scene_aspect(), aspect)
+            }
+            None => viewport::FULL.rect,
+        }
+    }
+
+    // Done every frame rather than on each change: the locked rect depends
+    // on the window's and the canvas's shape, which change in several
+    // places, and a new renderer after a device loss starts without any
+    fn update_viewports(&mut self) {
+        let n_pipelines = self.renderer.render_pipeline_names().len();
+        let viewports = if let Some((columns, rows)) = self.grid_view {
+            viewport::grid(columns, rows, n_pipelines)
+        } else if self.split_screen {
+            viewport::split_screen()
+        } else if self.aspect_lock.is_some() {
+            vec![viewport::FULL]
+        } else {
+            Vec::new()
+        };
+        let rect = self.letterbox_rect();
+        self.renderer.viewports = viewports
+            .into_iter()
+            .map(|viewport| viewport.within(rect))
+            .collect();
+    }
+
+    pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
+        let renderer = &mut self.renderer;
+        let cursor_clear_color = &mut self.cursor_clear_color;
+        self.debug_ui.run(renderer.size(), |context| {
+            debug_ui::panel(context, renderer, cursor_clear_color)
+        });
 
-        Ok(())
+        let debug_ui = &mut self.debug_ui;
+        self.renderer.render_with(|overlay| debug_ui.paint(overlay))
     }
 }
 
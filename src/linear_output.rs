@@ -0,0 +1,198 @@
+use winit::dpi::PhysicalSize;
+
+// Presents through a linear (non-sRGB) view of an sRGB surface, with the
+// sRGB encoding done by hand in linear_output.wgsl rather than by the
+// hardware as pixels are written.
+//
+// Whether writing to a texture encodes is decided by the format of the view
+// being written, which is what `TextureFormat::is_srgb()` reports: an sRGB
+// view takes linear values and encodes them on write, a linear view stores
+// exactly what the shader returns. Getting the two mixed up is the classic
+// double correction. Gamma correcting in the shader and then writing to an
+// sRGB view encodes twice, which looks washed out (see the Gamma post
+// effect); writing linear values to a linear view without correcting
+// encodes never, which looks too dark with crushed shadows. Each output
+// needs exactly one of the two.
+//
+// Every pipeline that draws the frame targets the surface's sRGB format, so
+// rather than building a linear variant of each, the frame is drawn as usual
+// into an sRGB texture here and copied across to the linear view at the end.
+// Needs the surface to list the linear format in its view formats, see
+// `Gpu::configure_surface`.
+pub struct LinearOutput {
+    linear_format: wgpu::TextureFormat,
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline: wgpu::RenderPipeline,
+    // Same size and format as the surface
+    frame_view: wgpu::TextureView,
+    bind_group: wgpu::BindGroup,
+}
+
+impl LinearOutput {
+    // None unless `config` is for an sRGB surface that can also be viewed
+    // as linear
+    pub fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+    ) -> Option<Self> {
+        let linear_format = config.format.remove_srgb_suffix();
+        if !config.format.is_srgb()
+            || !config.view_formats.contains(&linear_format)
+        {
+            return None;
+        }
+
+        let bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Linear Output Bind Group Layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float {
+                            filterable: false,
+                        },
+                    },
+                    count: None,
+                }],
+            });
+
+        let shader = device
+            .create_shader_module(wgpu::include_wgsl!("linear_output.wgsl"));
+
+        let pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Linear Output Pipeline Layout"),
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        let pipeline =
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Linear Output Pipeline"),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[],
+                    compilation_options:
+                        wgpu::PipelineCompilationOptions::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some("fs_main"),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: linear_format,
+                        blend: Some(wgpu::BlendState::REPLACE),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options:
+                        wgpu::PipelineCompilationOptions::default(),
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            });
+
+        let (frame_view, bind_group) = Self::create_frame(
+            device,
+            &bind_group_layout,
+            config.format,
+            PhysicalSize::new(config.width, config.height),
+        );
+
+        Some(Self {
+            linear_format,
+            bind_group_layout,
+            pipeline,
+            frame_view,
+            bind_group,
+        })
+    }
+
+    fn create_frame(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        format: wgpu::TextureFormat,
+        size: PhysicalSize<u32>,
+    ) -> (wgpu::TextureView, wgpu::BindGroup) {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Linear Output Frame"),
+            size: wgpu::Extent3d {
+                width: size.width.max(1),
+                height: size.height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Linear Output Bind Group"),
+            layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&view),
+            }],
+        });
+        (view, bind_group)
+    }
+
+    pub fn resize(
+        &mut self,
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+    ) {
+        (self.frame_view, self.bind_group) = Self::create_frame(
+            device,
+            &self.bind_group_layout,
+            config.format,
+            PhysicalSize::new(config.width, config.height),
+        );
+    }
+
+    // Where the frame gets drawn in place of the surface
+    pub fn frame_view(&self) -> &wgpu::TextureView {
+        &self.frame_view
+    }
+
+    // Records a pass copying the frame into a linear view of `target`, the
+    // surface's texture for this frame
+    pub fn present(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::Texture,
+    ) {
+        let view = target.create_view(&wgpu::TextureViewDescriptor {
+            format: Some(self.linear_format),
+            ..Default::default()
+        });
+        let mut render_pass =
+            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Linear Output Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,32 @@
+// Copies the finished frame into a linear (non-sRGB) view of the surface,
+// doing the sRGB encoding the view won't do on write
+
+// The frame, drawn into an sRGB texture. Loading from it decodes back to
+// linear.
+@group(0) @binding(0)
+var t_frame: texture_2d<f32>;
+
+// A single triangle covering the screen: (-1, -1), (3, -1), (-1, 3)
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(
+        f32((vertex_index << 1u) & 2u),
+        f32(vertex_index & 2u),
+    );
+    return vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+}
+
+// The sRGB transfer function: a short linear segment near black, then a
+// 2.4 power curve. A plain pow(c, 1.0 / 2.2) is close but not exact.
+fn linear_to_srgb(linear: vec3<f32>) -> vec3<f32> {
+    let low = linear * 12.92;
+    let high = 1.055 * pow(linear, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, linear <= vec3<f32>(0.0031308));
+}
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    // Same size as the output, so no filtering
+    let color = textureLoad(t_frame, vec2<i32>(position.xy), 0);
+    return vec4<f32>(linear_to_srgb(color.rgb), color.a);
+}
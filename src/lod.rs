@@ -0,0 +1,48 @@
+use crate::scene::MeshId;
+
+// How far past a level's distance the camera has to go before switching,
+// as a fraction of the distance. Without it an object sitting right on a
+// threshold flickers between levels as the camera bobs about.
+pub const HYSTERESIS: f32 = 0.1;
+
+// Versions of one mesh in decreasing detail, each with the distance from
+// the camera it's good for up to. Entities pointing at one (see
+// `scene::Entity::lod`) have their mesh picked from it every frame.
+#[derive(Debug, Clone)]
+pub struct Lod {
+    // (max distance, mesh), closest first. The last level is used however
+    // far away things get, so its distance doesn't matter.
+    levels: Vec<(f32, MeshId)>,
+}
+
+impl Lod {
+    // `levels` can be in any order. Panics if there aren't any.
+    pub fn new(mut levels: Vec<(f32, MeshId)>) -> Self {
+        assert!(!levels.is_empty(), "A LOD needs at least one level");
+        levels.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Self { levels }
+    }
+
+    pub fn mesh(&self, level: usize) -> MeshId {
+        self.levels[level.min(self.levels.len() - 1)].1
+    }
+
+    // The level to draw at `distance` when `current` was drawn last frame.
+    // Going to a coarser level takes being HYSTERESIS past the threshold,
+    // and coming back takes being as far inside it, so there's a band
+    // around each threshold where whatever was there stays.
+    pub fn select(&self, distance: f32, current: usize) -> usize {
+        self.levels[..self.levels.len() - 1]
+            .iter()
+            .enumerate()
+            .filter(|&(boundary, &(max_distance, _))| {
+                let margin = if current > boundary {
+                    1.0 - HYSTERESIS
+                } else {
+                    1.0 + HYSTERESIS
+                };
+                distance > max_distance * margin
+            })
+            .count()
+    }
+}
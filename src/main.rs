@@ -1,9 +1,34 @@
+use std::process::ExitCode;
+
+use clap::Parser;
 use log::error;
 
-use learn_wgpu::run;
+use learn_wgpu::{run, Args};
+
+// What bad arguments exit with (sysexits.h's EX_USAGE), rather than clap's
+// usual 2, which `RunError::exit_code` already uses for no GPU adapter
+const USAGE_EXIT_CODE: u8 = 64;
 
-fn main() {
-    if let Err(e) = pollster::block_on(run()) {
-        error!("Error: {:?}", e);
+// Exits nonzero when `run` fails, see `RunError::exit_code` for which code
+// means what
+fn main() -> ExitCode {
+    let args = match Args::try_parse() {
+        Ok(args) => args,
+        // --help and --version come through here too, printed to stdout
+        Err(e) => {
+            let _ = e.print();
+            return if e.use_stderr() {
+                ExitCode::from(USAGE_EXIT_CODE)
+            } else {
+                ExitCode::SUCCESS
+            };
+        }
+    };
+    match pollster::block_on(run(args)) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            error!("Error: {}", e);
+            ExitCode::from(e.exit_code())
+        }
     }
 }
@@ -0,0 +1,105 @@
+use wgpu::util::DeviceExt;
+
+// Specular exponents the +/- keys stay between. Below 1 the highlight
+// covers more than the lit side; far above a few thousand it's too small to
+// land on a pixel.
+pub const MIN_SHININESS: f32 = 1.0;
+pub const MAX_SHININESS: f32 = 1024.0;
+
+pub const DEFAULT_SHININESS: f32 = 32.0;
+pub const DEFAULT_SPECULAR_COLOR: [f32; 3] = [1.0, 1.0, 1.0];
+
+// Matte, nothing of the environment shows
+pub const DEFAULT_REFLECTIVITY: f32 = 0.0;
+
+// see: shader.wgsl MaterialUniform
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct MaterialUniform {
+    specular_color: [f32; 3],
+    shininess: f32,
+    uv_scale: [f32; 2],
+    reflectivity: f32,
+    _padding: f32,
+}
+
+// The `uv_scale` that shows the middle of a texture `aspect_ratio` wide
+// (see texture::Texture::aspect_ratio) over a square of UV space without
+// stretching it: the longer side is cropped to match the shorter, like CSS's
+// object-fit: cover. 1 for square textures.
+pub fn uv_scale_for_aspect(aspect_ratio: f32) -> [f32; 2] {
+    if aspect_ratio >= 1.0 {
+        [1.0 / aspect_ratio, 1.0]
+    } else {
+        [1.0, aspect_ratio]
+    }
+}
+
+// How shiny a surface is, for shader.wgsl's Blinn-Phong highlight, and how
+// its textures sit on its UVs. Bound with the material's textures at
+// @group(0) @binding(6), so every draw picks up its own. Changing the fields
+// does nothing until `update`.
+pub struct Material {
+    // Color and strength of the highlight, multiplied by the light
+    pub specular_color: [f32; 3],
+    // The exponent on how closely the half vector lines up with the normal:
+    // the higher it is, the smaller and sharper the highlight
+    pub shininess: f32,
+    // Texture coordinates are scaled by this around the middle of the
+    // texture before sampling. [1, 1] leaves them alone, see
+    // `uv_scale_for_aspect` for undoing a non-square texture's stretch.
+    pub uv_scale: [f32; 2],
+    // How much of the skybox is mirrored in the surface, from 0 (none, just
+    // the lit texture) to 1 (a perfect mirror)
+    pub reflectivity: f32,
+    buffer: wgpu::Buffer,
+}
+
+impl Material {
+    pub fn new(
+        device: &wgpu::Device,
+        specular_color: [f32; 3],
+        shininess: f32,
+    ) -> Self {
+        let uv_scale = [1.0, 1.0];
+        let reflectivity = DEFAULT_REFLECTIVITY;
+        let buffer =
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Material Buffer"),
+                contents: bytemuck::cast_slice(&[MaterialUniform {
+                    specular_color,
+                    shininess,
+                    uv_scale,
+                    reflectivity,
+                    _padding: 0.0,
+                }]),
+                usage: wgpu::BufferUsages::UNIFORM
+                    | wgpu::BufferUsages::COPY_DST,
+            });
+        Self {
+            specular_color,
+            shininess,
+            uv_scale,
+            reflectivity,
+            buffer,
+        }
+    }
+
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+
+    pub fn update(&self, queue: &wgpu::Queue) {
+        queue.write_buffer(
+            &self.buffer,
+            0,
+            bytemuck::cast_slice(&[MaterialUniform {
+                specular_color: self.specular_color,
+                shininess: self.shininess,
+                uv_scale: self.uv_scale,
+                reflectivity: self.reflectivity,
+                _padding: 0.0,
+            }]),
+        );
+    }
+}
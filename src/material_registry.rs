@@ -0,0 +1,50 @@
+use crate::scene::{MaterialId, PipelineId};
+
+// What an entity is drawn with, as far as binding goes: its textures and
+// specular uniform (see renderer::create_texture_bind_group) at
+// @group(0), and the pipeline to draw them with if it isn't the active one
+pub struct Material {
+    pub bind_group: wgpu::BindGroup,
+    // None draws with whichever render pipeline is active. An entity's own
+    // `pipeline` wins over this.
+    pub pipeline: Option<PipelineId>,
+}
+
+// Every material the scenes' entities can refer to, looked up by
+// MaterialId. The pentagon's textures are always there as
+// MaterialId::DEFAULT, which is what entities start out drawn with.
+pub struct MaterialRegistry {
+    materials: Vec<Material>,
+}
+
+impl MaterialRegistry {
+    pub fn new(default: Material) -> Self {
+        Self {
+            materials: vec![default],
+        }
+    }
+
+    pub fn add(&mut self, material: Material) -> MaterialId {
+        self.materials.push(material);
+        MaterialId(self.materials.len() - 1)
+    }
+
+    pub fn get(&self, id: MaterialId) -> &Material {
+        &self.materials[id.0]
+    }
+
+    // E.g. to point the default material at a texture that finished
+    // streaming in
+    pub fn set_bind_group(
+        &mut self,
+        id: MaterialId,
+        bind_group: wgpu::BindGroup,
+    ) {
+        self.materials[id.0].bind_group = bind_group;
+    }
+
+    // The pentagon's textures, for the draws that don't go through an entity
+    pub fn default_bind_group(&self) -> &wgpu::BindGroup {
+        &self.get(MaterialId::DEFAULT).bind_group
+    }
+}
@@ -0,0 +1,129 @@
+use std::path::Path;
+
+use anyhow::*;
+
+use crate::geometry::Vertex;
+
+// A .mesh file is a 16 byte header followed by the raw vertex and index
+// data, all little-endian:
+//
+//   magic         4 bytes, "MESH"
+//   version       u32
+//   vertex count  u32
+//   index count   u32
+//   vertices      vertex count * size_of::<Vertex>() bytes, laid out exactly
+//                 like geometry::Vertex
+//   indices       index count * u32
+//
+// The data sections are cast straight to and from `Vertex` and `u32` with
+// bytemuck, so any change to Vertex's layout needs a version bump.
+const MAGIC: &[u8; 4] = b"MESH";
+const VERSION: u32 = 1;
+const HEADER_SIZE: usize = 16;
+
+pub struct MeshData {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+}
+
+impl MeshData {
+    // Radius of the smallest origin-centered sphere containing every vertex
+    pub fn bounding_radius(&self) -> f32 {
+        self.vertices
+            .iter()
+            .map(|v| {
+                let [x, y, z] = v.position;
+                (x * x + y * y + z * z).sqrt()
+            })
+            .fold(0.0, f32::max)
+    }
+}
+
+pub fn load_mesh(path: impl AsRef<Path>) -> Result<MeshData> {
+    let path = path.as_ref();
+    let bytes = std::fs::read(path)
+        .with_context(|| format!("Failed to read mesh {}", path.display()))?;
+    parse_mesh(&bytes).with_context(|| format!("Loading {}", path.display()))
+}
+
+pub fn parse_mesh(bytes: &[u8]) -> Result<MeshData> {
+    ensure!(
+        bytes.len() >= HEADER_SIZE,
+        "Expected at least a {} byte header, got {} bytes",
+        HEADER_SIZE,
+        bytes.len()
+    );
+    ensure!(
+        &bytes[0..4] == MAGIC,
+        "Not a .mesh file (magic was {:?}, expected {:?})",
+        &bytes[0..4],
+        MAGIC
+    );
+    let read_u32 = |offset: usize| {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    };
+    let version = read_u32(4);
+    ensure!(
+        version == VERSION,
+        "Unsupported .mesh version {} (expected {})",
+        version,
+        VERSION
+    );
+    let n_vertices = read_u32(8) as usize;
+    let n_indices = read_u32(12) as usize;
+
+    let vertices_end = HEADER_SIZE + n_vertices * std::mem::size_of::<Vertex>();
+    let indices_end = vertices_end + n_indices * std::mem::size_of::<u32>();
+    ensure!(
+        bytes.len() == indices_end,
+        "Header says {} vertices and {} indices ({} bytes) but the file is {} \
+         bytes",
+        n_vertices,
+        n_indices,
+        indices_end,
+        bytes.len()
+    );
+
+    // pod_collect_to_vec copies, so the file bytes don't need to be aligned
+    // for Vertex or u32
+    let vertices: Vec<Vertex> =
+        bytemuck::pod_collect_to_vec(&bytes[HEADER_SIZE..vertices_end]);
+    let indices: Vec<u32> =
+        bytemuck::pod_collect_to_vec(&bytes[vertices_end..indices_end]);
+
+    if let Some(index) = indices.iter().find(|&&i| i as usize >= n_vertices) {
+        bail!(
+            "Index {} is out of range for {} vertices",
+            index,
+            n_vertices
+        );
+    }
+
+    Ok(MeshData { vertices, indices })
+}
+
+pub fn write_mesh(
+    path: impl AsRef<Path>,
+    vertices: &[Vertex],
+    indices: &[u32],
+) -> Result<()> {
+    let path = path.as_ref();
+    let mut bytes = Vec::with_capacity(
+        HEADER_SIZE
+            + std::mem::size_of_val(vertices)
+            + std::mem::size_of_val(indices),
+    );
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&VERSION.to_le_bytes());
+    bytes.extend_from_slice(&(vertices.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(&(indices.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(bytemuck::cast_slice(vertices));
+    bytes.extend_from_slice(bytemuck::cast_slice(indices));
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Creating {}", dir.display()))?;
+    }
+    std::fs::write(path, bytes)
+        .with_context(|| format!("Failed to write mesh {}", path.display()))
+}
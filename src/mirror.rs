@@ -0,0 +1,217 @@
+use cgmath::InnerSpace;
+
+use crate::camera::{Camera, CameraUniform};
+use crate::texture;
+use crate::uniform::Uniform;
+
+// Size of the mirror's render target. Fixed rather than following the
+// window, so resizing never has to recreate it (or the sprite bind group
+// reading it); the quad it's shown on scales it to fit.
+pub const MIRROR_WIDTH: u32 = 384;
+pub const MIRROR_HEIGHT: u32 = 216;
+
+// Where the mirror's camera sits relative to the main one: this many times
+// as far from the target, and raised by this many times that distance
+const OVERVIEW_PULLBACK: f32 = 1.5;
+const OVERVIEW_RISE: f32 = 1.0;
+
+// The scene drawn a second time, into a texture of its own and from a
+// second camera, for showing on a quad over the main view like a rear-view
+// mirror. The camera hangs back and above the main one, looking down at the
+// same target, so it gives an overview of whatever the main view is close
+// in on. (Looking back from the far side would be more mirror-like, but the
+// pentagons are single sided and would be culled.)
+pub struct Mirror {
+    // What the quad samples. With MSAA the scene is drawn into `msaa_view`
+    // and resolved into this, the same as the main scene target.
+    texture: texture::Texture,
+    msaa_view: Option<wgpu::TextureView>,
+    depth_texture: texture::Texture,
+    camera: Camera,
+    camera_uniform: CameraUniform,
+    // Its bind group takes the place of the main camera's at group 1
+    camera_buffer: Uniform<CameraUniform>,
+}
+
+impl Mirror {
+    // `format`, `sample_count` and `depth_format` have to match the main
+    // scene's so the same pipelines can draw into both. `camera_layout` is
+    // the main camera's bind group layout.
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        depth_format: wgpu::TextureFormat,
+        camera_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let size = wgpu::Extent3d {
+            width: MIRROR_WIDTH,
+            height: MIRROR_HEIGHT,
+            depth_or_array_layers: 1,
+        };
+        let color_texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Mirror Texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view =
+            color_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Mirror Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+        let texture = texture::Texture {
+            texture: color_texture,
+            view,
+            sampler,
+        };
+
+        let (msaa_view, depth_texture) = Self::create_multisampled(
+            device,
+            format,
+            sample_count,
+            depth_format,
+        );
+
+        let camera = Camera::new(MIRROR_WIDTH as f32 / MIRROR_HEIGHT as f32);
+        let camera_uniform = CameraUniform::new();
+        let camera_buffer = Uniform::with_layout(
+            device,
+            "Mirror Camera",
+            camera_layout,
+            &camera_uniform,
+        );
+
+        Self {
+            texture,
+            msaa_view,
+            depth_texture,
+            camera,
+            camera_uniform,
+            camera_buffer,
+        }
+    }
+
+    // The targets that have `sample_count` samples: the color one drawn
+    // into when multisampling, and the depth one
+    fn create_multisampled(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        depth_format: wgpu::TextureFormat,
+    ) -> (Option<wgpu::TextureView>, texture::Texture) {
+        let msaa_view = (sample_count > 1).then(|| {
+            device
+                .create_texture(&wgpu::TextureDescriptor {
+                    label: Some("Mirror Multisampled Framebuffer"),
+                    size: wgpu::Extent3d {
+                        width: MIRROR_WIDTH,
+                        height: MIRROR_HEIGHT,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count,
+                    dimension: wgpu::TextureDimension::D2,
+                    format,
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                    view_formats: &[],
+                })
+                .create_view(&wgpu::TextureViewDescriptor::default())
+        });
+
+        let depth_texture = texture::Texture::create_depth_texture(
+            device,
+            &wgpu::SurfaceConfiguration {
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                format,
+                width: MIRROR_WIDTH,
+                height: MIRROR_HEIGHT,
+                present_mode: wgpu::PresentMode::Fifo,
+                desired_maximum_frame_latency: 2,
+                alpha_mode: wgpu::CompositeAlphaMode::Auto,
+                view_formats: vec![],
+            },
+            sample_count,
+            depth_format,
+            false,
+            "Mirror Depth Texture",
+        );
+        (msaa_view, depth_texture)
+    }
+
+    // Follows the main scene to a new sample count. The texture the quad
+    // samples stays the same, so its bind group does too.
+    pub fn set_sample_count(
+        &mut self,
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        depth_format: wgpu::TextureFormat,
+    ) {
+        (self.msaa_view, self.depth_texture) = Self::create_multisampled(
+            device,
+            format,
+            sample_count,
+            depth_format,
+        );
+    }
+
+    pub fn texture(&self) -> &texture::Texture {
+        &self.texture
+    }
+
+    pub fn camera_bind_group(&self) -> &wgpu::BindGroup {
+        self.camera_buffer.bind_group()
+    }
+
+    pub fn depth_view(&self) -> &wgpu::TextureView {
+        &self.depth_texture.view
+    }
+
+    // The view to draw into and the one to resolve into, if any, in the
+    // same shape as the main scene pass's
+    pub fn color_views(
+        &self,
+    ) -> (&wgpu::TextureView, Option<&wgpu::TextureView>) {
+        match &self.msaa_view {
+            Some(msaa_view) => (msaa_view, Some(&self.texture.view)),
+            None => (&self.texture.view, None),
+        }
+    }
+
+    // Moves the mirror's camera to follow `main`, keeping its target
+    pub fn update(&mut self, queue: &wgpu::Queue, main: &Camera) {
+        let offset = main.eye - main.target;
+        let distance = offset.magnitude();
+        let eye = main.target
+            + offset * OVERVIEW_PULLBACK
+            + cgmath::Vector3::unit_y() * distance * OVERVIEW_RISE;
+        self.camera.target = main.target;
+        self.camera.eye = eye;
+        // look_at breaks down when looking straight along `up`
+        self.camera.up = if (eye - main.target).normalize().y.abs() > 0.99 {
+            cgmath::Vector3::unit_z()
+        } else {
+            cgmath::Vector3::unit_y()
+        };
+        self.camera.fovy = main.fovy;
+
+        self.camera_uniform.update_view_proj(
+            self.camera.build_view_projection_matrix(),
+            self.camera.eye,
+        );
+        self.camera_buffer.update(queue, &self.camera_uniform);
+    }
+}
@@ -0,0 +1,347 @@
+use std::ops::Range;
+use std::path::Path;
+
+use anyhow::*;
+use cgmath::{InnerSpace, Matrix4, SquareMatrix, Vector2, Vector3};
+use image::{DynamicImage, ImageBuffer};
+
+use crate::geometry::Vertex;
+use crate::mesh_file::MeshData;
+
+// A glTF 2.0 file (a .gltf with its buffers and images next to it, or a
+// self-contained .glb) read into vertex and index data ready for
+// geometry::Mesh. Static geometry only: animations, skins and morph targets
+// are ignored, and of each material only the base color is used.
+pub struct Model {
+    // One per glTF primitive rather than per glTF mesh, since each
+    // primitive can have a material of its own
+    pub meshes: Vec<ModelMesh>,
+    pub materials: Vec<ModelMaterial>,
+    // Every place a mesh is drawn in the file's scene. A mesh used by more
+    // than one node shows up once for each.
+    pub instances: Vec<ModelInstance>,
+}
+
+pub struct ModelMesh {
+    pub name: String,
+    pub data: MeshData,
+    // Index into `materials`. None is glTF's default material, plain white.
+    pub material: Option<usize>,
+}
+
+pub struct ModelMaterial {
+    pub name: String,
+    // The base color factor is multiplied into the vertex colors instead,
+    // which shader.wgsl multiplies with the texture anyway
+    pub base_color_texture: Option<image::RgbaImage>,
+}
+
+pub struct ModelInstance {
+    // Index into `meshes`
+    pub mesh: usize,
+    // The node's transform and all of its parents', flattened into one
+    // model matrix
+    pub transform: Matrix4<f32>,
+}
+
+pub fn load_gltf(path: impl AsRef<Path>) -> Result<Model> {
+    let path = path.as_ref();
+    let (document, buffers, images) = gltf::import(path)
+        .with_context(|| format!("Failed to load glTF {}", path.display()))?;
+
+    let materials = document
+        .materials()
+        .map(|material| {
+            let name = material.name().unwrap_or("Material").to_string();
+            let base_color_texture = material
+                .pbr_metallic_roughness()
+                .base_color_texture()
+                .map(|info| to_rgba(&images[info.texture().source().index()]))
+                .transpose()
+                .with_context(|| format!("Reading material {}", name))?;
+            Ok(ModelMaterial {
+                name,
+                base_color_texture,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    // Which of `meshes` each glTF mesh's primitives ended up as
+    let mut primitive_ranges: Vec<Range<usize>> = Vec::new();
+    let mut meshes = Vec::new();
+    for mesh in document.meshes() {
+        let start = meshes.len();
+        let mesh_name = mesh.name().unwrap_or("Mesh");
+        for primitive in mesh.primitives() {
+            let name = format!("{} {}", mesh_name, primitive.index());
+            if primitive.mode() != gltf::mesh::Mode::Triangles {
+                log::warn!(
+                    "Skipping {}, drawn as {:?} rather than triangles",
+                    name,
+                    primitive.mode()
+                );
+                continue;
+            }
+            let data = read_primitive(&primitive, &buffers)
+                .with_context(|| format!("Reading {}", name))?;
+            meshes.push(ModelMesh {
+                name,
+                data,
+                material: primitive.material().index(),
+            });
+        }
+        primitive_ranges.push(start..meshes.len());
+    }
+
+    let scene = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+        .with_context(|| format!("{} has no scenes", path.display()))?;
+    let mut instances = Vec::new();
+    for node in scene.nodes() {
+        flatten_node(
+            &node,
+            Matrix4::identity(),
+            &primitive_ranges,
+            &mut instances,
+        );
+    }
+    ensure!(
+        !instances.is_empty(),
+        "{} has nothing to draw in its scene",
+        path.display()
+    );
+
+    Ok(Model {
+        meshes,
+        materials,
+        instances,
+    })
+}
+
+fn flatten_node(
+    node: &gltf::Node,
+    parent: Matrix4<f32>,
+    primitive_ranges: &[Range<usize>],
+    instances: &mut Vec<ModelInstance>,
+) {
+    let transform = parent * Matrix4::from(node.transform().matrix());
+    if let Some(mesh) = node.mesh() {
+        instances.extend(
+            primitive_ranges[mesh.index()]
+                .clone()
+                .map(|mesh| ModelInstance { mesh, transform }),
+        );
+    }
+    for child in node.children() {
+        flatten_node(&child, transform, primitive_ranges, instances);
+    }
+}
+
+fn read_primitive(
+    primitive: &gltf::Primitive,
+    buffers: &[gltf::buffer::Data],
+) -> Result<MeshData> {
+    let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+    let positions: Vec<[f32; 3]> = reader
+        .read_positions()
+        .context("No vertex positions")?
+        .collect();
+    let n_vertices = positions.len();
+
+    let indices: Vec<u32> = match reader.read_indices() {
+        Some(indices) => indices.into_u32().collect(),
+        None => (0..n_vertices as u32).collect(),
+    };
+    ensure!(
+        indices.len().is_multiple_of(3),
+        "{} indices don't make whole triangles",
+        indices.len()
+    );
+    if let Some(index) = indices.iter().find(|&&i| i as usize >= n_vertices) {
+        bail!(
+            "Index {} is out of range for {} vertices",
+            index,
+            n_vertices
+        );
+    }
+
+    // The default material's base color is white, so leaving the material
+    // out looks the same as having none
+    let pbr = primitive.material().pbr_metallic_roughness();
+    let tex_coord_set =
+        pbr.base_color_texture().map_or(0, |info| info.tex_coord());
+    let [r, g, b, _] = pbr.base_color_factor();
+
+    let tex_coords: Vec<[f32; 2]> = match reader.read_tex_coords(tex_coord_set)
+    {
+        Some(tex_coords) => tex_coords.into_f32().collect(),
+        None => vec![[0.0; 2]; n_vertices],
+    };
+    let colors: Vec<[f32; 3]> = match reader.read_colors(0) {
+        Some(colors) => colors
+            .into_rgb_f32()
+            .map(|[cr, cg, cb]| [cr * r, cg * g, cb * b])
+            .collect(),
+        None => vec![[r, g, b]; n_vertices],
+    };
+    let normals: Vec<[f32; 3]> = match reader.read_normals() {
+        Some(normals) => normals.collect(),
+        None => vertex_normals(&positions, &indices),
+    };
+    ensure!(
+        [tex_coords.len(), colors.len(), normals.len()]
+            .iter()
+            .all(|&len| len == n_vertices),
+        "Attributes have different numbers of vertices"
+    );
+
+    let tangent_frames = match reader.read_tangents() {
+        Some(tangents) => tangents
+            .zip(&normals)
+            .map(|([x, y, z, w], &normal)| {
+                let tangent = Vector3::new(x, y, z);
+                let bitangent = Vector3::from(normal).cross(tangent) * w;
+                (tangent.into(), bitangent.into())
+            })
+            .collect(),
+        None => tangent_frames(&positions, &tex_coords, &normals, &indices),
+    };
+    ensure!(
+        tangent_frames.len() == n_vertices,
+        "Attributes have different numbers of vertices"
+    );
+
+    let vertices = (0..n_vertices)
+        .map(|i| Vertex {
+            position: positions[i],
+            tex_coords: tex_coords[i],
+            normal: normals[i],
+            tangent: tangent_frames[i].0,
+            bitangent: tangent_frames[i].1,
+            color: colors[i],
+        })
+        .collect();
+
+    Ok(MeshData { vertices, indices })
+}
+
+// What glTF asks for when a primitive has no normals is flat shading.
+// Averaging the faces around each vertex smooths over shared vertices
+// instead, but matches wherever the vertices aren't shared.
+fn vertex_normals(positions: &[[f32; 3]], indices: &[u32]) -> Vec<[f32; 3]> {
+    let mut sums = vec![Vector3::new(0.0, 0.0, 0.0); positions.len()];
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] =
+            [0, 1, 2].map(|i| Vector3::from(positions[triangle[i] as usize]));
+        // Not normalized, so bigger faces count for more
+        let face_normal = (b - a).cross(c - a);
+        for &i in triangle {
+            sums[i as usize] += face_normal;
+        }
+    }
+    sums.into_iter()
+        .map(|sum| {
+            if sum.magnitude2() > 0.0 {
+                sum.normalize().into()
+            } else {
+                [0.0, 0.0, 1.0]
+            }
+        })
+        .collect()
+}
+
+// Tangent and bitangent per vertex from how the UVs run across each face
+// touching it, made perpendicular to the normal. Vertices with no usable
+// UVs get any frame around the normal.
+fn tangent_frames(
+    positions: &[[f32; 3]],
+    tex_coords: &[[f32; 2]],
+    normals: &[[f32; 3]],
+    indices: &[u32],
+) -> Vec<([f32; 3], [f32; 3])> {
+    let zero = Vector3::new(0.0, 0.0, 0.0);
+    let mut sums = vec![(zero, zero); positions.len()];
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| triangle[i] as usize);
+        let edge1 = Vector3::from(positions[b]) - Vector3::from(positions[a]);
+        let edge2 = Vector3::from(positions[c]) - Vector3::from(positions[a]);
+        let duv1 = Vector2::from(tex_coords[b]) - Vector2::from(tex_coords[a]);
+        let duv2 = Vector2::from(tex_coords[c]) - Vector2::from(tex_coords[a]);
+        let det = duv1.x * duv2.y - duv2.x * duv1.y;
+        if det.abs() < f32::EPSILON {
+            continue;
+        }
+        let tangent = (edge1 * duv2.y - edge2 * duv1.y) / det;
+        let bitangent = (edge2 * duv1.x - edge1 * duv2.x) / det;
+        for i in [a, b, c] {
+            sums[i].0 += tangent;
+            sums[i].1 += bitangent;
+        }
+    }
+
+    sums.into_iter()
+        .zip(normals)
+        .map(|((tangent, bitangent), &normal)| {
+            let normal = Vector3::from(normal);
+            // Gram-Schmidt: drop whatever part of the tangent lies along
+            // the normal
+            let mut tangent = tangent - normal * normal.dot(tangent);
+            if tangent.magnitude2() < f32::EPSILON {
+                let axis = if normal.x.abs() < 0.9 {
+                    Vector3::unit_x()
+                } else {
+                    Vector3::unit_y()
+                };
+                tangent = axis - normal * normal.dot(axis);
+            }
+            let tangent = tangent.normalize();
+            // Mirrored UVs flip the bitangent
+            let handedness = if normal.cross(tangent).dot(bitangent) < 0.0 {
+                -1.0
+            } else {
+                1.0
+            };
+            let bitangent = normal.cross(tangent) * handedness;
+            (tangent.into(), bitangent.into())
+        })
+        .collect()
+}
+
+// glTF hands images over decoded, in whichever of its formats matches the
+// file. Everything is converted to RGBA8 for Texture::from_rgba.
+fn to_rgba(image: &gltf::image::Data) -> Result<image::RgbaImage> {
+    use gltf::image::Format;
+
+    let (width, height) = (image.width, image.height);
+    let bytes = || image.pixels.clone();
+    let wide = || bytemuck::pod_collect_to_vec::<u8, u16>(&image.pixels);
+    let float = || bytemuck::pod_collect_to_vec::<u8, f32>(&image.pixels);
+    let decoded = match image.format {
+        Format::R8 => ImageBuffer::from_raw(width, height, bytes())
+            .map(DynamicImage::ImageLuma8),
+        Format::R8G8 => ImageBuffer::from_raw(width, height, bytes())
+            .map(DynamicImage::ImageLumaA8),
+        Format::R8G8B8 => ImageBuffer::from_raw(width, height, bytes())
+            .map(DynamicImage::ImageRgb8),
+        Format::R8G8B8A8 => ImageBuffer::from_raw(width, height, bytes())
+            .map(DynamicImage::ImageRgba8),
+        Format::R16 => ImageBuffer::from_raw(width, height, wide())
+            .map(DynamicImage::ImageLuma16),
+        Format::R16G16 => ImageBuffer::from_raw(width, height, wide())
+            .map(DynamicImage::ImageLumaA16),
+        Format::R16G16B16 => ImageBuffer::from_raw(width, height, wide())
+            .map(DynamicImage::ImageRgb16),
+        Format::R16G16B16A16 => ImageBuffer::from_raw(width, height, wide())
+            .map(DynamicImage::ImageRgba16),
+        Format::R32G32B32FLOAT => ImageBuffer::from_raw(width, height, float())
+            .map(DynamicImage::ImageRgb32F),
+        Format::R32G32B32A32FLOAT => {
+            ImageBuffer::from_raw(width, height, float())
+                .map(DynamicImage::ImageRgba32F)
+        }
+    };
+    decoded
+        .map(|image| image.to_rgba8())
+        .context("Image data is the wrong size for its dimensions")
+}
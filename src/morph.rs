@@ -0,0 +1,144 @@
+use cgmath::{InnerSpace, Vector2};
+
+use crate::geometry::{Vertex, VERTICES};
+
+// Radians per second of the cycle from pentagon to star and back, which
+// takes 2π / MORPH_SPEED seconds
+const MORPH_SPEED: f32 = 1.5;
+// How far in the star's inner corners sit, as a fraction of the distance
+// to the outer ones. 0.382 makes a regular pentagram.
+const STAR_INNER_RADIUS: f32 = 0.382;
+
+// Vertex animation done on the CPU: every frame the morph targets (shapes
+// with the same number of vertices, in the same order) are blended into one
+// set of vertices that gets uploaded over the mesh's vertex buffer. Simple
+// and flexible, at the cost of re-sending every vertex each frame; a vertex
+// shader blending targets stored on the GPU scales better.
+pub struct Morph {
+    morph_targets: Vec<Vec<Vertex>>,
+    // Reused every frame
+    blended: Vec<Vertex>,
+}
+
+impl Morph {
+    // Panics if there are no targets or they don't all have the same number
+    // of vertices
+    pub fn new(morph_targets: Vec<Vec<Vertex>>) -> Self {
+        let n_vertices = morph_targets.first().map(Vec::len);
+        assert!(
+            n_vertices.is_some()
+                && morph_targets
+                    .iter()
+                    .all(|target| Some(target.len()) == n_vertices),
+            "Morph targets need the same number of vertices"
+        );
+        let blended = morph_targets[0].clone();
+        Self {
+            morph_targets,
+            blended,
+        }
+    }
+
+    // The vertices at `time` seconds, easing from the first target through
+    // each of the others to the last and then back again
+    pub fn blend(&mut self, time: f32) -> &[Vertex] {
+        let last = (self.morph_targets.len() - 1) as f32;
+        let position = (1.0 - (time * MORPH_SPEED).cos()) * 0.5 * last;
+        let from =
+            (position.floor() as usize).min(self.morph_targets.len() - 1);
+        let to = (from + 1).min(self.morph_targets.len() - 1);
+        let t = position - from as f32;
+
+        for ((out, a), b) in self
+            .blended
+            .iter_mut()
+            .zip(&self.morph_targets[from])
+            .zip(&self.morph_targets[to])
+        {
+            *out = lerp_vertex(a, b, t);
+        }
+        &self.blended
+    }
+}
+
+fn lerp_vertex(a: &Vertex, b: &Vertex, t: f32) -> Vertex {
+    fn lerp<const N: usize>(a: [f32; N], b: [f32; N], t: f32) -> [f32; N] {
+        std::array::from_fn(|i| a[i] + (b[i] - a[i]) * t)
+    }
+    Vertex {
+        position: lerp(a.position, b.position, t),
+        tex_coords: lerp(a.tex_coords, b.tex_coords, t),
+        normal: lerp(a.normal, b.normal, t),
+        tangent: lerp(a.tangent, b.tangent, t),
+        bitangent: lerp(a.bitangent, b.bitangent, t),
+        color: lerp(a.color, b.color, t),
+    }
+}
+
+// The built-in pentagon and a five pointed star with the same outer corners,
+// as two morph targets sharing one set of indices. The pentagon alone only
+// has its five corners, so both are built as a fan around the center with a
+// rim of ten vertices: the corners, and between them a point that sits on
+// the pentagon's edge in one and is pulled in towards the center in the
+// other.
+pub fn pentagon_star() -> (Vec<Vec<Vertex>>, Vec<u32>) {
+    let corners: Vec<Vector2<f32>> = VERTICES
+        .iter()
+        .map(|v| Vector2::new(v.position[0], v.position[1]))
+        .collect();
+    let center = corners
+        .iter()
+        .fold(Vector2::new(0.0, 0.0), |sum, &c| sum + c)
+        / 5.0;
+    let outer_radius = corners
+        .iter()
+        .map(|&c| (c - center).magnitude())
+        .sum::<f32>()
+        / 5.0;
+
+    let target = |inner: &dyn Fn(Vector2<f32>) -> Vector2<f32>| {
+        let mut vertices =
+            vec![vertex(center, average_color(&[0, 1, 2, 3, 4]))];
+        for i in 0..5 {
+            let next = (i + 1) % 5;
+            vertices.push(vertex(corners[i], VERTICES[i].color));
+            let midpoint = (corners[i] + corners[next]) * 0.5;
+            vertices.push(vertex(inner(midpoint), average_color(&[i, next])));
+        }
+        vertices
+    };
+    let pentagon = target(&|midpoint| midpoint);
+    let star = target(&|midpoint| {
+        center
+            + (midpoint - center).normalize() * outer_radius * STAR_INNER_RADIUS
+    });
+
+    // Counter-clockwise like the pentagon's corners
+    let indices = (0..10u32)
+        .flat_map(|i| [0, i + 1, (i + 1) % 10 + 1])
+        .collect();
+
+    (vec![pentagon, star], indices)
+}
+
+// Flat in XY facing +Z like the pentagon, with UVs following x and y
+fn vertex(position: Vector2<f32>, color: [f32; 3]) -> Vertex {
+    Vertex {
+        position: [position.x, position.y, 0.0],
+        tex_coords: [position.x + 0.5, position.y + 0.5],
+        normal: [0.0, 0.0, 1.0],
+        tangent: [1.0, 0.0, 0.0],
+        bitangent: [0.0, 1.0, 0.0],
+        color,
+    }
+}
+
+fn average_color(corners: &[usize]) -> [f32; 3] {
+    let mut sum = [0.0; 3];
+    for &i in corners {
+        for (s, c) in sum.iter_mut().zip(VERTICES[i].color) {
+            *s += c;
+        }
+    }
+    sum.map(|s| s / corners.len() as f32)
+}
@@ -0,0 +1,78 @@
+// Draws world space vertex normals as colors, each axis from -1..1 to
+// 0..1: +X red, +Y green, +Z blue. Only the interpolated vertex normal, so
+// it shows what the mesh (or its importer) says before any normal map or
+// lighting gets involved. N shows the normal mapped ones in shader.wgsl.
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(2) normal: vec3<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) normal: vec3<f32>,
+};
+
+// see: camera::CameraUniform
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+    inv_view_proj: mat4x4<f32>,
+    view_position: vec4<f32>,
+}
+
+@group(1) @binding(0)
+var<uniform> camera: CameraUniform;
+
+// see: objects::ObjectUniform
+struct ObjectUniform {
+    model: mat4x4<f32>,
+    tint: vec4<f32>,
+}
+
+@group(3) @binding(0)
+var<uniform> object: ObjectUniform;
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.normal = model.normal;
+    out.clip_position = camera.view_proj * vec4<f32>(model.position, 1.0);
+    return out;
+}
+
+// vs_main with the current object's model matrix applied. Objects are only
+// ever translated and rotated, see vs_object in shader.wgsl.
+@vertex
+fn vs_object(
+    model: VertexInput,
+) -> VertexOutput {
+    let rotation = mat3x3<f32>(
+        object.model[0].xyz,
+        object.model[1].xyz,
+        object.model[2].xyz,
+    );
+    var out: VertexOutput;
+    out.normal = rotation * model.normal;
+    out.clip_position =
+        camera.view_proj * object.model * vec4<f32>(model.position, 1.0);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return shade(in);
+}
+
+// Untinted, unlike fs_object in shader.wgsl: a tint would change what the
+// colors say
+@fragment
+fn fs_object(in: VertexOutput) -> @location(0) vec4<f32> {
+    return shade(in);
+}
+
+fn shade(in: VertexOutput) -> vec4<f32> {
+    // Interpolation denormalizes it
+    return vec4<f32>(normalize(in.normal) * 0.5 + 0.5, 1.0);
+}
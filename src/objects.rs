@@ -0,0 +1,146 @@
+use cgmath::Matrix4;
+
+use crate::geometry::Mesh;
+use crate::indirect::IndirectDraws;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct ObjectUniform {
+    model: [[f32; 4]; 4],
+    // Only read when push constants aren't available, see draw_constants
+    tint: [f32; 4],
+}
+
+// One model matrix per object, each in its own slot of a single uniform
+// buffer. A draw picks its slot with a dynamic offset when the bind group is
+// set, so objects can be drawn one call at a time with different transforms
+// and no extra bind groups or buffer writes in between.
+//
+// Dynamic offsets have to be multiples of the device's
+// `min_uniform_buffer_offset_alignment` (usually 256 bytes), so each slot is
+// padded out to that even though a slot is only 80 bytes.
+pub struct ObjectUniforms {
+    buffer: wgpu::Buffer,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    // Bytes between the start of one slot and the next
+    stride: wgpu::BufferAddress,
+    capacity: usize,
+    // Staging for `set_models`, kept to avoid reallocating every frame
+    bytes: Vec<u8>,
+}
+
+impl ObjectUniforms {
+    pub fn new(device: &wgpu::Device, capacity: usize) -> Self {
+        let size = std::mem::size_of::<ObjectUniform>() as wgpu::BufferAddress;
+        let alignment = device.limits().min_uniform_buffer_offset_alignment
+            as wgpu::BufferAddress;
+        let stride = wgpu::util::align_to(size, alignment);
+
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Object Uniform Buffer"),
+            size: stride * capacity as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX
+                        | wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: true,
+                        min_binding_size: wgpu::BufferSize::new(size),
+                    },
+                    count: None,
+                }],
+                label: Some("Object Bind Group Layout"),
+            });
+
+        // The binding only covers one slot, the offset picks which
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: &buffer,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(size),
+                }),
+            }],
+            label: Some("Object Bind Group"),
+        });
+
+        Self {
+            buffer,
+            bind_group_layout,
+            bind_group,
+            stride,
+            capacity,
+            bytes: vec![0; (stride * capacity as wgpu::BufferAddress) as usize],
+        }
+    }
+
+    pub fn bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layout
+    }
+
+    // Writes `models` and `tints` (one per model) into the first slots in
+    // one go. Panics if there are more than the buffer was created for.
+    pub fn set_models(
+        &mut self,
+        queue: &wgpu::Queue,
+        models: &[Matrix4<f32>],
+        tints: &[[f32; 4]],
+    ) {
+        debug_assert_eq!(models.len(), tints.len());
+        assert!(
+            models.len() <= self.capacity,
+            "{} objects but room for {}",
+            models.len(),
+            self.capacity
+        );
+        for (i, (model, &tint)) in models.iter().zip(tints).enumerate() {
+            let uniform = ObjectUniform {
+                model: (*model).into(),
+                tint,
+            };
+            let start = i * self.stride as usize;
+            let bytes = bytemuck::bytes_of(&uniform);
+            self.bytes[start..start + bytes.len()].copy_from_slice(bytes);
+        }
+        let len = models.len() * self.stride as usize;
+        queue.write_buffer(&self.buffer, 0, &self.bytes[..len]);
+    }
+
+    // Binds object `index`'s matrix to `group` and draws `mesh` with it,
+    // taking the index count from slot `index` of `indirect`. The pipeline
+    // and every other bind group are left to the caller.
+    pub fn draw_object(
+        &self,
+        render_pass: &mut wgpu::RenderPass,
+        group: u32,
+        index: usize,
+        mesh: &Mesh,
+        indirect: &IndirectDraws,
+    ) {
+        self.bind_object(render_pass, group, index);
+        indirect.draw(render_pass, index, mesh);
+    }
+
+    // Only binds object `index`'s matrix to `group`, for drawing it some
+    // other way
+    pub fn bind_object(
+        &self,
+        render_pass: &mut wgpu::RenderPass,
+        group: u32,
+        index: usize,
+    ) {
+        debug_assert!(index < self.capacity);
+        let offset = (index as wgpu::BufferAddress * self.stride) as u32;
+        render_pass.set_bind_group(group, &self.bind_group, &[offset]);
+    }
+}
@@ -0,0 +1,197 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+
+// Written from the map_async callback
+const MAP_PENDING: u8 = 0;
+const MAP_OK: u8 = 1;
+const MAP_FAILED: u8 = 2;
+
+// Frames of results that can be waiting on the GPU at once, as in gpu_timer
+const N_SLOTS: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SlotState {
+    Idle,
+    // Resolved results are being copied in but haven't been submitted yet
+    Copying,
+    Mapping,
+}
+
+struct Slot {
+    buffer: wgpu::Buffer,
+    state: SlotState,
+    map_status: Arc<AtomicU8>,
+    // How many of the queries were resolved into `buffer`
+    n_queries: u32,
+}
+
+// Counts how many samples of each entity pass the depth test, for seeing
+// how much of what's drawn actually ends up on screen. Every draw in the
+// scene pass is wrapped in an occlusion query of its own, indexed by entity,
+// and the counts come back a frame or two later the same way gpu_timer's
+// timestamps do.
+//
+// The GL backend only reports whether any sample passed, so there the
+// counts are all 0 or 1.
+pub struct OcclusionQueries {
+    query_set: wgpu::QuerySet,
+    capacity: u32,
+    resolve_buffer: wgpu::Buffer,
+    slots: Vec<Slot>,
+    next: usize,
+    // Samples passed per entity, from the newest frame that's come back
+    counts: Vec<u64>,
+}
+
+impl OcclusionQueries {
+    // Room for `capacity` entities. None if the device can't create
+    // occlusion query sets.
+    pub fn new(device: &wgpu::Device, capacity: u32) -> Option<Self> {
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("Occlusion Query Set"),
+            ty: wgpu::QueryType::Occlusion,
+            count: capacity,
+        });
+        if let Some(e) = pollster::block_on(device.pop_error_scope()) {
+            log::info!("Occlusion queries not supported: {}", e);
+            return None;
+        }
+
+        let size = capacity as wgpu::BufferAddress
+            * wgpu::QUERY_SIZE as wgpu::BufferAddress;
+        let resolve_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Occlusion Resolve Buffer"),
+            size,
+            usage: wgpu::BufferUsages::QUERY_RESOLVE
+                | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        let slots = (0..N_SLOTS)
+            .map(|_| Slot {
+                buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Occlusion Staging Buffer"),
+                    size,
+                    usage: wgpu::BufferUsages::COPY_DST
+                        | wgpu::BufferUsages::MAP_READ,
+                    mapped_at_creation: false,
+                }),
+                state: SlotState::Idle,
+                map_status: Arc::new(AtomicU8::new(MAP_PENDING)),
+                n_queries: 0,
+            })
+            .collect();
+
+        Some(Self {
+            query_set,
+            capacity,
+            resolve_buffer,
+            slots,
+            next: 0,
+            counts: Vec::new(),
+        })
+    }
+
+    // Goes in the descriptor of the pass the queries are made in. Query `i`
+    // is entity `i`'s.
+    pub fn query_set(&self) -> &wgpu::QuerySet {
+        &self.query_set
+    }
+
+    // Call after the pass has been recorded, with how many entities it had.
+    // Every one of those queries has to have been written, even if only by
+    // an empty begin and end. Skips the frame if every staging buffer is
+    // still in flight.
+    pub fn resolve(&mut self, encoder: &mut wgpu::CommandEncoder, n: u32) {
+        let n_queries = n.min(self.capacity);
+        if n_queries == 0 {
+            return;
+        }
+        let Some(index) = (0..N_SLOTS)
+            .map(|i| (self.next + i) % N_SLOTS)
+            .find(|&i| self.slots[i].state == SlotState::Idle)
+        else {
+            return;
+        };
+        self.next = (index + 1) % N_SLOTS;
+
+        encoder.resolve_query_set(
+            &self.query_set,
+            0..n_queries,
+            &self.resolve_buffer,
+            0,
+        );
+        let slot = &mut self.slots[index];
+        encoder.copy_buffer_to_buffer(
+            &self.resolve_buffer,
+            0,
+            &slot.buffer,
+            0,
+            n_queries as wgpu::BufferAddress
+                * wgpu::QUERY_SIZE as wgpu::BufferAddress,
+        );
+        slot.state = SlotState::Copying;
+        slot.n_queries = n_queries;
+    }
+
+    // Call after the encoder passed to `resolve` has been submitted
+    pub fn after_submit(&mut self) {
+        for slot in &mut self.slots {
+            if slot.state != SlotState::Copying {
+                continue;
+            }
+            let map_status = slot.map_status.clone();
+            map_status.store(MAP_PENDING, Ordering::Release);
+            slot.buffer.slice(..).map_async(
+                wgpu::MapMode::Read,
+                move |result| {
+                    let status = match result {
+                        Ok(()) => MAP_OK,
+                        Err(e) => {
+                            log::error!("Occlusion query map failed: {:?}", e);
+                            MAP_FAILED
+                        }
+                    };
+                    map_status.store(status, Ordering::Release);
+                },
+            );
+            slot.state = SlotState::Mapping;
+        }
+    }
+
+    // Picks up any results that have come back, without waiting
+    pub fn poll(&mut self, device: &wgpu::Device) {
+        device.poll(wgpu::Maintain::Poll);
+
+        for slot in &mut self.slots {
+            if slot.state != SlotState::Mapping {
+                continue;
+            }
+            match slot.map_status.load(Ordering::Acquire) {
+                MAP_OK => {}
+                MAP_FAILED => {
+                    slot.state = SlotState::Idle;
+                    continue;
+                }
+                _ => continue,
+            }
+
+            {
+                let data = slot.buffer.slice(..).get_mapped_range();
+                let counts: &[u64] = bytemuck::cast_slice(&data);
+                self.counts.clear();
+                self.counts
+                    .extend_from_slice(&counts[..slot.n_queries as usize]);
+            }
+            slot.buffer.unmap();
+            slot.state = SlotState::Idle;
+        }
+    }
+
+    // Samples that passed per entity in the newest results. Culled entities
+    // count 0.
+    pub fn counts(&self) -> &[u64] {
+        &self.counts
+    }
+}
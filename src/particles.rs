@@ -0,0 +1,309 @@
+use wgpu::util::DeviceExt;
+
+const WORKGROUP_SIZE: u32 = 64;
+
+// Seconds, matching MAX_LIFE in particles.wgsl
+const MAX_LIFE: f32 = 2.0;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct Particle {
+    // NDC
+    pos: [f32; 2],
+    vel: [f32; 2],
+    // Seconds left. Respawns at the emitter once it runs out.
+    life: f32,
+    // WGSL rounds the struct up to its 8 byte alignment
+    _padding: f32,
+}
+
+impl Particle {
+    // Only pos and life are read when drawing, vel is skipped over
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Particle>()
+                as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 4]>()
+                        as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32,
+                },
+            ],
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct ParticleUniform {
+    emitter: [f32; 2],
+    resolution: [f32; 2],
+    dt: f32,
+    time: f32,
+    count: u32,
+    _padding: u32,
+}
+
+// Particles simulated entirely on the GPU. A compute pass steps every
+// particle in a storage buffer, respawning the dead ones at the emitter, and
+// the same buffer is then read as per-instance vertex data to draw each one
+// as a small additive quad in screen space.
+pub struct ParticleSystem {
+    n_particles: u32,
+    // STORAGE for the compute pass, VERTEX for drawing
+    particle_buffer: wgpu::Buffer,
+    uniform_buffer: wgpu::Buffer,
+    compute_pipeline: wgpu::ComputePipeline,
+    compute_bind_group: wgpu::BindGroup,
+    render_pipeline: wgpu::RenderPipeline,
+    // Just the uniforms. A pass can't bind the particle buffer as writable
+    // storage while also reading it as a vertex buffer.
+    render_bind_group: wgpu::BindGroup,
+    emitter: [f32; 2],
+    // Seconds simulated so far, seeding where particles respawn
+    time: f32,
+}
+
+impl ParticleSystem {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        n_particles: u32,
+    ) -> Self {
+        // Parked off screen with staggered lives so they trickle out of the
+        // emitter instead of all bursting out on the first frame
+        let particles: Vec<Particle> = (0..n_particles)
+            .map(|i| Particle {
+                pos: [10.0, 10.0],
+                vel: [0.0, 0.0],
+                life: MAX_LIFE * i as f32 / n_particles as f32,
+                _padding: 0.0,
+            })
+            .collect();
+
+        let particle_buffer =
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Particle Buffer"),
+                contents: bytemuck::cast_slice(&particles),
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::VERTEX,
+            });
+
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Particle Uniform Buffer"),
+            size: std::mem::size_of::<ParticleUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let uniform_entry = |visibility| wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+
+        let compute_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Particle Compute Bind Group Layout"),
+                entries: &[
+                    uniform_entry(wgpu::ShaderStages::COMPUTE),
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage {
+                                read_only: false,
+                            },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+
+        let render_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Particle Render Bind Group Layout"),
+                entries: &[uniform_entry(wgpu::ShaderStages::VERTEX)],
+            });
+
+        let compute_bind_group =
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Particle Compute Bind Group"),
+                layout: &compute_bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: uniform_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: particle_buffer.as_entire_binding(),
+                    },
+                ],
+            });
+
+        let render_bind_group =
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Particle Render Bind Group"),
+                layout: &render_bind_group_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                }],
+            });
+
+        let shader =
+            device.create_shader_module(wgpu::include_wgsl!("particles.wgsl"));
+
+        let compute_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Particle Compute Pipeline Layout"),
+                bind_group_layouts: &[&compute_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        let compute_pipeline =
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("Particle Compute Pipeline"),
+                layout: Some(&compute_pipeline_layout),
+                module: &shader,
+                entry_point: Some("cs_main"),
+                compilation_options: wgpu::PipelineCompilationOptions::default(
+                ),
+                cache: None,
+            });
+
+        let render_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Particle Render Pipeline Layout"),
+                bind_group_layouts: &[&render_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        // Additive, so overlapping particles glow rather than cover each
+        // other and draw order doesn't matter
+        let additive = wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::One,
+            dst_factor: wgpu::BlendFactor::One,
+            operation: wgpu::BlendOperation::Add,
+        };
+
+        let render_pipeline =
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Particle Render Pipeline"),
+                layout: Some(&render_pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[Particle::desc()],
+                    compilation_options:
+                        wgpu::PipelineCompilationOptions::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some("fs_main"),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format,
+                        blend: Some(wgpu::BlendState {
+                            color: additive,
+                            alpha: additive,
+                        }),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options:
+                        wgpu::PipelineCompilationOptions::default(),
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    cull_mode: None,
+                    ..Default::default()
+                },
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            });
+
+        Self {
+            n_particles,
+            particle_buffer,
+            uniform_buffer,
+            compute_pipeline,
+            compute_bind_group,
+            render_pipeline,
+            render_bind_group,
+            emitter: [0.0, 0.0],
+            time: 0.0,
+        }
+    }
+
+    // In NDC, so (0, 0) is the middle of the screen and y points up
+    pub fn set_emitter(&mut self, x: f32, y: f32) {
+        self.emitter = [x, y];
+    }
+
+    // Steps the simulation by `dt` seconds. Writes the uniforms straight
+    // away, so each step needs submitting before the next one's recorded,
+    // and all of them before the pass that calls `render`.
+    pub fn update(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        queue: &wgpu::Queue,
+        screen_size: winit::dpi::PhysicalSize<u32>,
+        dt: f32,
+    ) {
+        self.time += dt;
+
+        let uniform = ParticleUniform {
+            emitter: self.emitter,
+            resolution: [
+                screen_size.width.max(1) as f32,
+                screen_size.height.max(1) as f32,
+            ],
+            dt,
+            time: self.time,
+            count: self.n_particles,
+            _padding: 0,
+        };
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[uniform]),
+        );
+
+        let mut compute_pass =
+            encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Particle Compute Pass"),
+                timestamp_writes: None,
+            });
+        compute_pass.set_pipeline(&self.compute_pipeline);
+        compute_pass.set_bind_group(0, &self.compute_bind_group, &[]);
+        compute_pass.dispatch_workgroups(
+            self.n_particles.div_ceil(WORKGROUP_SIZE),
+            1,
+            1,
+        );
+    }
+
+    pub fn render(&self, render_pass: &mut wgpu::RenderPass) {
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_bind_group(0, &self.render_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.particle_buffer.slice(..));
+        // Six vertices make up each particle's quad, see vs_main
+        render_pass.draw(0..6, 0..self.n_particles);
+    }
+}
@@ -0,0 +1,115 @@
+// see: particles::Particle
+struct Particle {
+    pos: vec2<f32>,
+    vel: vec2<f32>,
+    life: f32,
+}
+
+// see: particles::ParticleUniform
+struct ParticleUniform {
+    // Where new particles appear, in NDC
+    emitter: vec2<f32>,
+    resolution: vec2<f32>,
+    dt: f32,
+    time: f32,
+    count: u32,
+}
+
+@group(0) @binding(0)
+var<uniform> params: ParticleUniform;
+
+@group(0) @binding(1)
+var<storage, read_write> particles: array<Particle>;
+
+// Seconds. Particles live between half of this and all of it.
+const MAX_LIFE: f32 = 2.0;
+// NDC units per second squared
+const GRAVITY: f32 = -1.5;
+// NDC units per second, at most
+const SPEED: f32 = 0.8;
+// Quad size in pixels
+const SIZE: f32 = 6.0;
+const COLOR: vec3<f32> = vec3<f32>(1.0, 0.6, 0.2);
+
+// PCG hash mapped to 0..1
+fn random(seed: u32) -> f32 {
+    var x = seed * 747796405u + 2891336453u;
+    x = ((x >> ((x >> 28u) + 4u)) ^ x) * 277803737u;
+    x = (x >> 22u) ^ x;
+    return f32(x) / 4294967295.0;
+}
+
+@compute @workgroup_size(64)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let index = id.x;
+    if index >= params.count {
+        return;
+    }
+
+    var particle = particles[index];
+    particle.life -= params.dt;
+
+    if particle.life <= 0.0 {
+        // Respawn at the emitter heading off in a random direction
+        let seed = index * 1973u + u32(params.time * 1000.0) * 9277u;
+        let angle = random(seed) * 6.2831853;
+        let speed = SPEED * (0.25 + 0.75 * random(seed + 1u));
+        // NDC is stretched along the longer side of the window, so squash x
+        // to keep the spray circular on screen
+        let aspect = params.resolution.y / params.resolution.x;
+        particle.pos = params.emitter;
+        particle.vel = vec2<f32>(cos(angle) * aspect, sin(angle)) * speed;
+        particle.life = MAX_LIFE * (0.5 + 0.5 * random(seed + 2u));
+    } else {
+        particle.vel.y += GRAVITY * params.dt;
+        particle.pos += particle.vel * params.dt;
+    }
+
+    particles[index] = particle;
+}
+
+// The particle buffer read back as per-instance vertex data
+struct InstanceInput {
+    @location(0) pos: vec2<f32>,
+    @location(1) life: f32,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    // -1..1 across the quad
+    @location(0) offset: vec2<f32>,
+    @location(1) fade: f32,
+}
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) vertex_index: u32,
+    particle: InstanceInput,
+) -> VertexOutput {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, 1.0),
+    );
+    let corner = corners[vertex_index];
+    // NDC spans 2 units per screen, so half of SIZE pixels is SIZE / resolution
+    let half_size = vec2<f32>(SIZE) / params.resolution;
+
+    var out: VertexOutput;
+    out.clip_position =
+        vec4<f32>(particle.pos + corner * half_size, 0.0, 1.0);
+    out.offset = corner;
+    out.fade = clamp(particle.life / MAX_LIFE, 0.0, 1.0);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Soft round dots that fade out as they age
+    let alpha = max(1.0 - length(in.offset), 0.0) * in.fade;
+    // Premultiplied, for the additive blend
+    return vec4<f32>(COLOR * alpha, alpha);
+}
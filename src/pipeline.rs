@@ -0,0 +1,429 @@
+// `format` has to match the depth texture the pipeline draws into, either
+// Texture::DEPTH_FORMAT or Texture::DEPTH_STENCIL_FORMAT. The stencil test
+// is left off; swap in `stencil_write` or `stencil_test` to use it.
+pub fn depth_stencil_state(
+    format: wgpu::TextureFormat,
+    depth_compare: wgpu::CompareFunction,
+    depth_write_enabled: bool,
+) -> wgpu::DepthStencilState {
+    wgpu::DepthStencilState {
+        format,
+        depth_write_enabled,
+        depth_compare,
+        stencil: wgpu::StencilState::default(),
+        bias: wgpu::DepthBiasState::default(),
+    }
+}
+
+// Stamps the pass's stencil reference (see `set_stencil_reference`) into
+// every pixel the pipeline draws, whatever was there before
+pub fn stencil_write() -> wgpu::StencilState {
+    let face = wgpu::StencilFaceState {
+        compare: wgpu::CompareFunction::Always,
+        fail_op: wgpu::StencilOperation::Keep,
+        depth_fail_op: wgpu::StencilOperation::Replace,
+        pass_op: wgpu::StencilOperation::Replace,
+    };
+    wgpu::StencilState {
+        front: face,
+        back: face,
+        read_mask: 0xff,
+        write_mask: 0xff,
+    }
+}
+
+// Only draws where the stored stencil value compares against the pass's
+// reference with `compare`, leaving the stencil buffer untouched
+pub fn stencil_test(compare: wgpu::CompareFunction) -> wgpu::StencilState {
+    let face = wgpu::StencilFaceState {
+        compare,
+        fail_op: wgpu::StencilOperation::Keep,
+        depth_fail_op: wgpu::StencilOperation::Keep,
+        pass_op: wgpu::StencilOperation::Keep,
+    };
+    wgpu::StencilState {
+        front: face,
+        back: face,
+        read_mask: 0xff,
+        write_mask: 0,
+    }
+}
+
+// An opaque color target, replacing whatever was there. `targets` below
+// takes one per @location fs_main writes to, usually just this for the
+// surface's format.
+pub fn color_target(
+    format: wgpu::TextureFormat,
+) -> Option<wgpu::ColorTargetState> {
+    Some(wgpu::ColorTargetState {
+        format,
+        blend: Some(wgpu::BlendState::REPLACE),
+        write_mask: wgpu::ColorWrites::ALL,
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn create_render_pipeline(
+    device: &wgpu::Device,
+    targets: &[Option<wgpu::ColorTargetState>],
+    shader: &wgpu::ShaderModule,
+    bind_group_layouts: &[&wgpu::BindGroupLayout],
+    buffers: &[wgpu::VertexBufferLayout],
+    sample_count: u32,
+    depth_stencil: Option<wgpu::DepthStencilState>,
+    polygon_mode: wgpu::PolygonMode,
+    conservative: bool,
+) -> wgpu::RenderPipeline {
+    create_pipeline(
+        device,
+        targets,
+        shader,
+        bind_group_layouts,
+        buffers,
+        sample_count,
+        depth_stencil,
+        polygon_mode,
+        conservative,
+        &OpacitySetup {
+            vertex_entry: "vs_main",
+            fragment_entry: "fs_main",
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            cull_mode: Some(wgpu::Face::Back),
+            front_face: wgpu::FrontFace::Ccw,
+            push_constant_ranges: &[],
+        },
+    )
+}
+
+// What the object pipelines can cull, in the order they're built and
+// cycled through. Back is the normal case; the other two are for checking a
+// mesh's winding, e.g. one that looks inside out is wound clockwise and only
+// shows its back faces with Front.
+pub const CULL_MODES: [Option<wgpu::Face>; 3] =
+    [Some(wgpu::Face::Back), Some(wgpu::Face::Front), None];
+
+pub fn cull_mode_name(cull_mode: Option<wgpu::Face>) -> &'static str {
+    match cull_mode {
+        Some(wgpu::Face::Back) => "back faces",
+        Some(wgpu::Face::Front) => "front faces",
+        None => "none",
+    }
+}
+
+// Which winding counts as facing the camera, for the object pipelines.
+// Counter-clockwise is what this repo's meshes (and glTF) use; clockwise is
+// for meshes authored the other way round, which otherwise get their
+// outsides culled.
+pub const FRONT_FACES: [wgpu::FrontFace; 2] =
+    [wgpu::FrontFace::Ccw, wgpu::FrontFace::Cw];
+
+pub fn front_face_name(front_face: wgpu::FrontFace) -> &'static str {
+    match front_face {
+        wgpu::FrontFace::Ccw => "counter-clockwise",
+        wgpu::FrontFace::Cw => "clockwise",
+    }
+}
+
+// Same as `create_render_pipeline`, but vertices go through `vs_object`,
+// which applies a model matrix from a dynamic offset uniform, and fragments
+// through `fs_object`, which tints them. `push_constant_ranges` is
+// `draw_constants::push_constant_ranges`, matching how `shader` was built.
+// A PointList `topology` draws every vertex as a single pixel, WGSL has no
+// way to make points any bigger.
+#[allow(clippy::too_many_arguments)]
+pub fn create_object_pipeline(
+    device: &wgpu::Device,
+    surface_configuration: &wgpu::SurfaceConfiguration,
+    shader: &wgpu::ShaderModule,
+    bind_group_layouts: &[&wgpu::BindGroupLayout],
+    push_constant_ranges: &[wgpu::PushConstantRange],
+    buffers: &[wgpu::VertexBufferLayout],
+    sample_count: u32,
+    depth_stencil: Option<wgpu::DepthStencilState>,
+    polygon_mode: wgpu::PolygonMode,
+    conservative: bool,
+    topology: wgpu::PrimitiveTopology,
+    cull_mode: Option<wgpu::Face>,
+    front_face: wgpu::FrontFace,
+) -> wgpu::RenderPipeline {
+    create_pipeline(
+        device,
+        &[color_target(surface_configuration.format)],
+        shader,
+        bind_group_layouts,
+        buffers,
+        sample_count,
+        depth_stencil,
+        polygon_mode,
+        conservative,
+        &OpacitySetup {
+            vertex_entry: "vs_object",
+            fragment_entry: "fs_object",
+            topology,
+            cull_mode,
+            front_face,
+            push_constant_ranges,
+        },
+    )
+}
+
+// For translucent surfaces. Fragments are alpha blended over what's already
+// there and depth is tested but not written, so a translucent surface never
+// hides anything drawn after it.
+//
+// Blending isn't order independent: these have to be drawn after all opaque
+// geometry, and back to front among themselves, or nearer surfaces get
+// blended under farther ones. Both faces are drawn so they stay visible from
+// behind.
+#[allow(clippy::too_many_arguments)]
+pub fn create_transparent_pipeline(
+    device: &wgpu::Device,
+    surface_configuration: &wgpu::SurfaceConfiguration,
+    shader: &wgpu::ShaderModule,
+    bind_group_layouts: &[&wgpu::BindGroupLayout],
+    buffers: &[wgpu::VertexBufferLayout],
+    sample_count: u32,
+    depth_format: wgpu::TextureFormat,
+    fragment_entry: &str,
+) -> wgpu::RenderPipeline {
+    create_pipeline(
+        device,
+        &[Some(wgpu::ColorTargetState {
+            format: surface_configuration.format,
+            blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+            write_mask: wgpu::ColorWrites::ALL,
+        })],
+        shader,
+        bind_group_layouts,
+        buffers,
+        sample_count,
+        Some(depth_stencil_state(
+            depth_format,
+            wgpu::CompareFunction::Less,
+            false,
+        )),
+        wgpu::PolygonMode::Fill,
+        false,
+        &OpacitySetup {
+            vertex_entry: "vs_main",
+            fragment_entry,
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            cull_mode: None,
+            front_face: wgpu::FrontFace::Ccw,
+            push_constant_ranges: &[],
+        },
+    )
+}
+
+// Marks the shape it draws in the stencil buffer without touching color or
+// depth, for a later `stencil_test` pipeline to draw through. It ignores
+// depth so the whole shape is marked even where something is in front of
+// it. The color target is only there because the pass has one.
+pub fn create_stencil_mask_pipeline(
+    device: &wgpu::Device,
+    surface_configuration: &wgpu::SurfaceConfiguration,
+    shader: &wgpu::ShaderModule,
+    bind_group_layouts: &[&wgpu::BindGroupLayout],
+    buffers: &[wgpu::VertexBufferLayout],
+    sample_count: u32,
+    depth_format: wgpu::TextureFormat,
+) -> wgpu::RenderPipeline {
+    create_pipeline(
+        device,
+        &[Some(wgpu::ColorTargetState {
+            format: surface_configuration.format,
+            blend: Some(wgpu::BlendState::REPLACE),
+            write_mask: wgpu::ColorWrites::empty(),
+        })],
+        shader,
+        bind_group_layouts,
+        buffers,
+        sample_count,
+        Some(wgpu::DepthStencilState {
+            stencil: stencil_write(),
+            ..depth_stencil_state(
+                depth_format,
+                wgpu::CompareFunction::Always,
+                false,
+            )
+        }),
+        wgpu::PolygonMode::Fill,
+        false,
+        &OpacitySetup {
+            vertex_entry: "vs_main",
+            fragment_entry: "fs_main",
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            cull_mode: None,
+            front_face: wgpu::FrontFace::Ccw,
+            push_constant_ranges: &[],
+        },
+    )
+}
+
+// What differs between the opaque, transparent, object and stencil mask
+// pipelines besides their color targets
+struct OpacitySetup<'a> {
+    vertex_entry: &'a str,
+    fragment_entry: &'a str,
+    topology: wgpu::PrimitiveTopology,
+    cull_mode: Option<wgpu::Face>,
+    front_face: wgpu::FrontFace,
+    push_constant_ranges: &'a [wgpu::PushConstantRange],
+}
+
+#[allow(clippy::too_many_arguments)]
+fn create_pipeline(
+    device: &wgpu::Device,
+    targets: &[Option<wgpu::ColorTargetState>],
+    shader: &wgpu::ShaderModule,
+    bind_group_layouts: &[&wgpu::BindGroupLayout],
+    buffers: &[wgpu::VertexBufferLayout],
+    sample_count: u32,
+    depth_stencil: Option<wgpu::DepthStencilState>,
+    polygon_mode: wgpu::PolygonMode,
+    conservative: bool,
+    setup: &OpacitySetup,
+) -> wgpu::RenderPipeline {
+    let render_pipeline_layout =
+        device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Render Pipeline Layout"),
+            bind_group_layouts,
+            push_constant_ranges: setup.push_constant_ranges,
+        });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Render Pipeline"),
+        layout: Some(&render_pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: Some(setup.vertex_entry),
+            buffers,
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: Some(setup.fragment_entry),
+            targets,
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: setup.topology,
+            strip_index_format: None,
+            front_face: setup.front_face,
+            cull_mode: setup.cull_mode,
+            // Line requires `Features::POLYGON_MODE_LINE` and Point
+            // requires `Features::POLYGON_MODE_POINT`
+            polygon_mode,
+            // Requires `Features::DEPTH_CLIP_CONTROL`
+            unclipped_depth: false,
+            // Requires `Features::CONSERVATIVE_RASTERIZATION`. Rasterizes
+            // every pixel a triangle touches at all, not just those whose
+            // center it covers.
+            conservative,
+        },
+        depth_stencil,
+        multisample: wgpu::MultisampleState {
+            count: sample_count,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+        cache: None,
+    })
+}
+
+// Vertex-only pipeline that just fills the depth buffer. It must run the
+// exact same vertex shader as the color pipeline that follows it or the
+// Equal depth test won't match.
+pub fn create_depth_prepass_pipeline(
+    device: &wgpu::Device,
+    shader: &wgpu::ShaderModule,
+    bind_group_layouts: &[&wgpu::BindGroupLayout],
+    buffers: &[wgpu::VertexBufferLayout],
+    sample_count: u32,
+    depth_format: wgpu::TextureFormat,
+) -> wgpu::RenderPipeline {
+    let layout =
+        device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Depth Prepass Pipeline Layout"),
+            bind_group_layouts,
+            push_constant_ranges: &[],
+        });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Depth Prepass Pipeline"),
+        layout: Some(&layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: Some("vs_main"),
+            buffers,
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        },
+        fragment: None,
+        primitive: wgpu::PrimitiveState {
+            cull_mode: Some(wgpu::Face::Back),
+            ..Default::default()
+        },
+        depth_stencil: Some(depth_stencil_state(
+            depth_format,
+            wgpu::CompareFunction::Less,
+            true,
+        )),
+        multisample: wgpu::MultisampleState {
+            count: sample_count,
+            ..Default::default()
+        },
+        multiview: None,
+        cache: None,
+    })
+}
+
+// Falls back to the highest supported count below `desired` (ultimately
+// 1, which is always supported) so we never build a pipeline the adapter
+// would reject. `formats` are the attachments' formats, e.g. color and
+// depth, which have to be multisampled alike.
+pub fn supported_sample_count(
+    adapter: &wgpu::Adapter,
+    formats: &[wgpu::TextureFormat],
+    desired: u32,
+) -> u32 {
+    let sample_count = [16, 8, 4, 2]
+        .into_iter()
+        .filter(|&count| count <= desired)
+        .find(|&count| sample_count_supported(adapter, formats, count))
+        .unwrap_or(1);
+    if sample_count != desired {
+        log::warn!(
+            "MSAA x{} not supported for {:?}, using x{}",
+            desired,
+            formats,
+            sample_count
+        );
+    }
+    sample_count
+}
+
+// The counts out of 1, 2, 4 and 8 that all of `formats` support, for
+// switching between at runtime
+pub fn supported_sample_counts(
+    adapter: &wgpu::Adapter,
+    formats: &[wgpu::TextureFormat],
+) -> Vec<u32> {
+    [1, 2, 4, 8]
+        .into_iter()
+        .filter(|&count| sample_count_supported(adapter, formats, count))
+        .collect()
+}
+
+fn sample_count_supported(
+    adapter: &wgpu::Adapter,
+    formats: &[wgpu::TextureFormat],
+    count: u32,
+) -> bool {
+    formats.iter().all(|&format| {
+        adapter
+            .get_texture_format_features(format)
+            .flags
+            .sample_count_supported(count)
+    })
+}
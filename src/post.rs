@@ -0,0 +1,540 @@
+use winit::dpi::PhysicalSize;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PostUniform {
+    pub resolution: [f32; 2],
+    pub time: f32,
+    _padding: u32,
+}
+
+// One step of the post-processing chain. Every effect reads the previous
+// step's output through `input` (texture at binding 0, sampler at binding 1)
+// and draws into `output`, with the shared `PostUniform` available through
+// `uniforms` (binding 0). See post.wgsl for the matching declarations.
+// `size` is the input's, for effects that make their own targets to match.
+//
+// Most effects are a single fullscreen pass and can just be a
+// `ShaderEffect`, but anything that needs its own intermediate targets
+// (bloom, depth of field, ...) can implement this directly.
+pub trait PostEffect {
+    fn name(&self) -> &str;
+
+    fn record(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        input: &wgpu::BindGroup,
+        output: &wgpu::TextureView,
+        uniforms: &wgpu::BindGroup,
+        size: PhysicalSize<u32>,
+    );
+}
+
+// A fullscreen triangle running one of the fragment entry points in
+// post.wgsl
+pub struct ShaderEffect {
+    name: String,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl ShaderEffect {
+    pub fn new(
+        device: &wgpu::Device,
+        chain: &PostChain,
+        name: &str,
+        entry_point: &str,
+    ) -> Self {
+        Self::build(
+            device,
+            chain.format,
+            &chain.input_layout,
+            &chain.uniform_layout,
+            name,
+            entry_point,
+        )
+    }
+
+    fn build(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        input_layout: &wgpu::BindGroupLayout,
+        uniform_layout: &wgpu::BindGroupLayout,
+        name: &str,
+        entry_point: &str,
+    ) -> Self {
+        let shader =
+            device.create_shader_module(wgpu::include_wgsl!("post.wgsl"));
+
+        let layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Post Effect Pipeline Layout"),
+                bind_group_layouts: &[input_layout, uniform_layout],
+                push_constant_ranges: &[],
+            });
+
+        let pipeline =
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(name),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[],
+                    compilation_options:
+                        wgpu::PipelineCompilationOptions::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some(entry_point),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format,
+                        blend: Some(wgpu::BlendState::REPLACE),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options:
+                        wgpu::PipelineCompilationOptions::default(),
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            });
+
+        Self {
+            name: name.to_string(),
+            pipeline,
+        }
+    }
+}
+
+impl PostEffect for ShaderEffect {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn record(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        input: &wgpu::BindGroup,
+        output: &wgpu::TextureView,
+        uniforms: &wgpu::BindGroup,
+        _size: PhysicalSize<u32>,
+    ) {
+        let mut render_pass =
+            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some(&self.name),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: output,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        // Every pixel gets overwritten
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, input, &[]);
+        render_pass.set_bind_group(1, uniforms, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+struct PostTarget {
+    // Kept alive for the view and bind group
+    _texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    bind_group: wgpu::BindGroup,
+}
+
+// The two ping-pong targets a `PostChain` runs between, plus the bind groups
+// that read them. These are the only part of the chain that depends on the
+// size, so they're made by `PostChain::create_targets` and owned by whoever
+// owns the other size dependent resources, while the chain itself (effects,
+// pipelines, layouts, sampler and uniforms) lives through any resize.
+pub struct PostTargets {
+    size: PhysicalSize<u32>,
+    targets: [PostTarget; 2],
+}
+
+impl PostTargets {
+    // Where the scene should be drawn (or resolved) to
+    pub fn scene_view(&self) -> &wgpu::TextureView {
+        &self.targets[0].view
+    }
+}
+
+struct ChainEntry {
+    effect: Box<dyn PostEffect>,
+    enabled: bool,
+}
+
+// An ordered, runtime-editable list of post effects. The scene is drawn into
+// the targets' `scene_view`, then each enabled effect reads the previous
+// result and writes the next, ping-ponging between the two targets, with the
+// last one writing to the final output. With nothing enabled the scene is
+// copied across as is.
+pub struct PostChain {
+    format: wgpu::TextureFormat,
+    input_layout: wgpu::BindGroupLayout,
+    uniform_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    // How `sampler` filters
+    filter: wgpu::FilterMode,
+    uniform_buffer: wgpu::Buffer,
+    uniform_bind_group: wgpu::BindGroup,
+    entries: Vec<ChainEntry>,
+    // The entry the editing keys act on
+    selected: usize,
+    copy: ShaderEffect,
+    // Run ahead of every entry when on, see `set_fxaa`
+    fxaa: ShaderEffect,
+    fxaa_enabled: bool,
+}
+
+impl PostChain {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+        // Compute effects (see blur.rs) read their input through it too
+        let input_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Post Input Bind Group Layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT
+                            | wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Float {
+                                filterable: true,
+                            },
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT
+                            | wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Sampler(
+                            wgpu::SamplerBindingType::Filtering,
+                        ),
+                        count: None,
+                    },
+                ],
+            });
+
+        let uniform_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Post Uniform Bind Group Layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX
+                        | wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            });
+
+        let sampler = create_sampler(device, wgpu::FilterMode::Linear);
+
+        // Filled in by every `run`
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Post Uniform Buffer"),
+            size: std::mem::size_of::<PostUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let uniform_bind_group =
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Post Uniform Bind Group"),
+                layout: &uniform_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                }],
+            });
+
+        let copy = ShaderEffect::build(
+            device,
+            format,
+            &input_layout,
+            &uniform_layout,
+            "Copy",
+            "fs_copy",
+        );
+        let fxaa = ShaderEffect::build(
+            device,
+            format,
+            &input_layout,
+            &uniform_layout,
+            "FXAA",
+            "fs_fxaa",
+        );
+
+        Self {
+            format,
+            input_layout,
+            uniform_layout,
+            sampler,
+            filter: wgpu::FilterMode::Linear,
+            uniform_buffer,
+            uniform_bind_group,
+            entries: vec![],
+            selected: 0,
+            copy,
+            fxaa,
+            fxaa_enabled: false,
+        }
+    }
+
+    // Effects that bind the previous step's output themselves (e.g. to
+    // their own intermediate targets) do it with this layout
+    pub fn input_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.input_layout
+    }
+
+    // How every pass samples its input. It only makes a difference when the
+    // targets are smaller than the output and get upscaled on the way out:
+    // nearest keeps hard edged pixels, linear blurs them together.
+    pub fn filter(&self) -> wgpu::FilterMode {
+        self.filter
+    }
+
+    // Targets from before keep sampling the old way, so create new ones
+    // afterwards
+    pub fn set_filter(
+        &mut self,
+        device: &wgpu::Device,
+        filter: wgpu::FilterMode,
+    ) {
+        self.sampler = create_sampler(device, filter);
+        self.filter = filter;
+    }
+
+    // A fresh pair of targets for the chain to run between, e.g. after a
+    // resize. Targets from before stay usable until they're dropped.
+    pub fn create_targets(
+        &self,
+        device: &wgpu::Device,
+        size: PhysicalSize<u32>,
+    ) -> PostTargets {
+        PostTargets {
+            size,
+            targets: [0, 1].map(|_| self.create_target(device, size)),
+        }
+    }
+
+    fn create_target(
+        &self,
+        device: &wgpu::Device,
+        size: PhysicalSize<u32>,
+    ) -> PostTarget {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Post Target"),
+            size: wgpu::Extent3d {
+                width: size.width.max(1),
+                height: size.height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: self.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Post Target Bind Group"),
+            layout: &self.input_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        });
+        PostTarget {
+            _texture: texture,
+            view,
+            bind_group,
+        }
+    }
+
+    // The built-in single pass effects from post.wgsl, all off
+    pub fn with_default_effects(mut self, device: &wgpu::Device) -> Self {
+        for (name, entry_point) in [
+            ("Grayscale", "fs_grayscale"),
+            ("Invert", "fs_invert"),
+            ("Gamma", "fs_gamma"),
+            ("Vignette", "fs_vignette"),
+        ] {
+            let effect = ShaderEffect::new(device, &self, name, entry_point);
+            self.push(Box::new(effect), false);
+        }
+        self
+    }
+
+    // Anti-aliases the scene before any of the effects run. Kept out of the
+    // entries so presets and the editing keys leave it alone: it's part of
+    // how the scene is drawn, see anti_aliasing::AaMode.
+    pub fn set_fxaa(&mut self, enabled: bool) {
+        self.fxaa_enabled = enabled;
+    }
+
+    pub fn fxaa(&self) -> bool {
+        self.fxaa_enabled
+    }
+
+    pub fn push(&mut self, effect: Box<dyn PostEffect>, enabled: bool) {
+        self.entries.push(ChainEntry { effect, enabled });
+    }
+
+    pub fn select_next(&mut self) {
+        if !self.entries.is_empty() {
+            self.selected = (self.selected + 1) % self.entries.len();
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        if !self.entries.is_empty() {
+            self.selected =
+                (self.selected + self.entries.len() - 1) % self.entries.len();
+        }
+    }
+
+    pub fn toggle_selected(&mut self) {
+        if let Some(entry) = self.entries.get_mut(self.selected) {
+            entry.enabled = !entry.enabled;
+        }
+    }
+
+    // Enables the effect called `name` and disables everything else, or
+    // disables everything when `name` is None
+    pub fn solo(&mut self, name: Option<&str>) {
+        for entry in &mut self.entries {
+            entry.enabled = Some(entry.effect.name()) == name;
+        }
+    }
+
+    // Moves the selected effect one step earlier (-1) or later (+1) in the
+    // chain, taking the selection with it
+    pub fn move_selected(&mut self, offset: isize) {
+        let Some(to) = self.selected.checked_add_signed(offset) else {
+            return;
+        };
+        if to < self.entries.len() {
+            self.entries.swap(self.selected, to);
+            self.selected = to;
+        }
+    }
+
+    // One line per effect in chain order, e.g. "> [x] Vignette"
+    pub fn describe(&self) -> Vec<String> {
+        self.entries
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| {
+                format!(
+                    "{} [{}] {}",
+                    if i == self.selected { ">" } else { " " },
+                    if entry.enabled { "x" } else { " " },
+                    entry.effect.name()
+                )
+            })
+            .collect()
+    }
+
+    // Runs every enabled effect over what was drawn into the targets'
+    // `scene_view` and writes the result into `output`, which must use the
+    // chain's format. The last pass always covers all of `output`, so targets
+    // smaller than the output (a reduced render scale) get upscaled on the
+    // way out.
+    pub fn run(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        queue: &wgpu::Queue,
+        targets: &PostTargets,
+        output: &wgpu::TextureView,
+        time: f32,
+    ) {
+        let PostTargets { size, targets } = targets;
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[PostUniform {
+                resolution: [size.width as f32, size.height as f32],
+                time,
+                _padding: 0,
+            }]),
+        );
+
+        let fxaa = self.fxaa_enabled.then_some(&self.fxaa as &dyn PostEffect);
+        let mut enabled = fxaa
+            .into_iter()
+            .chain(
+                self.entries
+                    .iter()
+                    .filter(|entry| entry.enabled)
+                    .map(|entry| entry.effect.as_ref()),
+            )
+            .peekable();
+
+        if enabled.peek().is_none() {
+            self.copy.record(
+                encoder,
+                &targets[0].bind_group,
+                output,
+                &self.uniform_bind_group,
+                *size,
+            );
+            return;
+        }
+
+        let mut source = 0;
+        while let Some(effect) = enabled.next() {
+            let destination = if enabled.peek().is_some() {
+                &targets[1 - source].view
+            } else {
+                output
+            };
+            effect.record(
+                encoder,
+                &targets[source].bind_group,
+                destination,
+                &self.uniform_bind_group,
+                *size,
+            );
+            source = 1 - source;
+        }
+    }
+}
+
+fn create_sampler(
+    device: &wgpu::Device,
+    filter: wgpu::FilterMode,
+) -> wgpu::Sampler {
+    device.create_sampler(&wgpu::SamplerDescriptor {
+        label: Some("Post Sampler"),
+        address_mode_u: wgpu::AddressMode::ClampToEdge,
+        address_mode_v: wgpu::AddressMode::ClampToEdge,
+        mag_filter: filter,
+        min_filter: filter,
+        ..Default::default()
+    })
+}
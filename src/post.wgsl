@@ -0,0 +1,144 @@
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+}
+
+// see: post::PostUniform
+struct PostUniform {
+    // Size of the input and output in pixels
+    resolution: vec2<f32>,
+    time: f32,
+}
+
+@group(0) @binding(0)
+var t_input: texture_2d<f32>;
+
+@group(0) @binding(1)
+var s_input: sampler;
+
+@group(1) @binding(0)
+var<uniform> post: PostUniform;
+
+// A single triangle covering the screen: (-1, -1), (3, -1), (-1, 3)
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    let uv = vec2<f32>(
+        f32((vertex_index << 1u) & 2u),
+        f32(vertex_index & 2u),
+    );
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.tex_coords = uv;
+    return out;
+}
+
+// Used when no effect is enabled
+@fragment
+fn fs_copy(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(t_input, s_input, in.tex_coords);
+}
+
+@fragment
+fn fs_invert(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(t_input, s_input, in.tex_coords);
+    return vec4<f32>(1.0 - color.rgb, color.a);
+}
+
+@fragment
+fn fs_grayscale(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(t_input, s_input, in.tex_coords);
+    // Rec. 709 luma weights, applied to linear color
+    let luma = dot(color.rgb, vec3<f32>(0.2126, 0.7152, 0.0722));
+    return vec4<f32>(vec3<f32>(luma), color.a);
+}
+
+// An extra gamma curve on top of the sRGB encoding the target already does,
+// which lifts the midtones. It's the washed out look you get from
+// gamma correcting twice.
+const GAMMA: f32 = 2.2;
+
+@fragment
+fn fs_gamma(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(t_input, s_input, in.tex_coords);
+    return vec4<f32>(pow(color.rgb, vec3<f32>(1.0 / GAMMA)), color.a);
+}
+
+@fragment
+fn fs_vignette(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(t_input, s_input, in.tex_coords);
+    // Correct for aspect so the falloff stays round rather than oval
+    let aspect = post.resolution.x / post.resolution.y;
+    let offset = (in.tex_coords - 0.5) * vec2<f32>(aspect, 1.0);
+    let falloff = smoothstep(0.8, 0.3, length(offset));
+    return vec4<f32>(color.rgb * falloff, color.a);
+}
+
+// FXAA, after Timothy Lottes' original: finds which way the edge through
+// each pixel runs from its diagonal neighbors' brightness, then blends
+// samples along it. Only pixels on an edge change much.
+//
+// How far along the edge to reach, in pixels
+const FXAA_SPAN_MAX: f32 = 8.0;
+// Keep flat and dark areas from counting as edges
+const FXAA_REDUCE_MUL: f32 = 1.0 / 8.0;
+const FXAA_REDUCE_MIN: f32 = 1.0 / 128.0;
+
+// Edges are judged by perceived brightness. The input is linear, so the
+// square root brings it roughly back to how it's seen.
+fn fxaa_luma(color: vec3<f32>) -> f32 {
+    return sqrt(dot(color, vec3<f32>(0.299, 0.587, 0.114)));
+}
+
+fn fxaa_sample(uv: vec2<f32>) -> vec3<f32> {
+    return textureSample(t_input, s_input, uv).rgb;
+}
+
+@fragment
+fn fs_fxaa(in: VertexOutput) -> @location(0) vec4<f32> {
+    let texel = 1.0 / post.resolution;
+    let uv = in.tex_coords;
+    let center = textureSample(t_input, s_input, uv);
+    let luma_nw = fxaa_luma(fxaa_sample(uv + vec2<f32>(-1.0, -1.0) * texel));
+    let luma_ne = fxaa_luma(fxaa_sample(uv + vec2<f32>(1.0, -1.0) * texel));
+    let luma_sw = fxaa_luma(fxaa_sample(uv + vec2<f32>(-1.0, 1.0) * texel));
+    let luma_se = fxaa_luma(fxaa_sample(uv + vec2<f32>(1.0, 1.0) * texel));
+    let luma_center = fxaa_luma(center.rgb);
+    let luma_min = min(
+        luma_center,
+        min(min(luma_nw, luma_ne), min(luma_sw, luma_se)),
+    );
+    let luma_max = max(
+        luma_center,
+        max(max(luma_nw, luma_ne), max(luma_sw, luma_se)),
+    );
+
+    // Across the brightness gradient is along the edge
+    var direction = vec2<f32>(
+        (luma_sw + luma_se) - (luma_nw + luma_ne),
+        (luma_nw + luma_sw) - (luma_ne + luma_se),
+    );
+    let reduce = max(
+        (luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * FXAA_REDUCE_MUL,
+        FXAA_REDUCE_MIN,
+    );
+    let scale = 1.0 / (min(abs(direction.x), abs(direction.y)) + reduce);
+    direction = clamp(
+        direction * scale,
+        vec2<f32>(-FXAA_SPAN_MAX),
+        vec2<f32>(FXAA_SPAN_MAX),
+    ) * texel;
+
+    // Two samples close in along the edge, and those plus two further out
+    let near = 0.5 * (
+        fxaa_sample(uv + direction * (1.0 / 3.0 - 0.5)) +
+        fxaa_sample(uv + direction * (2.0 / 3.0 - 0.5))
+    );
+    let far = near * 0.5 + 0.25 * (
+        fxaa_sample(uv - direction * 0.5) + fxaa_sample(uv + direction * 0.5)
+    );
+    // Reaching further can cross into something else entirely, which shows
+    // as a brightness outside the neighborhood's
+    let luma_far = fxaa_luma(far);
+    let color = select(far, near, luma_far < luma_min || luma_far > luma_max);
+    return vec4<f32>(color, center.a);
+}
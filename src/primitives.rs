@@ -0,0 +1,124 @@
+use crate::geometry::Vertex;
+
+// A flat, `size` x `size` square on the XZ plane centered at the origin,
+// facing +Y, split into `subdivisions` quads along each side. UVs span 0..1
+// across the whole plane, with u running along +X and v along +Z.
+pub fn plane(size: f32, subdivisions: u32) -> (Vec<Vertex>, Vec<u32>) {
+    let subdivisions = subdivisions.max(1);
+    let n = subdivisions + 1;
+    let half = size * 0.5;
+
+    let vertices = (0..n)
+        .flat_map(|row| {
+            (0..n).map(move |col| {
+                let u = col as f32 / subdivisions as f32;
+                let v = row as f32 / subdivisions as f32;
+                Vertex {
+                    position: [u * size - half, 0.0, v * size - half],
+                    tex_coords: [u, v],
+                    normal: [0.0, 1.0, 0.0],
+                    tangent: [1.0, 0.0, 0.0],
+                    bitangent: [0.0, 0.0, 1.0],
+                    color: [1.0, 1.0, 1.0],
+                }
+            })
+        })
+        .collect();
+
+    let indices = (0..subdivisions)
+        .flat_map(|row| {
+            (0..subdivisions).flat_map(move |col| {
+                let top_left = row * n + col;
+                let top_right = top_left + 1;
+                let bottom_left = top_left + n;
+                let bottom_right = bottom_left + 1;
+                // Counter-clockwise when seen from above
+                [
+                    top_left,
+                    bottom_left,
+                    top_right,
+                    top_right,
+                    bottom_left,
+                    bottom_right,
+                ]
+            })
+        })
+        .collect();
+
+    (vertices, indices)
+}
+
+// A `size` x `size` square on the XY plane centered at `center`, facing +Z,
+// with every vertex tinted `color`
+pub fn quad(
+    center: [f32; 3],
+    size: f32,
+    color: [f32; 3],
+) -> (Vec<Vertex>, Vec<u32>) {
+    let half = size * 0.5;
+    let [cx, cy, cz] = center;
+    let corner = |x: f32, y: f32, u: f32, v: f32| Vertex {
+        position: [cx + x * half, cy + y * half, cz],
+        tex_coords: [u, v],
+        normal: [0.0, 0.0, 1.0],
+        tangent: [1.0, 0.0, 0.0],
+        bitangent: [0.0, 1.0, 0.0],
+        color,
+    };
+    let vertices = vec![
+        corner(-1.0, -1.0, 0.0, 1.0),
+        corner(1.0, -1.0, 1.0, 1.0),
+        corner(1.0, 1.0, 1.0, 0.0),
+        corner(-1.0, 1.0, 0.0, 0.0),
+    ];
+    // Counter-clockwise when seen from +Z
+    let indices = vec![0, 1, 2, 0, 2, 3];
+    (vertices, indices)
+}
+
+// The same surface with every triangle split into four at its edge
+// midpoints, so four times the triangles. Each vertex along an edge is the
+// average of the edge's ends, all attributes included, and shared between
+// the two triangles either side of it.
+pub fn subdivide(
+    vertices: &[Vertex],
+    indices: &[u32],
+) -> (Vec<Vertex>, Vec<u32>) {
+    let mut vertices = vertices.to_vec();
+    let mut midpoints = std::collections::HashMap::new();
+    let mut midpoint = |a: u32, b: u32| {
+        *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+            let (va, vb) = (vertices[a as usize], vertices[b as usize]);
+            let mix = |x: [f32; 3], y: [f32; 3]| {
+                [
+                    (x[0] + y[0]) * 0.5,
+                    (x[1] + y[1]) * 0.5,
+                    (x[2] + y[2]) * 0.5,
+                ]
+            };
+            vertices.push(Vertex {
+                position: mix(va.position, vb.position),
+                tex_coords: [
+                    (va.tex_coords[0] + vb.tex_coords[0]) * 0.5,
+                    (va.tex_coords[1] + vb.tex_coords[1]) * 0.5,
+                ],
+                normal: mix(va.normal, vb.normal),
+                tangent: mix(va.tangent, vb.tangent),
+                bitangent: mix(va.bitangent, vb.bitangent),
+                color: mix(va.color, vb.color),
+            });
+            vertices.len() as u32 - 1
+        })
+    };
+
+    let indices = indices
+        .chunks_exact(3)
+        .flat_map(|triangle| {
+            let [a, b, c] = [triangle[0], triangle[1], triangle[2]];
+            let (ab, bc, ca) = (midpoint(a, b), midpoint(b, c), midpoint(c, a));
+            // Same winding as the original
+            [a, ab, ca, ab, b, bc, ca, bc, c, ab, bc, ca]
+        })
+        .collect();
+    (vertices, indices)
+}
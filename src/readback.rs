@@ -0,0 +1,465 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+
+// Written from the map_async callback
+const MAP_PENDING: u8 = 0;
+const MAP_OK: u8 = 1;
+const MAP_FAILED: u8 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SlotState {
+    // Free to receive a new copy
+    Idle,
+    // A copy into the buffer has been recorded but not submitted yet
+    Copying,
+    // Submitted and map_async requested; `map_status` says when it's done
+    Mapping,
+}
+
+struct Slot<T> {
+    buffer: Option<wgpu::Buffer>,
+    state: SlotState,
+    map_status: Arc<AtomicU8>,
+    width: u32,
+    height: u32,
+    padded_bytes_per_row: u32,
+    tag: Option<T>,
+}
+
+pub struct ReadbackFrame<T> {
+    pub width: u32,
+    pub height: u32,
+    // Tightly packed rows, 4 bytes per pixel in the source texture's channel
+    // order
+    pub bytes: Vec<u8>,
+    pub tag: T,
+}
+
+// Blocks until the single pixel at (x, y) of a 4 byte per pixel texture is
+// back on the CPU, in the texture's channel order. None if mapping failed.
+pub fn read_pixel(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+    x: u32,
+    y: u32,
+) -> Option<[u8; 4]> {
+    // A one row copy only needs room for the pixel itself. bytes_per_row
+    // still has to be a multiple of 256 even though there's no second row
+    // for it to step to.
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Pixel Readback Buffer"),
+        size: 4,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+    let mut encoder =
+        device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Pixel Readback Encoder"),
+        });
+    encoder.copy_texture_to_buffer(
+        wgpu::TexelCopyTextureInfo {
+            texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d { x, y, z: 0 },
+            aspect: wgpu::TextureAspect::All,
+        },
+        wgpu::TexelCopyBufferInfo {
+            buffer: &buffer,
+            layout: wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT),
+                rows_per_image: Some(1),
+            },
+        },
+        wgpu::Extent3d {
+            width: 1,
+            height: 1,
+            depth_or_array_layers: 1,
+        },
+    );
+    queue.submit(std::iter::once(encoder.finish()));
+
+    if !map_and_wait(device, &buffer) {
+        return None;
+    }
+
+    let pixel = buffer.slice(..).get_mapped_range()[..4].try_into().ok();
+    buffer.unmap();
+    pixel
+}
+
+// Blocks until the first `count` `T`s of `buffer` are back on the CPU, for
+// checking what mesh building code actually uploaded. `buffer` needs
+// COPY_SRC usage. Panics if mapping fails, which only tests should be
+// calling this for.
+pub fn read_buffer<T: bytemuck::Pod>(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    buffer: &wgpu::Buffer,
+    count: usize,
+) -> Vec<T> {
+    let size = (count * std::mem::size_of::<T>()) as wgpu::BufferAddress;
+    // Copies have to be a multiple of COPY_BUFFER_ALIGNMENT (4 bytes), the
+    // extra bytes are dropped again below
+    let copy_size = size.next_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT);
+    let staging = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Buffer Readback Buffer"),
+        size: copy_size,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+    let mut encoder =
+        device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Buffer Readback Encoder"),
+        });
+    encoder.copy_buffer_to_buffer(buffer, 0, &staging, 0, copy_size);
+    queue.submit(std::iter::once(encoder.finish()));
+
+    assert!(
+        map_and_wait(device, &staging),
+        "Couldn't map the buffer readback buffer"
+    );
+
+    // Copied out element by element rather than cast in place, since the
+    // mapped range is only guaranteed to be 4 byte aligned
+    let items = bytemuck::pod_collect_to_vec(
+        &staging.slice(..).get_mapped_range()[..size as usize],
+    );
+    staging.unmap();
+    items
+}
+
+// Maps all of `buffer` for reading and blocks until it's done. False if
+// mapping failed.
+fn map_and_wait(device: &wgpu::Device, buffer: &wgpu::Buffer) -> bool {
+    let map_status = Arc::new(AtomicU8::new(MAP_PENDING));
+    let callback_status = map_status.clone();
+    buffer
+        .slice(..)
+        .map_async(wgpu::MapMode::Read, move |result| {
+            let status = if result.is_ok() { MAP_OK } else { MAP_FAILED };
+            callback_status.store(status, Ordering::Release);
+        });
+    device.poll(wgpu::Maintain::Wait);
+    map_status.load(Ordering::Acquire) == MAP_OK
+}
+
+// A ring of staging buffers for reading textures back to the CPU without
+// stalling. A copy is recorded into the next free buffer, mapped after the
+// frame is submitted and picked up by `poll` on a later frame once the GPU is
+// done with it, so several frames can be in flight at once. When every buffer
+// is still busy the capture is skipped rather than blocking.
+//
+// `T` is an arbitrary tag handed back with the frame, e.g. a frame number.
+pub struct ReadbackPool<T> {
+    slots: Vec<Slot<T>>,
+    next: usize,
+}
+
+impl<T> ReadbackPool<T> {
+    pub fn new(size: usize) -> Self {
+        let slots = (0..size.max(1))
+            .map(|_| Slot {
+                buffer: None,
+                state: SlotState::Idle,
+                map_status: Arc::new(AtomicU8::new(MAP_PENDING)),
+                width: 0,
+                height: 0,
+                padded_bytes_per_row: 0,
+                tag: None,
+            })
+            .collect();
+        Self { slots, next: 0 }
+    }
+
+    pub fn size(&self) -> usize {
+        self.slots.len()
+    }
+
+    // Records a copy of a 4 byte per pixel texture into the next free staging
+    // buffer. Returns false if every buffer is still in flight.
+    pub fn copy_texture(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        texture: &wgpu::Texture,
+        tag: T,
+    ) -> bool {
+        let n_slots = self.slots.len();
+        let Some(index) = (0..n_slots)
+            .map(|i| (self.next + i) % n_slots)
+            .find(|&i| self.slots[i].state == SlotState::Idle)
+        else {
+            log::debug!("Readback pool exhausted, skipping capture");
+            return false;
+        };
+        self.next = (index + 1) % n_slots;
+
+        let width = texture.width();
+        let height = texture.height();
+        // Rows in a buffer copy have to be aligned to 256 bytes
+        let padded_bytes_per_row =
+            (width * 4).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let size = (padded_bytes_per_row * height) as wgpu::BufferAddress;
+
+        let slot = &mut self.slots[index];
+        let buffer = match &slot.buffer {
+            Some(buffer) if buffer.size() >= size => buffer,
+            _ => slot.buffer.insert(device.create_buffer(
+                &wgpu::BufferDescriptor {
+                    label: Some("Readback Staging Buffer"),
+                    size,
+                    usage: wgpu::BufferUsages::COPY_DST
+                        | wgpu::BufferUsages::MAP_READ,
+                    mapped_at_creation: false,
+                },
+            )),
+        };
+
+        encoder.copy_texture_to_buffer(
+            wgpu::TexelCopyTextureInfo {
+                texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::TexelCopyBufferInfo {
+                buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        slot.state = SlotState::Copying;
+        slot.width = width;
+        slot.height = height;
+        slot.padded_bytes_per_row = padded_bytes_per_row;
+        slot.tag = Some(tag);
+
+        true
+    }
+
+    // Call after the encoder holding the copies has been submitted. Mapping
+    // before submission would fail validation since the buffer is still used
+    // by the pending copy.
+    pub fn after_submit(&mut self) {
+        for slot in &mut self.slots {
+            if slot.state != SlotState::Copying {
+                continue;
+            }
+            let Some(buffer) = &slot.buffer else {
+                continue;
+            };
+            let map_status = slot.map_status.clone();
+            map_status.store(MAP_PENDING, Ordering::Release);
+            buffer
+                .slice(..)
+                .map_async(wgpu::MapMode::Read, move |result| {
+                    let status = match result {
+                        Ok(()) => MAP_OK,
+                        Err(e) => {
+                            log::error!("Readback map failed: {:?}", e);
+                            MAP_FAILED
+                        }
+                    };
+                    map_status.store(status, Ordering::Release);
+                });
+            slot.state = SlotState::Mapping;
+        }
+    }
+
+    // Returns every frame whose buffer finished mapping since the last call
+    // without waiting on the GPU.
+    pub fn poll(&mut self, device: &wgpu::Device) -> Vec<ReadbackFrame<T>> {
+        device.poll(wgpu::Maintain::Poll);
+
+        let mut frames = vec![];
+        for slot in &mut self.slots {
+            if slot.state != SlotState::Mapping {
+                continue;
+            }
+            match slot.map_status.load(Ordering::Acquire) {
+                MAP_OK => {}
+                MAP_FAILED => {
+                    // Drop the capture but keep the slot usable
+                    slot.state = SlotState::Idle;
+                    slot.tag = None;
+                    continue;
+                }
+                _ => continue,
+            }
+            let Some(buffer) = &slot.buffer else {
+                continue;
+            };
+
+            let unpadded_bytes_per_row = (slot.width * 4) as usize;
+            let mut bytes = Vec::with_capacity(
+                unpadded_bytes_per_row * slot.height as usize,
+            );
+            {
+                let data = buffer.slice(..).get_mapped_range();
+                for row in data
+                    .chunks(slot.padded_bytes_per_row as usize)
+                    .take(slot.height as usize)
+                {
+                    bytes.extend_from_slice(&row[..unpadded_bytes_per_row]);
+                }
+            }
+            buffer.unmap();
+
+            slot.state = SlotState::Idle;
+            if let Some(tag) = slot.tag.take() {
+                frames.push(ReadbackFrame {
+                    width: slot.width,
+                    height: slot.height,
+                    bytes,
+                    tag,
+                });
+            }
+        }
+        frames
+    }
+}
+
+// Turns `raw`, tightly packed pixels of a 4 byte per pixel texture as they
+// came back from a copy, into RGBA8 that's sRGB encoded, which is what a
+// PNG holds.
+//
+// The bytes of an *Srgb texture are already encoded and only need their
+// channels put in order. A linear texture holds whatever the shaders wrote
+// with nothing encoding it on the way in, so saving its bytes as they are
+// makes everything look too dark; they're encoded here instead. Formats
+// this doesn't know are passed through unchanged, with a warning.
+pub fn convert_surface_bytes(
+    format: wgpu::TextureFormat,
+    width: u32,
+    height: u32,
+    raw: &[u8],
+) -> Vec<u8> {
+    let len = (width * height * 4) as usize;
+    debug_assert_eq!(raw.len(), len, "Expected {}x{} pixels", width, height);
+    let raw = &raw[..len.min(raw.len())];
+
+    let pixels = raw.chunks_exact(4);
+    match format {
+        wgpu::TextureFormat::Rgba8UnormSrgb => raw.to_vec(),
+        wgpu::TextureFormat::Bgra8UnormSrgb => pixels
+            .flat_map(|bgra| [bgra[2], bgra[1], bgra[0], bgra[3]])
+            .collect(),
+        wgpu::TextureFormat::Rgba8Unorm => pixels
+            .flat_map(|rgba| {
+                encode_srgb([rgba[0], rgba[1], rgba[2]].map(unorm8), rgba[3])
+            })
+            .collect(),
+        wgpu::TextureFormat::Bgra8Unorm => pixels
+            .flat_map(|bgra| {
+                encode_srgb([bgra[2], bgra[1], bgra[0]].map(unorm8), bgra[3])
+            })
+            .collect(),
+        // 10 bits each of red, green and blue from the low bits up, then
+        // 2 of alpha
+        wgpu::TextureFormat::Rgb10a2Unorm => pixels
+            .flat_map(|packed| {
+                let bits = u32::from_le_bytes(packed.try_into().unwrap());
+                let channel =
+                    |shift: u32| ((bits >> shift) & 0x3ff) as f32 / 1023.0;
+                let alpha = ((bits >> 30) * 255 / 3) as u8;
+                encode_srgb([channel(0), channel(10), channel(20)], alpha)
+            })
+            .collect(),
+        _ => {
+            log::warn!("No conversion for {:?}, saving it as is", format);
+            raw.to_vec()
+        }
+    }
+}
+
+fn unorm8(value: u8) -> f32 {
+    value as f32 / 255.0
+}
+
+// The sRGB transfer function, the same curve an *Srgb format applies when
+// it's written to. Alpha is never encoded.
+fn encode_srgb(linear: [f32; 3], alpha: u8) -> [u8; 4] {
+    let [r, g, b] = linear.map(|c| {
+        let c = c.clamp(0.0, 1.0);
+        let encoded = if c <= 0.0031308 {
+            c * 12.92
+        } else {
+            1.055 * c.powf(1.0 / 2.4) - 0.055
+        };
+        (encoded * 255.0).round() as u8
+    });
+    [r, g, b, alpha]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Mid grey: 0.5 encoded is 188, and 188 decoded is about 0.5 (128)
+    const LINEAR_HALF: u8 = 128;
+    const SRGB_HALF: u8 = 188;
+
+    #[test]
+    fn srgb_formats_only_reorder_channels() {
+        let raw = [10, 20, 30, 40, 50, 60, 70, 80];
+        assert_eq!(
+            convert_surface_bytes(
+                wgpu::TextureFormat::Rgba8UnormSrgb,
+                2,
+                1,
+                &raw
+            ),
+            raw
+        );
+        assert_eq!(
+            convert_surface_bytes(
+                wgpu::TextureFormat::Bgra8UnormSrgb,
+                1,
+                2,
+                &raw
+            ),
+            [30, 20, 10, 40, 70, 60, 50, 80]
+        );
+    }
+
+    #[test]
+    fn linear_formats_are_encoded() {
+        let rgba = [LINEAR_HALF, 0, 255, 77];
+        let expected = [SRGB_HALF, 0, 255, 77];
+        assert_eq!(
+            convert_surface_bytes(wgpu::TextureFormat::Rgba8Unorm, 1, 1, &rgba),
+            expected
+        );
+        let bgra = [255, 0, LINEAR_HALF, 77];
+        assert_eq!(
+            convert_surface_bytes(wgpu::TextureFormat::Bgra8Unorm, 1, 1, &bgra),
+            expected
+        );
+    }
+
+    #[test]
+    fn packed_10_bit_channels_are_unpacked_and_encoded() {
+        // Red at full, green at half, blue at zero, alpha at full
+        let bits: u32 = 1023 | (512 << 10) | (3 << 30);
+        assert_eq!(
+            convert_surface_bytes(
+                wgpu::TextureFormat::Rgb10a2Unorm,
+                1,
+                1,
+                &bits.to_le_bytes()
+            ),
+            [255, SRGB_HALF, 0, 255]
+        );
+    }
+}
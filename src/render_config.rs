@@ -0,0 +1,44 @@
+use std::path::Path;
+
+use anyhow::*;
+use serde::{Deserialize, Serialize};
+
+use crate::camera_bookmarks::CameraBookmark;
+
+// A snapshot of the settings that decide what a frame looks like, for
+// attaching to bug reports: F6 saves one, and `--load-state path` starts
+// from it on another machine. See State::snapshot and State::apply_config.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RenderConfig {
+    // Only takes effect at startup, the surface is configured with it once
+    pub present_mode: wgpu::PresentMode,
+    // Whatever the surface preferred. Recorded to compare against, it can't
+    // be chosen.
+    pub surface_format: wgpu::TextureFormat,
+    pub clear_color: wgpu::Color,
+    // By name, since which index a pipeline gets depends on the adapter's
+    // features, see Renderer::render_pipeline_names
+    pub pipeline: String,
+    pub sample_count: u32,
+    // Snapshots from before FXAA existed don't have it
+    #[serde(default)]
+    pub fxaa: bool,
+    // Where the orbit camera was, or where the fly camera was looking from
+    pub camera: CameraBookmark,
+    pub ortho_camera: bool,
+}
+
+impl RenderConfig {
+    pub fn load(path: &Path) -> Result<Self> {
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("Reading {}", path.display()))?;
+        serde_json::from_str(&json)
+            .with_context(|| format!("Parsing {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json)
+            .with_context(|| format!("Writing {}", path.display()))
+    }
+}
@@ -0,0 +1,98 @@
+// A texture (or other attachment) that passes hand to each other. Only the
+// name matters; the graph uses it to work out which pass has to run before
+// which, while the resources themselves stay wherever they already live.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Resource(pub &'static str);
+
+// One node of a `RenderGraph`. `C` is whatever per-frame state the passes
+// need to record their commands (views, bind groups, toggles, ...).
+pub trait Pass<C> {
+    fn name(&self) -> &str;
+
+    // Resources this pass reads. Anything no pass in the graph writes is
+    // assumed to already be available.
+    fn inputs(&self) -> &[Resource] {
+        &[]
+    }
+
+    fn outputs(&self) -> &[Resource];
+
+    fn record(&self, encoder: &mut wgpu::CommandEncoder, ctx: &C);
+}
+
+// Passes registered in any order and recorded in dependency order: a pass
+// runs after every pass that writes one of its inputs. Passes that write the
+// same resource keep their registration order, so e.g. a pass that loads a
+// target and draws on top goes after the one that cleared it. Independent
+// passes also keep registration order.
+//
+// The graph only borrows its passes and is cheap to build, so it can be put
+// together fresh each frame from whichever passes are switched on.
+pub struct RenderGraph<'p, C> {
+    passes: Vec<&'p dyn Pass<C>>,
+}
+
+impl<'p, C> RenderGraph<'p, C> {
+    pub fn new() -> Self {
+        Self { passes: vec![] }
+    }
+
+    pub fn add_pass(&mut self, pass: &'p dyn Pass<C>) {
+        self.passes.push(pass);
+    }
+
+    // Panics if the passes' inputs and outputs form a cycle, which is a bug
+    // in how the graph was put together rather than something to recover
+    // from
+    pub fn execute(&self, encoder: &mut wgpu::CommandEncoder, ctx: &C) {
+        for index in self.order() {
+            self.passes[index].record(encoder, ctx);
+        }
+    }
+
+    // Whether pass `before` has to be recorded before pass `after`
+    fn depends(&self, before: usize, after: usize) -> bool {
+        let (a, b) = (self.passes[before], self.passes[after]);
+        let feeds = a.outputs().iter().any(|r| b.inputs().contains(r));
+        let earlier_writer = before < after
+            && a.outputs().iter().any(|r| b.outputs().contains(r));
+        feeds || earlier_writer
+    }
+
+    // Kahn's algorithm, always taking the earliest registered pass that's
+    // ready so independent passes don't get reshuffled
+    fn order(&self) -> Vec<usize> {
+        let n = self.passes.len();
+        let mut n_blockers: Vec<usize> = (0..n)
+            .map(|after| {
+                (0..n)
+                    .filter(|&before| {
+                        before != after && self.depends(before, after)
+                    })
+                    .count()
+            })
+            .collect();
+        let mut done = vec![false; n];
+        let mut order = Vec::with_capacity(n);
+
+        while order.len() < n {
+            let Some(next) = (0..n).find(|&i| !done[i] && n_blockers[i] == 0)
+            else {
+                let stuck: Vec<&str> = (0..n)
+                    .filter(|&i| !done[i])
+                    .map(|i| self.passes[i].name())
+                    .collect();
+                panic!("Render graph has a cycle between {:?}", stuck);
+            };
+            done[next] = true;
+            order.push(next);
+            for after in 0..n {
+                if !done[after] && after != next && self.depends(next, after) {
+                    n_blockers[after] -= 1;
+                }
+            }
+        }
+
+        order
+    }
+}
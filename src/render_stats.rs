@@ -0,0 +1,44 @@
+// What the last frame's scene passes (shadow, depth prepass, scene and
+// mirror) asked the GPU to draw. Reset at the start of each frame and added
+// to as draws are recorded, which costs a couple of integer adds per draw.
+// The 2D overlay and post effects aren't counted.
+//
+// Triangles come from index counts times instance counts, so a triangle
+// limit is taken into account. The instance field's GPU culling happens on
+// the GPU after this is counted, so its draw always counts whole.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RenderStats {
+    pub draw_calls: u32,
+    // Everything the passes were asked to draw, including entities frustum
+    // culling then skipped
+    pub triangles_submitted: u64,
+    // Just what was actually sent to the GPU
+    pub triangles_after_culling: u64,
+    // How often the scene pass's entities changed pipeline or material from
+    // one draw to the next. Entities are sorted so that's once per group
+    // rather than once per entity.
+    pub pipeline_switches: u32,
+    pub material_switches: u32,
+}
+
+impl RenderStats {
+    // One draw call of `triangles` triangles
+    pub fn draw(&mut self, triangles: u64) {
+        self.draw_calls += 1;
+        self.triangles_submitted += triangles;
+        self.triangles_after_culling += triangles;
+    }
+
+    // `triangles` that would have been drawn if culling hadn't skipped them
+    pub fn cull(&mut self, triangles: u64) {
+        self.triangles_submitted += triangles;
+    }
+
+    pub fn switch_pipeline(&mut self) {
+        self.pipeline_switches += 1;
+    }
+
+    pub fn switch_material(&mut self) {
+        self.material_switches += 1;
+    }
+}
@@ -0,0 +1,5045 @@
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use cgmath::MetricSpace;
+use wgpu::util::DeviceExt;
+use winit::window::Window;
+
+use crate::geometry::{self, Vertex, INDICES, VERTICES};
+use crate::gpu::{self, Frame, Gpu, RenderTarget, StateError};
+use crate::pipeline::{self, depth_stencil_state};
+use crate::render_graph::{Pass, RenderGraph, Resource};
+use crate::sprite::{self, Rect};
+use crate::{
+    adaptive_resolution, anti_aliasing, asset_loader, assets, blur, camera,
+    canvas, debug_draw, depth_view, draw_constants, frame_resources, frustum,
+    gbuffer, gpu_timer, gradient, heightmap, indirect, instancing,
+    linear_output, lod, material, material_registry, mesh_file, mirror, model,
+    morph, objects, occlusion, particles, post, primitives, readback,
+    render_stats, scene, sdf_text, shader_validation, shadow, skybox, splat,
+    sprite_array, texture, tilemap, uniform, viewport, world_labels,
+};
+
+// Written by `cargo run --example write_pentagon_mesh`
+const PENTAGON_MESH_PATH: &str = "assets/pentagon.mesh";
+// Written by `cargo run --example write_heightmap`
+const HEIGHTMAP_PATH: &str = "assets/heightmap.png";
+// How wide the heightmap terrain is, how high its white parts stand above
+// its black ones, and where its black parts are
+const HEIGHTMAP_SCALE: f32 = 6.0;
+const HEIGHTMAP_HEIGHT: f32 = 1.2;
+const HEIGHTMAP_BASE: f32 = -1.5;
+
+const CAPTURE_DIR: &str = "captures";
+// How many frames of readback can be in flight before captures get skipped
+const READBACK_POOL_SIZE: usize = 3;
+
+// Centers and tints of the translucent quads hovering in front of the
+// pentagon. Spread out in depth so the draw order visibly matters.
+const TRANSLUCENT_QUADS: &[([f32; 3], [f32; 3])] = &[
+    ([-0.25, 0.1, 0.3], [1.0, 0.3, 0.3]),
+    ([0.0, -0.1, 0.5], [0.3, 1.0, 0.3]),
+    ([0.25, 0.1, 0.7], [0.3, 0.4, 1.0]),
+];
+const TRANSLUCENT_QUAD_SIZE: f32 = 0.5;
+
+// Entities a scene can hold, i.e. slots in the object uniform buffer
+const MAX_ENTITIES: usize = 256;
+
+// Where the separately drawn pentagons sit, how fast each spins in radians
+// per second, and the tint each is drawn with (see draw_constants)
+const OBJECTS: &[([f32; 3], f32, [f32; 4])] = &[
+    ([-1.2, 0.0, -0.5], 0.5, [1.0, 0.7, 0.7, 1.0]),
+    ([0.0, 0.0, 0.0], -0.8, [1.0, 1.0, 1.0, 1.0]),
+    ([1.2, 0.0, -0.5], 1.1, [0.7, 0.8, 1.0, 1.0]),
+];
+
+// How far from the camera the separate objects switch from the pentagon
+// split into 48 triangles to 12, and from 12 to its plain 3. Wireframe (L)
+// shows the switch.
+const PENTAGON_LOD_DISTANCES: [f32; 2] = [2.5, 4.0];
+
+// The way the shadow casting light shines. Angled across the separate
+// objects so the middle one's shadow falls on the one to its right.
+const LIGHT_DIRECTION: [f32; 3] = [1.0, -0.3, -0.5];
+
+// Fixed canvas aspects that C cycles through (after which the scene goes back
+// to following the window)
+const CANVAS_ASPECTS: &[(&str, f32)] =
+    &[("16:9", 16.0 / 9.0), ("1:1", 1.0), ("9:16", 9.0 / 16.0)];
+
+#[derive(Debug, Clone, Copy)]
+enum Capture {
+    Screenshot,
+    // Frame number within a continuous capture
+    Sequence(u32),
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct GlobalsUniform {
+    // Seconds since startup
+    time: f32,
+    // Nonzero to draw normal map normals as colors instead of shading
+    show_normals: u32,
+    // How much of the detail texture is blended over the diffuse one, 0..1
+    detail_mix: f32,
+    // Nonzero to darken fragments the shadow map says are in shadow
+    shadows: u32,
+    // Nonzero to add each material's specular highlight
+    specular: u32,
+    // Smoothed microphone loudness, 0..1, see `Renderer::audio_amplitude`
+    audio_amplitude: f32,
+    // clip_plane is a vec4, so it starts on a 16 byte boundary
+    _padding: [u32; 2],
+    // xyz is the normal and w the distance along it from the plane to the
+    // origin. Fragments where dot(position, xyz) + w is negative are
+    // discarded.
+    clip_plane: [f32; 4],
+}
+
+// The uniforms rewritten every frame, one set per frame in flight (see
+// frame_resources)
+struct FrameUniforms {
+    // @group(1) of the scene and prepass
+    camera: uniform::Uniform<camera::CameraUniform>,
+    globals_buffer: wgpu::Buffer,
+    // @group(2): `globals_buffer` along with the light, shadow map and
+    // skybox cubemap, which every set shares
+    globals_bind_group: wgpu::BindGroup,
+}
+
+impl FrameUniforms {
+    fn new(
+        device: &wgpu::Device,
+        camera_layout: &wgpu::BindGroupLayout,
+        globals_layout: &wgpu::BindGroupLayout,
+        shadow_map: &shadow::ShadowMap,
+        skybox: &skybox::Skybox,
+        camera_uniform: &camera::CameraUniform,
+    ) -> Self {
+        let camera = uniform::Uniform::with_layout(
+            device,
+            "Camera",
+            camera_layout,
+            camera_uniform,
+        );
+
+        let globals_buffer =
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Globals Buffer"),
+                contents: bytemuck::cast_slice(&[GlobalsUniform {
+                    time: 0.0,
+                    show_normals: 0,
+                    detail_mix: 0.0,
+                    shadows: 0,
+                    specular: 0,
+                    audio_amplitude: 0.0,
+                    _padding: [0; 2],
+                    clip_plane: NO_CLIP_PLANE,
+                }]),
+                usage: wgpu::BufferUsages::UNIFORM
+                    | wgpu::BufferUsages::COPY_DST,
+            });
+
+        let globals_bind_group =
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: globals_layout,
+                entries: &[
+                    // @group(2) @binding(0)
+                    // var<uniform> globals: GlobalsUniform;
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: globals_buffer.as_entire_binding(),
+                    },
+                    // @group(2) @binding(1)
+                    // var<uniform> light: LightUniform;
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: shadow_map.light_buffer().as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::TextureView(
+                            shadow_map.view(),
+                        ),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: wgpu::BindingResource::Sampler(
+                            shadow_map.sampler(),
+                        ),
+                    },
+                    // @group(2) @binding(4)
+                    // var t_environment: texture_cube<f32>;
+                    wgpu::BindGroupEntry {
+                        binding: 4,
+                        resource: wgpu::BindingResource::TextureView(
+                            &skybox.cubemap().view,
+                        ),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 5,
+                        resource: wgpu::BindingResource::Sampler(
+                            &skybox.cubemap().sampler,
+                        ),
+                    },
+                ],
+                label: Some("Globals Bind Group"),
+            });
+
+        Self {
+            camera,
+            globals_buffer,
+            globals_bind_group,
+        }
+    }
+}
+
+// The scene shader, loose (see assets) or built in. F5 reloads the loose
+// copy.
+const SCENE_SHADER_NAME: &str = "shader.wgsl";
+const SCENE_SHADER: &str = include_str!("shader.wgsl");
+
+// Anything above 1 enables MSAA. Lowered at startup if the adapter doesn't
+// support it for the surface format.
+const DESIRED_SAMPLE_COUNT: u32 = 4;
+
+// Adaptive resolution keeps the scene's internal render scale between these
+// while trying to hold the frame time under the target
+const TARGET_FRAME_MS: f32 = 1000.0 / 60.0;
+const MIN_RENDER_SCALE: f32 = 0.5;
+const MAX_RENDER_SCALE: f32 = 1.0;
+
+// Fixed render scales that Shift+R cycles through, for speed or a chunky
+// pixel look. Adaptive resolution scales further down from whichever is
+// picked.
+const RENDER_SCALES: &[f32] = &[1.0, 0.5, 0.25, 0.125];
+// The smallest fixed render scale accepted, below which there's hardly
+// anything left of the scene
+const MIN_FIXED_RENDER_SCALE: f32 = 0.05;
+
+// How many copies of the texture fit across the pentagon with its UVs
+// tiled, see `Renderer::toggle_uv_tiling`. Odd, so one sits in the middle.
+const UV_TILING: f32 = 3.0;
+
+// Which way the user clip plane faces. Fragments behind it are discarded,
+// see `Renderer::toggle_clip_plane`.
+const CLIP_PLANE_NORMAL: [f32; 3] = [0.8, 0.6, 0.0];
+// How far from the origin the plane can be moved either way
+const CLIP_PLANE_RANGE: f32 = 2.0;
+// A plane nothing is behind, for when it's off
+const NO_CLIP_PLANE: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+
+// The frame latencies F7 cycles through, see gpu::DEFAULT_FRAME_LATENCY
+const FRAME_LATENCIES: std::ops::RangeInclusive<u32> = 1..=3;
+
+// Single effects that G cycles through, with everything else in the post
+// chain switched off
+const POST_PRESETS: &[Option<&str>] = &[
+    None,
+    Some("Grayscale"),
+    Some("Invert"),
+    Some("Gamma"),
+    Some("Blur"),
+];
+
+// Frames per logged average of the main pass's GPU time
+const GPU_TIMER_LOG_INTERVAL: u32 = 120;
+
+const N_PARTICLES: u32 = 4096;
+
+// shader.wgsl and shader2.wgsl, the pipelines turning wireframe, conservative
+// rasterization or points off goes back to
+const N_FILL_PIPELINES: usize = 2;
+
+// Group 0 of the pentagon pipelines: the diffuse texture, normal map and
+// detail texture, and the material's specular parameters
+fn create_texture_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    diffuse: &texture::Texture,
+    normal: &texture::Texture,
+    detail: &texture::Texture,
+    material: &material::Material,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout,
+        entries: &[
+            // @group(0) @binding(0)
+            // var t_diffuse: texture_2d<f32>;
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&diffuse.view),
+            },
+            // @group(0) @binding(1)
+            // var s_diffuse: sampler;
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(&diffuse.sampler),
+            },
+            // @group(0) @binding(2)
+            // var t_normal: texture_2d<f32>;
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::TextureView(&normal.view),
+            },
+            // @group(0) @binding(3)
+            // var s_normal: sampler;
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: wgpu::BindingResource::Sampler(&normal.sampler),
+            },
+            // @group(0) @binding(4)
+            // var t_detail: texture_2d<f32>;
+            wgpu::BindGroupEntry {
+                binding: 4,
+                resource: wgpu::BindingResource::TextureView(&detail.view),
+            },
+            // @group(0) @binding(5)
+            // var s_detail: sampler;
+            wgpu::BindGroupEntry {
+                binding: 5,
+                resource: wgpu::BindingResource::Sampler(&detail.sampler),
+            },
+            // @group(0) @binding(6)
+            // var<uniform> material: MaterialUniform;
+            wgpu::BindGroupEntry {
+                binding: 6,
+                resource: material.buffer().as_entire_binding(),
+            },
+        ],
+        label: Some("diffuse_bind_group"),
+    })
+}
+
+// An object shader's source with `draw_tint()` defined (see draw_constants),
+// from the loose copy of `name` if there is one (see assets). One that
+// doesn't validate is logged and `embedded` used instead, rather than
+// failing to build its pipelines.
+fn object_shader_source(
+    name: &str,
+    embedded: &'static str,
+    push_constants: bool,
+) -> String {
+    let source = draw_constants::shader_source(
+        &assets::load_str(name, embedded),
+        push_constants,
+    );
+    match shader_validation::validate_wgsl(&source) {
+        Ok(()) => source,
+        Err(e) => {
+            log::error!(
+                "Using the built-in {}, the loose one is invalid:\n{}",
+                name,
+                e
+            );
+            draw_constants::shader_source(embedded, push_constants)
+        }
+    }
+}
+
+// Adds a glTF model's meshes to `meshes` and its materials to `materials`
+// (one per material in the file, then glTF's default material for
+// primitives without one), and returns a scene placing the meshes. The
+// specular parameters of each material come back too, to adjust later.
+#[allow(clippy::too_many_arguments)]
+fn load_model(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    layout: &wgpu::BindGroupLayout,
+    detail: &texture::Texture,
+    anisotropy: u16,
+    path: &Path,
+    meshes: &mut Vec<geometry::Mesh>,
+    materials: &mut material_registry::MaterialRegistry,
+) -> anyhow::Result<(scene::Scene, Vec<material::Material>)> {
+    let model = model::load_gltf(path)?;
+
+    // Only base colors are loaded, so the normal map is flat
+    let solid = |[r, g, b]: [u8; 3], label, kind| {
+        texture::Texture::from_rgba(
+            device,
+            queue,
+            &image::RgbaImage::from_pixel(1, 1, image::Rgba([r, g, b, 255])),
+            Some(label),
+            kind,
+        )
+    };
+    let flat_normal = solid(
+        [128, 128, 255],
+        "Flat Normal Texture",
+        texture::TextureKind::LinearData,
+    );
+    let white = solid(
+        [255, 255, 255],
+        "White Texture",
+        texture::TextureKind::Color,
+    );
+
+    // glTF's metallic-roughness isn't loaded either, so every material
+    // starts out with the same highlight
+    let specular = || {
+        material::Material::new(
+            device,
+            material::DEFAULT_SPECULAR_COLOR,
+            material::DEFAULT_SHININESS,
+        )
+    };
+
+    // Every material is loaded before any is registered, so a failure
+    // part way through leaves the registry as it was
+    let mut bind_groups = Vec::with_capacity(model.materials.len() + 1);
+    let mut speculars = Vec::with_capacity(model.materials.len() + 1);
+    for material in &model.materials {
+        let diffuse = match &material.base_color_texture {
+            Some(rgba) => texture::Texture::from_rgba(
+                device,
+                queue,
+                rgba,
+                Some(&material.name),
+                texture::TextureKind::Color,
+            )
+            .with_sampler(
+                device,
+                wgpu::AddressMode::ClampToEdge,
+                anisotropy,
+            )?,
+            None => solid(
+                [255, 255, 255],
+                "White Texture",
+                texture::TextureKind::Color,
+            ),
+        };
+        let specular = specular();
+        bind_groups.push(create_texture_bind_group(
+            device,
+            layout,
+            &diffuse,
+            &flat_normal,
+            detail,
+            &specular,
+        ));
+        speculars.push(specular);
+    }
+    let specular = specular();
+    bind_groups.push(create_texture_bind_group(
+        device,
+        layout,
+        &white,
+        &flat_normal,
+        detail,
+        &specular,
+    ));
+    speculars.push(specular);
+    let mut ids: Vec<scene::MaterialId> = bind_groups
+        .into_iter()
+        .map(|bind_group| {
+            materials.add(material_registry::Material {
+                bind_group,
+                pipeline: None,
+            })
+        })
+        .collect();
+    let default_material = ids.pop().unwrap();
+
+    let first_mesh = meshes.len();
+    meshes.extend(model.meshes.iter().map(|mesh| {
+        geometry::Mesh::new(
+            device,
+            &mesh.name,
+            &mesh.data.vertices,
+            &mesh.data.indices,
+        )
+    }));
+
+    if model.instances.len() > MAX_ENTITIES {
+        log::warn!(
+            "{} places {} meshes, only drawing the first {}",
+            path.display(),
+            model.instances.len(),
+            MAX_ENTITIES
+        );
+    }
+    let mut scene = scene::Scene::new();
+    for instance in model.instances.iter().take(MAX_ENTITIES) {
+        let material = model.meshes[instance.mesh]
+            .material
+            .map_or(default_material, |index| ids[index]);
+        scene.add(scene::Entity {
+            transform: instance.transform,
+            material,
+            ..scene::Entity::new(scene::MeshId(first_mesh + instance.mesh))
+        });
+    }
+    log::info!(
+        "Loaded {}: {} meshes, {} materials",
+        path.display(),
+        model.meshes.len(),
+        model.materials.len()
+    );
+    Ok((scene, speculars))
+}
+
+// Everything whose size follows the scene's render target (the surface or
+// canvas, scaled by the render scale), plus the bind groups that point at
+// it. It's only ever rebuilt whole, so nothing can end up holding a view of
+// a texture from before a resize.
+//
+// Everything else survives a resize: pipelines, bind group layouts,
+// samplers, uniform buffers and their bind groups, meshes and textures
+// loaded from disk. The canvas manages its own window-sized output.
+struct SizedResources {
+    size: winit::dpi::PhysicalSize<u32>,
+    // Multisampled color target that resolves into the post chain's scene
+    // target. None when sample_count is 1.
+    msaa_view: Option<wgpu::TextureView>,
+    depth_texture: texture::Texture,
+    // The post chain's ping-pong targets and the bind groups reading them
+    post_targets: post::PostTargets,
+}
+
+impl SizedResources {
+    fn new(
+        device: &wgpu::Device,
+        target_configuration: &wgpu::SurfaceConfiguration,
+        sample_count: u32,
+        depth_format: wgpu::TextureFormat,
+        backend: wgpu::Backend,
+        post_chain: &post::PostChain,
+    ) -> Self {
+        let size = winit::dpi::PhysicalSize::new(
+            target_configuration.width,
+            target_configuration.height,
+        );
+        Self {
+            size,
+            msaa_view: create_multisampled_framebuffer(
+                device,
+                target_configuration,
+                sample_count,
+            ),
+            depth_texture: texture::Texture::create_depth_texture(
+                device,
+                target_configuration,
+                sample_count,
+                depth_format,
+                depth_can_be_sampled(backend, sample_count),
+                "Depth Texture",
+            ),
+            post_targets: post_chain.create_targets(device, size),
+        }
+    }
+}
+
+// wgpu's GL backend gives a multisampled texture that can also be sampled a
+// target that can't be multisampled, so its storage fails to allocate and
+// everything drawn with it comes out blank. On GL the multisampled depth
+// buffer is only ever a render attachment, and the depth view is off.
+fn depth_can_be_sampled(backend: wgpu::Backend, sample_count: u32) -> bool {
+    sample_count == 1 || backend != wgpu::Backend::Gl
+}
+
+// `size` times `scale`, at least a pixel each way
+fn scaled_size(
+    size: winit::dpi::PhysicalSize<u32>,
+    scale: f32,
+) -> winit::dpi::PhysicalSize<u32> {
+    winit::dpi::PhysicalSize::new(
+        ((size.width as f32 * scale).round() as u32).max(1),
+        ((size.height as f32 * scale).round() as u32).max(1),
+    )
+}
+
+fn create_multisampled_framebuffer(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+    sample_count: u32,
+) -> Option<wgpu::TextureView> {
+    if sample_count <= 1 {
+        return None;
+    }
+
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Multisampled Framebuffer"),
+        size: wgpu::Extent3d {
+            width: config.width,
+            height: config.height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count,
+        dimension: wgpu::TextureDimension::D2,
+        format: config.format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        view_formats: &[],
+    });
+
+    Some(texture.create_view(&wgpu::TextureViewDescriptor::default()))
+}
+
+// Choices made once when a renderer is created. The defaults are
+// what the app does without a config file.
+#[derive(Debug, Clone)]
+pub struct RendererOptions {
+    // None keeps the default dark gray
+    pub clear_color: Option<wgpu::Color>,
+    // None takes the first mode the surface supports
+    pub present_mode: Option<wgpu::PresentMode>,
+    // Lowered to what the adapter supports
+    pub sample_count: u32,
+    // None reads WGPU_BACKEND. Falls back to any backend, with a warning,
+    // when none of these have an adapter.
+    pub backends: Option<wgpu::Backends>,
+    // None reads WGPU_POWER_PREF
+    pub power_preference: Option<wgpu::PowerPreference>,
+    // Ask for a software adapter instead of a GPU. Off still lets
+    // WGPU_FORCE_FALLBACK_ADAPTER turn it on.
+    pub force_fallback_adapter: bool,
+    // None uses the texture built into the binary. May be a .ktx2 file, see
+    // `Config::diffuse_texture`.
+    pub diffuse_texture: Option<PathBuf>,
+    // Gives the depth buffer a stencil aspect. Without it the stencil
+    // reveal demo isn't available.
+    pub stencil: bool,
+    // Anisotropic filtering for the pentagon's textures, 1 for none. See
+    // `Texture::with_sampler`.
+    pub anisotropy: u16,
+    // A glTF model shown in place of the pentagon, see `Config::model`
+    pub model: Option<PathBuf>,
+    // The tile map's atlas and how to cut it up. None uses a generated one.
+    pub tile_atlas: Option<(PathBuf, tilemap::AtlasLayout)>,
+    // The scene's internal resolution as a fraction of the output's, see
+    // `Renderer::set_render_scale`
+    pub render_scale: f32,
+    // How the scene is scaled up to the output when `render_scale` is below
+    // 1
+    pub upscale_filter: wgpu::FilterMode,
+    // Top and bottom colors of a gradient shown in place of the clear color
+    // (and the skybox) from the start. None starts with the skybox and
+    // leaves the gradient at its default colors.
+    pub background_gradient: Option<(wgpu::Color, wgpu::Color)>,
+    // Clamps the shadow map's depth rather than clipping it, if the adapter
+    // has DEPTH_CLIP_CONTROL
+    pub unclipped_depth: bool,
+}
+
+impl Default for RendererOptions {
+    fn default() -> Self {
+        Self {
+            clear_color: None,
+            present_mode: None,
+            sample_count: DESIRED_SAMPLE_COUNT,
+            backends: None,
+            power_preference: None,
+            force_fallback_adapter: false,
+            diffuse_texture: None,
+            stencil: true,
+            anisotropy: 1,
+            model: None,
+            tile_atlas: None,
+            render_scale: 1.0,
+            upscale_filter: wgpu::FilterMode::Linear,
+            unclipped_depth: false,
+            background_gradient: None,
+        }
+    }
+}
+
+// What `Renderer::render_with` hands its overlay: the frame being drawn and
+// the means to draw on it
+pub struct Overlay<'a> {
+    pub device: &'a wgpu::Device,
+    pub queue: &'a wgpu::Queue,
+    pub encoder: &'a mut wgpu::CommandEncoder,
+    pub view: &'a wgpu::TextureView,
+    pub size: winit::dpi::PhysicalSize<u32>,
+}
+
+// Everything needed to draw the scene into a window or an offscreen
+// texture. Input handling lives with whatever owns it.
+pub struct Renderer<'a> {
+    target: RenderTarget<'a>,
+    // Kept so more windows can be opened on the same device
+    instance: wgpu::Instance,
+    adapter: wgpu::Adapter,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    // See `Gpu::device_lost`
+    device_lost: Arc<AtomicBool>,
+    // For offscreen targets this only describes the target texture
+    surface_configuration: wgpu::SurfaceConfiguration,
+    size: winit::dpi::PhysicalSize<u32>,
+    // Set by a zero size resize. The surface keeps its last real size but
+    // can't hand out textures until the window is restored, so rendering is
+    // skipped until then.
+    is_minimized: bool,
+    pub clear_color: wgpu::Color,
+    // Everything drawing the scene, rebuilt when the sample count changes
+    pipelines: ScenePipelines,
+    active_render_pipeline_index: usize,
+    // The pipeline to go back to when wireframe or conservative
+    // rasterization is switched off
+    fill_pipeline_index: usize,
+    // Index into pipeline::CULL_MODES, see `cycle_cull_mode`
+    cull_mode_index: usize,
+    // Index into pipeline::FRONT_FACES, see `toggle_front_face`
+    front_face_index: usize,
+    // What the scene pipelines were built from, kept so more copies can be
+    // made, see `create_scene_pipeline` and `set_sample_count`
+    scene_shader: wgpu::ShaderModule,
+    // Whether draw constants go through push constants, and so whether
+    // the object pipelines' layouts have a push constant range
+    push_constants: bool,
+    scene_bind_group_layouts: Vec<wgpu::BindGroupLayout>,
+    // Looked up by scene::MeshId
+    meshes: Vec<geometry::Mesh>,
+    pentagon: scene::MeshId,
+    // The pentagon again with its positions, UVs and the rest of each
+    // vertex in separate buffers, for the split streams pipeline
+    pentagon_streams: geometry::Mesh,
+    // Stands in for the pentagon while `show_morph` is on, its vertices
+    // rewritten every frame by `morph`
+    morph_mesh: scene::MeshId,
+    morph: morph::Morph,
+    pub show_morph: bool,
+    // Looked up by scene::LodId
+    lods: Vec<lod::Lod>,
+    // Just the pentagon at the origin
+    scene: scene::Scene,
+    // The pentagon once per entry in `OBJECTS`, shown instead of `scene`
+    // while `show_objects` is set
+    objects_scene: scene::Scene,
+    // The configured glTF model, shown instead of either of the others
+    // while `show_model` is set. Empty without one.
+    model_scene: scene::Scene,
+    pub show_model: bool,
+    // A terrain built from HEIGHTMAP_PATH, shown instead of any of the
+    // others while `show_heightmap` is set. Empty if it couldn't be read.
+    heightmap_scene: scene::Scene,
+    pub show_heightmap: bool,
+    // Looked up by scene::MaterialId. The default material is the
+    // pentagon's textures.
+    materials: material_registry::MaterialRegistry,
+    // The specular parameters bound in the model's materials
+    model_speculars: Vec<material::Material>,
+    // Bound in the default material
+    pentagon_material: material::Material,
+    // How the pentagon's textures are sampled outside 0..1, one of
+    // texture::ADDRESS_MODES
+    address_mode: wgpu::AddressMode,
+    // Scales the pentagon's UVs up by UV_TILING, so what `address_mode`
+    // does shows around the middle copy of the texture
+    tile_uvs: bool,
+    // The pentagon textures' `Texture::with_sampler` anisotropy, kept for
+    // changing `address_mode`
+    anisotropy: u16,
+    // Adds each material's Blinn-Phong highlight on top of the ambient and
+    // diffuse light. Off to compare against the scene without it.
+    pub show_specular: bool,
+    // Discards everything behind the plane `clip_plane_offset` along
+    // CLIP_PLANE_NORMAL, slicing the scene open
+    pub show_clip_plane: bool,
+    clip_plane_offset: f32,
+    // Kept to rebuild the default material's bind group once a streamed
+    // texture arrives
+    texture_bind_group_layout: wgpu::BindGroupLayout,
+    builtin_diffuse_texture: texture::Texture,
+    normal_texture: texture::Texture,
+    detail_texture: texture::Texture,
+    asset_loader: asset_loader::AssetLoader,
+    // The configured diffuse texture, streaming in or streamed. None when
+    // there isn't one or it failed to load.
+    diffuse_texture_handle: Option<asset_loader::TextureHandle>,
+    // Only present when an SDF font atlas could be loaded
+    text: Option<sdf_text::SdfTextRenderer>,
+    // Names floating over the pentagon and translucent quads. Also needs the
+    // SDF font.
+    labels: Option<world_labels::WorldLabels>,
+    pub show_labels: bool,
+    // Let geometry in front of a label hide it
+    pub occlude_labels: bool,
+    pub camera: camera::Camera,
+    // Flat 2D view of the XY plane, used instead of `camera` when
+    // `use_ortho_camera` is set
+    pub ortho_camera: camera::OrthoCamera,
+    pub use_ortho_camera: bool,
+    camera_uniform: camera::CameraUniform,
+    // The camera and globals uniforms, see FrameUniforms
+    frames: frame_resources::FrameResources<FrameUniforms>,
+    // Transforms of the active scene's entities
+    objects: objects::ObjectUniforms,
+    // Draw arguments of the active scene's entities, one slot each like
+    // `objects`
+    indirect_draws: indirect::IndirectDraws,
+    // Draws only the first this many triangles of each entity, by changing
+    // the index counts in `indirect_draws`. None draws them all.
+    pub triangle_limit: Option<u32>,
+    // Indices of the active scene's entities that made it through frustum
+    // culling this frame
+    visible_entities: Vec<usize>,
+    entity_stats: scene::CullStats,
+    render_stats: render_stats::RenderStats,
+    // Draw `objects_scene` in place of the single pentagon
+    pub show_objects: bool,
+    pub light: shadow::DirectionalLight,
+    shadow_map: shadow::ShadowMap,
+    // Only the scene's entities cast and receive shadows, the instance
+    // field and terrain don't
+    pub show_shadows: bool,
+    pub instance_field: instancing::InstanceField,
+    // Draw the instance field in place of the single pentagon
+    pub show_instances: bool,
+    last_frame: Instant,
+    // Exponential moving average so the HUD number is readable
+    pub frame_time_ms: f32,
+    sample_count: u32,
+    // What `sample_count` can be switched to, in increasing order. Always
+    // has 1.
+    sample_counts: Vec<u32>,
+    // Render targets that follow the scene's size, see `resize`
+    sized: SizedResources,
+    skybox: skybox::Skybox,
+    // Falls back to the gradient, or the (cursor driven) clear color, when
+    // off
+    pub show_skybox: bool,
+    gradient: gradient::BackgroundGradient,
+    // Drawn behind everything in place of the clear color
+    pub show_gradient: bool,
+    // Fills each viewport with the clear color, so with viewports the pass
+    // can clear to `bar_color` and have that only show around them
+    viewport_fill: gradient::BackgroundGradient,
+    // Whatever of the target no viewport covers, e.g. the bars around an
+    // aspect locked view
+    pub bar_color: wgpu::Color,
+    terrain: splat::SplatTerrain,
+    pub show_terrain: bool,
+    // Ground grid and axes at the origin
+    debug_draw: debug_draw::DebugDraw,
+    pub show_grid: bool,
+    // Texture::DEPTH_STENCIL_FORMAT unless the stencil was turned off
+    depth_format: wgpu::TextureFormat,
+    pub show_stencil_reveal: bool,
+    // Lay down the instance field's depth in a separate depth-only pass
+    // before shading it
+    pub depth_prepass: bool,
+    // Let attachments that nothing reads after their pass be discarded
+    // instead of stored, see `transient_store`
+    pub discard_transient: bool,
+    // Seconds of animation the frame being drawn shows, somewhere between
+    // the last two steps' `simulated_time`s (see `prepare_frame`)
+    time: f32,
+    // Seconds of animation so far. Moves on by whatever `step` is given, so
+    // it stands still while paused.
+    simulated_time: f32,
+    // `simulated_time` before the last step
+    previous_simulated_time: f32,
+    pub show_normals: bool,
+    // Replace the finished scene with its depth buffer, see depth_view.rs
+    pub show_depth: bool,
+    depth_view: depth_view::DepthView,
+    // How much of the detail texture shader.wgsl blends over the diffuse
+    // one, from 0 (none) to 1 (only detail). shader2.wgsl ignores it.
+    pub detail_mix: f32,
+    // How loud the microphone is, 0..1, which shader.wgsl brightens the
+    // scene by. Set every update with the audio feature, 0 otherwise.
+    pub audio_amplitude: f32,
+    translucent_quads: Vec<TranslucentQuad>,
+    pub show_translucent: bool,
+    // False when the surface can't be used as a copy source. Always true for
+    // offscreen targets.
+    surface_can_capture: bool,
+    readback_pool: readback::ReadbackPool<Capture>,
+    screenshot_requested: bool,
+    // Next frame number while a continuous capture is running
+    capture_sequence: Option<u32>,
+    // When set the scene renders at a fixed aspect into an offscreen canvas
+    // that's fitted into the window, and captures come from the canvas
+    canvas: Option<canvas::Canvas>,
+    // Only there when the surface can be viewed as linear
+    linear_output: Option<linear_output::LinearOutput>,
+    // Present through the linear view, encoding to sRGB in a shader instead
+    // of on write
+    pub linear_view: bool,
+    // Index into CANVAS_ASPECTS while the canvas is on
+    canvas_aspect_index: usize,
+    // Kept across turning the canvas off and on
+    canvas_fit: canvas::FitMode,
+    // Every frame goes through this, even with no effects enabled
+    pub post_chain: post::PostChain,
+    pub show_post_hud: bool,
+    // Index into POST_PRESETS
+    post_preset_index: usize,
+    // The blur effect's, None when it couldn't be made
+    blur_radius: Option<Arc<AtomicU32>>,
+    // The blur effect's override constants, see `set_blur_constants`
+    blur_constants: Option<Arc<Mutex<HashMap<String, f64>>>>,
+    // Times the main scene pass, from the start of the depth prepass when
+    // that runs. None without timestamp query support.
+    gpu_timer: Option<gpu_timer::GpuTimer>,
+    // None if occlusion queries can't be made
+    occlusion: Option<occlusion::OcclusionQueries>,
+    // Wrap every entity's draw in an occlusion query, see
+    // `entity_sample_counts`
+    pub count_occlusion: bool,
+    // Sprayed from the cursor and drawn over the finished frame
+    particles: particles::ParticleSystem,
+    pub show_particles: bool,
+    // 2D overlay drawn after the post chain, under the HUD text
+    sprite_batch: sprite::SpriteBatch,
+    // The pentagon's diffuse and normal textures, for the demo sprites
+    sprite_textures: [sprite::SpriteTexture; 2],
+    pub show_sprites: bool,
+    // Drawn over the pentagon along with the demo sprites, one layer of a
+    // texture array at a time
+    sprite_array: sprite_array::SpriteArray,
+    sprite_layer: u32,
+    // A 16x16 map of tiles over the scene. The arrow keys scroll it while
+    // it's shown.
+    tile_map: tilemap::TileMapRenderer,
+    pub show_tile_map: bool,
+    // The scene from a second camera, shown in the top-right corner through
+    // the sprite batch
+    mirror: mirror::Mirror,
+    mirror_sprite: sprite::SpriteTexture,
+    pub show_mirror: bool,
+    // Rectangles of the scene target each drawn through their own camera,
+    // e.g. viewport::split_screen. Empty draws the whole target through the
+    // main camera as usual.
+    pub viewports: Vec<viewport::Viewport>,
+    // One per viewport, written in `update`
+    viewport_cameras: Vec<uniform::Uniform<camera::CameraUniform>>,
+    gbuffer: gbuffer::GBuffer,
+    // Albedo then normals, for showing them over the scene
+    gbuffer_sprites: [sprite::SpriteTexture; 2],
+    // The G-buffer pass only runs while one of its targets is shown
+    pub gbuffer_view: gbuffer::GBufferView,
+    // Renders the scene below output resolution when frames run long. The
+    // post chain's last pass scales it back up.
+    pub adaptive_resolution: adaptive_resolution::AdaptiveResolution,
+    // The scene's internal resolution as a fraction of the output's,
+    // whatever adaptive resolution is doing. Upscaled the same way.
+    render_scale: f32,
+}
+
+impl<'a> Renderer<'a> {
+    pub async fn new(
+        window: Arc<Window>,
+        options: &RendererOptions,
+    ) -> Result<Renderer<'a>, StateError> {
+        let (gpu, surface) = Gpu::new_windowed(
+            window.clone(),
+            options.backends,
+            options.power_preference,
+            options.force_fallback_adapter,
+        )
+        .await?;
+        Ok(Self::with_surface(gpu, surface, window, options))
+    }
+
+    // Same as `new` for another window, drawing with `other`'s device and
+    // queue instead of requesting new ones. Everything else (pipelines,
+    // textures, the scene) is the window's own.
+    pub fn new_sharing(
+        other: &Renderer,
+        window: Arc<Window>,
+        options: &RendererOptions,
+    ) -> Result<Renderer<'a>, StateError> {
+        let gpu = other.gpu();
+        let surface = gpu.create_surface(window.clone())?;
+        Ok(Self::with_surface(gpu, surface, window, options))
+    }
+
+    fn with_surface(
+        gpu: Gpu,
+        surface: wgpu::Surface<'a>,
+        window: Arc<Window>,
+        options: &RendererOptions,
+    ) -> Self {
+        let (surface_configuration, can_capture) = gpu.configure_surface(
+            &surface,
+            window.inner_size(),
+            options.present_mode,
+        );
+        gpu::log_gpu_diagnostics(
+            &gpu.adapter,
+            &gpu.device,
+            Some((&surface, &surface_configuration)),
+        );
+
+        Self::from_gpu(
+            gpu,
+            surface_configuration,
+            can_capture,
+            RenderTarget::Surface {
+                surface: Some(surface),
+                window,
+            },
+            options,
+        )
+    }
+
+    // Same as `new` but drawing into an offscreen texture of the given size
+    // instead of a window's surface
+    pub async fn new_headless(
+        width: u32,
+        height: u32,
+        options: &RendererOptions,
+    ) -> Result<Self, StateError> {
+        if width == 0 || height == 0 {
+            return Err(StateError::InvalidSize { width, height });
+        }
+
+        let gpu = Gpu::new_headless(
+            options.backends,
+            options.power_preference,
+            options.force_fallback_adapter,
+        )
+        .await?;
+        gpu::log_gpu_diagnostics(&gpu.adapter, &gpu.device, None);
+        let surface_configuration = gpu::offscreen_configuration(width, height);
+        let texture =
+            gpu::create_offscreen_texture(&gpu.device, &surface_configuration);
+
+        Ok(Self::from_gpu(
+            gpu,
+            surface_configuration,
+            true,
+            RenderTarget::Offscreen { texture },
+            options,
+        ))
+    }
+
+    // Everything that only needs a device and a description of the output,
+    // shared by the windowed and headless paths
+    fn from_gpu(
+        gpu: Gpu,
+        surface_configuration: wgpu::SurfaceConfiguration,
+        surface_can_capture: bool,
+        target: RenderTarget<'a>,
+        options: &RendererOptions,
+    ) -> Self {
+        let Gpu {
+            instance,
+            adapter,
+            device,
+            queue,
+            device_lost,
+        } = gpu;
+        let size = winit::dpi::PhysicalSize::new(
+            surface_configuration.width,
+            surface_configuration.height,
+        );
+
+        let depth_format = if options.stencil {
+            texture::Texture::DEPTH_STENCIL_FORMAT
+        } else {
+            texture::Texture::DEPTH_FORMAT
+        };
+        let attachment_formats = [surface_configuration.format, depth_format];
+        let sample_count = pipeline::supported_sample_count(
+            &adapter,
+            &attachment_formats,
+            options.sample_count,
+        );
+        let sample_counts =
+            pipeline::supported_sample_counts(&adapter, &attachment_formats);
+
+        const DIFFUSE_TEXTURE: &[u8] = include_bytes!("g25.png");
+        let builtin_diffuse_texture = texture::Texture::from_bytes(
+            &device,
+            &queue,
+            &assets::load("g25.png", DIFFUSE_TEXTURE),
+            Some("Diffuse Texture"),
+            texture::TextureKind::Color,
+        )
+        .or_else(|e| {
+            log::warn!("{:#}, using the built-in g25.png", e);
+            texture::Texture::from_bytes(
+                &device,
+                &queue,
+                DIFFUSE_TEXTURE,
+                Some("Diffuse Texture"),
+                texture::TextureKind::Color,
+            )
+        })
+        .and_then(|texture| {
+            texture.with_sampler(
+                &device,
+                wgpu::AddressMode::ClampToEdge,
+                options.anisotropy,
+            )
+        })
+        .unwrap();
+
+        // A configured texture streams in while the pentagon is drawn with a
+        // placeholder, see `update`
+        let mut asset_loader =
+            asset_loader::AssetLoader::new(options.anisotropy);
+        let diffuse_texture_handle =
+            options.diffuse_texture.as_ref().map(|path| {
+                asset_loader.load_texture(
+                    &device,
+                    &queue,
+                    path,
+                    "Diffuse Texture",
+                    texture::TextureKind::Color,
+                )
+            });
+        let diffuse_texture = diffuse_texture_handle
+            .map_or(&builtin_diffuse_texture, |handle| {
+                asset_loader.texture(handle)
+            });
+
+        let normal_texture = texture::Texture::from_bytes(
+            &device,
+            &queue,
+            include_bytes!("g25_normal.png"),
+            Some("Normal Texture"),
+            texture::TextureKind::LinearData,
+        )
+        .and_then(|texture| {
+            texture.with_sampler(
+                &device,
+                wgpu::AddressMode::ClampToEdge,
+                options.anisotropy,
+            )
+        })
+        .unwrap();
+
+        let detail_texture = texture::Texture::from_bytes(
+            &device,
+            &queue,
+            include_bytes!("detail.png"),
+            Some("Detail Texture"),
+            texture::TextureKind::Color,
+        )
+        .and_then(|texture| {
+            texture.with_sampler(
+                &device,
+                wgpu::AddressMode::ClampToEdge,
+                options.anisotropy,
+            )
+        })
+        .unwrap();
+
+        let texture_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Float {
+                                filterable: true,
+                            },
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        // This should match the filterable field of the
+                        // corresponding Texture entry above.
+                        ty: wgpu::BindingType::Sampler(
+                            wgpu::SamplerBindingType::Filtering,
+                        ),
+                        count: None,
+                    },
+                    // Normal map
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Float {
+                                filterable: true,
+                            },
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(
+                            wgpu::SamplerBindingType::Filtering,
+                        ),
+                        count: None,
+                    },
+                    // Detail texture, blended over the diffuse one
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 4,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Float {
+                                filterable: true,
+                            },
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 5,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(
+                            wgpu::SamplerBindingType::Filtering,
+                        ),
+                        count: None,
+                    },
+                    // Specular color and shininess
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 6,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+                label: Some("Texture Bind Group Layour"),
+            });
+
+        // The pentagon's UVs span a square, so a texture that isn't one is
+        // cropped to fit rather than stretched over it. glTF materials are
+        // left alone, their UVs were made for their textures.
+        let mut pentagon_material = material::Material::new(
+            &device,
+            material::DEFAULT_SPECULAR_COLOR,
+            material::DEFAULT_SHININESS,
+        );
+        pentagon_material.uv_scale =
+            material::uv_scale_for_aspect(diffuse_texture.aspect_ratio());
+        pentagon_material.update(&queue);
+        let mut materials = material_registry::MaterialRegistry::new(
+            material_registry::Material {
+                bind_group: create_texture_bind_group(
+                    &device,
+                    &texture_bind_group_layout,
+                    diffuse_texture,
+                    &normal_texture,
+                    &detail_texture,
+                    &pentagon_material,
+                ),
+                pipeline: None,
+            },
+        );
+
+        // The consts the file is generated from are still there to fall
+        // back on
+        let pentagon_data = mesh_file::load_mesh(PENTAGON_MESH_PATH)
+            .unwrap_or_else(|e| {
+                log::warn!("{:#}, using the built-in pentagon", e);
+                mesh_file::MeshData {
+                    vertices: VERTICES.to_vec(),
+                    indices: INDICES.to_vec(),
+                }
+            });
+        let (morph_targets, morph_indices) = morph::pentagon_star();
+        let mut meshes = vec![
+            geometry::Mesh::new(
+                &device,
+                "Pentagon",
+                &pentagon_data.vertices,
+                &pentagon_data.indices,
+            ),
+            geometry::Mesh::new_dynamic(
+                &device,
+                "Morph",
+                &morph_targets[0],
+                &morph_indices,
+            ),
+        ];
+        let pentagon = scene::MeshId(0);
+        let pentagon_streams = geometry::Mesh::new_split(
+            &device,
+            "Pentagon Streams",
+            &pentagon_data.vertices,
+            &pentagon_data.indices,
+        );
+        let morph_mesh = scene::MeshId(1);
+        let morph = morph::Morph::new(morph_targets);
+
+        let mut lods = vec![];
+        let pentagon_lod = scene::LodId(lods.len());
+        let (medium_vertices, medium_indices) = primitives::subdivide(
+            &pentagon_data.vertices,
+            &pentagon_data.indices,
+        );
+        let (fine_vertices, fine_indices) =
+            primitives::subdivide(&medium_vertices, &medium_indices);
+        lods.push(lod::Lod::new(vec![
+            (PENTAGON_LOD_DISTANCES[0], scene::MeshId(meshes.len())),
+            (PENTAGON_LOD_DISTANCES[1], scene::MeshId(meshes.len() + 1)),
+            (f32::INFINITY, pentagon),
+        ]));
+        meshes.push(geometry::Mesh::new(
+            &device,
+            "Pentagon LOD 0",
+            &fine_vertices,
+            &fine_indices,
+        ));
+        meshes.push(geometry::Mesh::new(
+            &device,
+            "Pentagon LOD 1",
+            &medium_vertices,
+            &medium_indices,
+        ));
+
+        let mut scene = scene::Scene::new();
+        scene.add(scene::Entity::new(pentagon));
+        let mut objects_scene = scene::Scene::new();
+        for &(_, _, tint) in OBJECTS {
+            objects_scene.add(scene::Entity {
+                tint,
+                lod: Some(pentagon_lod),
+                ..scene::Entity::new(pentagon)
+            });
+        }
+        let (model_scene, model_speculars) = match &options.model {
+            Some(path) => load_model(
+                &device,
+                &queue,
+                &texture_bind_group_layout,
+                &detail_texture,
+                options.anisotropy,
+                path,
+                &mut meshes,
+                &mut materials,
+            )
+            .unwrap_or_else(|e| {
+                log::error!("{:#}, showing the pentagon instead", e);
+                Default::default()
+            }),
+            None => Default::default(),
+        };
+        let mut heightmap_scene = scene::Scene::new();
+        match heightmap::Terrain::from_heightmap(
+            HEIGHTMAP_PATH,
+            HEIGHTMAP_SCALE,
+            HEIGHTMAP_HEIGHT,
+        ) {
+            Ok(terrain) => {
+                heightmap_scene.add(scene::Entity {
+                    transform: cgmath::Matrix4::from_translation(
+                        (0.0, HEIGHTMAP_BASE, 0.0).into(),
+                    ),
+                    ..scene::Entity::new(scene::MeshId(meshes.len()))
+                });
+                meshes.push(geometry::Mesh::new(
+                    &device,
+                    "Heightmap Terrain",
+                    &terrain.vertices,
+                    &terrain.indices,
+                ));
+            }
+            Err(e) => log::warn!("{:#}, no heightmap terrain", e),
+        }
+
+        let push_constants = draw_constants::push_constants_supported(&device);
+        log::info!(
+            "Per-draw constants go through {}",
+            if push_constants {
+                "push constants"
+            } else {
+                "the object uniform buffer"
+            }
+        );
+        let shader =
+            device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Shader"),
+                source: wgpu::ShaderSource::Wgsl(
+                    object_shader_source(
+                        SCENE_SHADER_NAME,
+                        SCENE_SHADER,
+                        push_constants,
+                    )
+                    .into(),
+                ),
+            });
+
+        let camera =
+            camera::Camera::new(size.width as f32 / size.height as f32);
+
+        let mut camera_uniform = camera::CameraUniform::new();
+        camera_uniform.update_view_proj(
+            camera.build_view_projection_matrix(),
+            camera.eye,
+        );
+
+        // @group(1) @binding(0)
+        // var<uniform> camera: CameraUniform;
+        let camera_bind_group_layout =
+            &uniform::Uniform::<camera::CameraUniform>::create_layout(
+                &device,
+                "Camera",
+                // The fragment stage needs the view position for specular
+                // highlights
+                wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+            );
+
+        // Scene entities are drawn one call each, with their transform
+        // picked from here by a dynamic offset
+        let objects = objects::ObjectUniforms::new(&device, MAX_ENTITIES);
+        // Likewise their index counts, from the GPU side
+        let indirect_draws =
+            indirect::IndirectDraws::new(&device, MAX_ENTITIES);
+
+        // Clamping instead of clipping the shadow map's depth keeps casters
+        // between the light and its near plane, which would otherwise be
+        // cut off and cast no shadow
+        let unclipped_depth = options.unclipped_depth
+            && device
+                .features()
+                .contains(wgpu::Features::DEPTH_CLIP_CONTROL);
+        if options.unclipped_depth && !unclipped_depth {
+            log::warn!(
+                "Adapter doesn't support DEPTH_CLIP_CONTROL, the shadow map's \
+                 depth is clipped"
+            );
+        }
+        let shadow_map = shadow::ShadowMap::new(
+            &device,
+            objects.bind_group_layout(),
+            unclipped_depth,
+        );
+
+        let globals_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::VERTEX
+                            | wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    // The light's view projection, to find a fragment in
+                    // the shadow map
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    // Shadow map
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Depth,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(
+                            wgpu::SamplerBindingType::Comparison,
+                        ),
+                        count: None,
+                    },
+                    // The skybox's cubemap, for reflections
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 4,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::Cube,
+                            sample_type: wgpu::TextureSampleType::Float {
+                                filterable: true,
+                            },
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 5,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(
+                            wgpu::SamplerBindingType::Filtering,
+                        ),
+                        count: None,
+                    },
+                ],
+                label: Some("Globals Bind Group Layout"),
+            });
+
+        // Also what the scene reflects, see FrameUniforms
+        let skybox = skybox::Skybox::new(
+            &device,
+            &queue,
+            [
+                include_bytes!("skybox/px.png"),
+                include_bytes!("skybox/nx.png"),
+                include_bytes!("skybox/py.png"),
+                include_bytes!("skybox/ny.png"),
+                include_bytes!("skybox/pz.png"),
+                include_bytes!("skybox/nz.png"),
+            ],
+        )
+        .unwrap();
+
+        let (gradient_top, gradient_bottom) = options
+            .background_gradient
+            .unwrap_or((gradient::DEFAULT_TOP, gradient::DEFAULT_BOTTOM));
+        let gradient = gradient::BackgroundGradient::new(
+            &device,
+            gradient_top,
+            gradient_bottom,
+        );
+
+        let clear_color = options.clear_color.unwrap_or(wgpu::Color {
+            r: 0.03,
+            g: 0.03,
+            b: 0.03,
+            a: 1.0,
+        });
+        let viewport_fill = gradient.sharing_layout(
+            &device,
+            "Viewport Fill",
+            clear_color,
+            clear_color,
+        );
+
+        let frames = frame_resources::FrameResources::new(
+            surface_configuration.desired_maximum_frame_latency as usize,
+            || {
+                FrameUniforms::new(
+                    &device,
+                    camera_bind_group_layout,
+                    &globals_bind_group_layout,
+                    &shadow_map,
+                    &skybox,
+                    &camera_uniform,
+                )
+            },
+        );
+
+        let object_bind_group_layouts = [
+            &texture_bind_group_layout,
+            camera_bind_group_layout,
+            &globals_bind_group_layout,
+            objects.bind_group_layout(),
+        ];
+
+        let translucent_quads = TRANSLUCENT_QUADS
+            .iter()
+            .map(|&(center, color)| {
+                let (vertices, indices) =
+                    primitives::quad(center, TRANSLUCENT_QUAD_SIZE, color);
+                TranslucentQuad {
+                    center: center.into(),
+                    mesh: geometry::Mesh::new(
+                        &device,
+                        "Translucent Quad",
+                        &vertices,
+                        &indices,
+                    ),
+                }
+            })
+            .collect();
+
+        let terrain = splat::SplatTerrain::new(
+            &device,
+            &queue,
+            &splat::demo_layers(),
+            &splat::demo_splat_map(),
+            8.0,
+            -0.75,
+        )
+        .unwrap();
+
+        let debug_draw = debug_draw::DebugDraw::new(&device);
+
+        let pipelines = ScenePipelines::new(
+            &device,
+            &surface_configuration,
+            &shader,
+            &object_bind_group_layouts,
+            push_constants,
+            sample_count,
+            depth_format,
+            &skybox,
+            &terrain,
+            &gradient,
+        );
+
+        // 100x100 pentagons spread far enough apart that from any reasonable
+        // orbit most of them are off-screen, which is where culling pays off
+        let instance_field = instancing::InstanceField::new_grid(
+            &device,
+            &queue,
+            100,
+            1.5,
+            meshes[pentagon.0].n_indices(),
+            pentagon_data.bounding_radius(),
+        );
+
+        // Only a broken loose font.png or font.json can fail this, the
+        // built-in ones are always there
+        let font = match sdf_text::SdfFont::load(&device, &queue) {
+            Ok(font) => Some(font),
+            Err(e) => {
+                log::warn!("SDF text disabled: {:#}", e);
+                None
+            }
+        };
+
+        // The same atlas for labels in the scene, drawn against its depth
+        let labels = font.clone().map(|font| {
+            create_labels(
+                &device,
+                &queue,
+                surface_configuration.format,
+                font,
+                sample_count,
+                depth_format,
+            )
+        });
+
+        let text = match font {
+            Some(font) => {
+                let mut text = sdf_text::SdfTextRenderer::new(
+                    &device,
+                    surface_configuration.format,
+                    font,
+                );
+                // A thin dark outline keeps the label readable over any
+                // clear color
+                text.set_style(
+                    &queue,
+                    sdf_text::TextStyle {
+                        outline_width: 0.15,
+                        ..Default::default()
+                    },
+                );
+                Some(text)
+            }
+            None => None,
+        };
+
+        let mut sprite_batch =
+            sprite::SpriteBatch::new(&device, surface_configuration.format);
+        let sprite_textures = [
+            sprite_batch.add_texture(&device, &builtin_diffuse_texture),
+            sprite_batch.add_texture(&device, &normal_texture),
+        ];
+
+        // Any 128x128 images will do, the layers of an array all share a
+        // size
+        let sprite_layers = texture::Texture::from_layer_bytes(
+            &device,
+            &queue,
+            &[
+                include_bytes!("detail.png"),
+                include_bytes!("skybox/px.png"),
+                include_bytes!("skybox/py.png"),
+                include_bytes!("skybox/nz.png"),
+            ],
+            Some("Sprite Layers"),
+        )
+        .unwrap();
+        let sprite_array = sprite_array::SpriteArray::new(
+            &device,
+            surface_configuration.format,
+            &sprite_layers,
+        );
+
+        let (atlas, atlas_layout) = match &options.tile_atlas {
+            Some((path, layout)) => {
+                match tilemap::load_atlas(&device, &queue, path) {
+                    Ok(atlas) => (atlas, *layout),
+                    Err(e) => {
+                        log::error!("{:#}, using the generated tile atlas", e);
+                        tilemap::generated_atlas(&device, &queue)
+                    }
+                }
+            }
+            None => tilemap::generated_atlas(&device, &queue),
+        };
+        let tile_map = tilemap::TileMapRenderer::new(
+            &device,
+            surface_configuration.format,
+            &atlas,
+            atlas_layout,
+            &tilemap::TileMap::demo(),
+        );
+
+        let mirror = mirror::Mirror::new(
+            &device,
+            surface_configuration.format,
+            sample_count,
+            depth_format,
+            camera_bind_group_layout,
+        );
+        let mirror_sprite = sprite_batch.add_texture(&device, mirror.texture());
+
+        let gbuffer = gbuffer::GBuffer::new(&device, depth_format);
+        let gbuffer_sprites = [gbuffer.albedo(), gbuffer.normal()]
+            .map(|texture| sprite_batch.add_texture(&device, texture));
+
+        let linear_output =
+            linear_output::LinearOutput::new(&device, &surface_configuration);
+
+        let gpu_timer =
+            gpu_timer::GpuTimer::new(&device, &queue, GPU_TIMER_LOG_INTERVAL);
+        let occlusion =
+            occlusion::OcclusionQueries::new(&device, MAX_ENTITIES as u32);
+
+        let depth_view = depth_view::DepthView::new(
+            &device,
+            surface_configuration.format,
+            adapter.get_info().backend,
+        );
+
+        let particles = particles::ParticleSystem::new(
+            &device,
+            surface_configuration.format,
+            N_PARTICLES,
+        );
+
+        let mut post_chain =
+            post::PostChain::new(&device, surface_configuration.format)
+                .with_default_effects(&device);
+        post_chain.set_filter(&device, options.upscale_filter);
+        // The blur's passes write storage textures, which not every adapter
+        // (or format) allows
+        let (blur_radius, blur_constants) = if blur::supported(&adapter) {
+            let blur = blur::BlurEffect::new(&device, &queue, &post_chain);
+            let handles = (blur.radius(), blur.constants());
+            post_chain.push(Box::new(blur), false);
+            (Some(handles.0), Some(handles.1))
+        } else {
+            log::warn!(
+                "Storage textures aren't supported, the blur effect is off"
+            );
+            (None, None)
+        };
+
+        let render_scale =
+            options.render_scale.clamp(MIN_FIXED_RENDER_SCALE, 1.0);
+        let scaled_size = scaled_size(size, render_scale);
+        let sized = SizedResources::new(
+            &device,
+            &wgpu::SurfaceConfiguration {
+                width: scaled_size.width,
+                height: scaled_size.height,
+                ..surface_configuration.clone()
+            },
+            sample_count,
+            depth_format,
+            adapter.get_info().backend,
+            &post_chain,
+        );
+
+        Self {
+            target,
+            instance,
+            adapter,
+            device,
+            queue,
+            device_lost,
+            surface_configuration,
+            size,
+            is_minimized: false,
+            pipelines,
+            active_render_pipeline_index: 0,
+            fill_pipeline_index: 0,
+            cull_mode_index: 0,
+            front_face_index: 0,
+            scene_bind_group_layouts: object_bind_group_layouts
+                .into_iter()
+                .cloned()
+                .collect(),
+            scene_shader: shader,
+            push_constants,
+            meshes,
+            pentagon,
+            pentagon_streams,
+            morph_mesh,
+            morph,
+            show_morph: false,
+            lods,
+            scene,
+            objects_scene,
+            show_model: !model_scene.entities.is_empty(),
+            model_scene,
+            heightmap_scene,
+            show_heightmap: false,
+            materials,
+            model_speculars,
+            pentagon_material,
+            address_mode: wgpu::AddressMode::ClampToEdge,
+            tile_uvs: false,
+            anisotropy: options.anisotropy,
+            show_specular: true,
+            show_clip_plane: false,
+            clip_plane_offset: 0.0,
+            texture_bind_group_layout,
+            builtin_diffuse_texture,
+            normal_texture,
+            detail_texture,
+            asset_loader,
+            diffuse_texture_handle,
+            text,
+            labels,
+            show_labels: false,
+            occlude_labels: true,
+            ortho_camera: camera::OrthoCamera::new(camera.aspect),
+            use_ortho_camera: false,
+            camera,
+            camera_uniform,
+            frames,
+            objects,
+            indirect_draws,
+            triangle_limit: None,
+            visible_entities: vec![],
+            entity_stats: scene::CullStats::default(),
+            render_stats: render_stats::RenderStats::default(),
+            show_objects: false,
+            light: shadow::DirectionalLight {
+                direction: LIGHT_DIRECTION.into(),
+            },
+            shadow_map,
+            show_shadows: true,
+            instance_field,
+            show_instances: false,
+            last_frame: Instant::now(),
+            frame_time_ms: 0.0,
+            sample_count,
+            sample_counts,
+            sized,
+            skybox,
+            // The skybox would hide a configured gradient
+            show_skybox: options.background_gradient.is_none(),
+            gradient,
+            show_gradient: options.background_gradient.is_some(),
+            viewport_fill,
+            bar_color: wgpu::Color::BLACK,
+            terrain,
+            show_terrain: false,
+            debug_draw,
+            show_grid: false,
+            depth_format,
+            show_stencil_reveal: false,
+            depth_prepass: false,
+            discard_transient: true,
+            time: 0.0,
+            simulated_time: 0.0,
+            previous_simulated_time: 0.0,
+            show_normals: false,
+            show_depth: false,
+            depth_view,
+            detail_mix: 0.0,
+            audio_amplitude: 0.0,
+            translucent_quads,
+            show_translucent: false,
+            surface_can_capture,
+            readback_pool: readback::ReadbackPool::new(READBACK_POOL_SIZE),
+            screenshot_requested: false,
+            capture_sequence: None,
+            canvas: None,
+            linear_output,
+            linear_view: false,
+            canvas_aspect_index: 0,
+            canvas_fit: canvas::FitMode::Letterbox,
+            post_chain,
+            show_post_hud: false,
+            post_preset_index: 0,
+            blur_radius,
+            blur_constants,
+            gpu_timer,
+            occlusion,
+            count_occlusion: false,
+            particles,
+            show_particles: false,
+            sprite_batch,
+            sprite_textures,
+            show_sprites: false,
+            sprite_array,
+            sprite_layer: 0,
+            tile_map,
+            show_tile_map: false,
+            mirror,
+            mirror_sprite,
+            show_mirror: false,
+            viewports: Vec::new(),
+            viewport_cameras: Vec::new(),
+            gbuffer,
+            gbuffer_sprites,
+            gbuffer_view: gbuffer::GBufferView::Off,
+            adaptive_resolution: adaptive_resolution::AdaptiveResolution::new(
+                TARGET_FRAME_MS,
+                MIN_RENDER_SCALE,
+                MAX_RENDER_SCALE,
+            ),
+            render_scale,
+            clear_color,
+        }
+    }
+
+    // Handles to the device this renderer draws with, for sharing it
+    pub fn gpu(&self) -> Gpu {
+        Gpu {
+            instance: self.instance.clone(),
+            adapter: self.adapter.clone(),
+            device: self.device.clone(),
+            queue: self.queue.clone(),
+            device_lost: self.device_lost.clone(),
+        }
+    }
+
+    // Whether the device is gone and everything has to be made again on a
+    // new one, see `State::reinitialize_device`
+    pub fn device_lost(&self) -> bool {
+        self.device_lost.load(Ordering::Acquire)
+    }
+
+    // None when rendering headless
+    pub fn window(&self) -> Option<&Window> {
+        match &self.target {
+            RenderTarget::Surface { window, .. } => Some(window),
+            RenderTarget::Offscreen { .. } => None,
+        }
+    }
+
+    // Another handle to the window, e.g. to build a new renderer for it
+    pub fn shared_window(&self) -> Option<Arc<Window>> {
+        match &self.target {
+            RenderTarget::Surface { window, .. } => Some(window.clone()),
+            RenderTarget::Offscreen { .. } => None,
+        }
+    }
+
+    pub fn size(&self) -> winit::dpi::PhysicalSize<u32> {
+        self.size
+    }
+
+    // Width over height of what the scene is drawn into, the canvas if
+    // there is one and the window otherwise
+    pub fn scene_aspect(&self) -> f32 {
+        match &self.canvas {
+            Some(canvas) => canvas.aspect(),
+            None => self.size.width as f32 / self.size.height as f32,
+        }
+    }
+
+    // Throws the window's surface away and makes a new one from the
+    // instance, configured the same way. For when reconfiguring isn't
+    // enough: a surface can be left permanently invalid, e.g. when the
+    // window's display goes away or its compositor restarts, while the
+    // device itself is still fine. Fails if the new surface can't be
+    // presented to with the adapter and format in use, which takes a new
+    // device. Does nothing for an offscreen target.
+    pub fn recreate_surface(&mut self) -> Result<(), StateError> {
+        let Some(window) = self.shared_window() else {
+            return Ok(());
+        };
+        // Some platforms only allow one surface per window at a time, so
+        // the old one goes first. Until there's a new one, acquiring a frame
+        // reports the surface as lost.
+        self.target = RenderTarget::Surface {
+            surface: None,
+            window: window.clone(),
+        };
+        let surface = self.instance.create_surface(window.clone())?;
+        if !self.adapter.is_surface_supported(&surface)
+            || !surface
+                .get_capabilities(&self.adapter)
+                .formats
+                .contains(&self.surface_configuration.format)
+        {
+            return Err(StateError::SurfaceUnsupported);
+        }
+        if !self.is_minimized {
+            surface.configure(&self.device, &self.surface_configuration);
+        }
+        self.target = RenderTarget::Surface {
+            surface: Some(surface),
+            window,
+        };
+        Ok(())
+    }
+
+    // Zero sizes only come from minimizing (going in and out of fullscreen
+    // always reports a real size), and there's nothing to draw then anyway.
+    // Restoring the window sends a real size again, which brings rendering
+    // back.
+    pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
+        self.is_minimized = new_size.width == 0 || new_size.height == 0;
+        if !self.is_minimized {
+            self.size = new_size;
+            self.surface_configuration.width = new_size.width;
+            self.surface_configuration.height = new_size.height;
+            match &mut self.target {
+                RenderTarget::Surface { surface, .. } => {
+                    if let Some(surface) = surface {
+                        surface.configure(
+                            &self.device,
+                            &self.surface_configuration,
+                        )
+                    }
+                }
+                RenderTarget::Offscreen { texture } => {
+                    *texture = gpu::create_offscreen_texture(
+                        &self.device,
+                        &self.surface_configuration,
+                    )
+                }
+            }
+            if let Some(canvas) = &mut self.canvas {
+                canvas.resize(&self.device, &self.queue, new_size);
+            }
+            if let Some(linear_output) = &mut self.linear_output {
+                linear_output.resize(&self.device, &self.surface_configuration);
+            }
+            self.recreate_render_targets();
+        }
+    }
+
+    // The MSAA, depth and post chain targets have to match whatever the
+    // scene is being drawn into: the canvas if there is one, the surface
+    // otherwise, scaled down by the current render scale. Only those are
+    // replaced; pipelines don't depend on the size and are left alone.
+    fn recreate_render_targets(&mut self) {
+        let output_size = match &self.canvas {
+            Some(canvas) => canvas.size(),
+            None => self.size,
+        };
+        let aspect = self.scene_aspect();
+        let size = scaled_size(
+            output_size,
+            self.render_scale * self.adaptive_resolution.scale(),
+        );
+        let target_configuration = wgpu::SurfaceConfiguration {
+            width: size.width,
+            height: size.height,
+            ..self.surface_configuration.clone()
+        };
+
+        self.camera.aspect = aspect;
+        self.ortho_camera.aspect = aspect;
+        self.sized = SizedResources::new(
+            &self.device,
+            &target_configuration,
+            self.sample_count,
+            self.depth_format,
+            self.adapter.get_info().backend,
+            &self.post_chain,
+        );
+    }
+
+    pub fn render_scale(&self) -> f32 {
+        self.render_scale
+    }
+
+    // Renders the scene at `scale` times the output's resolution, e.g. 0.5
+    // for a quarter of the pixels, and scales it up on the way out. The
+    // targets follow the output's size, so the fraction holds through
+    // resizes.
+    pub fn set_render_scale(&mut self, scale: f32) {
+        self.render_scale = scale.clamp(MIN_FIXED_RENDER_SCALE, 1.0);
+        self.recreate_render_targets();
+    }
+
+    // Steps to the next of RENDER_SCALES, back to the first after the last
+    pub fn cycle_render_scale(&mut self) {
+        let next = RENDER_SCALES
+            .iter()
+            .position(|&scale| scale < self.render_scale)
+            .map_or(RENDER_SCALES[0], |index| RENDER_SCALES[index]);
+        self.set_render_scale(next);
+        log::info!("Fixed render scale: {}%", self.render_scale * 100.0);
+    }
+
+    pub fn upscale_filter(&self) -> wgpu::FilterMode {
+        self.post_chain.filter()
+    }
+
+    // Nearest for crisp pixels, linear for a soft look
+    pub fn toggle_upscale_filter(&mut self) {
+        let filter = match self.post_chain.filter() {
+            wgpu::FilterMode::Linear => wgpu::FilterMode::Nearest,
+            wgpu::FilterMode::Nearest => wgpu::FilterMode::Linear,
+        };
+        self.post_chain.set_filter(&self.device, filter);
+        self.recreate_render_targets();
+        log::info!("Upscale filter: {:?}", filter);
+    }
+
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+
+    pub fn aa_mode(&self) -> anti_aliasing::AaMode {
+        if self.post_chain.fxaa() {
+            anti_aliasing::AaMode::Fxaa
+        } else if self.sample_count > 1 {
+            anti_aliasing::AaMode::Msaa(self.sample_count)
+        } else {
+            anti_aliasing::AaMode::None
+        }
+    }
+
+    // Switching to or from MSAA rebuilds everything the sample count is
+    // baked into, see `set_sample_count`. FXAA is just a post pass, but
+    // turning it on drops MSAA to one sample.
+    pub fn set_aa_mode(&mut self, mode: anti_aliasing::AaMode) {
+        self.set_sample_count(mode.sample_count());
+        self.post_chain
+            .set_fxaa(mode == anti_aliasing::AaMode::Fxaa);
+        log::info!("Anti-aliasing: {}", self.aa_mode().name());
+    }
+
+    // No AA, each supported MSAA sample count, then FXAA
+    pub fn cycle_aa_mode(&mut self) {
+        self.set_aa_mode(self.aa_mode().next(&self.sample_counts));
+    }
+
+    // The sample count is baked into every pipeline drawing the scene and
+    // into its color and depth targets, so all of them are built again.
+    // Counts the color and depth formats don't both support are refused.
+    pub fn set_sample_count(&mut self, sample_count: u32) {
+        if sample_count == self.sample_count {
+            return;
+        }
+        if !self.sample_counts.contains(&sample_count) {
+            log::warn!(
+                "MSAA x{} not supported, staying at x{}",
+                sample_count,
+                self.sample_count
+            );
+            return;
+        }
+        self.sample_count = sample_count;
+
+        self.rebuild_scene_pipelines();
+        if let Some(labels) = &self.labels {
+            self.labels = Some(create_labels(
+                &self.device,
+                &self.queue,
+                self.surface_configuration.format,
+                labels.font().clone(),
+                sample_count,
+                self.depth_format,
+            ));
+        }
+        self.mirror.set_sample_count(
+            &self.device,
+            self.surface_configuration.format,
+            sample_count,
+            self.depth_format,
+        );
+        self.recreate_render_targets();
+        log::info!("MSAA: x{}", sample_count);
+    }
+
+    // Reads the loose shader.wgsl (see assets) again and rebuilds the scene
+    // pipelines with it, for working on the shader without restarting. If
+    // it can't be read or doesn't validate the error is logged and the
+    // pipelines from before stay.
+    // See gpu::log_memory_report
+    pub fn log_memory_report(&self) {
+        gpu::log_memory_report(&self.instance, &self.device);
+    }
+
+    pub fn reload_scene_shader(&mut self) {
+        let Some(path) = assets::find(SCENE_SHADER_NAME) else {
+            log::error!("No loose {} to reload", SCENE_SHADER_NAME);
+            return;
+        };
+        let source = match std::fs::read_to_string(&path) {
+            Ok(source) => source,
+            Err(e) => {
+                log::error!("Couldn't read {}: {}", path.display(), e);
+                return;
+            }
+        };
+        let source =
+            draw_constants::shader_source(&source, self.push_constants);
+        if let Err(e) = shader_validation::validate_wgsl(&source) {
+            log::error!(
+                "Keeping the old shader, {} is invalid:\n{}",
+                path.display(),
+                e
+            );
+            return;
+        }
+
+        self.scene_shader =
+            self.device
+                .create_shader_module(wgpu::ShaderModuleDescriptor {
+                    label: Some("Shader"),
+                    source: wgpu::ShaderSource::Wgsl(source.into()),
+                });
+        self.rebuild_scene_pipelines();
+        log::info!("Reloaded {}", path.display());
+    }
+
+    // After the scene shader or sample count changed. The active pipeline
+    // index still refers to the same pipeline afterwards.
+    fn rebuild_scene_pipelines(&mut self) {
+        let layouts: Vec<&wgpu::BindGroupLayout> =
+            self.scene_bind_group_layouts.iter().collect();
+        self.pipelines = ScenePipelines::new(
+            &self.device,
+            &self.surface_configuration,
+            &self.scene_shader,
+            &layouts,
+            self.push_constants,
+            self.sample_count,
+            self.depth_format,
+            &self.skybox,
+            &self.terrain,
+            &self.gradient,
+        );
+    }
+
+    // The canvas can always be copied from, the surface only sometimes
+    fn can_capture(&self) -> bool {
+        self.canvas.is_some() || self.surface_can_capture
+    }
+
+    pub fn device(&self) -> &wgpu::Device {
+        &self.device
+    }
+
+    pub fn format(&self) -> wgpu::TextureFormat {
+        self.surface_configuration.format
+    }
+
+    pub fn present_mode(&self) -> wgpu::PresentMode {
+        self.surface_configuration.present_mode
+    }
+
+    pub fn frame_latency(&self) -> u32 {
+        self.surface_configuration.desired_maximum_frame_latency
+    }
+
+    // Clamped to FRAME_LATENCIES. The surface can only hold so many frames
+    // (wgpu doesn't say how many, but clamps to it when configuring), so
+    // the latency actually used can be lower still.
+    pub fn set_frame_latency(&mut self, latency: u32) {
+        let latency =
+            latency.clamp(*FRAME_LATENCIES.start(), *FRAME_LATENCIES.end());
+        self.surface_configuration.desired_maximum_frame_latency = latency;
+        // Whatever is still in flight from the old ring has to be done
+        // before its slots are handed out again from the start
+        self.wait_idle();
+        let [_, camera_layout, globals_layout, _] =
+            &self.scene_bind_group_layouts[..]
+        else {
+            unreachable!("The scene has four bind group layouts");
+        };
+        self.frames.resize(latency as usize, || {
+            FrameUniforms::new(
+                &self.device,
+                camera_layout,
+                globals_layout,
+                &self.shadow_map,
+                &self.skybox,
+                &self.camera_uniform,
+            )
+        });
+        // Offscreen targets are never presented, so there's nothing to
+        // reconfigure. A minimized window gets configured on restore.
+        if let RenderTarget::Surface {
+            surface: Some(surface),
+            ..
+        } = &self.target
+        {
+            if !self.is_minimized {
+                surface.configure(&self.device, &self.surface_configuration);
+            }
+        }
+    }
+
+    // The main camera's @group(1) for the frame being drawn
+    fn camera_bind_group(&self) -> &wgpu::BindGroup {
+        self.frames.current().camera.bind_group()
+    }
+
+    // @group(2) for the frame being drawn
+    fn globals_bind_group(&self) -> &wgpu::BindGroup {
+        &self.frames.current().globals_bind_group
+    }
+
+    pub fn cycle_frame_latency(&mut self) {
+        let next = if self.frame_latency() >= *FRAME_LATENCIES.end() {
+            *FRAME_LATENCIES.start()
+        } else {
+            self.frame_latency() + 1
+        };
+        self.set_frame_latency(next);
+        log::info!(
+            "Maximum frame latency: {} frame{}",
+            self.frame_latency(),
+            if self.frame_latency() == 1 { "" } else { "s" }
+        );
+    }
+
+    // The pentagon pipelines in index order
+    pub fn render_pipeline_names(&self) -> &[&'static str] {
+        &self.pipelines.render_pipeline_names
+    }
+
+    pub fn active_render_pipeline_index(&self) -> usize {
+        self.active_render_pipeline_index
+    }
+
+    pub fn set_active_render_pipeline(&mut self, index: usize) {
+        if index >= self.pipelines.render_pipelines.len() {
+            return;
+        }
+        self.active_render_pipeline_index = index;
+        // Keep Space and L cycling from wherever this left things
+        if index < N_FILL_PIPELINES {
+            self.fill_pipeline_index = index;
+        }
+    }
+
+    // A new pipeline identical to the first scene pipeline (shader.wgsl,
+    // filled), for timing pipeline creation. Nothing draws with it.
+    pub fn create_scene_pipeline(&self) -> wgpu::RenderPipeline {
+        let layouts: Vec<&wgpu::BindGroupLayout> =
+            self.scene_bind_group_layouts.iter().collect();
+        pipeline::create_object_pipeline(
+            &self.device,
+            &self.surface_configuration,
+            &self.scene_shader,
+            &layouts,
+            draw_constants::push_constant_ranges(self.push_constants),
+            &[Vertex::desc()],
+            self.sample_count,
+            Some(depth_stencil_state(
+                self.depth_format,
+                wgpu::CompareFunction::Less,
+                true,
+            )),
+            wgpu::PolygonMode::Fill,
+            false,
+            wgpu::PrimitiveTopology::TriangleList,
+            pipeline::CULL_MODES[0],
+            pipeline::FRONT_FACES[0],
+        )
+    }
+
+    // The render pipeline at `index` (see `render_pipeline_names`) with
+    // the current cull mode and front face
+    fn render_pipeline(&self, index: usize) -> &wgpu::RenderPipeline {
+        &self.pipelines.render_pipelines[index][self.front_face_index]
+            [self.cull_mode_index]
+    }
+
+    pub fn cull_mode(&self) -> Option<wgpu::Face> {
+        pipeline::CULL_MODES[self.cull_mode_index]
+    }
+
+    // Back faces, front faces, then none. Applies to every pipeline in
+    // `render_pipeline_names`, including ones entities pick for themselves;
+    // the instanced, terrain and other special purpose pipelines keep
+    // culling back faces.
+    pub fn cycle_cull_mode(&mut self) {
+        self.cull_mode_index =
+            (self.cull_mode_index + 1) % pipeline::CULL_MODES.len();
+        log::info!("Culling: {}", pipeline::cull_mode_name(self.cull_mode()));
+    }
+
+    pub fn front_face(&self) -> wgpu::FrontFace {
+        pipeline::FRONT_FACES[self.front_face_index]
+    }
+
+    // Swaps which winding counts as the front, for the same pipelines
+    // `cycle_cull_mode` applies to
+    pub fn toggle_front_face(&mut self) {
+        self.front_face_index =
+            (self.front_face_index + 1) % pipeline::FRONT_FACES.len();
+        log::info!(
+            "Front faces wind {}",
+            pipeline::front_face_name(self.front_face())
+        );
+    }
+
+    // Steps through every render pipeline, wireframe and conservative
+    // included, wrapping around at either end
+    pub fn cycle_render_pipeline(&mut self, backwards: bool) {
+        let n = self.pipelines.render_pipelines.len();
+        let index = if backwards {
+            (self.active_render_pipeline_index + n - 1) % n
+        } else {
+            (self.active_render_pipeline_index + 1) % n
+        };
+        self.set_active_render_pipeline(index);
+        log::info!(
+            "Render pipeline {}/{}: {}",
+            index + 1,
+            n,
+            self.pipelines.render_pipeline_names[index]
+        );
+    }
+
+    // Whichever of the two cameras is active
+    fn view_projection_matrix(&self) -> cgmath::Matrix4<f32> {
+        if self.use_ortho_camera {
+            self.ortho_camera.build_view_projection_matrix()
+        } else {
+            self.camera.build_view_projection_matrix()
+        }
+    }
+
+    fn eye(&self) -> cgmath::Point3<f32> {
+        if self.use_ortho_camera {
+            self.ortho_camera.eye()
+        } else {
+            self.camera.eye
+        }
+    }
+
+    // Swaps streamed textures in for their placeholders as they finish
+    fn poll_assets(&mut self) {
+        for (handle, result) in
+            self.asset_loader.poll(&self.device, &self.queue)
+        {
+            if Some(handle) != self.diffuse_texture_handle {
+                continue;
+            }
+            match result {
+                // Uploaded with a sampler that clamps, like every streamed
+                // texture
+                Ok(()) => {
+                    if let Err(e) =
+                        self.asset_loader.texture_mut(handle).set_sampler(
+                            &self.device,
+                            self.address_mode,
+                            self.anisotropy,
+                        )
+                    {
+                        log::error!("{:#}", e);
+                    }
+                }
+                Err(e) => {
+                    log::error!("{:#}, using the built-in texture", e);
+                    self.diffuse_texture_handle = None;
+                }
+            }
+            // The placeholder's shape was nothing to do with it
+            self.rebuild_pentagon_material();
+        }
+    }
+
+    // The pentagon's diffuse texture as it stands: the configured one (or
+    // its placeholder) if there is one, otherwise the built-in one
+    fn pentagon_diffuse_texture(&self) -> &texture::Texture {
+        match self.diffuse_texture_handle {
+            Some(handle) => self.asset_loader.texture(handle),
+            None => &self.builtin_diffuse_texture,
+        }
+    }
+
+    // Rewrites the pentagon's UV scale for its diffuse texture and UV
+    // tiling, and rebinds its textures to pick up new ones or new samplers
+    fn rebuild_pentagon_material(&mut self) {
+        let tiling = if self.tile_uvs { UV_TILING } else { 1.0 };
+        let [u, v] = material::uv_scale_for_aspect(
+            self.pentagon_diffuse_texture().aspect_ratio(),
+        );
+        self.pentagon_material.uv_scale = [u * tiling, v * tiling];
+        self.pentagon_material.update(&self.queue);
+        let bind_group = create_texture_bind_group(
+            &self.device,
+            &self.texture_bind_group_layout,
+            self.pentagon_diffuse_texture(),
+            &self.normal_texture,
+            &self.detail_texture,
+            &self.pentagon_material,
+        );
+        self.materials
+            .set_bind_group(scene::MaterialId::DEFAULT, bind_group);
+    }
+
+    // Steps through texture::ADDRESS_MODES for the pentagon's textures.
+    // Only visible outside 0..1, see `toggle_uv_tiling`.
+    pub fn cycle_address_mode(&mut self) {
+        let index = texture::ADDRESS_MODES
+            .iter()
+            .position(|&mode| mode == self.address_mode)
+            .unwrap_or(0);
+        self.address_mode =
+            texture::ADDRESS_MODES[(index + 1) % texture::ADDRESS_MODES.len()];
+        let (device, address_mode, anisotropy) =
+            (&self.device, self.address_mode, self.anisotropy);
+        let streamed = self
+            .diffuse_texture_handle
+            .map(|handle| self.asset_loader.texture_mut(handle));
+        for texture in [
+            &mut self.builtin_diffuse_texture,
+            &mut self.normal_texture,
+            &mut self.detail_texture,
+        ]
+        .into_iter()
+        .chain(streamed)
+        {
+            if let Err(e) =
+                texture.set_sampler(device, address_mode, anisotropy)
+            {
+                log::error!("{:#}", e);
+            }
+        }
+        self.rebuild_pentagon_material();
+        log::info!("Texture address mode: {:?}", self.address_mode);
+    }
+
+    pub fn tile_uvs(&self) -> bool {
+        self.tile_uvs
+    }
+
+    // Spreads the pentagon's UVs out past 0..1, to see how the address mode
+    // fills the rest
+    pub fn toggle_uv_tiling(&mut self) {
+        self.tile_uvs = !self.tile_uvs;
+        self.rebuild_pentagon_material();
+    }
+
+    // A depth-stencil attachment has to say what to do with the stencil
+    // aspect too, but a depth-only one mustn't
+    fn stencil_ops(
+        &self,
+        load: wgpu::LoadOp<u32>,
+    ) -> Option<wgpu::Operations<u32>> {
+        self.depth_format
+            .has_stencil_aspect()
+            .then_some(wgpu::Operations {
+                load,
+                store: wgpu::StoreOp::Store,
+            })
+    }
+
+    // How to end a pass for an attachment nothing reads afterwards.
+    //
+    // Storing writes the attachment back to memory when the pass ends.
+    // On a tiled GPU (most mobile ones) a pass is drawn a tile at a time in
+    // fast on-chip memory, so that write-back is most of what the
+    // attachment costs, and Discard skips it. Elsewhere it makes little
+    // difference either way. Discarding leaves the contents undefined, so
+    // it's only safe when no later pass loads, samples or copies the
+    // attachment: a multisampled color target whose resolve target is what
+    // gets kept, or a depth buffer only tested against within the pass.
+    // Anything that reads one later (the labels, the depth view, a depth
+    // prepass feeding the scene pass) needs Store.
+    fn transient_store(&self) -> wgpu::StoreOp {
+        if self.discard_transient {
+            wgpu::StoreOp::Discard
+        } else {
+            wgpu::StoreOp::Store
+        }
+    }
+
+    // Projected with whichever camera is active, into the scene target
+    fn queue_labels(&mut self) {
+        let view_proj = self.view_projection_matrix();
+        let Some(labels) = &mut self.labels else {
+            return;
+        };
+        labels.set_occluded(self.occlude_labels);
+
+        if self.show_instances || self.show_model || self.show_heightmap {
+            // Too many to label, or nothing to call them
+        } else if self.show_objects {
+            for (i, ([x, y, z], _, _)) in OBJECTS.iter().enumerate() {
+                let label = format!("Object {}", i + 1);
+                labels.queue((*x, y + 0.6, *z).into(), &label);
+            }
+        } else {
+            labels.queue((0.0, 0.6, 0.0).into(), "Pentagon");
+        }
+        if self.show_translucent {
+            for (i, (center, _)) in TRANSLUCENT_QUADS.iter().enumerate() {
+                let [x, y, z] = *center;
+                let top = y + TRANSLUCENT_QUAD_SIZE / 2.0;
+                labels.queue((x, top, z).into(), &format!("Quad {}", i + 1));
+            }
+        }
+
+        labels.prepare(&self.queue, &view_proj, self.sized.size);
+    }
+
+    fn mesh(&self, id: scene::MeshId) -> &geometry::Mesh {
+        &self.meshes[id.0]
+    }
+
+    // Group 0 for drawing `entity`
+    fn material_bind_group(&self, entity: &scene::Entity) -> &wgpu::BindGroup {
+        &self.materials.get(entity.material).bind_group
+    }
+
+    // Index into the render pipelines: the entity's own, else its
+    // material's, else the active one
+    fn entity_pipeline(&self, entity: &scene::Entity) -> usize {
+        self.entity_pipeline_or(entity, self.active_render_pipeline_index)
+    }
+
+    // Same, but with `fallback` in place of the active one
+    fn entity_pipeline_or(
+        &self,
+        entity: &scene::Entity,
+        fallback: usize,
+    ) -> usize {
+        entity
+            .pipeline
+            .or(self.materials.get(entity.material).pipeline)
+            .map_or(fallback, |id| id.0)
+    }
+
+    // Pushes `entity`'s draw constants, if they go through push constants.
+    // Otherwise fs_object reads them from the object uniforms, which
+    // `draw_object` binds.
+    fn set_draw_constants(
+        &self,
+        render_pass: &mut wgpu::RenderPass,
+        entity: &scene::Entity,
+    ) {
+        if self.push_constants {
+            let constants = draw_constants::DrawConstants { tint: entity.tint };
+            render_pass.set_push_constants(
+                draw_constants::STAGES,
+                0,
+                bytemuck::bytes_of(&constants),
+            );
+        }
+    }
+
+    // Whether HEIGHTMAP_PATH was there to build the terrain from
+    pub fn has_heightmap(&self) -> bool {
+        !self.heightmap_scene.entities.is_empty()
+    }
+
+    // Whether the depth view can read the depth buffer, see
+    // depth_can_be_sampled
+    pub fn can_show_depth(&self) -> bool {
+        self.sized
+            .depth_texture
+            .texture
+            .usage()
+            .contains(wgpu::TextureUsages::TEXTURE_BINDING)
+    }
+
+    // Whether there's a font for the labels. Only a broken loose font.png or
+    // font.json leaves them without one.
+    pub fn has_labels(&self) -> bool {
+        self.labels.is_some()
+    }
+
+    // Whether a model was configured and loaded
+    pub fn has_model(&self) -> bool {
+        !self.model_scene.entities.is_empty()
+    }
+
+    pub fn entity_stats(&self) -> scene::CullStats {
+        self.entity_stats
+    }
+
+    // Draw calls and triangles of the last frame rendered
+    pub fn render_stats(&self) -> render_stats::RenderStats {
+        self.render_stats
+    }
+
+    // What one draw of `entity` comes to, with the triangle limit applied
+    // Draws entity `index` of the active scene, with render pipeline
+    // `pipeline` already set and the other bind groups bound. The points
+    // pipeline gets every vertex once rather than going through the
+    // indices, and the split streams pipeline only draws the pentagon.
+    // Returns how many triangles that drew, none for points.
+    fn draw_entity(
+        &self,
+        render_pass: &mut wgpu::RenderPass,
+        index: usize,
+        entity: &scene::Entity,
+        pipeline: usize,
+    ) -> u64 {
+        if pipeline == self.pipelines.split_streams_pipeline_index {
+            // Nothing else has its vertices split up
+            if entity.mesh != self.pentagon {
+                return 0;
+            }
+            self.objects.draw_object(
+                render_pass,
+                3,
+                index,
+                &self.pentagon_streams,
+                &self.indirect_draws,
+            );
+            return self.entity_triangles(entity);
+        }
+        let mesh = self.mesh(entity.mesh);
+        if pipeline == self.pipelines.points_pipeline_index {
+            self.objects.bind_object(render_pass, 3, index);
+            mesh.draw_points(render_pass);
+            return 0;
+        }
+        self.objects.draw_object(
+            render_pass,
+            3,
+            index,
+            mesh,
+            &self.indirect_draws,
+        );
+        self.entity_triangles(entity)
+    }
+
+    fn entity_triangles(&self, entity: &scene::Entity) -> u64 {
+        let args =
+            indirect::mesh_args(self.mesh(entity.mesh), self.triangle_limit);
+        args.index_count as u64 / 3
+    }
+
+    // How many samples of each of the active scene's entities passed the
+    // depth test a frame or two ago, while `count_occlusion` is on. None if
+    // occlusion queries aren't supported.
+    pub fn entity_sample_counts(&self) -> Option<&[u64]> {
+        self.occlusion.as_ref().map(|occlusion| occlusion.counts())
+    }
+
+    // Finds the entities whose bounds are at least partly in view, so the
+    // rest can be skipped before their draw calls
+    fn cull_entities(&mut self) {
+        let frustum =
+            frustum::Frustum::from_view_proj(&self.view_projection_matrix());
+        let mut visible = std::mem::take(&mut self.visible_entities);
+        visible.clear();
+        let entities = &self.active_scene().entities;
+        // Only the main camera's frustum is culled against, so anything
+        // looking elsewhere gets everything. So does a Main viewport wider
+        // than the camera, which `refit_aspect` lets see further to the
+        // sides.
+        let other_cameras = self.viewports.iter().any(|viewport| {
+            let [_, _, width, height] = viewport.pixel_rect(self.sized.size);
+            viewport.camera != viewport::ViewportCamera::Main
+                || width as f32 > height.max(1) as f32 * self.camera.aspect
+        });
+        visible.extend(entities.iter().enumerate().filter_map(
+            |(index, entity)| {
+                let bounds = self.world_bounds(entity);
+                (other_cameras || frustum.intersects_aabb(&bounds))
+                    .then_some(index)
+            },
+        ));
+        // Grouped so the scene pass switches pipeline and material as
+        // little as it can. The sort is stable, so within a group entities
+        // keep their scene order.
+        visible.sort_by_key(|&index| {
+            let entity = &entities[index];
+            (self.entity_pipeline(entity), entity.material)
+        });
+        self.entity_stats = scene::CullStats {
+            drawn: visible.len(),
+            culled: entities.len() - visible.len(),
+        };
+        self.visible_entities = visible;
+    }
+
+    // Points the active scene's entities that have a LOD at the level for
+    // their distance from the camera
+    fn select_lods(&mut self) {
+        use cgmath::{EuclideanSpace, InnerSpace};
+
+        let eye = self.eye().to_vec();
+        let scene = if self.show_heightmap {
+            &mut self.heightmap_scene
+        } else if self.show_model {
+            &mut self.model_scene
+        } else if self.show_objects {
+            &mut self.objects_scene
+        } else {
+            &mut self.scene
+        };
+        for (index, entity) in scene.entities.iter_mut().enumerate() {
+            let Some(lod) = entity.lod.map(|id| &self.lods[id.0]) else {
+                continue;
+            };
+            let distance = (entity.transform.w.truncate() - eye).magnitude();
+            let level = lod.select(distance, entity.lod_level);
+            if level != entity.lod_level {
+                log::debug!(
+                    "Entity {} LOD {} -> {} at distance {:.2}",
+                    index,
+                    entity.lod_level,
+                    level,
+                    distance
+                );
+            }
+            entity.lod_level = level;
+            entity.mesh = lod.mesh(level);
+        }
+    }
+
+    fn active_scene(&self) -> &scene::Scene {
+        if self.show_heightmap {
+            &self.heightmap_scene
+        } else if self.show_model {
+            &self.model_scene
+        } else if self.show_objects {
+            &self.objects_scene
+        } else {
+            &self.scene
+        }
+    }
+
+    // Goes 1, 2, ... triangles per entity until the biggest mesh in the
+    // active scene would be drawn whole, then back to no limit. Only the
+    // indirect draw arguments change; the commands recorded are the same.
+    pub fn cycle_triangle_limit(&mut self) {
+        let max_triangles = self
+            .active_scene()
+            .entities
+            .iter()
+            .map(|entity| self.mesh(entity.mesh).n_indices() / 3)
+            .max()
+            .unwrap_or(0);
+        self.triangle_limit = match self.triangle_limit {
+            None => Some(1),
+            Some(limit) if limit + 1 < max_triangles => Some(limit + 1),
+            Some(_) => None,
+        };
+        match self.triangle_limit {
+            Some(limit) => log::info!("Triangles per entity: {}", limit),
+            None => log::info!("Triangles per entity: all"),
+        }
+    }
+
+    pub fn adjust_detail_mix(&mut self, delta: f32) {
+        self.detail_mix = (self.detail_mix + delta).clamp(0.0, 1.0);
+        log::info!("Detail texture mix: {:.0}%", self.detail_mix * 100.0);
+    }
+
+    pub fn shininess(&self) -> f32 {
+        self.pentagon_material.shininess
+    }
+
+    // Sets every material's shininess, the pentagon's and the model's alike
+    pub fn set_shininess(&mut self, shininess: f32) {
+        let shininess =
+            shininess.clamp(material::MIN_SHININESS, material::MAX_SHININESS);
+        for material in std::iter::once(&mut self.pentagon_material)
+            .chain(&mut self.model_speculars)
+        {
+            material.shininess = shininess;
+            material.update(&self.queue);
+        }
+    }
+
+    pub fn reflectivity(&self) -> f32 {
+        self.pentagon_material.reflectivity
+    }
+
+    // Sets how much of the skybox every material mirrors, from 0 to 1
+    pub fn set_reflectivity(&mut self, reflectivity: f32) {
+        let reflectivity = reflectivity.clamp(0.0, 1.0);
+        for material in std::iter::once(&mut self.pentagon_material)
+            .chain(&mut self.model_speculars)
+        {
+            material.reflectivity = reflectivity;
+            material.update(&self.queue);
+        }
+    }
+
+    pub fn adjust_reflectivity(&mut self, delta: f32) {
+        self.set_reflectivity(self.reflectivity() + delta);
+        log::info!("Reflectivity: {:.0}%", self.reflectivity() * 100.0);
+    }
+
+    // Multiplies the shininess by `factor`. The exponent's effect is roughly
+    // logarithmic, so equal factors make similar looking steps.
+    pub fn scale_shininess(&mut self, factor: f32) {
+        self.set_shininess(self.shininess() * factor);
+        log::info!("Shininess: {:.0}", self.shininess());
+    }
+
+    pub fn toggle_specular(&mut self) {
+        self.show_specular = !self.show_specular;
+        log::info!(
+            "Specular highlights: {}",
+            if self.show_specular { "on" } else { "off" }
+        );
+    }
+
+    // The background gradient's top and bottom color
+    pub fn gradient_colors(&self) -> (wgpu::Color, wgpu::Color) {
+        self.gradient.colors()
+    }
+
+    pub fn set_gradient_colors(
+        &mut self,
+        top: wgpu::Color,
+        bottom: wgpu::Color,
+    ) {
+        self.gradient.set_colors(&self.queue, top, bottom);
+    }
+
+    // Turns the gradient upside down
+    pub fn swap_gradient_colors(&mut self) {
+        let (top, bottom) = self.gradient_colors();
+        self.set_gradient_colors(bottom, top);
+    }
+
+    pub fn toggle_clip_plane(&mut self) {
+        self.show_clip_plane = !self.show_clip_plane;
+        log::info!(
+            "Clip plane: {}",
+            if self.show_clip_plane { "on" } else { "off" }
+        );
+    }
+
+    // Slides the clip plane `distance` along its normal, within
+    // CLIP_PLANE_RANGE of the origin
+    pub fn move_clip_plane(&mut self, distance: f32) {
+        self.clip_plane_offset = (self.clip_plane_offset + distance)
+            .clamp(-CLIP_PLANE_RANGE, CLIP_PLANE_RANGE);
+    }
+
+    // The plane as GlobalsUniform::clip_plane has it
+    fn clip_plane(&self) -> [f32; 4] {
+        if !self.show_clip_plane {
+            return NO_CLIP_PLANE;
+        }
+        let [x, y, z] = CLIP_PLANE_NORMAL;
+        [x, y, z, -self.clip_plane_offset]
+    }
+
+    pub fn toggle_wireframe(&mut self) {
+        let Some(wireframe_index) = self.pipelines.wireframe_pipeline_index
+        else {
+            log::warn!("Wireframe isn't supported on this adapter");
+            return;
+        };
+        self.toggle_fill_variant(wireframe_index);
+    }
+
+    pub fn toggle_conservative_rasterization(&mut self) {
+        let Some(conservative_index) =
+            self.pipelines.conservative_pipeline_index
+        else {
+            log::warn!(
+                "Conservative rasterization isn't supported on this adapter"
+            );
+            return;
+        };
+        self.toggle_fill_variant(conservative_index);
+    }
+
+    pub fn toggle_points(&mut self) {
+        self.toggle_fill_variant(self.pipelines.points_pipeline_index);
+    }
+
+    pub fn toggle_split_streams(&mut self) {
+        self.toggle_fill_variant(self.pipelines.split_streams_pipeline_index);
+    }
+
+    pub fn toggle_normals_pipeline(&mut self) {
+        self.toggle_fill_variant(self.pipelines.normals_pipeline_index);
+    }
+
+    // Switches to the pipeline at `index`, or back to the last fill pipeline
+    // if it's already active
+    fn toggle_fill_variant(&mut self, index: usize) {
+        self.active_render_pipeline_index =
+            if self.active_render_pipeline_index == index {
+                self.fill_pipeline_index
+            } else {
+                index
+            };
+    }
+
+    pub fn request_screenshot(&mut self) {
+        self.screenshot_requested = self.can_capture();
+    }
+
+    pub fn toggle_capture_sequence(&mut self) {
+        self.capture_sequence = match self.capture_sequence {
+            Some(n) => {
+                log::info!("Capture stopped after {} frames", n);
+                None
+            }
+            None if self.can_capture() => {
+                log::info!(
+                    "Capturing frames to {}/ ({} staging buffers)",
+                    CAPTURE_DIR,
+                    self.readback_pool.size()
+                );
+                Some(0)
+            }
+            None => None,
+        };
+    }
+
+    // Steps through CANVAS_ASPECTS and then back to following the window
+    pub fn cycle_canvas_aspect(&mut self) {
+        let next_index = match self.canvas {
+            Some(_) => self.canvas_aspect_index + 1,
+            None => 0,
+        };
+        match CANVAS_ASPECTS.get(next_index) {
+            Some(&(name, aspect)) => {
+                match &mut self.canvas {
+                    Some(canvas) => {
+                        canvas.set_aspect(&self.device, &self.queue, aspect)
+                    }
+                    None => {
+                        self.canvas = Some(canvas::Canvas::new(
+                            &self.device,
+                            self.surface_configuration.format,
+                            aspect,
+                            self.canvas_fit,
+                            self.size,
+                        ))
+                    }
+                }
+                self.canvas_aspect_index = next_index;
+                log::info!("Canvas: {} ({:?})", name, self.canvas_fit);
+            }
+            None => {
+                self.canvas = None;
+                log::info!("Canvas: off");
+            }
+        }
+        self.recreate_render_targets();
+    }
+
+    pub fn cycle_canvas_fit(&mut self) {
+        self.canvas_fit = self.canvas_fit.next();
+        if let Some(canvas) = &mut self.canvas {
+            canvas.set_fit(&self.device, &self.queue, self.canvas_fit);
+            self.recreate_render_targets();
+        }
+        log::info!("Canvas fit: {:?}", self.canvas_fit);
+    }
+
+    // A row of bobbing tiles along the bottom of the screen, alternating
+    // between two textures and each showing a different quarter of it
+    fn queue_demo_sprites(&mut self) {
+        const N_SPRITES: usize = 24;
+        const SPRITE_SIZE: f32 = 48.0;
+        const QUARTERS: [Rect; 4] = [
+            Rect::new(0.0, 0.0, 0.5, 0.5),
+            Rect::new(0.5, 0.0, 0.5, 0.5),
+            Rect::new(0.0, 0.5, 0.5, 0.5),
+            Rect::new(0.5, 0.5, 0.5, 0.5),
+        ];
+
+        let time = self.time;
+        let spacing = self.size.width as f32 / N_SPRITES as f32;
+        let baseline = self.size.height as f32 - SPRITE_SIZE * 2.0;
+
+        for i in 0..N_SPRITES {
+            let bob = (time * 2.0 + i as f32 * 0.5).sin() * SPRITE_SIZE * 0.25;
+            let rect = Rect::new(
+                i as f32 * spacing + (spacing - SPRITE_SIZE) * 0.5,
+                baseline + bob,
+                SPRITE_SIZE,
+                SPRITE_SIZE,
+            );
+            let uv = if i % 3 == 0 {
+                Rect::FULL_UV
+            } else {
+                QUARTERS[i % QUARTERS.len()]
+            };
+            self.sprite_batch.set_texture(self.sprite_textures[i % 2]);
+            self.sprite_batch.draw_sprite(rect, uv);
+        }
+    }
+
+    // The sprite array's current layer, over the middle of the pentagon
+    fn queue_pentagon_sprite(&mut self) {
+        const SPRITE_SIZE: f32 = 96.0;
+
+        let center = self.view_projection_matrix()
+            * cgmath::Vector4::new(0.0, 0.0, 0.0, 1.0);
+        // Behind the camera
+        if center.w <= 0.0 {
+            return;
+        }
+        let x = (center.x / center.w * 0.5 + 0.5) * self.size.width as f32;
+        let y = (0.5 - center.y / center.w * 0.5) * self.size.height as f32;
+        let rect = Rect::new(
+            x - SPRITE_SIZE * 0.5,
+            y - SPRITE_SIZE * 0.5,
+            SPRITE_SIZE,
+            SPRITE_SIZE,
+        );
+        self.sprite_array
+            .draw_sprite(rect, Rect::FULL_UV, self.sprite_layer);
+    }
+
+    // Steps the sprite over the pentagon to the texture array's next layer
+    pub fn cycle_sprite_layer(&mut self) {
+        self.sprite_layer =
+            (self.sprite_layer + 1) % self.sprite_array.layer_count();
+        log::info!(
+            "Sprite layer: {} of {}",
+            self.sprite_layer + 1,
+            self.sprite_array.layer_count()
+        );
+    }
+
+    // The mirror's quad, a quarter of the window wide in its top-right
+    // corner
+    fn queue_mirror_sprite(&mut self) {
+        const MARGIN: f32 = 16.0;
+        let width = self.size.width as f32 * 0.25;
+        let height =
+            width * mirror::MIRROR_HEIGHT as f32 / mirror::MIRROR_WIDTH as f32;
+        let rect = Rect::new(
+            self.size.width as f32 - width - MARGIN,
+            MARGIN,
+            width,
+            height,
+        );
+        self.sprite_batch.set_texture(self.mirror_sprite);
+        self.sprite_batch.draw_sprite(rect, Rect::FULL_UV);
+    }
+
+    // Drawn over the scene in the next frame only, see debug_draw::DebugDraw
+    pub fn debug_line(
+        &mut self,
+        from: cgmath::Point3<f32>,
+        to: cgmath::Point3<f32>,
+        color: [f32; 3],
+    ) {
+        self.debug_draw.line(from.into(), to.into(), color);
+    }
+
+    pub fn debug_box(&mut self, aabb: &frustum::Aabb, color: [f32; 3]) {
+        self.debug_draw.aabb(aabb, color);
+    }
+
+    // What each of the active scene's entities is frustum culled by, in
+    // world space
+    pub fn entity_bounds(&self) -> Vec<frustum::Aabb> {
+        self.active_scene()
+            .entities
+            .iter()
+            .map(|entity| self.world_bounds(entity))
+            .collect()
+    }
+
+    fn world_bounds(&self, entity: &scene::Entity) -> frustum::Aabb {
+        self.mesh(entity.mesh)
+            .bounds()
+            .transformed(&entity.transform)
+    }
+
+    // In screen pixels, positive x and y moving the view right and down
+    // the map
+    pub fn scroll_tile_map(&mut self, dx: f32, dy: f32) {
+        self.tile_map.scroll(dx, dy);
+    }
+
+    // The G-buffer target `gbuffer_view` picks, over the whole window
+    fn queue_gbuffer_sprite(&mut self) {
+        let sprite = match self.gbuffer_view {
+            gbuffer::GBufferView::Off => return,
+            gbuffer::GBufferView::Albedo => self.gbuffer_sprites[0],
+            gbuffer::GBufferView::Normal => self.gbuffer_sprites[1],
+        };
+        let rect = Rect::new(
+            0.0,
+            0.0,
+            self.size.width as f32,
+            self.size.height as f32,
+        );
+        self.sprite_batch.set_texture(sprite);
+        self.sprite_batch.draw_sprite(rect, Rect::FULL_UV);
+    }
+
+    pub fn cycle_gbuffer_view(&mut self) {
+        self.gbuffer_view = self.gbuffer_view.next();
+        log::info!("G-buffer view: {}", self.gbuffer_view.name());
+    }
+
+    // Particles spray from here, e.g. the cursor. In physical pixels from the
+    // window's top-left.
+    pub fn set_particle_emitter(
+        &mut self,
+        position: winit::dpi::PhysicalPosition<f64>,
+    ) {
+        let x = position.x / self.size.width.max(1) as f64 * 2.0 - 1.0;
+        let y = 1.0 - position.y / self.size.height.max(1) as f64 * 2.0;
+        self.particles.set_emitter(x as f32, y as f32);
+    }
+
+    pub fn toggle_linear_view(&mut self) {
+        if self.linear_output.is_none() {
+            log::warn!(
+                "Surface format {:?} can't be viewed as linear",
+                self.surface_configuration.format
+            );
+            return;
+        }
+        self.linear_view = !self.linear_view;
+        log::info!(
+            "Output: {}",
+            if self.linear_view {
+                "linear view, encoded in the shader"
+            } else {
+                "sRGB view, encoded on write"
+            }
+        );
+    }
+
+    // Whether `linear_view` does anything
+    pub fn has_linear_output(&self) -> bool {
+        self.linear_output.is_some()
+    }
+
+    pub fn cycle_post_preset(&mut self) {
+        self.post_preset_index =
+            (self.post_preset_index + 1) % POST_PRESETS.len();
+        let preset = POST_PRESETS[self.post_preset_index];
+        self.post_chain.solo(preset);
+        log::info!("Post effect: {}", preset.unwrap_or("None"));
+    }
+
+    // Changes the blur's radius by `delta` pixels, whether or not the blur
+    // is in use
+    pub fn adjust_blur_radius(&mut self, delta: i32) {
+        let Some(radius) = &self.blur_radius else {
+            log::info!("The blur effect isn't supported on this adapter");
+            return;
+        };
+        let value = radius
+            .load(Ordering::Relaxed)
+            .saturating_add_signed(delta)
+            .min(blur::MAX_RADIUS);
+        radius.store(value, Ordering::Relaxed);
+        log::info!("Blur radius: {}px", value);
+    }
+
+    // Replaces the values for blur.wgsl's override constants, by name (see
+    // blur::SAMPLE_SPACING). The blur's pipeline is rebuilt with them the
+    // next time it runs.
+    pub fn set_blur_constants(&mut self, constants: &HashMap<String, f64>) {
+        if let Some(blur_constants) = &self.blur_constants {
+            *blur_constants.lock().unwrap() = constants.clone();
+        }
+    }
+
+    // Names what the main pass is drawing with so logged GPU times can be
+    // told apart
+    fn scene_pass_label(&self) -> &'static str {
+        if self.runs_depth_prepass() {
+            // The timer spans both, so turning the prepass on and off
+            // compares like with like
+            "depth prepass + render pass (instanced.wgsl)"
+        } else if self.show_instances {
+            "render pass (instanced.wgsl)"
+        } else if Some(self.active_render_pipeline_index)
+            == self.pipelines.wireframe_pipeline_index
+        {
+            "render pass (wireframe)"
+        } else if Some(self.active_render_pipeline_index)
+            == self.pipelines.conservative_pipeline_index
+        {
+            "render pass (conservative)"
+        } else if self.active_render_pipeline_index
+            == self.pipelines.points_pipeline_index
+        {
+            "render pass (points)"
+        } else if self.active_render_pipeline_index
+            == self.pipelines.split_streams_pipeline_index
+        {
+            "render pass (split streams)"
+        } else if self.active_render_pipeline_index
+            == self.pipelines.normals_pipeline_index
+        {
+            "render pass (normals)"
+        } else if self.fill_pipeline_index == 0 {
+            "render pass (shader.wgsl)"
+        } else {
+            "render pass (shader2.wgsl)"
+        }
+    }
+
+    // The prepass lays down depth for the whole target from the main
+    // camera, which viewports wouldn't match
+    fn runs_depth_prepass(&self) -> bool {
+        self.show_instances && self.depth_prepass && self.viewports.is_empty()
+    }
+
+    // Starts frame timing over, e.g. after rendering was stopped for a while,
+    // so the gap isn't counted as one very slow frame
+    pub fn reset_frame_clock(&mut self) {
+        self.last_frame = Instant::now();
+    }
+
+    // A step and a frame showing where it ended up, for when frames and
+    // steps go one for one. Call once per frame before render(), after
+    // moving the camera.
+    pub fn update(&mut self, dt: f32) {
+        self.step(dt);
+        self.prepare_frame(1.0);
+    }
+
+    // Moves the animation and the particles on by `dt` seconds, so 0 freezes
+    // them while frames keep being drawn. Apart from the particles nothing
+    // is drawn any differently until `prepare_frame`.
+    pub fn step(&mut self, dt: f32) {
+        self.previous_simulated_time = self.simulated_time;
+        self.simulated_time += dt;
+
+        // Simulated in a submission of its own per step, ahead of the
+        // frame's. Frozen in place while the animation is paused.
+        if self.show_particles && dt > 0.0 {
+            let mut encoder = self.device.create_command_encoder(
+                &wgpu::CommandEncoderDescriptor {
+                    label: Some("Particle Encoder"),
+                },
+            );
+            self.particles
+                .update(&mut encoder, &self.queue, self.size, dt);
+            self.queue.submit(std::iter::once(encoder.finish()));
+        }
+    }
+
+    // Seconds of animation the last frame showed
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    // Jumps the animation to `time` seconds in, clamped at zero. The next
+    // frame shows it there without interpolating from where it was, and
+    // the particles, which can't run backwards, stay as they are.
+    pub fn set_time(&mut self, time: f32) {
+        self.simulated_time = time.max(0.0);
+        self.previous_simulated_time = self.simulated_time;
+        self.time = self.simulated_time;
+    }
+
+    // Call once per frame before render(), after any steps and moving the
+    // camera. The frame shows the animation `alpha` of the way from where
+    // the step before last left it to where the last step did, see
+    // frame_pacing::FixedTimestep.
+    pub fn prepare_frame(&mut self, alpha: f32) {
+        self.frames.advance();
+        self.time = self.previous_simulated_time
+            + (self.simulated_time - self.previous_simulated_time) * alpha;
+        let now = Instant::now();
+        let dt_ms = (now - self.last_frame).as_secs_f32() * 1000.0;
+        self.last_frame = now;
+        self.frame_time_ms = self.frame_time_ms * 0.95 + dt_ms * 0.05;
+
+        self.poll_assets();
+
+        if let Some(scale) = self.adaptive_resolution.update(dt_ms) {
+            log::info!(
+                "Render scale: {:.0}% ({:.2}ms frame time)",
+                scale * 100.0,
+                self.frame_time_ms
+            );
+            self.recreate_render_targets();
+        }
+
+        self.queue.write_buffer(
+            &self.frames.current().globals_buffer,
+            0,
+            bytemuck::cast_slice(&[GlobalsUniform {
+                time: self.time,
+                show_normals: self.show_normals as u32,
+                detail_mix: self.detail_mix,
+                shadows: self.show_shadows as u32,
+                specular: self.show_specular as u32,
+                audio_amplitude: self.audio_amplitude,
+                _padding: [0; 2],
+                clip_plane: self.clip_plane(),
+            }]),
+        );
+        self.shadow_map.update(&self.queue, &self.light);
+        if !self.viewports.is_empty()
+            && self.viewport_fill.colors().0 != self.clear_color
+        {
+            self.viewport_fill.set_colors(
+                &self.queue,
+                self.clear_color,
+                self.clear_color,
+            );
+        }
+
+        if self.show_objects {
+            let time = self.time;
+            for (entity, &(position, spin, _)) in
+                self.objects_scene.entities.iter_mut().zip(OBJECTS)
+            {
+                entity.transform =
+                    cgmath::Matrix4::from_translation(position.into())
+                        * cgmath::Matrix4::from_angle_z(cgmath::Rad(
+                            time * spin,
+                        ));
+            }
+        }
+        if self.show_instances {
+            self.instance_field.bob_middle(&self.queue, self.time * 2.0);
+        }
+        // Both scenes draw the same mesh, whichever it is. While it's the
+        // pentagon the separate objects then pick their level of detail.
+        let mesh = if self.show_morph {
+            self.morph_mesh
+        } else {
+            self.pentagon
+        };
+        for entity in self
+            .scene
+            .entities
+            .iter_mut()
+            .chain(&mut self.objects_scene.entities)
+        {
+            entity.mesh = mesh;
+        }
+        if !self.show_morph {
+            self.select_lods();
+        }
+        if self.show_morph {
+            let vertices = self.morph.blend(self.time);
+            self.meshes[self.morph_mesh.0]
+                .write_vertices(&self.queue, vertices);
+        }
+
+        let transforms = self.active_scene().transforms();
+        let tints = self.active_scene().tints();
+        self.objects.set_models(&self.queue, &transforms, &tints);
+        let draw_args: Vec<_> = self
+            .active_scene()
+            .entities
+            .iter()
+            .map(|entity| {
+                indirect::mesh_args(self.mesh(entity.mesh), self.triangle_limit)
+            })
+            .collect();
+        self.indirect_draws.set_args(&self.queue, &draw_args);
+
+        for frame in self.readback_pool.poll(&self.device) {
+            save_capture(frame, self.surface_configuration.format);
+        }
+
+        let pass_label = self.scene_pass_label();
+        if let Some(timer) = &mut self.gpu_timer {
+            timer.poll(&self.device, pass_label);
+        }
+        if let Some(occlusion) = &mut self.occlusion {
+            occlusion.poll(&self.device);
+        }
+
+        if self.show_mirror {
+            self.mirror.update(&self.queue, &self.camera);
+        }
+
+        self.camera_uniform
+            .update_view_proj(self.view_projection_matrix(), self.eye());
+        self.frames
+            .current()
+            .camera
+            .update(&self.queue, &self.camera_uniform);
+        self.update_viewport_cameras();
+    }
+
+    // Makes sure there's a camera uniform per viewport and writes each. A
+    // Main viewport sees what the window would, refitted to its shape.
+    fn update_viewport_cameras(&mut self) {
+        while self.viewport_cameras.len() < self.viewports.len() {
+            self.viewport_cameras.push(uniform::Uniform::with_layout(
+                &self.device,
+                "Viewport Camera",
+                self.frames.current().camera.layout(),
+                &self.camera_uniform,
+            ));
+        }
+        for (viewport, camera_buffer) in
+            self.viewports.iter().zip(&self.viewport_cameras)
+        {
+            let [_, _, width, height] = viewport.pixel_rect(self.sized.size);
+            let aspect = width.max(1) as f32 / height.max(1) as f32;
+            let (view_proj, eye) = match viewport.camera {
+                viewport::ViewportCamera::Main => (
+                    viewport::refit_aspect(
+                        self.view_projection_matrix(),
+                        self.camera.aspect,
+                        aspect,
+                    ),
+                    self.eye(),
+                ),
+                viewport::ViewportCamera::TopDown => {
+                    let camera =
+                        viewport::top_down_camera(&self.camera, aspect);
+                    (camera.build_view_projection_matrix(), camera.eye)
+                }
+            };
+            let mut camera_uniform = self.camera_uniform;
+            camera_uniform.update_view_proj(view_proj, eye);
+            camera_buffer.update(&self.queue, &camera_uniform);
+        }
+    }
+
+    pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
+        self.render_with(|_| {})
+    }
+
+    // Same as `render` but `overlay` gets to record its own passes on top of
+    // the finished frame, after the HUD
+    pub fn render_with(
+        &mut self,
+        overlay: impl FnOnce(Overlay),
+    ) -> Result<(), wgpu::SurfaceError> {
+        // Asking a minimized window's surface for a texture fails (or
+        // times out) every frame, so don't
+        if self.is_minimized {
+            return Ok(());
+        }
+
+        let output = self.acquire_frame()?;
+        let commands = self.record_frame(&output, overlay);
+        self.submit_frame(commands, output);
+        Ok(())
+    }
+
+    // Same as `render` but returns how long recording the frame's commands
+    // took on the CPU. The GPU may still be working on the frame when this
+    // returns, so none of its execution time is included.
+    pub fn render_timing_encode(
+        &mut self,
+    ) -> Result<std::time::Duration, wgpu::SurfaceError> {
+        if self.is_minimized {
+            return Ok(std::time::Duration::ZERO);
+        }
+
+        let output = self.acquire_frame()?;
+        let start = Instant::now();
+        let commands = self.record_frame(&output, |_| {});
+        let encode_time = start.elapsed();
+        self.submit_frame(commands, output);
+        Ok(encode_time)
+    }
+
+    // Blocks until the GPU has finished everything submitted so far
+    pub fn wait_idle(&self) {
+        self.device.poll(wgpu::Maintain::Wait);
+    }
+
+    fn acquire_frame(&self) -> Result<Frame, wgpu::SurfaceError> {
+        Ok(match &self.target {
+            RenderTarget::Surface { surface, .. } => Frame::Surface(
+                surface
+                    .as_ref()
+                    .ok_or(wgpu::SurfaceError::Lost)?
+                    .get_current_texture()?,
+            ),
+            RenderTarget::Offscreen { texture } => {
+                Frame::Offscreen(texture.clone())
+            }
+        })
+    }
+
+    // Everything a frame draws, from the scene passes to captures, in one
+    // command buffer that's ready to submit
+    fn record_frame(
+        &mut self,
+        output: &Frame,
+        overlay: impl FnOnce(Overlay),
+    ) -> wgpu::CommandBuffer {
+        // Through a linear view, the frame is drawn into an sRGB texture
+        // first and copied across at the end
+        let linear_output =
+            self.linear_output.as_ref().filter(|_| self.linear_view);
+        let view = match linear_output {
+            Some(linear_output) => linear_output.frame_view().clone(),
+            None => output
+                .texture()
+                .create_view(&wgpu::TextureViewDescriptor::default()),
+        };
+
+        let mut encoder = self.device.create_command_encoder(
+            &wgpu::CommandEncoderDescriptor {
+                label: Some("Render Encoder"),
+            },
+        );
+
+        let show_labels = self.show_labels && self.labels.is_some();
+        if show_labels {
+            self.queue_labels();
+        }
+        self.debug_draw.prepare(&self.queue);
+
+        if !self.show_instances {
+            self.cull_entities();
+        }
+
+        // The scene is drawn into the post chain, whose output goes straight
+        // to the surface unless there's a canvas to fit into it afterwards
+        let scene_view = self.sized.post_targets.scene_view();
+        let post_output_view = self.canvas.as_ref().map_or(&view, |c| c.view());
+
+        // With MSAA we draw into the multisampled texture and let the pass
+        // resolve it into the scene target on store
+        let (color_view, resolve_target) = match &self.sized.msaa_view {
+            Some(msaa_view) => (msaa_view, Some(scene_view)),
+            None => (scene_view, None),
+        };
+        // The depth attachment has to match the color attachment it's drawn
+        // with, which wgpu would otherwise only report as a pass validation
+        // error
+        debug_assert_eq!(
+            self.sized.depth_texture.texture.sample_count(),
+            if self.sized.msaa_view.is_some() {
+                self.sample_count
+            } else {
+                1
+            },
+            "Depth and color sample counts differ"
+        );
+
+        if self.show_instances {
+            self.instance_field.cull(
+                &mut encoder,
+                &self.queue,
+                &self.view_projection_matrix(),
+            );
+        }
+
+        let run_depth_prepass = self.runs_depth_prepass();
+        // Only the scene's entities are drawn into the mirror, not the
+        // instance field
+        let show_mirror = self.show_mirror && !self.show_instances;
+        // Like the mirror, the G-buffer only has the scene's entities
+        let show_gbuffer = self.gbuffer_view != gbuffer::GBufferView::Off
+            && !self.show_instances;
+        // The instance field is a single draw, so there's nothing to count
+        // per entity. Viewports draw each entity more than once a pass,
+        // which a query can't span.
+        let count_occlusion = self.count_occlusion
+            && !self.show_instances
+            && self.viewports.is_empty();
+
+        // The scene passes go through the render graph. Everything after
+        // them is still recorded in order by hand.
+        let render_stats = {
+            let ctx = FrameContext {
+                renderer: self,
+                color_view,
+                resolve_target,
+                run_depth_prepass,
+                keep_scene_attachments: show_labels || self.show_depth,
+                occlusion_query_set: self
+                    .occlusion
+                    .as_ref()
+                    .filter(|_| count_occlusion)
+                    .map(|occlusion| occlusion.query_set()),
+                stats: Cell::default(),
+            };
+            let mut graph = RenderGraph::new();
+            if self.show_shadows && !self.show_instances {
+                graph.add_pass(&ShadowPass);
+            }
+            if run_depth_prepass {
+                graph.add_pass(&DepthPrepass);
+            }
+            graph.add_pass(&ScenePass);
+            if show_mirror {
+                graph.add_pass(&MirrorPass);
+            }
+            if show_gbuffer {
+                graph.add_pass(&GBufferPass);
+            }
+            if show_labels {
+                graph.add_pass(&LabelPass);
+            }
+            graph.execute(&mut encoder, &ctx);
+            ctx.stats.get()
+        };
+        self.render_stats = render_stats;
+
+        if let Some(timer) = &mut self.gpu_timer {
+            timer.resolve(&mut encoder);
+        }
+        let n_entities = self.active_scene().entities.len() as u32;
+        if let (true, Some(occlusion)) = (count_occlusion, &mut self.occlusion)
+        {
+            occlusion.resolve(&mut encoder, n_entities);
+        }
+
+        self.post_chain.run(
+            &mut encoder,
+            &self.queue,
+            &self.sized.post_targets,
+            post_output_view,
+            self.time,
+        );
+
+        // Drawn over the post chain's output so the HUD and overlays still
+        // go on top
+        if self.show_depth && self.can_show_depth() {
+            let (near, far, orthographic) = if self.use_ortho_camera {
+                (camera::ORTHO_ZNEAR, camera::ORTHO_ZFAR, true)
+            } else {
+                (self.camera.znear, self.camera.zfar, false)
+            };
+            self.depth_view.draw(
+                &self.device,
+                &self.queue,
+                &mut encoder,
+                &self.sized.depth_texture.texture,
+                post_output_view,
+                depth_view::DepthViewUniform::new(near, far, orthographic),
+            );
+        }
+
+        if let Some(canvas) = &self.canvas {
+            canvas.blit(&mut encoder, &view);
+        }
+
+        if self.show_sprites {
+            self.queue_demo_sprites();
+            self.queue_pentagon_sprite();
+        }
+        if self.show_tile_map {
+            self.tile_map.prepare(&self.queue, self.size);
+        }
+        if show_mirror {
+            self.queue_mirror_sprite();
+        }
+        if show_gbuffer {
+            self.queue_gbuffer_sprite();
+        }
+        self.sprite_batch.prepare(&self.queue, self.size);
+        self.sprite_array.prepare(&self.queue, self.size);
+
+        if let Some(text) = &mut self.text {
+            text.queue_text("Learn WGPU", [16.0, 16.0], 32.0);
+            if self.show_instances {
+                let on_off = |on| if on { "on" } else { "off" };
+                let hud = format!(
+                    "Instances: {}  GPU culling: {}  Depth prepass: {}  \
+                     {:.2}ms",
+                    self.instance_field.n_instances(),
+                    on_off(self.instance_field.culling_enabled),
+                    on_off(self.depth_prepass),
+                    self.frame_time_ms
+                );
+                text.queue_text(&hud, [16.0, 56.0], 20.0);
+            }
+            if self.show_post_hud {
+                let lines = self.post_chain.describe();
+                for (i, line) in lines.iter().enumerate() {
+                    let y = 96.0 + i as f32 * 22.0;
+                    text.queue_text(line, [16.0, y], 18.0);
+                }
+            }
+            text.prepare(&self.queue, self.size);
+        }
+
+        {
+            // Particles, sprites and text are drawn in their own pass on top
+            // of the finished scene so they never interact with the scene's
+            // pipelines or attachments. Text goes last to stay readable.
+            let mut overlay_pass =
+                encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("Overlay Render Pass"),
+                    color_attachments: &[Some(
+                        wgpu::RenderPassColorAttachment {
+                            view: &view,
+                            resolve_target: None,
+                            ops: wgpu::Operations {
+                                load: wgpu::LoadOp::Load,
+                                store: wgpu::StoreOp::Store,
+                            },
+                        },
+                    )],
+                    depth_stencil_attachment: None,
+                    occlusion_query_set: None,
+                    timestamp_writes: None,
+                });
+
+            if self.show_particles {
+                self.particles.render(&mut overlay_pass);
+            }
+            if self.show_tile_map {
+                self.tile_map.render(&mut overlay_pass);
+            }
+            self.sprite_batch.render(&mut overlay_pass);
+            self.sprite_array.render(&mut overlay_pass);
+            if let Some(text) = &self.text {
+                text.render(&mut overlay_pass);
+            }
+        }
+
+        overlay(Overlay {
+            device: &self.device,
+            queue: &self.queue,
+            encoder: &mut encoder,
+            view: &view,
+            size: self.size,
+        });
+
+        if let (true, Some(linear_output)) =
+            (self.linear_view, &self.linear_output)
+        {
+            linear_output.present(&mut encoder, output.texture());
+        }
+
+        // Captures come from the canvas when there is one so they have its
+        // composition rather than the window's (and no letterbox bars or
+        // HUD text)
+        let capture_texture = match &self.canvas {
+            Some(canvas) => canvas.texture(),
+            None => output.texture(),
+        };
+        if !self.can_capture() {
+            // The canvas was switched off mid-sequence and the surface can't
+            // be copied from
+            self.capture_sequence = None;
+        }
+
+        if self.screenshot_requested
+            && self.readback_pool.copy_texture(
+                &self.device,
+                &mut encoder,
+                capture_texture,
+                Capture::Screenshot,
+            )
+        {
+            self.screenshot_requested = false;
+        }
+
+        if let Some(n) = self.capture_sequence {
+            // A skipped frame (all staging buffers busy) just leaves a gap
+            // rather than stalling the render loop
+            if self.readback_pool.copy_texture(
+                &self.device,
+                &mut encoder,
+                capture_texture,
+                Capture::Sequence(n),
+            ) {
+                self.capture_sequence = Some(n + 1);
+            }
+        }
+
+        encoder.finish()
+    }
+
+    fn submit_frame(&mut self, commands: wgpu::CommandBuffer, output: Frame) {
+        // Submit will accept anything that implements `IntoIter`
+        self.queue.submit(std::iter::once(commands));
+        self.readback_pool.after_submit();
+        if let Some(timer) = &mut self.gpu_timer {
+            timer.after_submit();
+        }
+        if let Some(occlusion) = &mut self.occlusion {
+            occlusion.after_submit();
+        }
+        output.present();
+    }
+
+    // The color of the pixel at physical position (x, y) as sRGB encoded
+    // RGBA, whatever format the surface stores it in. A surface texture is
+    // gone once presented and can't always be copied from anyway, so this
+    // draws a fresh frame (without the debug panel) into a texture that can,
+    // and blocks until the one pixel is back on the CPU.
+    pub fn pick_pixel(
+        &mut self,
+        x: u32,
+        y: u32,
+    ) -> Result<[u8; 4], StateError> {
+        if self.is_minimized {
+            return Err(StateError::Readback);
+        }
+
+        let configuration = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::COPY_SRC,
+            ..self.surface_configuration.clone()
+        };
+        let texture =
+            gpu::create_offscreen_texture(&self.device, &configuration);
+        let output = Frame::Offscreen(texture.clone());
+        let commands = self.record_frame(&output, |_| {});
+        self.submit_frame(commands, output);
+
+        let x = x.min(configuration.width - 1);
+        let y = y.min(configuration.height - 1);
+        let pixel =
+            readback::read_pixel(&self.device, &self.queue, &texture, x, y)
+                .ok_or(StateError::Readback)?;
+        let rgba =
+            readback::convert_surface_bytes(configuration.format, 1, 1, &pixel);
+        rgba.try_into().map_err(|_| StateError::Readback)
+    }
+
+    // Blocks until the offscreen target's current contents are back on the
+    // CPU, as sRGB encoded RGBA. Only meaningful after rendering headless.
+    pub fn read_offscreen(&self) -> Result<Vec<u8>, StateError> {
+        let RenderTarget::Offscreen { texture } = &self.target else {
+            return Err(StateError::Readback);
+        };
+
+        let mut pool = readback::ReadbackPool::new(1);
+        let mut encoder = self.device.create_command_encoder(
+            &wgpu::CommandEncoderDescriptor {
+                label: Some("Offscreen Readback Encoder"),
+            },
+        );
+        pool.copy_texture(&self.device, &mut encoder, texture, ());
+        self.queue.submit(std::iter::once(encoder.finish()));
+        pool.after_submit();
+
+        // Wait for the copy and the map to finish instead of polling
+        self.device.poll(wgpu::Maintain::Wait);
+        pool.poll(&self.device)
+            .pop()
+            .map(|frame| {
+                readback::convert_surface_bytes(
+                    texture.format(),
+                    frame.width,
+                    frame.height,
+                    &frame.bytes,
+                )
+            })
+            .ok_or(StateError::Readback)
+    }
+}
+
+struct TranslucentQuad {
+    // What it's sorted by
+    center: cgmath::Point3<f32>,
+    mesh: geometry::Mesh,
+}
+
+// Graph resources written by the scene passes
+const DEPTH: Resource = Resource("depth");
+const SHADOW_MAP: Resource = Resource("shadow map");
+const SCENE_COLOR: Resource = Resource("scene color");
+const MIRROR_COLOR: Resource = Resource("mirror color");
+const GBUFFER: Resource = Resource("g-buffer");
+
+// What the scene passes need from `Renderer::render_with` for one frame
+struct FrameContext<'f> {
+    renderer: &'f Renderer<'f>,
+    // The multisampled target when MSAA is on, otherwise the scene target
+    color_view: &'f wgpu::TextureView,
+    resolve_target: Option<&'f wgpu::TextureView>,
+    run_depth_prepass: bool,
+    // Set when a pass after the scene pass reads its depth or multisampled
+    // color, which then can't be discarded
+    keep_scene_attachments: bool,
+    // Set when the scene pass should count each entity's samples
+    occlusion_query_set: Option<&'f wgpu::QuerySet>,
+    // Added to by every pass as it records its draws. Passes only get a
+    // shared reference, hence the Cell.
+    stats: Cell<render_stats::RenderStats>,
+}
+
+impl FrameContext<'_> {
+    fn count_draw(&self, triangles: u64) {
+        let mut stats = self.stats.get();
+        stats.draw(triangles);
+        self.stats.set(stats);
+    }
+
+    fn count_culled(&self, triangles: u64) {
+        let mut stats = self.stats.get();
+        stats.cull(triangles);
+        self.stats.set(stats);
+    }
+
+    fn count_pipeline_switch(&self) {
+        let mut stats = self.stats.get();
+        stats.switch_pipeline();
+        self.stats.set(stats);
+    }
+
+    fn count_material_switch(&self) {
+        let mut stats = self.stats.get();
+        stats.switch_material();
+        self.stats.set(stats);
+    }
+}
+
+// Lays down the instance field's depth so the scene pass only shades the
+// nearest fragment of each pixel
+struct DepthPrepass;
+
+impl Pass<FrameContext<'_>> for DepthPrepass {
+    fn name(&self) -> &str {
+        "Depth Prepass"
+    }
+
+    fn outputs(&self) -> &[Resource] {
+        &[DEPTH]
+    }
+
+    fn record(&self, encoder: &mut wgpu::CommandEncoder, ctx: &FrameContext) {
+        let r = ctx.renderer;
+        let mut prepass =
+            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Depth Prepass"),
+                color_attachments: &[],
+                depth_stencil_attachment: Some(
+                    wgpu::RenderPassDepthStencilAttachment {
+                        view: &r.sized.depth_texture.view,
+                        depth_ops: Some(wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(1.0),
+                            store: wgpu::StoreOp::Store,
+                        }),
+                        stencil_ops: r.stencil_ops(wgpu::LoadOp::Clear(0)),
+                    },
+                ),
+                occlusion_query_set: None,
+                // The scene pass ends the measurement
+                timestamp_writes: r
+                    .gpu_timer
+                    .as_ref()
+                    .map(|timer| timer.begin_writes()),
+            });
+
+        prepass.set_pipeline(&r.pipelines.instanced_depth_prepass_pipeline);
+        prepass.set_bind_group(0, r.materials.default_bind_group(), &[]);
+        prepass.set_bind_group(1, r.camera_bind_group(), &[]);
+        prepass.set_bind_group(2, r.globals_bind_group(), &[]);
+        r.mesh(r.pentagon).bind(&mut prepass);
+        r.instance_field.draw(&mut prepass);
+        ctx.count_draw(r.instance_field.n_triangles());
+    }
+}
+
+// The active scene's depth from the light. Every entity is drawn, not just
+// the ones in view, since something off-screen can still cast a shadow onto
+// the screen.
+struct ShadowPass;
+
+impl Pass<FrameContext<'_>> for ShadowPass {
+    fn name(&self) -> &str {
+        "Shadow Pass"
+    }
+
+    fn outputs(&self) -> &[Resource] {
+        &[SHADOW_MAP]
+    }
+
+    fn record(&self, encoder: &mut wgpu::CommandEncoder, ctx: &FrameContext) {
+        let r = ctx.renderer;
+        let entities = &r.active_scene().entities;
+        for entity in entities {
+            ctx.count_draw(r.entity_triangles(entity));
+        }
+        r.shadow_map.render(
+            encoder,
+            &r.objects,
+            &r.indirect_draws,
+            entities
+                .iter()
+                .enumerate()
+                .map(|(index, entity)| (index, r.mesh(entity.mesh))),
+        );
+    }
+}
+
+// Stencil value the mask pipeline writes and the reveal pipeline tests for
+const STENCIL_REVEAL_REFERENCE: u32 = 1;
+
+// Every pipeline that draws into the scene's attachments, which all have
+// the sample count baked in. Changing it means building the lot again, see
+// `Renderer::set_sample_count`.
+struct ScenePipelines {
+    render_pipelines: Vec<ObjectPipelines>,
+    // Shown in logs and the debug panel, one per render pipeline
+    render_pipeline_names: Vec<&'static str>,
+    // None when the adapter can't rasterize lines
+    wireframe_pipeline_index: Option<usize>,
+    // None without Features::CONSERVATIVE_RASTERIZATION
+    conservative_pipeline_index: Option<usize>,
+    points_pipeline_index: usize,
+    split_streams_pipeline_index: usize,
+    normals_pipeline_index: usize,
+    transparent_render_pipeline: wgpu::RenderPipeline,
+    instanced_render_pipeline: wgpu::RenderPipeline,
+    instanced_after_prepass_pipeline: wgpu::RenderPipeline,
+    instanced_depth_prepass_pipeline: wgpu::RenderPipeline,
+    // Only there when the depth texture has a stencil aspect
+    stencil_reveal: Option<StencilReveal>,
+    skybox_render_pipeline: wgpu::RenderPipeline,
+    gradient_render_pipeline: wgpu::RenderPipeline,
+    terrain_render_pipeline: wgpu::RenderPipeline,
+    debug_draw_pipeline: wgpu::RenderPipeline,
+    // Single sampled whatever `sample_count` is, the G-buffer's targets are
+    gbuffer_pipeline: wgpu::RenderPipeline,
+}
+
+// One render pipeline in every combination of pipeline::FRONT_FACES and
+// pipeline::CULL_MODES, indexed in that order, see `Renderer::cycle_cull_mode`
+// and `Renderer::toggle_front_face`
+type ObjectPipelines = [[wgpu::RenderPipeline; 3]; 2];
+
+impl ScenePipelines {
+    // `object_bind_group_layouts` are the texture, camera, globals and
+    // object layouts, in that order. Every pipeline gets the same
+    // `sample_count` and `depth_format`.
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        device: &wgpu::Device,
+        surface_configuration: &wgpu::SurfaceConfiguration,
+        shader: &wgpu::ShaderModule,
+        object_bind_group_layouts: &[&wgpu::BindGroupLayout],
+        push_constants: bool,
+        sample_count: u32,
+        depth_format: wgpu::TextureFormat,
+        skybox: &skybox::Skybox,
+        terrain: &splat::SplatTerrain,
+        gradient: &gradient::BackgroundGradient,
+    ) -> Self {
+        let bind_group_layouts = &object_bind_group_layouts[..3];
+        // What everything but the G-buffer pipeline draws into
+        let surface_target =
+            [pipeline::color_target(surface_configuration.format)];
+        let camera_bind_group_layout = object_bind_group_layouts[1];
+        let push_constant_ranges =
+            draw_constants::push_constant_ranges(push_constants);
+
+        let object_pipelines = |shader: &wgpu::ShaderModule,
+                                buffers: &[wgpu::VertexBufferLayout],
+                                polygon_mode: wgpu::PolygonMode,
+                                conservative: bool,
+                                topology: wgpu::PrimitiveTopology|
+         -> ObjectPipelines {
+            pipeline::FRONT_FACES.map(|front_face| {
+                pipeline::CULL_MODES.map(|cull_mode| {
+                    pipeline::create_object_pipeline(
+                        device,
+                        surface_configuration,
+                        shader,
+                        object_bind_group_layouts,
+                        push_constant_ranges,
+                        buffers,
+                        sample_count,
+                        Some(depth_stencil_state(
+                            depth_format,
+                            wgpu::CompareFunction::Less,
+                            true,
+                        )),
+                        polygon_mode,
+                        conservative,
+                        topology,
+                        cull_mode,
+                        front_face,
+                    )
+                })
+            })
+        };
+
+        let triangles = wgpu::PrimitiveTopology::TriangleList;
+        let interleaved = [Vertex::desc()];
+        let render_pipeline = object_pipelines(
+            shader,
+            &interleaved,
+            wgpu::PolygonMode::Fill,
+            false,
+            triangles,
+        );
+
+        let transparent_render_pipeline = pipeline::create_transparent_pipeline(
+            device,
+            surface_configuration,
+            shader,
+            bind_group_layouts,
+            &[Vertex::desc()],
+            sample_count,
+            depth_format,
+            "fs_translucent",
+        );
+
+        let render_pipeline2 = object_pipelines(
+            &device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("shader2.wgsl"),
+                source: wgpu::ShaderSource::Wgsl(
+                    object_shader_source(
+                        "shader2.wgsl",
+                        include_str!("shader2.wgsl"),
+                        push_constants,
+                    )
+                    .into(),
+                ),
+            }),
+            &interleaved,
+            wgpu::PolygonMode::Fill,
+            false,
+            triangles,
+        );
+
+        let mut render_pipelines = vec![render_pipeline, render_pipeline2];
+        let mut render_pipeline_names = vec!["shader.wgsl", "shader2.wgsl"];
+
+        // Same as the first pipeline but rasterizing only triangle edges.
+        // It's appended after the fill pipelines so it has somewhere to go
+        // back to when L switches it off.
+        let wireframe_supported = device
+            .features()
+            .contains(wgpu::Features::POLYGON_MODE_LINE);
+        let wireframe_pipeline_index = wireframe_supported.then(|| {
+            render_pipelines.push(object_pipelines(
+                shader,
+                &interleaved,
+                wgpu::PolygonMode::Line,
+                false,
+                triangles,
+            ));
+            render_pipeline_names.push("wireframe");
+            render_pipelines.len() - 1
+        });
+
+        // Same again with conservative rasterization, which covers every
+        // pixel a triangle touches at all. Edges come out a pixel fatter,
+        // which is the point for voxelization or coverage tests. H switches
+        // back the same way L does.
+        let conservative_supported = device
+            .features()
+            .contains(wgpu::Features::CONSERVATIVE_RASTERIZATION);
+        let conservative_pipeline_index = conservative_supported.then(|| {
+            render_pipelines.push(object_pipelines(
+                shader,
+                &interleaved,
+                wgpu::PolygonMode::Fill,
+                true,
+                triangles,
+            ));
+            render_pipeline_names.push("conservative");
+            render_pipelines.len() - 1
+        });
+
+        // Same again drawing only the vertices, each as a single pixel, for
+        // a point cloud look. The scene pass draws these without the index
+        // buffer, see `Renderer::draw_entity`. ; switches back the same way
+        // L does.
+        render_pipelines.push(object_pipelines(
+            shader,
+            &interleaved,
+            wgpu::PolygonMode::Fill,
+            false,
+            wgpu::PrimitiveTopology::PointList,
+        ));
+        render_pipeline_names.push("points");
+        let points_pipeline_index = render_pipelines.len() - 1;
+
+        // The first pipeline again, reading the pentagon's attributes from
+        // several vertex buffers instead of one (see `Mesh::new_split`). It
+        // should look no different. Insert switches back the same way L
+        // does.
+        render_pipelines.push(object_pipelines(
+            shader,
+            &Vertex::split_desc(),
+            wgpu::PolygonMode::Fill,
+            false,
+            triangles,
+        ));
+        render_pipeline_names.push("split streams");
+        let split_streams_pipeline_index = render_pipelines.len() - 1;
+
+        // World space vertex normals as colors, for checking a mesh's
+        // normals (and which way its tangent frame starts out) before
+        // trusting the lighting. Shift+Insert switches back the same way L
+        // does.
+        render_pipelines.push(object_pipelines(
+            &device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("normals.wgsl"),
+                source: wgpu::ShaderSource::Wgsl(
+                    object_shader_source(
+                        "normals.wgsl",
+                        include_str!("normals.wgsl"),
+                        push_constants,
+                    )
+                    .into(),
+                ),
+            }),
+            &interleaved,
+            wgpu::PolygonMode::Fill,
+            false,
+            triangles,
+        ));
+        render_pipeline_names.push("normals");
+        let normals_pipeline_index = render_pipelines.len() - 1;
+
+        let instanced_shader =
+            device.create_shader_module(wgpu::include_wgsl!("instanced.wgsl"));
+        let instanced_buffers =
+            [Vertex::desc(), instancing::InstanceRaw::desc()];
+
+        let instanced_render_pipeline = pipeline::create_render_pipeline(
+            device,
+            &surface_target,
+            &instanced_shader,
+            bind_group_layouts,
+            &instanced_buffers,
+            sample_count,
+            Some(depth_stencil_state(
+                depth_format,
+                wgpu::CompareFunction::Less,
+                true,
+            )),
+            wgpu::PolygonMode::Fill,
+            false,
+        );
+
+        // With a prepass the depth buffer already holds the nearest surface,
+        // so only the fragment that produced it passes Equal and the
+        // expensive fragment shader runs once per pixel. Depth is already
+        // final so there's no point writing it again.
+        let instanced_after_prepass_pipeline = pipeline::create_render_pipeline(
+            device,
+            &surface_target,
+            &instanced_shader,
+            bind_group_layouts,
+            &instanced_buffers,
+            sample_count,
+            Some(depth_stencil_state(
+                depth_format,
+                wgpu::CompareFunction::Equal,
+                false,
+            )),
+            wgpu::PolygonMode::Fill,
+            false,
+        );
+
+        let instanced_depth_prepass_pipeline =
+            pipeline::create_depth_prepass_pipeline(
+                device,
+                &instanced_shader,
+                bind_group_layouts,
+                &instanced_buffers,
+                sample_count,
+                depth_format,
+            );
+
+        let stencil_reveal =
+            depth_format.has_stencil_aspect().then(|| StencilReveal {
+                mask_pipeline: pipeline::create_stencil_mask_pipeline(
+                    device,
+                    surface_configuration,
+                    shader,
+                    bind_group_layouts,
+                    &[Vertex::desc()],
+                    sample_count,
+                    depth_format,
+                ),
+                reveal_pipeline: pipeline::create_render_pipeline(
+                    device,
+                    &surface_target,
+                    &device.create_shader_module(wgpu::include_wgsl!(
+                        "stencil_reveal.wgsl"
+                    )),
+                    &[],
+                    &[],
+                    sample_count,
+                    Some(wgpu::DepthStencilState {
+                        stencil: pipeline::stencil_test(
+                            wgpu::CompareFunction::Equal,
+                        ),
+                        ..depth_stencil_state(
+                            depth_format,
+                            wgpu::CompareFunction::Always,
+                            false,
+                        )
+                    }),
+                    wgpu::PolygonMode::Fill,
+                    false,
+                ),
+            });
+
+        // The sky sits at exactly the far plane, i.e. the cleared depth, so
+        // it needs LessEqual to show up at all. Not writing depth leaves
+        // everything drawn after it free to land in front.
+        let skybox_render_pipeline = pipeline::create_render_pipeline(
+            device,
+            &surface_target,
+            &device.create_shader_module(wgpu::include_wgsl!("skybox.wgsl")),
+            &[skybox.bind_group_layout(), camera_bind_group_layout],
+            &[],
+            sample_count,
+            Some(depth_stencil_state(
+                depth_format,
+                wgpu::CompareFunction::LessEqual,
+                false,
+            )),
+            wgpu::PolygonMode::Fill,
+            false,
+        );
+
+        // Drawn first, so it passes wherever depth is still clear; Always
+        // just saves the comparison. Like the skybox it writes no depth.
+        let gradient_render_pipeline = pipeline::create_render_pipeline(
+            device,
+            &surface_target,
+            &device.create_shader_module(wgpu::include_wgsl!("gradient.wgsl")),
+            &[gradient.bind_group_layout()],
+            &[],
+            sample_count,
+            Some(depth_stencil_state(
+                depth_format,
+                wgpu::CompareFunction::Always,
+                false,
+            )),
+            wgpu::PolygonMode::Fill,
+            false,
+        );
+
+        let terrain_render_pipeline = pipeline::create_render_pipeline(
+            device,
+            &surface_target,
+            &device.create_shader_module(wgpu::include_wgsl!("splat.wgsl")),
+            &[terrain.bind_group_layout(), camera_bind_group_layout],
+            &[Vertex::desc()],
+            sample_count,
+            Some(depth_stencil_state(
+                depth_format,
+                wgpu::CompareFunction::Less,
+                true,
+            )),
+            wgpu::PolygonMode::Fill,
+            false,
+        );
+
+        let debug_draw_pipeline = debug_draw::create_pipeline(
+            device,
+            surface_configuration,
+            camera_bind_group_layout,
+            sample_count,
+            depth_format,
+        );
+
+        let gbuffer_pipeline = pipeline::create_render_pipeline(
+            device,
+            &gbuffer::GBuffer::targets(),
+            &device.create_shader_module(wgpu::include_wgsl!("gbuffer.wgsl")),
+            object_bind_group_layouts,
+            &[Vertex::desc()],
+            1,
+            Some(depth_stencil_state(
+                depth_format,
+                wgpu::CompareFunction::Less,
+                true,
+            )),
+            wgpu::PolygonMode::Fill,
+            false,
+        );
+
+        Self {
+            render_pipelines,
+            render_pipeline_names,
+            wireframe_pipeline_index,
+            conservative_pipeline_index,
+            points_pipeline_index,
+            split_streams_pipeline_index,
+            normals_pipeline_index,
+            transparent_render_pipeline,
+            instanced_render_pipeline,
+            instanced_after_prepass_pipeline,
+            instanced_depth_prepass_pipeline,
+            stencil_reveal,
+            skybox_render_pipeline,
+            gradient_render_pipeline,
+            terrain_render_pipeline,
+            debug_draw_pipeline,
+            gbuffer_pipeline,
+        }
+    }
+}
+
+// Text renderer for the labels, drawn in the scene pass against its depth
+fn create_labels(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    format: wgpu::TextureFormat,
+    font: sdf_text::SdfFont,
+    sample_count: u32,
+    depth_format: wgpu::TextureFormat,
+) -> world_labels::WorldLabels {
+    let mut text = sdf_text::SdfTextRenderer::new_in_scene(
+        device,
+        format,
+        font,
+        sample_count,
+        depth_format,
+    );
+    text.set_style(
+        queue,
+        sdf_text::TextStyle {
+            outline_width: 0.15,
+            ..Default::default()
+        },
+    );
+    world_labels::WorldLabels::new(text)
+}
+
+// Two pipelines sharing the stencil buffer: the mask stamps the pentagon's
+// shape into it, then the reveal draws a fullscreen pattern that only shows
+// up inside that shape
+struct StencilReveal {
+    mask_pipeline: wgpu::RenderPipeline,
+    reveal_pipeline: wgpu::RenderPipeline,
+}
+
+// Skybox, terrain and the pentagon (or instance field), into the scene
+// target the post chain reads from
+struct ScenePass;
+
+impl Pass<FrameContext<'_>> for ScenePass {
+    fn name(&self) -> &str {
+        "Render Pass"
+    }
+
+    fn inputs(&self) -> &[Resource] {
+        &[DEPTH, SHADOW_MAP]
+    }
+
+    fn outputs(&self) -> &[Resource] {
+        &[SCENE_COLOR, DEPTH]
+    }
+
+    fn record(&self, encoder: &mut wgpu::CommandEncoder, ctx: &FrameContext) {
+        let r = ctx.renderer;
+        // Begin_render_pass() borrows encoder mutably (aka &mut self). We
+        // can't call encoder.finish() until we release that mutable borrow,
+        // which happens here when render_pass is dropped at the end of the
+        // function.
+        let transient_store = if ctx.keep_scene_attachments {
+            wgpu::StoreOp::Store
+        } else {
+            r.transient_store()
+        };
+        let mut render_pass =
+            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: ctx.color_view,
+                    resolve_target: ctx.resolve_target,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(if r.viewports.is_empty() {
+                            r.clear_color
+                        } else {
+                            r.bar_color
+                        }),
+                        // Only what's resolved out of a multisampled target
+                        // is kept
+                        store: if ctx.resolve_target.is_some() {
+                            transient_store
+                        } else {
+                            wgpu::StoreOp::Store
+                        },
+                    },
+                })],
+                depth_stencil_attachment: Some(
+                    wgpu::RenderPassDepthStencilAttachment {
+                        view: &r.sized.depth_texture.view,
+                        depth_ops: Some(wgpu::Operations {
+                            // Keep what the prepass laid down
+                            load: if ctx.run_depth_prepass {
+                                wgpu::LoadOp::Load
+                            } else {
+                                wgpu::LoadOp::Clear(1.0)
+                            },
+                            store: transient_store,
+                        }),
+                        stencil_ops: r
+                            .stencil_ops(if ctx.run_depth_prepass {
+                                wgpu::LoadOp::Load
+                            } else {
+                                wgpu::LoadOp::Clear(0)
+                            })
+                            .map(|ops| wgpu::Operations {
+                                store: transient_store,
+                                ..ops
+                            }),
+                    },
+                ),
+                occlusion_query_set: ctx.occlusion_query_set,
+                timestamp_writes: r.gpu_timer.as_ref().map(|timer| {
+                    if ctx.run_depth_prepass {
+                        timer.end_writes()
+                    } else {
+                        timer.timestamp_writes()
+                    }
+                }),
+            });
+
+        if r.viewports.is_empty() {
+            Self::draw(
+                &mut render_pass,
+                ctx,
+                r.camera_bind_group(),
+                r.active_render_pipeline_index,
+            );
+            return;
+        }
+        // The pass has already cleared the whole target to the bar color,
+        // once. Each viewport only draws inside its own rectangle, the
+        // scissor keeping fullscreen draws like the skybox's in there too.
+        for (viewport, camera) in r.viewports.iter().zip(&r.viewport_cameras) {
+            let [x, y, width, height] = viewport.pixel_rect(r.sized.size);
+            if width == 0 || height == 0 {
+                continue;
+            }
+            render_pass.set_viewport(
+                x as f32,
+                y as f32,
+                width as f32,
+                height as f32,
+                0.0,
+                1.0,
+            );
+            render_pass.set_scissor_rect(x, y, width, height);
+            Self::draw(
+                &mut render_pass,
+                ctx,
+                camera.bind_group(),
+                viewport.pipeline.unwrap_or(r.active_render_pipeline_index),
+            );
+        }
+    }
+}
+
+impl ScenePass {
+    // Everything the scene pass draws, through `camera`, with entities that
+    // don't have a pipeline of their own drawn with `render_pipeline`
+    fn draw(
+        render_pass: &mut wgpu::RenderPass,
+        ctx: &FrameContext,
+        camera: &wgpu::BindGroup,
+        render_pipeline: usize,
+    ) {
+        let r = ctx.renderer;
+        // Background first so it only ever fills in what nothing else
+        // covers
+        if r.show_gradient {
+            render_pass.set_pipeline(&r.pipelines.gradient_render_pipeline);
+            r.gradient.draw(render_pass);
+            ctx.count_draw(1);
+        } else if !r.viewports.is_empty() {
+            render_pass.set_pipeline(&r.pipelines.gradient_render_pipeline);
+            r.viewport_fill.draw(render_pass);
+            ctx.count_draw(1);
+        }
+        if r.show_skybox {
+            render_pass.set_pipeline(&r.pipelines.skybox_render_pipeline);
+            render_pass.set_bind_group(1, camera, &[]);
+            r.skybox.draw(render_pass);
+            // A single full screen triangle
+            ctx.count_draw(1);
+        }
+
+        // The terrain isn't part of the prepass and still tests against
+        // (and writes) depth normally. Anything it covers then fails the
+        // instance field's Equal test, so the result is the same either
+        // way.
+        if r.show_terrain {
+            render_pass.set_pipeline(&r.pipelines.terrain_render_pipeline);
+            render_pass.set_bind_group(1, camera, &[]);
+            r.terrain.draw(render_pass);
+            ctx.count_draw(r.terrain.n_triangles());
+        }
+
+        let active_render_pipeline = if ctx.run_depth_prepass {
+            &r.pipelines.instanced_after_prepass_pipeline
+        } else if r.show_instances {
+            &r.pipelines.instanced_render_pipeline
+        } else {
+            r.render_pipeline(render_pipeline)
+        };
+
+        render_pass.set_pipeline(active_render_pipeline);
+        render_pass.set_bind_group(0, r.materials.default_bind_group(), &[]);
+        render_pass.set_bind_group(1, camera, &[]);
+        render_pass.set_bind_group(2, r.globals_bind_group(), &[]);
+        if r.show_instances {
+            r.mesh(r.pentagon).bind(render_pass);
+            r.instance_field.draw(render_pass);
+            ctx.count_draw(r.instance_field.n_triangles());
+        } else {
+            let entities = &r.active_scene().entities;
+            let counting = ctx.occlusion_query_set.is_some();
+            let mut visible_triangles = 0;
+            // `visible_entities` comes sorted by pipeline and material (see
+            // `cull_entities`), so each only needs binding when it changes
+            let mut bound_pipeline = None;
+            let mut bound_material = None;
+            for &index in &r.visible_entities {
+                let entity = &entities[index];
+                visible_triangles += r.entity_triangles(entity);
+                if counting {
+                    render_pass.begin_occlusion_query(index as u32);
+                }
+                let pipeline = r.entity_pipeline_or(entity, render_pipeline);
+                if bound_pipeline != Some(pipeline) {
+                    render_pass.set_pipeline(r.render_pipeline(pipeline));
+                    bound_pipeline = Some(pipeline);
+                    ctx.count_pipeline_switch();
+                }
+                if bound_material != Some(entity.material) {
+                    render_pass.set_bind_group(
+                        0,
+                        r.material_bind_group(entity),
+                        &[],
+                    );
+                    bound_material = Some(entity.material);
+                    ctx.count_material_switch();
+                }
+                r.set_draw_constants(render_pass, entity);
+                ctx.count_draw(r.draw_entity(
+                    render_pass,
+                    index,
+                    entity,
+                    pipeline,
+                ));
+                if counting {
+                    render_pass.end_occlusion_query();
+                }
+            }
+            let all_triangles: u64 =
+                entities.iter().map(|e| r.entity_triangles(e)).sum();
+            ctx.count_culled(all_triangles - visible_triangles);
+            // Culled entities get an empty query so their count reads 0.
+            // Resolving a query that was never written isn't allowed.
+            if counting {
+                for index in 0..entities.len() {
+                    if !r.visible_entities.contains(&index) {
+                        render_pass.begin_occlusion_query(index as u32);
+                        render_pass.end_occlusion_query();
+                    }
+                }
+            }
+            render_pass.set_bind_group(
+                0,
+                r.materials.default_bind_group(),
+                &[],
+            );
+        }
+
+        if r.show_grid || r.debug_draw.has_lines() {
+            render_pass.set_pipeline(&r.pipelines.debug_draw_pipeline);
+            render_pass.set_bind_group(0, camera, &[]);
+            // Lines, so no triangles
+            if r.show_grid {
+                r.debug_draw.draw_grid(render_pass);
+                ctx.count_draw(0);
+            }
+            if r.debug_draw.has_lines() {
+                r.debug_draw.draw_lines(render_pass);
+                ctx.count_draw(0);
+            }
+            render_pass.set_bind_group(
+                0,
+                r.materials.default_bind_group(),
+                &[],
+            );
+            render_pass.set_bind_group(1, camera, &[]);
+        }
+
+        // Transparent geometry goes last so everything opaque is already
+        // there to blend over, and back to front from the camera so each
+        // quad blends over the ones behind it. Sorting by center is only
+        // exact for surfaces that don't intersect, which these don't.
+        if r.show_translucent {
+            let eye = r.eye();
+            let mut quads: Vec<&TranslucentQuad> =
+                r.translucent_quads.iter().collect();
+            quads.sort_by(|a, b| {
+                eye.distance2(b.center).total_cmp(&eye.distance2(a.center))
+            });
+
+            // Same bind groups as the pentagon
+            render_pass.set_pipeline(&r.pipelines.transparent_render_pipeline);
+            for quad in quads {
+                quad.mesh.draw(render_pass);
+                ctx.count_draw(quad.mesh.n_triangles());
+            }
+        }
+
+        // Over everything, since neither pipeline tests depth
+        if let (true, Some(reveal)) =
+            (r.show_stencil_reveal, &r.pipelines.stencil_reveal)
+        {
+            render_pass.set_stencil_reference(STENCIL_REVEAL_REFERENCE);
+            render_pass.set_pipeline(&reveal.mask_pipeline);
+            render_pass.set_bind_group(
+                0,
+                r.materials.default_bind_group(),
+                &[],
+            );
+            render_pass.set_bind_group(1, camera, &[]);
+            render_pass.set_bind_group(2, r.globals_bind_group(), &[]);
+            r.mesh(r.pentagon).draw(render_pass);
+            ctx.count_draw(r.mesh(r.pentagon).n_triangles());
+
+            render_pass.set_pipeline(&reveal.reveal_pipeline);
+            render_pass.draw(0..3, 0..1);
+            ctx.count_draw(1);
+        }
+    }
+}
+
+// The skybox, terrain and every entity of the active scene again, into the
+// mirror's target from its own camera. Culling is left out since the
+// visible set is worked out for the main camera.
+struct MirrorPass;
+
+impl Pass<FrameContext<'_>> for MirrorPass {
+    fn name(&self) -> &str {
+        "Mirror Pass"
+    }
+
+    fn inputs(&self) -> &[Resource] {
+        &[SHADOW_MAP]
+    }
+
+    fn outputs(&self) -> &[Resource] {
+        &[MIRROR_COLOR]
+    }
+
+    fn record(&self, encoder: &mut wgpu::CommandEncoder, ctx: &FrameContext) {
+        let r = ctx.renderer;
+        let mirror = &r.mirror;
+        let (color_view, resolve_target) = mirror.color_views();
+        let mut render_pass =
+            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Mirror Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: color_view,
+                    resolve_target,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(r.clear_color),
+                        store: if resolve_target.is_some() {
+                            r.transient_store()
+                        } else {
+                            wgpu::StoreOp::Store
+                        },
+                    },
+                })],
+                // Only ever tested against in this pass
+                depth_stencil_attachment: Some(
+                    wgpu::RenderPassDepthStencilAttachment {
+                        view: mirror.depth_view(),
+                        depth_ops: Some(wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(1.0),
+                            store: r.transient_store(),
+                        }),
+                        stencil_ops: r.stencil_ops(wgpu::LoadOp::Clear(0)).map(
+                            |ops| wgpu::Operations {
+                                store: r.transient_store(),
+                                ..ops
+                            },
+                        ),
+                    },
+                ),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+
+        let camera_bind_group = mirror.camera_bind_group();
+        if r.show_gradient {
+            render_pass.set_pipeline(&r.pipelines.gradient_render_pipeline);
+            r.gradient.draw(&mut render_pass);
+            ctx.count_draw(1);
+        }
+        if r.show_skybox {
+            render_pass.set_pipeline(&r.pipelines.skybox_render_pipeline);
+            render_pass.set_bind_group(1, camera_bind_group, &[]);
+            r.skybox.draw(&mut render_pass);
+            ctx.count_draw(1);
+        }
+        if r.show_terrain {
+            render_pass.set_pipeline(&r.pipelines.terrain_render_pipeline);
+            render_pass.set_bind_group(1, camera_bind_group, &[]);
+            r.terrain.draw(&mut render_pass);
+            ctx.count_draw(r.terrain.n_triangles());
+        }
+
+        render_pass.set_bind_group(0, r.materials.default_bind_group(), &[]);
+        render_pass.set_bind_group(1, camera_bind_group, &[]);
+        render_pass.set_bind_group(2, r.globals_bind_group(), &[]);
+        for (index, entity) in r.active_scene().entities.iter().enumerate() {
+            let pipeline = r.entity_pipeline(entity);
+            render_pass.set_pipeline(r.render_pipeline(pipeline));
+            render_pass.set_bind_group(0, r.material_bind_group(entity), &[]);
+            r.set_draw_constants(&mut render_pass, entity);
+            ctx.count_draw(r.draw_entity(
+                &mut render_pass,
+                index,
+                entity,
+                pipeline,
+            ));
+        }
+    }
+}
+
+// The visible entities again, into both of the G-buffer's targets at once
+struct GBufferPass;
+
+impl Pass<FrameContext<'_>> for GBufferPass {
+    fn name(&self) -> &str {
+        "G-Buffer Pass"
+    }
+
+    fn outputs(&self) -> &[Resource] {
+        &[GBUFFER]
+    }
+
+    fn record(&self, encoder: &mut wgpu::CommandEncoder, ctx: &FrameContext) {
+        let r = ctx.renderer;
+        let gbuffer = &r.gbuffer;
+        let mut render_pass =
+            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("G-Buffer Render Pass"),
+                color_attachments: &gbuffer.color_attachments(),
+                // Only the color attachments are shown afterwards
+                depth_stencil_attachment: Some(
+                    wgpu::RenderPassDepthStencilAttachment {
+                        view: gbuffer.depth_view(),
+                        depth_ops: Some(wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(1.0),
+                            store: r.transient_store(),
+                        }),
+                        stencil_ops: r.stencil_ops(wgpu::LoadOp::Clear(0)).map(
+                            |ops| wgpu::Operations {
+                                store: r.transient_store(),
+                                ..ops
+                            },
+                        ),
+                    },
+                ),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+
+        render_pass.set_pipeline(&r.pipelines.gbuffer_pipeline);
+        render_pass.set_bind_group(1, r.camera_bind_group(), &[]);
+        render_pass.set_bind_group(2, r.globals_bind_group(), &[]);
+        let entities = &r.active_scene().entities;
+        for &index in &r.visible_entities {
+            let entity = &entities[index];
+            render_pass.set_bind_group(0, r.material_bind_group(entity), &[]);
+            ctx.count_draw(r.entity_triangles(entity));
+            r.objects.draw_object(
+                &mut render_pass,
+                3,
+                index,
+                r.mesh(entity.mesh),
+                &r.indirect_draws,
+            );
+        }
+    }
+}
+
+// World space labels over the finished scene. A pass of its own so it
+// always comes last, after anything added to the scene pass.
+struct LabelPass;
+
+impl Pass<FrameContext<'_>> for LabelPass {
+    fn name(&self) -> &str {
+        "Label Pass"
+    }
+
+    fn inputs(&self) -> &[Resource] {
+        &[SCENE_COLOR, DEPTH]
+    }
+
+    fn outputs(&self) -> &[Resource] {
+        &[SCENE_COLOR]
+    }
+
+    fn record(&self, encoder: &mut wgpu::CommandEncoder, ctx: &FrameContext) {
+        let r = ctx.renderer;
+        let Some(labels) = &r.labels else {
+            return;
+        };
+        let mut render_pass =
+            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Label Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: ctx.color_view,
+                    resolve_target: ctx.resolve_target,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                // Read only, the labels test against the scene's depth but
+                // never write it
+                depth_stencil_attachment: Some(
+                    wgpu::RenderPassDepthStencilAttachment {
+                        view: &r.sized.depth_texture.view,
+                        depth_ops: None,
+                        stencil_ops: None,
+                    },
+                ),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+        labels.render(&mut render_pass);
+    }
+}
+
+// Encoding PNGs is slow enough to hitch the render loop, so it happens on a
+// throwaway thread
+fn save_capture(
+    frame: readback::ReadbackFrame<Capture>,
+    format: wgpu::TextureFormat,
+) {
+    std::thread::spawn(move || {
+        let readback::ReadbackFrame {
+            width,
+            height,
+            bytes,
+            tag,
+        } = frame;
+        let bytes =
+            readback::convert_surface_bytes(format, width, height, &bytes);
+
+        let path = match tag {
+            Capture::Screenshot => {
+                let timestamp = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map(|d| d.as_millis())
+                    .unwrap_or_default();
+                format!("{}/screenshot_{}.png", CAPTURE_DIR, timestamp)
+            }
+            Capture::Sequence(n) => {
+                format!("{}/frame_{:05}.png", CAPTURE_DIR, n)
+            }
+        };
+
+        let result = std::fs::create_dir_all(CAPTURE_DIR)
+            .map_err(anyhow::Error::from)
+            .and_then(|_| {
+                image::RgbaImage::from_raw(width, height, bytes)
+                    .ok_or_else(|| anyhow::anyhow!("Bad capture dimensions"))
+            })
+            .and_then(|img| img.save(&path).map_err(anyhow::Error::from));
+
+        match (result, tag) {
+            (Ok(()), Capture::Screenshot) => {
+                log::info!("Saved screenshot {}", path)
+            }
+            (Ok(()), Capture::Sequence(_)) => log::debug!("Saved {}", path),
+            (Err(e), _) => log::error!("Failed to save {}: {}", path, e),
+        }
+    });
+}
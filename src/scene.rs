@@ -0,0 +1,93 @@
+use cgmath::{Matrix4, SquareMatrix};
+
+// Index into the renderer's meshes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MeshId(pub usize);
+
+// Handle into the renderer's material_registry::MaterialRegistry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MaterialId(pub usize);
+
+impl MaterialId {
+    // The pentagon's textures, always registered first
+    pub const DEFAULT: MaterialId = MaterialId(0);
+}
+
+// Index into the renderer's levels of detail, see lod::Lod
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LodId(pub usize);
+
+// Index into the renderer's render pipelines, i.e. the same numbering as
+// `Renderer::set_active_render_pipeline`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PipelineId(pub usize);
+
+#[derive(Debug, Clone, Copy)]
+pub struct Entity {
+    pub mesh: MeshId,
+    // Model matrix, from the mesh's space into the world
+    pub transform: Matrix4<f32>,
+    // None draws with whichever pipeline is active (Space, L and H pick it)
+    pub pipeline: Option<PipelineId>,
+    // MaterialId::DEFAULT draws with the pentagon's textures
+    pub material: MaterialId,
+    // Multiplied into the color it's drawn with, see draw_constants
+    pub tint: [f32; 4],
+    // Picks `mesh` by distance from the camera every frame when set, in
+    // which case `mesh` is overwritten
+    pub lod: Option<LodId>,
+    // Which of the LOD's levels was picked last, for its hysteresis
+    pub lod_level: usize,
+}
+
+impl Entity {
+    // At the origin, drawn with the active pipeline
+    pub fn new(mesh: MeshId) -> Self {
+        Self {
+            mesh,
+            transform: Matrix4::identity(),
+            pipeline: None,
+            material: MaterialId::DEFAULT,
+            tint: [1.0; 4],
+            lod: None,
+            lod_level: 0,
+        }
+    }
+}
+
+// How many entities the last frame drew, and how many it skipped for being
+// outside the view frustum
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CullStats {
+    pub drawn: usize,
+    pub culled: usize,
+}
+
+// What the scene pass draws, one draw call per entity in order. Each
+// entity's transform goes into its own slot of the object uniforms, see
+// `objects::ObjectUniforms`.
+#[derive(Debug, Default)]
+pub struct Scene {
+    pub entities: Vec<Entity>,
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, entity: Entity) {
+        self.entities.push(entity);
+    }
+
+    pub fn transforms(&self) -> Vec<Matrix4<f32>> {
+        self.entities
+            .iter()
+            .map(|entity| entity.transform)
+            .collect()
+    }
+
+    pub fn tints(&self) -> Vec<[f32; 4]> {
+        self.entities.iter().map(|entity| entity.tint).collect()
+    }
+}
@@ -0,0 +1,732 @@
+use std::collections::HashMap;
+
+use anyhow::*;
+use serde::Deserialize;
+use wgpu::util::DeviceExt;
+
+use crate::growable_buffer::GrowableBuffer;
+use crate::{assets, texture};
+
+// Glyph metrics follow the JSON layout written by msdf-atlas-gen
+// (https://github.com/Chlumsky/msdf-atlas-gen). The built-in font.png and
+// font.json come from `cargo run --release --example write_sdf_font`, and
+// loose ones made with e.g.
+//
+//   msdf-atlas-gen -font font.ttf -type sdf -format png \
+//     -imageout font.png -json font.json
+//
+// take their place, see assets.rs.
+//
+// Plane bounds are in em units relative to the baseline, atlas bounds are in
+// atlas pixels.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FontMetricsFile {
+    atlas: AtlasInfo,
+    metrics: LineMetrics,
+    glyphs: Vec<GlyphInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AtlasInfo {
+    #[serde(rename = "type")]
+    kind: String,
+    distance_range: f32,
+    width: f32,
+    height: f32,
+    #[serde(default = "default_y_origin")]
+    y_origin: String,
+}
+
+fn default_y_origin() -> String {
+    "bottom".to_string()
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LineMetrics {
+    line_height: f32,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GlyphInfo {
+    unicode: u32,
+    advance: f32,
+    plane_bounds: Option<Bounds>,
+    atlas_bounds: Option<Bounds>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+struct Bounds {
+    left: f32,
+    bottom: f32,
+    right: f32,
+    top: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Glyph {
+    advance: f32,
+    // (plane bounds in em units, uv rect as [u0, v0, u1, v1])
+    quad: Option<(Bounds, [f32; 4])>,
+}
+
+// Everything about a font but its atlas, as read from the metrics file
+#[derive(Debug, Clone)]
+struct FontMetrics {
+    glyphs: HashMap<u32, Glyph>,
+    line_height: f32,
+    unit_range: [f32; 2],
+    multi_channel: bool,
+}
+
+impl FontMetrics {
+    fn parse(json: &str) -> Result<Self> {
+        let metrics: FontMetricsFile =
+            serde_json::from_str(json).context("Invalid glyph metrics JSON")?;
+
+        let AtlasInfo {
+            kind,
+            distance_range,
+            width,
+            height,
+            y_origin,
+        } = metrics.atlas;
+
+        let flip_y = y_origin == "bottom";
+
+        let glyphs = metrics
+            .glyphs
+            .into_iter()
+            .map(|g| {
+                let quad = match (g.plane_bounds, g.atlas_bounds) {
+                    (Some(plane), Some(atlas)) => {
+                        let (v0, v1) = if flip_y {
+                            (
+                                1.0 - atlas.top / height,
+                                1.0 - atlas.bottom / height,
+                            )
+                        } else {
+                            (atlas.top / height, atlas.bottom / height)
+                        };
+                        let uv =
+                            [atlas.left / width, v0, atlas.right / width, v1];
+                        Some((plane, uv))
+                    }
+                    _ => None,
+                };
+                let glyph = Glyph {
+                    advance: g.advance,
+                    quad,
+                };
+                (g.unicode, glyph)
+            })
+            .collect();
+
+        Ok(Self {
+            glyphs,
+            line_height: metrics.metrics.line_height,
+            unit_range: [distance_range / width, distance_range / height],
+            multi_channel: kind == "msdf" || kind == "mtsdf",
+        })
+    }
+
+    // Falls back to '?' for characters the atlas doesn't have
+    fn glyph(&self, c: char) -> Option<&Glyph> {
+        self.glyphs
+            .get(&(c as u32))
+            .or_else(|| self.glyphs.get(&('?' as u32)))
+    }
+
+    fn measure(&self, text: &str, size: f32) -> f32 {
+        text.lines()
+            .map(|line| {
+                line.chars()
+                    .filter_map(|c| self.glyph(c))
+                    .map(|glyph| glyph.advance * size)
+                    .sum::<f32>()
+            })
+            .fold(0.0, f32::max)
+    }
+
+    // Appends two triangles per visible glyph of `queued`, in NDC for a
+    // screen of `screen_size` pixels
+    fn layout(
+        &self,
+        queued: &QueuedText,
+        screen_size: [f32; 2],
+        vertices: &mut Vec<TextVertex>,
+    ) {
+        let QueuedText {
+            text,
+            position,
+            size,
+            depth,
+        } = queued;
+        let [w, h] = screen_size;
+        let to_ndc =
+            |x: f32, y: f32| [x / w * 2.0 - 1.0, 1.0 - y / h * 2.0, *depth];
+
+        let mut pen_x = position[0];
+        let mut baseline = position[1] + self.line_height * size;
+
+        for c in text.chars() {
+            if c == '\n' {
+                pen_x = position[0];
+                baseline += self.line_height * size;
+                continue;
+            }
+
+            let Some(&glyph) = self.glyph(c) else {
+                continue;
+            };
+
+            if let Some((plane, [u0, v0, u1, v1])) = glyph.quad {
+                let x0 = pen_x + plane.left * size;
+                let x1 = pen_x + plane.right * size;
+                // Plane bounds are y-up, screen space is y-down
+                let y0 = baseline - plane.top * size;
+                let y1 = baseline - plane.bottom * size;
+
+                let tl = TextVertex {
+                    position: to_ndc(x0, y0),
+                    tex_coords: [u0, v0],
+                };
+                let tr = TextVertex {
+                    position: to_ndc(x1, y0),
+                    tex_coords: [u1, v0],
+                };
+                let bl = TextVertex {
+                    position: to_ndc(x0, y1),
+                    tex_coords: [u0, v1],
+                };
+                let br = TextVertex {
+                    position: to_ndc(x1, y1),
+                    tex_coords: [u1, v1],
+                };
+
+                vertices.extend_from_slice(&[tl, bl, br, tl, br, tr]);
+            }
+
+            pen_x += glyph.advance * size;
+        }
+    }
+}
+
+// Cheap to clone, the atlas is shared rather than copied
+#[derive(Clone)]
+pub struct SdfFont {
+    atlas: texture::Texture,
+    metrics: FontMetrics,
+}
+
+impl SdfFont {
+    // The built-in font, or loose font.png and font.json from the assets
+    // directory
+    pub fn load(device: &wgpu::Device, queue: &wgpu::Queue) -> Result<Self> {
+        Self::from_bytes(
+            device,
+            queue,
+            &assets::load("font.png", include_bytes!("font.png")),
+            &assets::load_str("font.json", include_str!("font.json")),
+        )
+    }
+
+    pub fn from_bytes(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        atlas_bytes: &[u8],
+        metrics_json: &str,
+    ) -> Result<Self> {
+        let metrics = FontMetrics::parse(metrics_json)?;
+
+        let img = image::load_from_memory(atlas_bytes)?;
+
+        // The distance field is data, not color, so it must be sampled
+        // without any sRGB conversion.
+        let atlas = texture::Texture::from_image(
+            device,
+            queue,
+            &img,
+            Some("SDF Font Atlas"),
+            texture::TextureKind::LinearData,
+        )?;
+
+        Ok(Self { atlas, metrics })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TextStyle {
+    pub color: [f32; 4],
+    pub outline_color: [f32; 4],
+    // In distance field units where 0.5 is the full field range, so useful
+    // values are roughly 0.0..0.4 depending on the atlas distance range
+    pub outline_width: f32,
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        Self {
+            color: [1.0, 1.0, 1.0, 1.0],
+            outline_color: [0.0, 0.0, 0.0, 1.0],
+            outline_width: 0.0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct TextUniform {
+    color: [f32; 4],
+    outline_color: [f32; 4],
+    unit_range: [f32; 2],
+    outline_width: f32,
+    multi_channel: u32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct TextVertex {
+    // NDC, z only matters to pipelines with a depth test
+    position: [f32; 3],
+    tex_coords: [f32; 2],
+}
+
+impl TextVertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 2] =
+        wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x2];
+
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<TextVertex>()
+                as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+// Text queued in screen pixels (origin top-left) and laid out into NDC quads
+// on `prepare`.
+struct QueuedText {
+    text: String,
+    position: [f32; 2],
+    size: f32,
+    depth: f32,
+}
+
+pub struct SdfTextRenderer {
+    font: SdfFont,
+    style: TextStyle,
+    pipeline: wgpu::RenderPipeline,
+    // Only built by `new_in_scene`, used instead of `pipeline` while
+    // `depth_test` is set
+    depth_tested_pipeline: Option<wgpu::RenderPipeline>,
+    pub depth_test: bool,
+    bind_group: wgpu::BindGroup,
+    uniform_buffer: wgpu::Buffer,
+    vertex_buffer: GrowableBuffer,
+    n_vertices: u32,
+    queued: Vec<QueuedText>,
+    vertices: Vec<TextVertex>,
+}
+
+impl SdfTextRenderer {
+    // For text drawn in a pass of its own with no depth attachment
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        font: SdfFont,
+    ) -> Self {
+        Self::with_target(device, format, font, 1, None)
+    }
+
+    // For text drawn inside the scene pass, e.g. labels anchored to world
+    // positions. Both pipelines match the scene's attachments, one of them
+    // tests against the scene's depth so geometry in front hides the text.
+    // Neither writes depth, so overlapping labels don't cut into each other.
+    pub fn new_in_scene(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        font: SdfFont,
+        sample_count: u32,
+        depth_format: wgpu::TextureFormat,
+    ) -> Self {
+        Self::with_target(
+            device,
+            format,
+            font,
+            sample_count,
+            Some(depth_format),
+        )
+    }
+
+    fn with_target(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        font: SdfFont,
+        sample_count: u32,
+        depth_format: Option<wgpu::TextureFormat>,
+    ) -> Self {
+        let style = TextStyle::default();
+
+        let uniform_buffer =
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("SDF Text Uniform Buffer"),
+                contents: bytemuck::cast_slice(&[Self::uniform(&font, &style)]),
+                usage: wgpu::BufferUsages::UNIFORM
+                    | wgpu::BufferUsages::COPY_DST,
+            });
+
+        // Distance fields rely on bilinear interpolation between texels, so
+        // unlike the diffuse texture this needs linear filtering both ways.
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("SDF Text Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("SDF Text Bind Group Layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Float {
+                                filterable: true,
+                            },
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(
+                            wgpu::SamplerBindingType::Filtering,
+                        ),
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("SDF Text Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(
+                        &font.atlas.view,
+                    ),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let shader =
+            device.create_shader_module(wgpu::include_wgsl!("sdf_text.wgsl"));
+
+        let layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("SDF Text Pipeline Layout"),
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        let create_pipeline = |depth_compare| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("SDF Text Pipeline"),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[TextVertex::desc()],
+                    compilation_options:
+                        wgpu::PipelineCompilationOptions::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some("fs_main"),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format,
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options:
+                        wgpu::PipelineCompilationOptions::default(),
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    // Quads are generated on the CPU so winding is ours to
+                    // pick, but there's no reason to cull screen-space text
+                    cull_mode: None,
+                    ..Default::default()
+                },
+                depth_stencil: depth_format.map(|format| {
+                    wgpu::DepthStencilState {
+                        format,
+                        depth_write_enabled: false,
+                        depth_compare,
+                        stencil: wgpu::StencilState::default(),
+                        bias: wgpu::DepthBiasState::default(),
+                    }
+                }),
+                multisample: wgpu::MultisampleState {
+                    count: sample_count,
+                    ..Default::default()
+                },
+                multiview: None,
+                cache: None,
+            })
+        };
+        let pipeline = create_pipeline(wgpu::CompareFunction::Always);
+        let depth_tested_pipeline =
+            depth_format.map(|_| create_pipeline(wgpu::CompareFunction::Less));
+
+        let vertex_buffer = GrowableBuffer::new(
+            device,
+            "SDF Text Vertex Buffer",
+            wgpu::BufferUsages::VERTEX,
+            (6 * 64 * std::mem::size_of::<TextVertex>()) as wgpu::BufferAddress,
+        );
+
+        Self {
+            font,
+            style,
+            pipeline,
+            depth_test: depth_tested_pipeline.is_some(),
+            depth_tested_pipeline,
+            bind_group,
+            uniform_buffer,
+            vertex_buffer,
+            n_vertices: 0,
+            queued: vec![],
+            vertices: vec![],
+        }
+    }
+
+    fn uniform(font: &SdfFont, style: &TextStyle) -> TextUniform {
+        TextUniform {
+            color: style.color,
+            outline_color: style.outline_color,
+            unit_range: font.metrics.unit_range,
+            outline_width: style.outline_width,
+            multi_channel: font.metrics.multi_channel as u32,
+        }
+    }
+
+    pub fn font(&self) -> &SdfFont {
+        &self.font
+    }
+
+    pub fn set_style(&mut self, queue: &wgpu::Queue, style: TextStyle) {
+        self.style = style;
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[Self::uniform(&self.font, &self.style)]),
+        );
+    }
+
+    // `position` is the top-left corner of the first line in physical pixels
+    // and `size` is the em size in pixels.
+    pub fn queue_text(&mut self, text: &str, position: [f32; 2], size: f32) {
+        self.queue_text_at_depth(text, position, size, 0.0);
+    }
+
+    // Like `queue_text`, with every glyph at NDC `depth` so it can be hidden
+    // by whatever is in front when the depth test is on
+    pub fn queue_text_at_depth(
+        &mut self,
+        text: &str,
+        position: [f32; 2],
+        size: f32,
+        depth: f32,
+    ) {
+        self.queued.push(QueuedText {
+            text: text.to_string(),
+            position,
+            size,
+            depth,
+        });
+    }
+
+    // Width in pixels of the widest line of `text` at `size`
+    pub fn measure(&self, text: &str, size: f32) -> f32 {
+        self.font.metrics.measure(text, size)
+    }
+
+    // Lays out everything queued since the last call into the vertex buffer.
+    // The buffer is only reallocated when it needs to grow.
+    pub fn prepare(
+        &mut self,
+        queue: &wgpu::Queue,
+        screen_size: winit::dpi::PhysicalSize<u32>,
+    ) {
+        self.vertices.clear();
+
+        let screen_size = [
+            screen_size.width.max(1) as f32,
+            screen_size.height.max(1) as f32,
+        ];
+        for queued in std::mem::take(&mut self.queued) {
+            self.font
+                .metrics
+                .layout(&queued, screen_size, &mut self.vertices);
+        }
+
+        self.vertex_buffer.write(queue, &self.vertices);
+        self.n_vertices = self.vertices.len() as u32;
+    }
+
+    pub fn render(&self, render_pass: &mut wgpu::RenderPass) {
+        if self.n_vertices == 0 {
+            return;
+        }
+        let pipeline = match &self.depth_tested_pipeline {
+            Some(pipeline) if self.depth_test => pipeline,
+            _ => &self.pipeline,
+        };
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice());
+        render_pass.draw(0..self.n_vertices, 0..1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A 100x50 atlas with 'A', a blank space and '?'
+    const METRICS: &str = r#"{
+        "atlas": {
+            "type": "sdf",
+            "distanceRange": 4,
+            "size": 32,
+            "width": 100,
+            "height": 50,
+            "yOrigin": "bottom"
+        },
+        "metrics": { "emSize": 1, "lineHeight": 1.25 },
+        "glyphs": [
+            {
+                "unicode": 65,
+                "advance": 0.5,
+                "planeBounds":
+                    { "left": 0, "bottom": 0, "right": 0.5, "top": 1 },
+                "atlasBounds":
+                    { "left": 0, "bottom": 10, "right": 20, "top": 50 }
+            },
+            { "unicode": 32, "advance": 0.25 },
+            {
+                "unicode": 63,
+                "advance": 0.75,
+                "planeBounds":
+                    { "left": 0, "bottom": 0, "right": 0.5, "top": 1 },
+                "atlasBounds":
+                    { "left": 20, "bottom": 10, "right": 40, "top": 50 }
+            }
+        ]
+    }"#;
+
+    fn queued(text: &str, position: [f32; 2], size: f32) -> QueuedText {
+        QueuedText {
+            text: text.to_string(),
+            position,
+            size,
+            depth: 0.5,
+        }
+    }
+
+    #[test]
+    fn parses_msdf_atlas_gen_metrics() {
+        let metrics = FontMetrics::parse(METRICS).unwrap();
+        assert_eq!(metrics.glyphs.len(), 3);
+        assert_eq!(metrics.line_height, 1.25);
+        assert_eq!(metrics.unit_range, [0.04, 0.08]);
+        assert!(!metrics.multi_channel);
+
+        // A bottom y origin flips the atlas bounds into texture coordinates
+        let (plane, uv) = metrics.glyphs[&65].quad.unwrap();
+        assert_eq!(plane.top, 1.0);
+        assert_eq!(uv, [0.0, 0.0, 0.2, 0.8]);
+        assert!(metrics.glyphs[&32].quad.is_none());
+
+        let msdf = METRICS.replace(r#""type": "sdf""#, r#""type": "msdf""#);
+        assert!(FontMetrics::parse(&msdf).unwrap().multi_channel);
+        assert!(FontMetrics::parse(r#"{ "glyphs": [] }"#).is_err());
+    }
+
+    #[test]
+    fn lays_out_glyphs_along_the_baseline() {
+        let metrics = FontMetrics::parse(METRICS).unwrap();
+        let mut vertices = vec![];
+        // 'é' isn't in the atlas and falls back to '?'
+        metrics.layout(
+            &queued("A é\nA", [10.0, 20.0], 40.0),
+            [200.0, 100.0],
+            &mut vertices,
+        );
+
+        // The space is only an advance
+        assert_eq!(vertices.len(), 3 * 6);
+        let corners = |glyph: usize| {
+            let quad = &vertices[glyph * 6..glyph * 6 + 6];
+            // Top left and bottom right, back in (whole) pixels
+            [quad[0].position, quad[2].position].map(|[x, y, _]| {
+                [
+                    ((x + 1.0) / 2.0 * 200.0).round(),
+                    ((1.0 - y) / 2.0 * 100.0).round(),
+                ]
+            })
+        };
+        // The first baseline is a line below the top, at 20 + 1.25 * 40
+        assert_eq!(corners(0), [[10.0, 30.0], [30.0, 70.0]]);
+        // After 'A' and the space, 0.5 + 0.25 ems in
+        assert_eq!(corners(1), [[40.0, 30.0], [60.0, 70.0]]);
+        assert_eq!(vertices[6].tex_coords, [0.2, 0.0]);
+        // A newline goes back to the left edge, a line further down
+        assert_eq!(corners(2), [[10.0, 80.0], [30.0, 120.0]]);
+        assert!(vertices.iter().all(|v| v.position[2] == 0.5));
+
+        assert_eq!(metrics.measure("A é\nA", 40.0), 60.0);
+    }
+
+    #[test]
+    fn builtin_font_covers_printable_ascii() {
+        let metrics = FontMetrics::parse(include_str!("font.json")).unwrap();
+        for c in ' '..='~' {
+            let glyph = metrics.glyphs[&(c as u32)];
+            assert!(glyph.advance > 0.0, "{:?}", c);
+            assert_eq!(glyph.quad.is_some(), c != ' ', "{:?}", c);
+        }
+    }
+}
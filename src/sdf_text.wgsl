@@ -0,0 +1,68 @@
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+}
+
+// see: sdf_text::TextUniform
+struct TextUniform {
+    color: vec4<f32>,
+    outline_color: vec4<f32>,
+    // distance_range / atlas_size, i.e. the distance range in UV units
+    unit_range: vec2<f32>,
+    outline_width: f32,
+    // 0 = single channel SDF, 1 = multi-channel (MSDF/MTSDF)
+    multi_channel: u32,
+}
+
+@group(0) @binding(0)
+var t_atlas: texture_2d<f32>;
+
+@group(0) @binding(1)
+var s_atlas: sampler;
+
+@group(0) @binding(2)
+var<uniform> text: TextUniform;
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.tex_coords = in.tex_coords;
+    out.clip_position = vec4<f32>(in.position, 1.0);
+    return out;
+}
+
+fn median(r: f32, g: f32, b: f32) -> f32 {
+    return max(min(r, g), min(max(r, g), b));
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let sample = textureSample(t_atlas, s_atlas, in.tex_coords);
+
+    var distance = sample.r;
+    if text.multi_channel == 1u {
+        distance = median(sample.r, sample.g, sample.b);
+    }
+
+    // How many screen pixels one unit of the distance field covers. This is
+    // what keeps edges ~1px wide no matter how large the glyph is drawn.
+    let screen_tex_size = vec2<f32>(1.0) / fwidth(in.tex_coords);
+    let screen_px_range = max(0.5 * dot(text.unit_range, screen_tex_size), 1.0);
+
+    let fill_px = screen_px_range * (distance - 0.5);
+    let outline_px = screen_px_range * (distance - 0.5 + text.outline_width);
+
+    let fill = smoothstep(-0.5, 0.5, fill_px);
+    let outline = smoothstep(-0.5, 0.5, outline_px);
+
+    var color = text.color;
+    if text.outline_width > 0.0 {
+        color = mix(text.outline_color, text.color, fill);
+    }
+    return vec4<f32>(color.rgb, color.a * outline);
+}
@@ -1,20 +1,120 @@
 struct VertexInput {
     @location(0) position: vec3<f32>,
     @location(1) tex_coords: vec2<f32>,
+    @location(2) normal: vec3<f32>,
+    @location(3) tangent: vec3<f32>,
+    @location(4) bitangent: vec3<f32>,
+    @location(5) color: vec3<f32>,
 }
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) tex_coords: vec2<f32>,
+    // World space tangent frame. vs_main has no model matrix, so these are
+    // just passed through.
+    @location(1) normal: vec3<f32>,
+    @location(2) tangent: vec3<f32>,
+    @location(3) bitangent: vec3<f32>,
+    @location(4) color: vec3<f32>,
+    // For looking the fragment up in the shadow map
+    @location(5) world_position: vec3<f32>,
 }
 
+// see: camera::CameraUniform
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+    inv_view_proj: mat4x4<f32>,
+    view_position: vec4<f32>,
+}
+
+@group(1) @binding(0)
+var<uniform> camera: CameraUniform;
+
+// see: GlobalsUniform
+struct GlobalsUniform {
+    time: f32,
+    show_normals: u32,
+    detail_mix: f32,
+    shadows: u32,
+    specular: u32,
+    audio_amplitude: f32,
+    clip_plane: vec4<f32>,
+}
+
+@group(2) @binding(0)
+var<uniform> globals: GlobalsUniform;
+
+// see: shadow::LightUniform
+struct LightUniform {
+    view_proj: mat4x4<f32>,
+    direction: vec4<f32>,
+}
+
+@group(2) @binding(1)
+var<uniform> light: LightUniform;
+
+@group(2) @binding(2)
+var t_shadow: texture_depth_2d;
+
+// Comparison sampler: returns how much of the lookup passes the test
+// rather than the depth itself
+@group(2) @binding(3)
+var s_shadow: sampler_comparison;
+
+// The skybox, for reflections
+@group(2) @binding(4)
+var t_environment: texture_cube<f32>;
+
+@group(2) @binding(5)
+var s_environment: sampler;
+
+// see: objects::ObjectUniform
+struct ObjectUniform {
+    model: mat4x4<f32>,
+    tint: vec4<f32>,
+}
+
+// Only vs_object and fs_object read this. Each draw binds it at a different
+// dynamic offset.
+@group(3) @binding(0)
+var<uniform> object: ObjectUniform;
+
 @vertex
 fn vs_main(
     model: VertexInput,
 ) -> VertexOutput {
     var out: VertexOutput;
     out.tex_coords = model.tex_coords;
-    out.clip_position = vec4<f32>(model.position, 1.0);
+    out.normal = model.normal;
+    out.tangent = model.tangent;
+    out.bitangent = model.bitangent;
+    out.color = model.color;
+    out.world_position = model.position;
+    out.clip_position = camera.view_proj * vec4<f32>(model.position, 1.0);
+    return out;
+}
+
+// vs_main with the current object's model matrix applied
+@vertex
+fn vs_object(
+    model: VertexInput,
+) -> VertexOutput {
+    // Objects are only ever translated and rotated, so the upper 3x3 can
+    // transform directions as is
+    let rotation = mat3x3<f32>(
+        object.model[0].xyz,
+        object.model[1].xyz,
+        object.model[2].xyz,
+    );
+    var out: VertexOutput;
+    out.tex_coords = model.tex_coords;
+    out.normal = rotation * model.normal;
+    out.tangent = rotation * model.tangent;
+    out.bitangent = rotation * model.bitangent;
+    out.color = model.color;
+    let world_position = object.model * vec4<f32>(model.position, 1.0);
+    out.world_position = world_position.xyz;
+    out.clip_position = camera.view_proj * world_position;
     return out;
 }
 
@@ -25,7 +125,164 @@ var t_diffuse: texture_2d<f32>;
 @group(0) @binding(1)
 var s_diffuse: sampler;
 
+@group(0) @binding(2)
+var t_normal: texture_2d<f32>;
+
+@group(0) @binding(3)
+var s_normal: sampler;
+
+@group(0) @binding(4)
+var t_detail: texture_2d<f32>;
+
+@group(0) @binding(5)
+var s_detail: sampler;
+
+// see: material::MaterialUniform
+struct MaterialUniform {
+    specular_color: vec3<f32>,
+    shininess: f32,
+    // Around the middle of the texture
+    uv_scale: vec2<f32>,
+    reflectivity: f32,
+}
+
+@group(0) @binding(6)
+var<uniform> material: MaterialUniform;
+
+// How bright a fully shadowed fragment still is
+const SHADOW_AMBIENT: f32 = 0.35;
+// How much brighter full scale microphone input makes the scene. Speech
+// tends to sit around 0.1 RMS, for a 40% pulse.
+const AUDIO_GAIN: f32 = 4.0;
+
+// 1 where the light reaches `world_position`, 0 where something is in the
+// way. Averages a 3x3 block of comparisons around it (percentage closer
+// filtering) so shadow edges come out soft instead of stair-stepped.
+fn shadow_factor(world_position: vec3<f32>) -> f32 {
+    let light_clip = light.view_proj * vec4<f32>(world_position, 1.0);
+    let ndc = light_clip.xyz / light_clip.w;
+    // Nothing was rendered into the map out there, so count it as lit
+    if ndc.z > 1.0 || any(abs(ndc.xy) > vec2<f32>(1.0)) {
+        return 1.0;
+    }
+
+    // Clip space y points up but texture v points down
+    let uv = ndc.xy * vec2<f32>(0.5, -0.5) + 0.5;
+    let texel = 1.0 / vec2<f32>(textureDimensions(t_shadow));
+    var lit = 0.0;
+    for (var y = -1; y <= 1; y++) {
+        for (var x = -1; x <= 1; x++) {
+            let offset = vec2<f32>(f32(x), f32(y)) * texel;
+            // The Level variant has no derivatives, so it's allowed under
+            // the non-uniform branch above
+            lit += textureSampleCompareLevel(
+                t_shadow,
+                s_shadow,
+                uv + offset,
+                ndc.z,
+            );
+        }
+    }
+    return lit / 9.0;
+}
+
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return textureSample(t_diffuse, s_diffuse, in.tex_coords);
-}
\ No newline at end of file
+    return shade(in);
+}
+
+// fs_main tinted per draw. `draw_tint()` isn't defined in this file, it's
+// prepended from draw_constants.rs reading either push constants or
+// `object`.
+@fragment
+fn fs_object(in: VertexOutput) -> @location(0) vec4<f32> {
+    return shade(in) * draw_tint();
+}
+
+fn shade(in: VertexOutput) -> vec4<f32> {
+    // The user clip plane, (0, 0, 0, 1) when it's off
+    if dot(vec4<f32>(in.world_position, 1.0), globals.clip_plane) < 0.0 {
+        discard;
+    }
+
+    // A gentle wobble so it's obvious time is flowing
+    let wobble = vec2<f32>(
+        sin(globals.time * 2.0 + in.tex_coords.y * 12.0),
+        cos(globals.time * 1.5 + in.tex_coords.x * 12.0),
+    ) * 0.01;
+    let tex_coords =
+        (in.tex_coords - 0.5) * material.uv_scale + 0.5 + wobble;
+
+    // Normal maps pack -1..1 into 0..1
+    let tangent_normal =
+        textureSample(t_normal, s_normal, tex_coords).xyz * 2.0 - 1.0;
+    // Interpolation denormalizes the frame, so renormalize before use
+    let tbn = mat3x3<f32>(
+        normalize(in.tangent),
+        normalize(in.bitangent),
+        normalize(in.normal),
+    );
+    let world_normal = normalize(tbn * tangent_normal);
+    if globals.show_normals != 0u {
+        return vec4<f32>(world_normal * 0.5 + 0.5, 1.0);
+    }
+
+    let diffuse = textureSample(t_diffuse, s_diffuse, tex_coords);
+    let detail = textureSample(t_detail, s_detail, tex_coords);
+    let color = mix(diffuse, detail, globals.detail_mix);
+    var shadow = 1.0;
+    var light_amount = 1.0;
+    if globals.shadows != 0u {
+        shadow = shadow_factor(in.world_position);
+        light_amount = mix(SHADOW_AMBIENT, 1.0, shadow);
+    }
+    // Pulses with the microphone, with the audio feature
+    let audio_boost = 1.0 + globals.audio_amplitude * AUDIO_GAIN;
+    var rgb = color.rgb * in.color * light_amount * audio_boost;
+    if globals.specular != 0u {
+        rgb += shadow * specular(in.world_position, world_normal);
+    }
+    rgb = mix(
+        rgb,
+        environment(in.world_position, world_normal),
+        material.reflectivity,
+    );
+    return vec4<f32>(rgb, color.a);
+}
+
+// What a mirror at `world_position` facing along `normal` shows: the view
+// ray bounced off the surface, looked up in the skybox like the skybox
+// looks up its own view rays. The skybox is infinitely far away, so where
+// on the surface the ray bounces makes no difference, only its direction.
+fn environment(world_position: vec3<f32>, normal: vec3<f32>) -> vec3<f32> {
+    let from_camera = normalize(world_position - camera.view_position.xyz);
+    let reflected = reflect(from_camera, normal);
+    return textureSample(t_environment, s_environment, reflected).rgb;
+}
+
+// Blinn-Phong: brightest where the normal is halfway between the directions
+// to the light and to the camera, i.e. where a mirror would reflect the
+// light straight at the eye. The half vector stands in for the reflected
+// ray, which is cheaper and keeps highlights from cutting off at grazing
+// angles.
+fn specular(world_position: vec3<f32>, normal: vec3<f32>) -> vec3<f32> {
+    let to_light = -normalize(light.direction.xyz);
+    // Nothing shines on the side facing away from the light
+    if dot(normal, to_light) <= 0.0 {
+        return vec3<f32>(0.0);
+    }
+    let to_camera = normalize(camera.view_position.xyz - world_position);
+    let half_vector = normalize(to_light + to_camera);
+    let strength =
+        pow(max(dot(normal, half_vector), 0.0), material.shininess);
+    return material.specular_color * strength;
+}
+
+// Flat tinted glass for the translucent quads, see
+// pipeline::create_transparent_pipeline
+const TRANSLUCENT_ALPHA: f32 = 0.5;
+
+@fragment
+fn fs_translucent(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(in.color, TRANSLUCENT_ALPHA);
+}
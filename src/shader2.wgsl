@@ -8,17 +8,58 @@ struct VertexOutput {
     @location(0) color: vec3<f32>,
 };
 
+// see: camera::CameraUniform
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+    inv_view_proj: mat4x4<f32>,
+    view_position: vec4<f32>,
+}
+
+@group(1) @binding(0)
+var<uniform> camera: CameraUniform;
+
+// see: objects::ObjectUniform
+struct ObjectUniform {
+    model: mat4x4<f32>,
+    tint: vec4<f32>,
+}
+
+@group(3) @binding(0)
+var<uniform> object: ObjectUniform;
+
 @vertex
 fn vs_main(
     model: VertexInput,
 ) -> VertexOutput {
     var out: VertexOutput;
     out.color = model.color;
-    out.clip_position = vec4<f32>(model.position, 1.0);
+    out.clip_position = camera.view_proj * vec4<f32>(model.position, 1.0);
+    return out;
+}
+
+// vs_main with the current object's model matrix applied
+@vertex
+fn vs_object(
+    model: VertexInput,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.color = model.color;
+    out.clip_position =
+        camera.view_proj * object.model * vec4<f32>(model.position, 1.0);
     return out;
 }
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return shade(in);
+}
+
+// See fs_object in shader.wgsl
+@fragment
+fn fs_object(in: VertexOutput) -> @location(0) vec4<f32> {
+    return shade(in) * draw_tint();
+}
+
+fn shade(in: VertexOutput) -> vec4<f32> {
     return vec4<f32>(in.color * 0.5, 1.0);
 }
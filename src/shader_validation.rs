@@ -0,0 +1,18 @@
+use naga::valid::{Capabilities, ValidationFlags, Validator};
+
+// Parses and validates WGSL the same way wgpu does inside
+// `create_shader_module`, but hands back the error instead of panicking
+// through the device's error handler. The message is naga's usual report,
+// with line numbers and the offending source underlined, ready to log.
+//
+// Every capability is allowed, since which ones the device has is only
+// known to wgpu. A shader that passes here can still be refused for using
+// a feature (like push constants) the device wasn't created with.
+pub fn validate_wgsl(source: &str) -> Result<(), String> {
+    let module = naga::front::wgsl::parse_str(source)
+        .map_err(|e| e.emit_to_string(source))?;
+    Validator::new(ValidationFlags::all(), Capabilities::all())
+        .validate(&module)
+        .map_err(|e| e.emit_to_string(source))?;
+    Ok(())
+}
@@ -0,0 +1,269 @@
+use cgmath::{
+    EuclideanSpace, InnerSpace, Matrix4, Point3, SquareMatrix, Vector3,
+};
+use wgpu::util::DeviceExt;
+
+use crate::camera::OPENGL_TO_WGPU_MATRIX;
+use crate::geometry::{Mesh, Vertex};
+use crate::indirect::IndirectDraws;
+use crate::objects::ObjectUniforms;
+
+// Width and height of the shadow map in texels
+pub const SHADOW_MAP_SIZE: u32 = 1024;
+pub const SHADOW_MAP_FORMAT: wgpu::TextureFormat =
+    wgpu::TextureFormat::Depth32Float;
+
+// Half the width and height of the box around the origin that casts and
+// receives shadows. Anything outside it is always lit.
+const LIGHT_EXTENT: f32 = 4.0;
+// How far back along its direction the light's projection starts
+const LIGHT_DISTANCE: f32 = 10.0;
+
+// A light infinitely far away, like the sun: every ray is parallel, so its
+// shadows come from an orthographic projection rather than a perspective
+// one
+pub struct DirectionalLight {
+    // The way the light travels, from the light towards the scene
+    pub direction: Vector3<f32>,
+}
+
+impl DirectionalLight {
+    pub fn build_view_projection_matrix(&self) -> Matrix4<f32> {
+        let direction = self.direction.normalize();
+        let eye = Point3::origin() - direction * LIGHT_DISTANCE;
+        // Any up works as long as it isn't parallel to the light
+        let up = if direction.y.abs() > 0.99 {
+            Vector3::unit_z()
+        } else {
+            Vector3::unit_y()
+        };
+        let view = Matrix4::look_at_rh(eye, Point3::origin(), up);
+        let proj = cgmath::ortho(
+            -LIGHT_EXTENT,
+            LIGHT_EXTENT,
+            -LIGHT_EXTENT,
+            LIGHT_EXTENT,
+            0.0,
+            LIGHT_DISTANCE * 2.0,
+        );
+        OPENGL_TO_WGPU_MATRIX * proj * view
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct LightUniform {
+    view_proj: [[f32; 4]; 4],
+    // DirectionalLight::direction, normalized, for shading. w is unused.
+    direction: [f32; 4],
+}
+
+// Depth of the scene as seen from a directional light. Rendered first each
+// frame; then a fragment shader projects its own world position with the
+// same matrix and compares its depth against what's stored there. Anything
+// farther from the light than the stored depth has something in between,
+// so it's in shadow.
+pub struct ShadowMap {
+    view: wgpu::TextureView,
+    // Compares instead of filtering, see `SamplerBindingType::Comparison`
+    sampler: wgpu::Sampler,
+    light_buffer: wgpu::Buffer,
+    // Group 0 of the shadow pipeline
+    light_bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl ShadowMap {
+    // `object_layout` is the `ObjectUniforms` layout whose transforms get
+    // drawn with, see `render`
+    pub fn new(
+        device: &wgpu::Device,
+        object_layout: &wgpu::BindGroupLayout,
+        unclipped_depth: bool,
+    ) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Shadow Map"),
+            size: wgpu::Extent3d {
+                width: SHADOW_MAP_SIZE,
+                height: SHADOW_MAP_SIZE,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: SHADOW_MAP_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        // Linear filtering on a comparison sampler blends the results of
+        // the comparisons, not the depths, which softens edges a little
+        // more on top of the shader's PCF
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Shadow Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            compare: Some(wgpu::CompareFunction::LessEqual),
+            ..Default::default()
+        });
+
+        let light_buffer =
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Light Buffer"),
+                contents: bytemuck::cast_slice(&[LightUniform {
+                    view_proj: Matrix4::identity().into(),
+                    direction: [0.0, -1.0, 0.0, 0.0],
+                }]),
+                usage: wgpu::BufferUsages::UNIFORM
+                    | wgpu::BufferUsages::COPY_DST,
+            });
+
+        let light_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+                label: Some("Light Bind Group Layout"),
+            });
+
+        let light_bind_group =
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &light_bind_group_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: light_buffer.as_entire_binding(),
+                }],
+                label: Some("Light Bind Group"),
+            });
+
+        let layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Shadow Pipeline Layout"),
+                bind_group_layouts: &[&light_bind_group_layout, object_layout],
+                push_constant_ranges: &[],
+            });
+
+        let shader =
+            device.create_shader_module(wgpu::include_wgsl!("shadow.wgsl"));
+        let pipeline =
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Shadow Pipeline"),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[Vertex::desc()],
+                    compilation_options:
+                        wgpu::PipelineCompilationOptions::default(),
+                },
+                fragment: None,
+                // Both faces, so flat meshes cast a shadow whichever side
+                // the light is on
+                // Unclipped depth needs `Features::DEPTH_CLIP_CONTROL`, see
+                // `Renderer::new`
+                primitive: wgpu::PrimitiveState {
+                    cull_mode: None,
+                    unclipped_depth,
+                    ..Default::default()
+                },
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: SHADOW_MAP_FORMAT,
+                    depth_write_enabled: true,
+                    depth_compare: wgpu::CompareFunction::Less,
+                    stencil: wgpu::StencilState::default(),
+                    // Pushes stored depths back a little so a surface
+                    // doesn't shadow itself where its own depth rounds
+                    // the wrong way (shadow acne). Sloped surfaces need
+                    // more, hence the slope scale.
+                    bias: wgpu::DepthBiasState {
+                        constant: 2,
+                        slope_scale: 2.0,
+                        clamp: 0.0,
+                    },
+                }),
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            });
+
+        Self {
+            view,
+            sampler,
+            light_buffer,
+            light_bind_group,
+            pipeline,
+        }
+    }
+
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    pub fn sampler(&self) -> &wgpu::Sampler {
+        &self.sampler
+    }
+
+    // Holds the light's view projection, for shaders that sample the map,
+    // and its direction
+    pub fn light_buffer(&self) -> &wgpu::Buffer {
+        &self.light_buffer
+    }
+
+    pub fn update(&self, queue: &wgpu::Queue, light: &DirectionalLight) {
+        queue.write_buffer(
+            &self.light_buffer,
+            0,
+            bytemuck::cast_slice(&[LightUniform {
+                view_proj: light.build_view_projection_matrix().into(),
+                direction: light.direction.normalize().extend(0.0).into(),
+            }]),
+        );
+    }
+
+    // Clears the map and draws each mesh with the transform and draw
+    // arguments in its slots of `objects` and `indirect`
+    pub fn render<'m>(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        objects: &ObjectUniforms,
+        indirect: &IndirectDraws,
+        draws: impl IntoIterator<Item = (usize, &'m Mesh)>,
+    ) {
+        let mut render_pass =
+            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Shadow Pass"),
+                color_attachments: &[],
+                depth_stencil_attachment: Some(
+                    wgpu::RenderPassDepthStencilAttachment {
+                        view: &self.view,
+                        depth_ops: Some(wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(1.0),
+                            store: wgpu::StoreOp::Store,
+                        }),
+                        stencil_ops: None,
+                    },
+                ),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.light_bind_group, &[]);
+        for (index, mesh) in draws {
+            objects.draw_object(&mut render_pass, 1, index, mesh, indirect);
+        }
+    }
+}
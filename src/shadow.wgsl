@@ -0,0 +1,25 @@
+// Depth only, from the light's point of view. There's no fragment stage;
+// the rasterizer writes depth on its own.
+
+// see: shadow::LightUniform
+struct LightUniform {
+    view_proj: mat4x4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> light: LightUniform;
+
+// see: objects::ObjectUniform
+struct ObjectUniform {
+    model: mat4x4<f32>,
+}
+
+@group(1) @binding(0)
+var<uniform> object: ObjectUniform;
+
+@vertex
+fn vs_main(
+    @location(0) position: vec3<f32>,
+) -> @builtin(position) vec4<f32> {
+    return light.view_proj * object.model * vec4<f32>(position, 1.0);
+}
@@ -0,0 +1,94 @@
+use anyhow::*;
+
+use crate::texture;
+
+// A cubemap drawn behind everything else. It has no geometry of its own: a
+// fullscreen triangle at the far plane looks up the cubemap along each
+// pixel's view ray, which the shader rebuilds from the camera's inverse
+// view-projection.
+pub struct Skybox {
+    cubemap: texture::Texture,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+}
+
+impl Skybox {
+    // `faces` are encoded images ordered +X, -X, +Y, -Y, +Z, -Z
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        faces: [&[u8]; 6],
+    ) -> Result<Self> {
+        let cubemap = texture::Texture::from_cubemap(
+            device,
+            queue,
+            faces,
+            Some("Skybox Cubemap"),
+        )?;
+
+        let bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Skybox Bind Group Layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::Cube,
+                            sample_type: wgpu::TextureSampleType::Float {
+                                filterable: true,
+                            },
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(
+                            wgpu::SamplerBindingType::Filtering,
+                        ),
+                        count: None,
+                    },
+                ],
+            });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Skybox Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&cubemap.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&cubemap.sampler),
+                },
+            ],
+        });
+
+        Ok(Self {
+            cubemap,
+            bind_group_layout,
+            bind_group,
+        })
+    }
+
+    // E.g. for reflecting the sky in other surfaces
+    pub fn cubemap(&self) -> &texture::Texture {
+        &self.cubemap
+    }
+
+    pub fn bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layout
+    }
+
+    // Expects the caller to have set a pipeline built against
+    // `bind_group_layout` at group 0 and the camera at group 1, with no
+    // vertex buffers.
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass) {
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
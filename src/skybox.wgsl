@@ -0,0 +1,46 @@
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) direction: vec3<f32>,
+}
+
+// see: camera::CameraUniform
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+    inv_view_proj: mat4x4<f32>,
+    view_position: vec4<f32>,
+}
+
+@group(1) @binding(0)
+var<uniform> camera: CameraUniform;
+
+// A single triangle covering the screen, pushed out to the far plane so
+// everything else drawn afterwards lands in front of it:
+// (-1, -1), (3, -1), (-1, 3)
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    let ndc = vec2<f32>(
+        f32((vertex_index << 1u) & 2u) * 2.0 - 1.0,
+        f32(vertex_index & 2u) * 2.0 - 1.0,
+    );
+    let clip_position = vec4<f32>(ndc, 1.0, 1.0);
+
+    // Unproject the far plane point back into the world; the view ray is the
+    // direction from the eye to it
+    let world = camera.inv_view_proj * clip_position;
+
+    var out: VertexOutput;
+    out.clip_position = clip_position;
+    out.direction = world.xyz / world.w - camera.view_position.xyz;
+    return out;
+}
+
+@group(0) @binding(0)
+var t_skybox: texture_cube<f32>;
+
+@group(0) @binding(1)
+var s_skybox: sampler;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(t_skybox, s_skybox, normalize(in.direction));
+}
@@ -0,0 +1,249 @@
+use anyhow::*;
+use image::{DynamicImage, Rgba, RgbaImage};
+use wgpu::util::DeviceExt;
+
+use crate::{geometry, primitives, texture};
+
+// The splat map stores one weight per RGBA channel
+const MAX_LAYERS: usize = 4;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct SplatUniform {
+    layer_count: u32,
+    tiling: f32,
+    _padding: [u32; 2],
+}
+
+// A subdivided ground plane textured by blending up to four ground textures
+// (stored as layers of one texture array) with per-pixel weights read from
+// the RGBA channels of a splat map: R weights layer 0, G layer 1, and so on.
+pub struct SplatTerrain {
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    mesh: geometry::Mesh,
+}
+
+impl SplatTerrain {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layers: &[DynamicImage],
+        splat_map: &DynamicImage,
+        size: f32,
+        height: f32,
+    ) -> Result<Self> {
+        // Channels past the last layer are ignored and layers past the fourth
+        // have no channel to weight them, so both mismatches are survivable
+        // but almost certainly not what the caller intended.
+        let layers = if layers.len() > MAX_LAYERS {
+            log::warn!(
+                "Splat map has {} channels but {} layers were given; \
+                 ignoring the extra layers",
+                MAX_LAYERS,
+                layers.len()
+            );
+            &layers[..MAX_LAYERS]
+        } else {
+            if layers.len() < MAX_LAYERS {
+                log::warn!(
+                    "Only {} terrain layers for a {} channel splat map; \
+                     the remaining channels are ignored",
+                    layers.len(),
+                    MAX_LAYERS
+                );
+            }
+            layers
+        };
+
+        let layer_texture = texture::Texture::from_layers(
+            device,
+            queue,
+            layers,
+            Some("Terrain Layers"),
+        )?;
+
+        // Weights are data, not color
+        let splat_texture = texture::Texture::from_image(
+            device,
+            queue,
+            splat_map,
+            Some("Terrain Splat Map"),
+            texture::TextureKind::LinearData,
+        )?;
+
+        let uniform = SplatUniform {
+            layer_count: layers.len() as u32,
+            tiling: size,
+            _padding: [0; 2],
+        };
+        let uniform_buffer =
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Splat Uniform Buffer"),
+                contents: bytemuck::cast_slice(&[uniform]),
+                usage: wgpu::BufferUsages::UNIFORM,
+            });
+
+        let texture_entry =
+            |binding, view_dimension| wgpu::BindGroupLayoutEntry {
+                binding,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension,
+                    sample_type: wgpu::TextureSampleType::Float {
+                        filterable: true,
+                    },
+                },
+                count: None,
+            };
+        let sampler_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+            count: None,
+        };
+
+        let bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Splat Bind Group Layout"),
+                entries: &[
+                    texture_entry(0, wgpu::TextureViewDimension::D2Array),
+                    sampler_entry(1),
+                    texture_entry(2, wgpu::TextureViewDimension::D2),
+                    sampler_entry(3),
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 4,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Splat Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(
+                        &layer_texture.view,
+                    ),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(
+                        &layer_texture.sampler,
+                    ),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(
+                        &splat_texture.view,
+                    ),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::Sampler(
+                        &splat_texture.sampler,
+                    ),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let (mut vertices, indices) = primitives::plane(size, 64);
+        for vertex in &mut vertices {
+            vertex.position[1] = height;
+        }
+
+        let mesh = geometry::Mesh::new(device, "Terrain", &vertices, &indices);
+
+        Ok(Self {
+            bind_group_layout,
+            bind_group,
+            mesh,
+        })
+    }
+
+    pub fn bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layout
+    }
+
+    pub fn n_triangles(&self) -> u64 {
+        self.mesh.n_triangles()
+    }
+
+    // Expects the caller to have set a pipeline built against
+    // `bind_group_layout` at group 0 and the camera at group 1.
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass) {
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        self.mesh.draw(render_pass);
+    }
+}
+
+// Procedural stand-ins for real ground textures so the demo works without
+// any extra assets: a noisy tint per layer plus a splat map that fades
+// between them across the plane.
+pub fn demo_layers() -> Vec<DynamicImage> {
+    const SIZE: u32 = 64;
+    let tints = [
+        [70u8, 120, 50], // grass
+        [120, 90, 60],   // dirt
+        [110, 110, 115], // rock
+        [235, 235, 240], // snow
+    ];
+    tints
+        .iter()
+        .enumerate()
+        .map(|(i, tint)| {
+            let img = RgbaImage::from_fn(SIZE, SIZE, |x, y| {
+                let noise = (hash(x, y, i as u32) % 40) as i32 - 20;
+                let channel = |c: u8| (c as i32 + noise).clamp(0, 255) as u8;
+                Rgba([
+                    channel(tint[0]),
+                    channel(tint[1]),
+                    channel(tint[2]),
+                    255,
+                ])
+            });
+            DynamicImage::ImageRgba8(img)
+        })
+        .collect()
+}
+
+pub fn demo_splat_map() -> DynamicImage {
+    const SIZE: u32 = 128;
+    let img = RgbaImage::from_fn(SIZE, SIZE, |x, y| {
+        let u = x as f32 / (SIZE - 1) as f32;
+        let v = y as f32 / (SIZE - 1) as f32;
+        // Distance from the center drives grass -> dirt -> rock -> snow
+        let d = ((u - 0.5).powi(2) + (v - 0.5).powi(2)).sqrt() * 2.0;
+        let band = |center: f32| (1.0 - (d - center).abs() * 3.0).max(0.0);
+        let to_byte = |w: f32| (w.min(1.0) * 255.0) as u8;
+        Rgba([
+            to_byte(band(0.0)),
+            to_byte(band(0.35)),
+            to_byte(band(0.7)),
+            to_byte(band(1.05)),
+        ])
+    });
+    DynamicImage::ImageRgba8(img)
+}
+
+fn hash(x: u32, y: u32, seed: u32) -> u32 {
+    let mut h = x
+        .wrapping_mul(374_761_393)
+        .wrapping_add(y.wrapping_mul(668_265_263))
+        .wrapping_add(seed.wrapping_mul(2_147_483_647));
+    h = (h ^ (h >> 13)).wrapping_mul(1_274_126_177);
+    h ^ (h >> 16)
+}
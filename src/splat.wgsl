@@ -0,0 +1,76 @@
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+}
+
+// see: camera::CameraUniform
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+    inv_view_proj: mat4x4<f32>,
+    view_position: vec4<f32>,
+}
+
+@group(1) @binding(0)
+var<uniform> camera: CameraUniform;
+
+@vertex
+fn vs_main(model: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.tex_coords = model.tex_coords;
+    out.clip_position = camera.view_proj * vec4<f32>(model.position, 1.0);
+    return out;
+}
+
+// see: splat::SplatUniform
+struct SplatUniform {
+    // How many of the splat map's RGBA channels map to a layer (1..=4)
+    layer_count: u32,
+    // How many times each ground texture repeats across the terrain
+    tiling: f32,
+}
+
+@group(0) @binding(0)
+var t_layers: texture_2d_array<f32>;
+
+@group(0) @binding(1)
+var s_layers: sampler;
+
+@group(0) @binding(2)
+var t_splat: texture_2d<f32>;
+
+@group(0) @binding(3)
+var s_splat: sampler;
+
+@group(0) @binding(4)
+var<uniform> splat: SplatUniform;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let weights = textureSample(t_splat, s_splat, in.tex_coords);
+    let uv = in.tex_coords * splat.tiling;
+
+    var color = vec3<f32>(0.0);
+    var total = 0.0;
+    for (var i = 0u; i < 4u; i++) {
+        // textureSample must stay in uniform control flow, so sample every
+        // layer and zero out the weight of channels without one instead of
+        // breaking early
+        let layer = min(i, splat.layer_count - 1u);
+        let sample = textureSample(t_layers, s_layers, uv, layer).rgb;
+        let weight = select(0.0, weights[i], i < splat.layer_count);
+        color += sample * weight;
+        total += weight;
+    }
+
+    // Renormalize so painted weights don't need to sum to exactly one; fall
+    // back to the first layer where nothing was painted at all
+    let base = textureSample(t_layers, s_layers, uv, 0).rgb;
+    color = select(base, color / max(total, 0.0001), total > 0.0001);
+
+    return vec4<f32>(color, 1.0);
+}
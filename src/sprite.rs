@@ -0,0 +1,278 @@
+use std::ops::Range;
+
+use crate::growable_buffer::GrowableBuffer;
+use crate::texture;
+
+// An axis aligned rectangle. Sprite rects are in screen pixels with the
+// origin at the top-left, UV rects are in 0..1 texture coordinates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Rect {
+    pub const fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    // The whole texture
+    pub const FULL_UV: Rect = Rect::new(0.0, 0.0, 1.0, 1.0);
+}
+
+// Handle returned by `SpriteBatch::add_texture`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SpriteTexture(usize);
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct SpriteVertex {
+    position: [f32; 2],
+    tex_coords: [f32; 2],
+}
+
+impl SpriteVertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 2] =
+        wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2];
+
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<SpriteVertex>()
+                as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+struct QueuedSprite {
+    texture: SpriteTexture,
+    rect: Rect,
+    uv: Rect,
+}
+
+// Collects textured screen-space quads over a frame and draws them with one
+// draw call per texture. Every quad shares a single vertex buffer that's
+// rewritten each frame and only reallocated when it needs to grow.
+//
+// Sprites are drawn in the order they were queued within each texture, and
+// textures in the order they were added, so overlapping sprites with
+// different textures don't necessarily stack in submission order.
+pub struct SpriteBatch {
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    // One per added texture, indexed by SpriteTexture
+    bind_groups: Vec<wgpu::BindGroup>,
+    vertex_buffer: GrowableBuffer,
+    current_texture: SpriteTexture,
+    queued: Vec<QueuedSprite>,
+    vertices: Vec<SpriteVertex>,
+    // Vertex ranges written by the last `prepare`
+    draws: Vec<(SpriteTexture, Range<u32>)>,
+}
+
+impl SpriteBatch {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+        let bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Sprite Bind Group Layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Float {
+                                filterable: true,
+                            },
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(
+                            wgpu::SamplerBindingType::Filtering,
+                        ),
+                        count: None,
+                    },
+                ],
+            });
+
+        let shader =
+            device.create_shader_module(wgpu::include_wgsl!("sprite.wgsl"));
+
+        let layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Sprite Pipeline Layout"),
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        let pipeline =
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Sprite Pipeline"),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[SpriteVertex::desc()],
+                    compilation_options:
+                        wgpu::PipelineCompilationOptions::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some("fs_main"),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format,
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options:
+                        wgpu::PipelineCompilationOptions::default(),
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    // Screen-space quads, nothing to gain from culling
+                    cull_mode: None,
+                    ..Default::default()
+                },
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            });
+
+        let vertex_buffer = GrowableBuffer::new(
+            device,
+            "Sprite Vertex Buffer",
+            wgpu::BufferUsages::VERTEX,
+            (6 * 64 * std::mem::size_of::<SpriteVertex>())
+                as wgpu::BufferAddress,
+        );
+
+        Self {
+            pipeline,
+            bind_group_layout,
+            bind_groups: vec![],
+            vertex_buffer,
+            current_texture: SpriteTexture(0),
+            queued: vec![],
+            vertices: vec![],
+            draws: vec![],
+        }
+    }
+
+    // Sampled with the texture's own sampler
+    pub fn add_texture(
+        &mut self,
+        device: &wgpu::Device,
+        texture: &texture::Texture,
+    ) -> SpriteTexture {
+        self.bind_groups.push(device.create_bind_group(
+            &wgpu::BindGroupDescriptor {
+                label: Some("Sprite Bind Group"),
+                layout: &self.bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(
+                            &texture.view,
+                        ),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(
+                            &texture.sampler,
+                        ),
+                    },
+                ],
+            },
+        ));
+        SpriteTexture(self.bind_groups.len() - 1)
+    }
+
+    // Texture for the sprites drawn after this. Starts out as the first one
+    // added.
+    pub fn set_texture(&mut self, texture: SpriteTexture) {
+        self.current_texture = texture;
+    }
+
+    // Queues `uv` of the current texture stretched over `rect` for the next
+    // `prepare`
+    pub fn draw_sprite(&mut self, rect: Rect, uv: Rect) {
+        self.queued.push(QueuedSprite {
+            texture: self.current_texture,
+            rect,
+            uv,
+        });
+    }
+
+    // Writes everything queued since the last call into the vertex buffer,
+    // grouped by texture
+    pub fn prepare(
+        &mut self,
+        queue: &wgpu::Queue,
+        screen_size: winit::dpi::PhysicalSize<u32>,
+    ) {
+        self.vertices.clear();
+        self.draws.clear();
+
+        let w = screen_size.width.max(1) as f32;
+        let h = screen_size.height.max(1) as f32;
+        let to_ndc = |x: f32, y: f32| [x / w * 2.0 - 1.0, 1.0 - y / h * 2.0];
+
+        // Stable, so submission order holds within a texture
+        self.queued.sort_by_key(|sprite| sprite.texture);
+
+        for QueuedSprite { texture, rect, uv } in self.queued.drain(..) {
+            if texture.0 >= self.bind_groups.len() {
+                log::warn!("Sprite drawn without a texture, skipping");
+                continue;
+            }
+
+            let start = self.vertices.len() as u32;
+            let vertex = |x, y, u, v| SpriteVertex {
+                position: to_ndc(x, y),
+                tex_coords: [u, v],
+            };
+            let (x0, y0) = (rect.x, rect.y);
+            let (x1, y1) = (rect.x + rect.width, rect.y + rect.height);
+            let (u0, v0) = (uv.x, uv.y);
+            let (u1, v1) = (uv.x + uv.width, uv.y + uv.height);
+            let tl = vertex(x0, y0, u0, v0);
+            let tr = vertex(x1, y0, u1, v0);
+            let bl = vertex(x0, y1, u0, v1);
+            let br = vertex(x1, y1, u1, v1);
+            self.vertices.extend_from_slice(&[tl, bl, br, tl, br, tr]);
+            let end = self.vertices.len() as u32;
+
+            match self.draws.last_mut() {
+                Some((last, range)) if *last == texture => range.end = end,
+                _ => self.draws.push((texture, start..end)),
+            }
+        }
+
+        self.vertex_buffer.write(queue, &self.vertices);
+    }
+
+    pub fn render(&self, render_pass: &mut wgpu::RenderPass) {
+        if self.draws.is_empty() {
+            return;
+        }
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice());
+        for (texture, range) in &self.draws {
+            render_pass.set_bind_group(0, &self.bind_groups[texture.0], &[]);
+            render_pass.draw(range.clone(), 0..1);
+        }
+    }
+}
@@ -0,0 +1,214 @@
+use crate::growable_buffer::GrowableBuffer;
+use crate::sprite::Rect;
+use crate::texture;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct SpriteInstance {
+    // Left, top, right and bottom in NDC
+    rect: [f32; 4],
+    // The same corners in texture coordinates
+    uv: [f32; 4],
+    // Which of the texture's layers to sample
+    layer: u32,
+}
+
+impl SpriteInstance {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 3] = wgpu::vertex_attr_array![
+        0 => Float32x4,
+        1 => Float32x4,
+        2 => Uint32,
+    ];
+
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<SpriteInstance>()
+                as wgpu::BufferAddress,
+            // Once per quad rather than once per corner; the shader makes
+            // the corners from the vertex index
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+// Like sprite::SpriteBatch, but every sprite comes from one layer of a
+// single texture array (see `Texture::from_layers`). The layer is an
+// instance attribute, so however many distinct images the sprites show
+// there's one bind group and the whole batch is one draw call, where a
+// SpriteBatch needs a bind group and a draw per texture.
+pub struct SpriteArray {
+    pipeline: wgpu::RenderPipeline,
+    bind_group: wgpu::BindGroup,
+    layer_count: u32,
+    instance_buffer: GrowableBuffer,
+    // Queued since the last `prepare`, in pixels, see `draw_sprite`
+    queued: Vec<(Rect, Rect, u32)>,
+    instances: Vec<SpriteInstance>,
+    // How many instances the last `prepare` wrote
+    n_instances: u32,
+}
+
+impl SpriteArray {
+    // `texture` has to have a D2Array view, as `Texture::from_layers` makes
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        texture: &texture::Texture,
+    ) -> Self {
+        let bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Sprite Array Bind Group Layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2Array,
+                            sample_type: wgpu::TextureSampleType::Float {
+                                filterable: true,
+                            },
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(
+                            wgpu::SamplerBindingType::Filtering,
+                        ),
+                        count: None,
+                    },
+                ],
+            });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Sprite Array Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&texture.sampler),
+                },
+            ],
+        });
+
+        let shader = device
+            .create_shader_module(wgpu::include_wgsl!("sprite_array.wgsl"));
+
+        let layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Sprite Array Pipeline Layout"),
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        let pipeline =
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Sprite Array Pipeline"),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[SpriteInstance::desc()],
+                    compilation_options:
+                        wgpu::PipelineCompilationOptions::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some("fs_main"),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format,
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options:
+                        wgpu::PipelineCompilationOptions::default(),
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    cull_mode: None,
+                    ..Default::default()
+                },
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            });
+
+        let instance_buffer = GrowableBuffer::new(
+            device,
+            "Sprite Array Instance Buffer",
+            wgpu::BufferUsages::VERTEX,
+            (64 * std::mem::size_of::<SpriteInstance>()) as wgpu::BufferAddress,
+        );
+
+        Self {
+            pipeline,
+            bind_group,
+            layer_count: texture.texture.depth_or_array_layers(),
+            instance_buffer,
+            queued: vec![],
+            instances: vec![],
+            n_instances: 0,
+        }
+    }
+
+    pub fn layer_count(&self) -> u32 {
+        self.layer_count
+    }
+
+    // Queues `uv` of `layer` stretched over `rect`, in screen pixels, for
+    // the next `prepare`. Sprites are drawn in the order they're queued.
+    pub fn draw_sprite(&mut self, rect: Rect, uv: Rect, layer: u32) {
+        if layer >= self.layer_count {
+            log::warn!(
+                "Sprite layer {} of a {} layer array, skipping",
+                layer,
+                self.layer_count
+            );
+            return;
+        }
+        self.queued.push((rect, uv, layer));
+    }
+
+    // Writes everything queued since the last call into the instance buffer
+    pub fn prepare(
+        &mut self,
+        queue: &wgpu::Queue,
+        screen_size: winit::dpi::PhysicalSize<u32>,
+    ) {
+        let w = screen_size.width.max(1) as f32;
+        let h = screen_size.height.max(1) as f32;
+        let to_ndc = |x: f32, y: f32| [x / w * 2.0 - 1.0, 1.0 - y / h * 2.0];
+
+        self.instances.clear();
+        for (rect, uv, layer) in self.queued.drain(..) {
+            let [left, top] = to_ndc(rect.x, rect.y);
+            let [right, bottom] =
+                to_ndc(rect.x + rect.width, rect.y + rect.height);
+            self.instances.push(SpriteInstance {
+                rect: [left, top, right, bottom],
+                uv: [uv.x, uv.y, uv.x + uv.width, uv.y + uv.height],
+                layer,
+            });
+        }
+        self.n_instances = self.instances.len() as u32;
+        self.instance_buffer.write(queue, &self.instances);
+    }
+
+    pub fn render(&self, render_pass: &mut wgpu::RenderPass) {
+        if self.n_instances == 0 {
+            return;
+        }
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.instance_buffer.slice());
+        render_pass.draw(0..6, 0..self.n_instances);
+    }
+}
@@ -0,0 +1,56 @@
+// see: sprite_array::SpriteInstance
+struct InstanceInput {
+    // Left, top, right and bottom in NDC
+    @location(0) rect: vec4<f32>,
+    // The same corners in texture coordinates
+    @location(1) uv: vec4<f32>,
+    @location(2) layer: u32,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    // Integers can't be interpolated, and every vertex of a quad has the
+    // same layer anyway
+    @location(1) @interpolate(flat) layer: u32,
+}
+
+@group(0) @binding(0)
+var t_sprites: texture_2d_array<f32>;
+
+@group(0) @binding(1)
+var s_sprites: sampler;
+
+// Six vertices per instance, two triangles in the same order as
+// sprite::SpriteBatch writes them: top left, bottom left, bottom right, top
+// left, bottom right, top right
+@vertex
+fn vs_main(
+    @builtin(vertex_index) index: u32,
+    instance: InstanceInput,
+) -> VertexOutput {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(1.0, 0.0),
+    );
+    let corner = corners[index];
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(
+        mix(instance.rect.xy, instance.rect.zw, corner),
+        0.0,
+        1.0,
+    );
+    out.tex_coords = mix(instance.uv.xy, instance.uv.zw, corner);
+    out.layer = instance.layer;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(t_sprites, s_sprites, in.tex_coords, in.layer);
+}
@@ -0,0 +1,32 @@
+// A fullscreen pattern that only shows where the stencil test passes, i.e.
+// inside whatever the mask pipeline drew. The pattern itself needs no
+// inputs, so there are no bind groups.
+
+const STRIPE_WIDTH: f32 = 12.0;
+const STRIPE_COLOR_A: vec3<f32> = vec3<f32>(1.0, 0.75, 0.1);
+const STRIPE_COLOR_B: vec3<f32> = vec3<f32>(0.1, 0.1, 0.1);
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+}
+
+// A single triangle covering the screen:
+// (-1, -1), (3, -1), (-1, 3)
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    let ndc = vec2<f32>(
+        f32((vertex_index << 1u) & 2u) * 2.0 - 1.0,
+        f32(vertex_index & 2u) * 2.0 - 1.0,
+    );
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(ndc, 0.0, 1.0);
+    return out;
+}
+
+// Diagonal hazard stripes in framebuffer pixels
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let diagonal = in.clip_position.x + in.clip_position.y;
+    let stripe = floor(diagonal / STRIPE_WIDTH) % 2.0;
+    return vec4<f32>(mix(STRIPE_COLOR_A, STRIPE_COLOR_B, stripe), 1.0);
+}
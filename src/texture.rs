@@ -1,22 +1,248 @@
+use std::io::Cursor;
+use std::path::Path;
+
 use anyhow::*;
-use image::GenericImageView;
+use image::{GenericImageView, ImageReader};
+
+use crate::ktx2;
+
+// What an image's texels hold, which decides the format it's uploaded as.
+// Getting it wrong doesn't fail, it just quietly skews the lighting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureKind {
+    // Colors as authored (base color, detail, sprites), stored in sRGB.
+    // Sampling converts them to the linear values lighting works in.
+    Color,
+    // Values that are already linear: normal maps, roughness, weights,
+    // distance fields. Sampling has to hand them back untouched, an sRGB
+    // decode would bend every normal.
+    LinearData,
+}
+
+impl TextureKind {
+    pub fn format(self) -> wgpu::TextureFormat {
+        match self {
+            TextureKind::Color => wgpu::TextureFormat::Rgba8UnormSrgb,
+            TextureKind::LinearData => wgpu::TextureFormat::Rgba8Unorm,
+        }
+    }
+}
 
+// What texture coordinates outside 0..1 sample, in the order
+// `Renderer::cycle_address_mode` steps through them: the edge texels
+// stretched outwards, the texture tiled, or tiled with every other copy
+// flipped so the seams line up
+pub const ADDRESS_MODES: [wgpu::AddressMode; 3] = [
+    wgpu::AddressMode::ClampToEdge,
+    wgpu::AddressMode::Repeat,
+    wgpu::AddressMode::MirrorRepeat,
+];
+
+#[derive(Clone)]
 pub struct Texture {
-    #[allow(unused)]
     pub texture: wgpu::Texture,
     pub view: wgpu::TextureView,
     pub sampler: wgpu::Sampler,
 }
 
 impl Texture {
+    pub const DEPTH_FORMAT: wgpu::TextureFormat =
+        wgpu::TextureFormat::Depth32Float;
+    // Trades some depth precision for an 8 bit stencil buffer alongside it
+    pub const DEPTH_STENCIL_FORMAT: wgpu::TextureFormat =
+        wgpu::TextureFormat::Depth24PlusStencil8;
+    // The most wgpu accepts. Drivers clamp it further to whatever the
+    // hardware supports, and adapters without
+    // DownlevelFlags::ANISOTROPIC_FILTERING ignore it.
+    pub const MAX_ANISOTROPY: u16 = 16;
+    // What stands in for an image that couldn't be loaded: loud enough that
+    // it can't be mistaken for the real thing
+    const MISSING_SIZE: u32 = 64;
+    const MISSING_COLORS: ([u8; 4], [u8; 4]) =
+        ([255, 0, 255, 255], [0, 0, 0, 255]);
+    // Squares along each side of a checkerboard
+    const CHECKERBOARD_SQUARES: u32 = 8;
+
+    pub fn width(&self) -> u32 {
+        self.texture.width()
+    }
+
+    pub fn height(&self) -> u32 {
+        self.texture.height()
+    }
+
+    // Width over height: above 1 for landscape images, below for portrait
+    pub fn aspect_ratio(&self) -> f32 {
+        self.width() as f32 / self.height() as f32
+    }
+
+    // `sampled` adds TEXTURE_BINDING, for reading the depth in a shader
+    // (see depth_view.rs) rather than only testing against it
+    pub fn create_depth_texture(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        sample_count: u32,
+        format: wgpu::TextureFormat,
+        sampled: bool,
+        label: &str,
+    ) -> Self {
+        // Our depth texture needs to be the same size as our screen if we want
+        // things to render correctly, and match the color target's sample count
+        let size = wgpu::Extent3d {
+            width: config.width.max(1),
+            height: config.height.max(1),
+            depth_or_array_layers: 1,
+        };
+        let desc = wgpu::TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            // Since we are rendering to this texture, we need to add the
+            // RENDER_ATTACHMENT flag to it
+            usage: if sampled {
+                wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::TEXTURE_BINDING
+            } else {
+                wgpu::TextureUsages::RENDER_ATTACHMENT
+            },
+            view_formats: &[],
+        };
+        let texture = device.create_texture(&desc);
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        // We don't technically need a sampler for a depth texture, but our
+        // Texture struct requires it and we'd need one to render the depth
+        // texture for debugging
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            compare: Some(wgpu::CompareFunction::LessEqual),
+            lod_min_clamp: 0.0,
+            lod_max_clamp: 100.0,
+            ..Default::default()
+        });
+
+        Self {
+            texture,
+            view,
+            sampler,
+        }
+    }
+
+    // Bytes that can't be decoded give the magenta and black checkerboard,
+    // with a warning, rather than an error
     pub fn from_bytes(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         bytes: &[u8],
         label: Option<&str>,
+        kind: TextureKind,
     ) -> Result<Self> {
-        let img = image::load_from_memory(bytes)?;
-        Self::from_image(device, queue, &img, label)
+        match image::load_from_memory(bytes) {
+            std::result::Result::Ok(img) => {
+                Self::from_image(device, queue, &img, label, kind)
+            }
+            Err(e) => {
+                log::warn!(
+                    "Failed to decode {}: {}, using a checkerboard",
+                    label.unwrap_or("texture"),
+                    e
+                );
+                Ok(Self::missing(device, queue))
+            }
+        }
+    }
+
+    // Reads and decodes an image file at runtime instead of baking it into
+    // the binary with include_bytes!. The format comes from the file's
+    // contents rather than its extension, and only formats the image crate
+    // was built with (see Cargo.toml) can be decoded. Blocks until the file
+    // is loaded; `AssetLoader` does the same work off the main thread. Like
+    // `from_bytes`, a file that can't be read or decoded comes back as a
+    // checkerboard.
+    #[allow(unused)]
+    pub fn from_path(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        path: impl AsRef<Path>,
+        label: Option<&str>,
+        kind: TextureKind,
+    ) -> Result<Self> {
+        match Self::decode_path(path) {
+            std::result::Result::Ok(img) => {
+                Self::from_image(device, queue, &img, label, kind)
+            }
+            Err(e) => {
+                log::warn!("{:#}, using a checkerboard", e);
+                Ok(Self::missing(device, queue))
+            }
+        }
+    }
+
+    // A `size` x `size` checkerboard of `color_a` and `color_b` (RGBA, in
+    // sRGB like any color texture), made on the CPU. The top-left square is
+    // `color_a`.
+    pub fn checkerboard(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        size: u32,
+        color_a: [u8; 4],
+        color_b: [u8; 4],
+    ) -> Self {
+        let size = size.max(1);
+        let square = (size / Self::CHECKERBOARD_SQUARES).max(1);
+        let rgba = image::RgbaImage::from_fn(size, size, |x, y| {
+            if (x / square + y / square).is_multiple_of(2) {
+                image::Rgba(color_a)
+            } else {
+                image::Rgba(color_b)
+            }
+        });
+        Self::from_rgba(
+            device,
+            queue,
+            &rgba,
+            Some("Checkerboard Texture"),
+            TextureKind::Color,
+        )
+    }
+
+    // The magenta and black checkerboard loaders fall back on
+    fn missing(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let (color_a, color_b) = Self::MISSING_COLORS;
+        Self::checkerboard(device, queue, Self::MISSING_SIZE, color_a, color_b)
+    }
+
+    // The CPU half of `from_path`, which doesn't need the GPU and so can run
+    // on another thread
+    pub fn decode_path(path: impl AsRef<Path>) -> Result<image::DynamicImage> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).with_context(|| {
+            format!("Failed to read texture {}", path.display())
+        })?;
+
+        let reader = ImageReader::new(Cursor::new(bytes))
+            .with_guessed_format()
+            .context("Guessing the image format")?;
+        let Some(format) = reader.format() else {
+            bail!("{} isn't an image format we recognise", path.display());
+        };
+        ensure!(
+            format.reading_enabled(),
+            "{} is {:?}, which this build can't decode",
+            path.display(),
+            format
+        );
+        reader.decode().with_context(|| {
+            format!("Failed to decode texture {}", path.display())
+        })
     }
 
     pub fn from_image(
@@ -24,9 +250,21 @@ impl Texture {
         queue: &wgpu::Queue,
         img: &image::DynamicImage,
         label: Option<&str>,
+        kind: TextureKind,
     ) -> Result<Self> {
-        let rgba = img.to_rgba8();
-        let dimensions = img.dimensions();
+        Ok(Self::from_rgba(device, queue, &img.to_rgba8(), label, kind))
+    }
+
+    // Uploads already decoded pixels as is, in `kind`'s format
+    pub fn from_rgba(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        rgba: &image::RgbaImage,
+        label: Option<&str>,
+        kind: TextureKind,
+    ) -> Self {
+        let dimensions = rgba.dimensions();
+        let format = kind.format();
 
         let size = wgpu::Extent3d {
             width: dimensions.0,
@@ -39,7 +277,7 @@ impl Texture {
             mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            format,
             usage: wgpu::TextureUsages::TEXTURE_BINDING
                 | wgpu::TextureUsages::COPY_DST,
             view_formats: &[],
@@ -52,7 +290,7 @@ impl Texture {
                 mip_level: 0,
                 origin: wgpu::Origin3d::ZERO,
             },
-            &rgba,
+            rgba,
             wgpu::TexelCopyBufferLayout {
                 offset: 0,
                 bytes_per_row: Some(4 * dimensions.0),
@@ -72,6 +310,401 @@ impl Texture {
             ..Default::default()
         });
 
+        Self {
+            texture,
+            view,
+            sampler,
+        }
+    }
+
+    // Uploads a KTX2 file's block compressed mip levels as they are, with no
+    // decoding on the CPU. The file names its own format, sRGB or not, so
+    // there's no TextureKind to pass. Fails if the file's format isn't one `ktx2`
+    // handles or the device can't sample it.
+    pub fn from_ktx2(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        bytes: &[u8],
+        label: Option<&str>,
+    ) -> Result<Self> {
+        let ktx2 = ktx2::Ktx2::parse(bytes)?;
+        Self::from_compressed(
+            device,
+            queue,
+            ktx2.format,
+            (ktx2.width, ktx2.height),
+            &ktx2.levels,
+            label,
+        )
+    }
+
+    // Block compressed formats are copied a whole block (4x4 texels for BC
+    // and ETC2) at a time, so `bytes_per_row` is the size of a row of blocks
+    // and `rows_per_image` counts rows of blocks, not texels.
+    pub fn from_compressed(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        format: wgpu::TextureFormat,
+        (width, height): (u32, u32),
+        levels: &[&[u8]],
+        label: Option<&str>,
+    ) -> Result<Self> {
+        let required = format.required_features();
+        ensure!(
+            device.features().contains(required),
+            "{:?} needs {:?}, which this device doesn't have",
+            format,
+            required
+        );
+        ensure!(!levels.is_empty(), "No mip levels to upload");
+        let (block_width, block_height) = format.block_dimensions();
+        let block_size = format
+            .block_copy_size(None)
+            .with_context(|| format!("{:?} can't be copied", format))?;
+        ensure!(
+            width % block_width == 0 && height % block_height == 0,
+            "{}x{} isn't a whole number of {}x{} blocks",
+            width,
+            height,
+            block_width,
+            block_height
+        );
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label,
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: levels.len() as u32,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        for (level, data) in levels.iter().enumerate() {
+            // Mips smaller than a block still take up a whole one
+            let blocks_wide = (width >> level).max(1).div_ceil(block_width);
+            let blocks_high = (height >> level).max(1).div_ceil(block_height);
+            let bytes_per_row = blocks_wide * block_size;
+            ensure!(
+                data.len() >= (bytes_per_row * blocks_high) as usize,
+                "Mip level {} is shorter than its size needs",
+                level
+            );
+
+            queue.write_texture(
+                wgpu::TexelCopyTextureInfo {
+                    aspect: wgpu::TextureAspect::All,
+                    texture: &texture,
+                    mip_level: level as u32,
+                    origin: wgpu::Origin3d::ZERO,
+                },
+                data,
+                wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(bytes_per_row),
+                    rows_per_image: Some(blocks_high),
+                },
+                wgpu::Extent3d {
+                    width: blocks_wide * block_width,
+                    height: blocks_high * block_height,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Ok(Self {
+            texture,
+            view,
+            sampler,
+        })
+    }
+
+    // Replaces the sampler with one that addresses texture coordinates
+    // outside 0..1 by `address_mode` (see ADDRESS_MODES) and filters
+    // anisotropically: up to `anisotropy_clamp` samples are taken along the
+    // direction the texture is stretched in, which keeps surfaces seen at a
+    // grazing angle sharp instead of blurry. 4, 8 and 16 are the usual
+    // settings. Anything above MAX_ANISOTROPY is clamped to it. ClampToEdge
+    // with an anisotropy of 1 leaves the texture as it is.
+    pub fn with_sampler(
+        mut self,
+        device: &wgpu::Device,
+        address_mode: wgpu::AddressMode,
+        anisotropy_clamp: u16,
+    ) -> Result<Self> {
+        ensure!(anisotropy_clamp >= 1, "Anisotropy clamp can't be 0");
+        if address_mode == wgpu::AddressMode::ClampToEdge
+            && anisotropy_clamp == 1
+        {
+            return Ok(self);
+        }
+        self.set_sampler(device, address_mode, anisotropy_clamp)?;
+        Ok(self)
+    }
+
+    // Like `with_sampler`, but always makes a new sampler, so it can also
+    // go back to clamping. Anisotropy only works with linear filtering at
+    // every stage, so above 1 the new sampler filters linearly throughout.
+    pub fn set_sampler(
+        &mut self,
+        device: &wgpu::Device,
+        address_mode: wgpu::AddressMode,
+        anisotropy_clamp: u16,
+    ) -> Result<()> {
+        ensure!(anisotropy_clamp >= 1, "Anisotropy clamp can't be 0");
+        let clamped = anisotropy_clamp.min(Self::MAX_ANISOTROPY);
+        if clamped != anisotropy_clamp {
+            log::warn!(
+                "Anisotropy x{} isn't supported, using x{}",
+                anisotropy_clamp,
+                clamped
+            );
+        }
+
+        let min_filter = if clamped > 1 {
+            wgpu::FilterMode::Linear
+        } else {
+            wgpu::FilterMode::Nearest
+        };
+
+        self.sampler = create_sampler(
+            device,
+            &wgpu::SamplerDescriptor {
+                address_mode_u: address_mode,
+                address_mode_v: address_mode,
+                address_mode_w: address_mode,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter,
+                mipmap_filter: wgpu::FilterMode::Linear,
+                anisotropy_clamp: clamped,
+                ..Default::default()
+            },
+        )?;
+        Ok(())
+    }
+
+    // Builds a single D2Array texture with one layer per image. Every layer
+    // of an array texture has the same size, so images that don't match the
+    // first one are resized to fit.
+    pub fn from_layers(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        images: &[image::DynamicImage],
+        label: Option<&str>,
+    ) -> Result<Self> {
+        let Some(first) = images.first() else {
+            bail!("A texture array needs at least one layer");
+        };
+        let (width, height) = first.dimensions();
+
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: images.len() as u32,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label,
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        for (layer, img) in images.iter().enumerate() {
+            let rgba = if img.dimensions() == (width, height) {
+                img.to_rgba8()
+            } else {
+                log::warn!(
+                    "Resizing texture array layer {} from {:?} to {:?}",
+                    layer,
+                    img.dimensions(),
+                    (width, height)
+                );
+                img.resize_exact(
+                    width,
+                    height,
+                    image::imageops::FilterType::Triangle,
+                )
+                .to_rgba8()
+            };
+
+            queue.write_texture(
+                wgpu::TexelCopyTextureInfo {
+                    aspect: wgpu::TextureAspect::All,
+                    texture: &texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d {
+                        x: 0,
+                        y: 0,
+                        z: layer as u32,
+                    },
+                },
+                &rgba,
+                wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(4 * width),
+                    rows_per_image: Some(height),
+                },
+                wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+        // Array layers are typically tiled across large surfaces
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::Repeat,
+            address_mode_w: wgpu::AddressMode::Repeat,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Ok(Self {
+            texture,
+            view,
+            sampler,
+        })
+    }
+
+    // `from_layers` for encoded images, e.g. from include_bytes!. Unlike
+    // `from_bytes` a layer that can't be decoded is an error, since a
+    // checkerboard in its place would shift which layer is which.
+    pub fn from_layer_bytes(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layers: &[&[u8]],
+        label: Option<&str>,
+    ) -> Result<Self> {
+        let images = layers
+            .iter()
+            .enumerate()
+            .map(|(layer, bytes)| {
+                image::load_from_memory(bytes).with_context(|| {
+                    format!("Failed to decode texture array layer {}", layer)
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Self::from_layers(device, queue, &images, label)
+    }
+
+    // Builds a cube texture from six square faces of the same size, ordered
+    // +X, -X, +Y, -Y, +Z, -Z as wgpu expects the layers of a cube.
+    pub fn from_cubemap(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        faces: [&[u8]; 6],
+        label: Option<&str>,
+    ) -> Result<Self> {
+        let faces = faces
+            .iter()
+            .map(|bytes| image::load_from_memory(bytes))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let (width, height) = faces[0].dimensions();
+        if width != height {
+            bail!("Cubemap faces must be square, got {}x{}", width, height);
+        }
+        if let Some(i) =
+            faces.iter().position(|f| f.dimensions() != (width, height))
+        {
+            bail!(
+                "Cubemap face {} is {:?} but face 0 is {:?}",
+                i,
+                faces[i].dimensions(),
+                (width, height)
+            );
+        }
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label,
+            size: wgpu::Extent3d {
+                width,
+                height,
+                // A cube is just a 2D array texture with six layers that's
+                // viewed as a cube
+                depth_or_array_layers: 6,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        for (layer, face) in faces.iter().enumerate() {
+            queue.write_texture(
+                wgpu::TexelCopyTextureInfo {
+                    aspect: wgpu::TextureAspect::All,
+                    texture: &texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d {
+                        x: 0,
+                        y: 0,
+                        z: layer as u32,
+                    },
+                },
+                &face.to_rgba8(),
+                wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(4 * width),
+                    rows_per_image: Some(height),
+                },
+                wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::Cube),
+            ..Default::default()
+        });
+        // Clamping keeps the seams between faces from picking up texels from
+        // the wrong edge
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
         Ok(Self {
             texture,
             view,
@@ -79,3 +712,75 @@ impl Texture {
         })
     }
 }
+
+// wgpu panics on a sampler it considers invalid, so catch the one mistake
+// that's easy to make here: anisotropic filtering without linear filters
+fn create_sampler(
+    device: &wgpu::Device,
+    desc: &wgpu::SamplerDescriptor,
+) -> Result<wgpu::Sampler> {
+    let linear = [desc.mag_filter, desc.min_filter, desc.mipmap_filter]
+        .iter()
+        .all(|&filter| filter == wgpu::FilterMode::Linear);
+    ensure!(
+        desc.anisotropy_clamp <= 1 || linear,
+        "Anisotropic filtering needs Linear mag, min and mipmap filters"
+    );
+    Ok(device.create_sampler(desc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gpu::test_gpu;
+
+    #[test]
+    fn color_textures_are_srgb() {
+        let format = TextureKind::Color.format();
+        assert_eq!(format, wgpu::TextureFormat::Rgba8UnormSrgb);
+        assert!(format.is_srgb());
+    }
+
+    #[test]
+    fn data_textures_are_linear() {
+        let format = TextureKind::LinearData.format();
+        assert_eq!(format, wgpu::TextureFormat::Rgba8Unorm);
+        assert!(!format.is_srgb());
+    }
+
+    #[test]
+    fn decoded_textures_get_their_kinds_format() {
+        let Some(gpu) = test_gpu() else { return };
+        let mut png = vec![];
+        image::RgbaImage::from_pixel(2, 2, image::Rgba([128, 64, 32, 255]))
+            .write_to(
+                &mut std::io::Cursor::new(&mut png),
+                image::ImageFormat::Png,
+            )
+            .unwrap();
+
+        for (kind, format) in [
+            (TextureKind::Color, wgpu::TextureFormat::Rgba8UnormSrgb),
+            (TextureKind::LinearData, wgpu::TextureFormat::Rgba8Unorm),
+        ] {
+            let texture = Texture::from_bytes(
+                &gpu.device,
+                &gpu.queue,
+                &png,
+                Some("Test Texture"),
+                kind,
+            )
+            .unwrap();
+            assert_eq!(texture.texture.format(), format, "{:?}", kind);
+        }
+    }
+
+    #[test]
+    fn kinds_differ_only_in_srgb() {
+        // Same texels either way, only the decode on sampling changes
+        assert_eq!(
+            TextureKind::Color.format().remove_srgb_suffix(),
+            TextureKind::LinearData.format()
+        );
+    }
+}
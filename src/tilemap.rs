@@ -0,0 +1,440 @@
+use std::path::Path;
+
+use anyhow::*;
+use wgpu::util::DeviceExt;
+
+use crate::texture;
+
+// Tiles are drawn this many screen pixels across, whatever the atlas cell
+// size
+const TILE_SCREEN_SIZE: f32 = 32.0;
+// Where the map's top-left corner sits on screen before scrolling
+const MAP_ORIGIN: [f32; 2] = [16.0, 96.0];
+
+// The generated atlas's grid, used when no atlas image is configured
+const GENERATED_CELL_SIZE: u32 = 16;
+const GENERATED_COLUMNS: u32 = 4;
+
+// How an atlas image is cut up: square cells `cell_size` pixels across,
+// `columns` to a row, numbered left to right and then top to bottom
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AtlasLayout {
+    pub cell_size: u32,
+    pub columns: u32,
+}
+
+// A grid of tiles, each an index into the atlas's cells. Row major from the
+// top-left.
+#[derive(Debug, Clone)]
+pub struct TileMap {
+    pub width: u32,
+    pub height: u32,
+    pub tiles: Vec<u16>,
+}
+
+impl TileMap {
+    // Left out when drawing
+    pub const EMPTY: u16 = u16::MAX;
+
+    // A small walled garden with a pond and a path, drawn with the
+    // generated atlas's tiles. The corners are left empty to round the
+    // wall off.
+    pub fn demo() -> Self {
+        const GRASS: u16 = 0;
+        const FLOWERS: u16 = 1;
+        const WALL: u16 = 4;
+        const WATER: u16 = 8;
+        const PATH: u16 = 12;
+
+        let (width, height) = (16, 16);
+        let tiles = (0..width * height)
+            .map(|i| {
+                let (x, y) = (i % width, i / width);
+                let (dx, dy) = (x as i32 - 10, y as i32 - 5);
+                let edge_x = x == 0 || x == width - 1;
+                let edge_y = y == 0 || y == height - 1;
+                if edge_x && edge_y {
+                    Self::EMPTY
+                } else if edge_x || edge_y {
+                    if x == 7 && y == height - 1 {
+                        PATH
+                    } else {
+                        WALL
+                    }
+                } else if dx * dx + dy * dy <= 8 {
+                    WATER
+                } else if x == 7 && y > 5 {
+                    PATH
+                } else if (x * 7 + y * 13) % 11 == 0 {
+                    FLOWERS
+                } else {
+                    GRASS
+                }
+            })
+            .collect();
+        Self {
+            width,
+            height,
+            tiles,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct TileInstance {
+    // In tiles from the map's top-left
+    grid_position: [f32; 2],
+    // Top-left of the tile's atlas cell in texture coordinates
+    uv_offset: [f32; 2],
+}
+
+impl TileInstance {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 2] =
+        wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2];
+
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<TileInstance>()
+                as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+// see: tilemap.wgsl
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct TileMapUniform {
+    screen_size: [f32; 2],
+    tile_size: [f32; 2],
+    // Screen position of the map's top-left, scrolling included
+    origin: [f32; 2],
+    // One cell of the atlas in texture coordinates
+    uv_cell_size: [f32; 2],
+}
+
+// Draws a TileMap in screen space with one instanced draw call: every tile
+// is an instance of the same quad, carrying where it goes in the grid and
+// which atlas cell it shows. The quad's corners come from the vertex index,
+// so there's no vertex buffer, only the instance buffer, which is rebuilt
+// only when the map changes. Scrolling just moves the origin in a uniform.
+pub struct TileMapRenderer {
+    pipeline: wgpu::RenderPipeline,
+    bind_group: wgpu::BindGroup,
+    uniform_buffer: wgpu::Buffer,
+    instance_buffer: wgpu::Buffer,
+    n_instances: u32,
+    atlas_size: [u32; 2],
+    layout: AtlasLayout,
+    // Size of the current map in tiles
+    map_size: [u32; 2],
+    // In screen pixels, positive moves the view right and down the map
+    scroll: [f32; 2],
+}
+
+impl TileMapRenderer {
+    // `atlas` is cut up by `layout`
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        atlas: &texture::Texture,
+        layout: AtlasLayout,
+        map: &TileMap,
+    ) -> Self {
+        let bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Tile Map Bind Group Layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Float {
+                                filterable: true,
+                            },
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(
+                            wgpu::SamplerBindingType::Filtering,
+                        ),
+                        count: None,
+                    },
+                ],
+            });
+
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Tile Map Uniform Buffer"),
+            size: std::mem::size_of::<TileMapUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        // Nearest so pixel art tiles stay crisp when scaled up, and so
+        // nothing is blended in from the neighbouring cells
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Tile Map Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Tile Map Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&atlas.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+
+        let shader =
+            device.create_shader_module(wgpu::include_wgsl!("tilemap.wgsl"));
+
+        let pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Tile Map Pipeline Layout"),
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        let pipeline =
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Tile Map Pipeline"),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[TileInstance::desc()],
+                    compilation_options:
+                        wgpu::PipelineCompilationOptions::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some("fs_main"),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format,
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options:
+                        wgpu::PipelineCompilationOptions::default(),
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    cull_mode: None,
+                    ..Default::default()
+                },
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            });
+
+        let size = atlas.texture.size();
+        let atlas_size = [size.width, size.height];
+        let (instance_buffer, n_instances) =
+            Self::create_instances(device, atlas_size, layout, map);
+
+        Self {
+            pipeline,
+            bind_group,
+            uniform_buffer,
+            instance_buffer,
+            n_instances,
+            atlas_size,
+            layout,
+            map_size: [map.width, map.height],
+            scroll: [0.0, 0.0],
+        }
+    }
+
+    // One instance per tile, leaving out empty ones and any index past the
+    // atlas's last cell
+    fn create_instances(
+        device: &wgpu::Device,
+        atlas_size: [u32; 2],
+        layout: AtlasLayout,
+        map: &TileMap,
+    ) -> (wgpu::Buffer, u32) {
+        let cell_size = layout.cell_size.max(1);
+        let columns = layout.columns.clamp(1, atlas_size[0] / cell_size);
+        let n_cells = columns * (atlas_size[1] / cell_size);
+
+        let instances: Vec<TileInstance> = map
+            .tiles
+            .iter()
+            .take((map.width * map.height) as usize)
+            .enumerate()
+            .filter(|&(_, &tile)| (tile as u32) < n_cells)
+            .map(|(i, &tile)| {
+                let (x, y) = (i as u32 % map.width, i as u32 / map.width);
+                let (column, row) =
+                    (tile as u32 % columns, tile as u32 / columns);
+                TileInstance {
+                    grid_position: [x as f32, y as f32],
+                    uv_offset: [
+                        (column * cell_size) as f32 / atlas_size[0] as f32,
+                        (row * cell_size) as f32 / atlas_size[1] as f32,
+                    ],
+                }
+            })
+            .collect();
+
+        let buffer =
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Tile Map Instance Buffer"),
+                // A buffer can't be empty
+                contents: if instances.is_empty() {
+                    &[0; std::mem::size_of::<TileInstance>()]
+                } else {
+                    bytemuck::cast_slice(&instances)
+                },
+                usage: wgpu::BufferUsages::VERTEX,
+            });
+        (buffer, instances.len() as u32)
+    }
+
+    // Moves the view over the map by screen pixels, stopping once the map's
+    // edge reaches the origin
+    pub fn scroll(&mut self, dx: f32, dy: f32) {
+        for (scroll, (delta, tiles)) in self
+            .scroll
+            .iter_mut()
+            .zip([(dx, self.map_size[0]), (dy, self.map_size[1])])
+        {
+            let max = tiles.saturating_sub(1) as f32 * TILE_SCREEN_SIZE;
+            *scroll = (*scroll + delta).clamp(0.0, max);
+        }
+    }
+
+    pub fn prepare(
+        &self,
+        queue: &wgpu::Queue,
+        screen_size: winit::dpi::PhysicalSize<u32>,
+    ) {
+        // Snapping to whole pixels keeps the nearest sampling stable while
+        // scrolling
+        let uniform = TileMapUniform {
+            screen_size: [
+                screen_size.width.max(1) as f32,
+                screen_size.height.max(1) as f32,
+            ],
+            tile_size: [TILE_SCREEN_SIZE; 2],
+            origin: [
+                (MAP_ORIGIN[0] - self.scroll[0]).round(),
+                (MAP_ORIGIN[1] - self.scroll[1]).round(),
+            ],
+            uv_cell_size: [
+                self.layout.cell_size as f32 / self.atlas_size[0] as f32,
+                self.layout.cell_size as f32 / self.atlas_size[1] as f32,
+            ],
+        };
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::bytes_of(&uniform),
+        );
+    }
+
+    pub fn render(&self, render_pass: &mut wgpu::RenderPass) {
+        if self.n_instances == 0 {
+            return;
+        }
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.instance_buffer.slice(..));
+        render_pass.draw(0..6, 0..self.n_instances);
+    }
+}
+
+// Reads an atlas image from `path`
+pub fn load_atlas(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    path: &Path,
+) -> Result<texture::Texture> {
+    let image = texture::Texture::decode_path(path)?;
+    Ok(texture::Texture::from_rgba(
+        device,
+        queue,
+        &image.to_rgba8(),
+        Some("Tile Atlas"),
+        texture::TextureKind::Color,
+    ))
+}
+
+// A stand-in atlas made on the CPU, with the layout to cut it by. Each row
+// is one kind of tile in four variations: grass, wall, water and path, from
+// the top. Cells have a darker rim so the grid shows.
+pub fn generated_atlas(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+) -> (texture::Texture, AtlasLayout) {
+    const ROW_COLORS: [[u8; 3]; 4] = [
+        [72, 140, 60],
+        [120, 110, 100],
+        [50, 100, 180],
+        [180, 150, 100],
+    ];
+    let cell = GENERATED_CELL_SIZE;
+    let size = cell * GENERATED_COLUMNS;
+    let rgba = image::RgbaImage::from_fn(size, size, |x, y| {
+        let (column, row) = (x / cell, y / cell);
+        let (cx, cy) = (x % cell, y % cell);
+        let base = ROW_COLORS[row as usize % ROW_COLORS.len()];
+        let rim = cx == 0 || cy == 0 || cx == cell - 1 || cy == cell - 1;
+        // A speckle whose density varies with the column
+        let speckle = (cx * 5 + cy * 3 + column * 7) % (column + 3) == 0;
+        let scale = if rim {
+            0.7
+        } else if speckle {
+            1.25
+        } else {
+            1.0
+        };
+        let [r, g, b] = base.map(|c| (c as f32 * scale).min(255.0) as u8);
+        image::Rgba([r, g, b, 255])
+    });
+    let texture = texture::Texture::from_rgba(
+        device,
+        queue,
+        &rgba,
+        Some("Generated Tile Atlas"),
+        texture::TextureKind::Color,
+    );
+    let layout = AtlasLayout {
+        cell_size: cell,
+        columns: GENERATED_COLUMNS,
+    };
+    (texture, layout)
+}
@@ -0,0 +1,59 @@
+struct InstanceInput {
+    @location(0) grid_position: vec2<f32>,
+    @location(1) uv_offset: vec2<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+}
+
+// see: tilemap::TileMapUniform
+struct TileMapUniform {
+    screen_size: vec2<f32>,
+    tile_size: vec2<f32>,
+    origin: vec2<f32>,
+    uv_cell_size: vec2<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> tile_map: TileMapUniform;
+
+@group(0) @binding(1)
+var t_atlas: texture_2d<f32>;
+
+@group(0) @binding(2)
+var s_atlas: sampler;
+
+// Two triangles covering the unit square, in the same order as the sprites'
+// quads
+var<private> CORNERS: array<vec2<f32>, 6> = array<vec2<f32>, 6>(
+    vec2<f32>(0.0, 0.0),
+    vec2<f32>(0.0, 1.0),
+    vec2<f32>(1.0, 1.0),
+    vec2<f32>(0.0, 0.0),
+    vec2<f32>(1.0, 1.0),
+    vec2<f32>(1.0, 0.0),
+);
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) vertex_index: u32,
+    instance: InstanceInput,
+) -> VertexOutput {
+    let corner = CORNERS[vertex_index];
+    // Screen pixels from the top-left, then NDC with y up
+    let pixel =
+        tile_map.origin + (instance.grid_position + corner) * tile_map.tile_size;
+    let ndc = pixel / tile_map.screen_size * 2.0 - 1.0;
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(ndc.x, -ndc.y, 0.0, 1.0);
+    out.tex_coords = instance.uv_offset + corner * tile_map.uv_cell_size;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(t_atlas, s_atlas, in.tex_coords);
+}
@@ -0,0 +1,194 @@
+use std::marker::PhantomData;
+
+use wgpu::util::DeviceExt;
+
+// WGSL rounds a uniform struct's size up to a multiple of its alignment,
+// which is 16 for anything holding a vec3, vec4 or matrix. A Rust struct
+// that isn't padded out to match leaves the end of the buffer short of what
+// the shader reads, so `Uniform` won't take one.
+const UNIFORM_ALIGNMENT: usize = 16;
+
+const fn is_padded<T>() -> bool {
+    std::mem::size_of::<T>().is_multiple_of(UNIFORM_ALIGNMENT)
+}
+
+// What `update` writes for `value`: its bytes exactly as laid out in
+// memory, which for a #[repr(C)] struct is field by field in declaration
+// order
+fn bytes<T: bytemuck::Pod>(value: &T) -> &[u8] {
+    const {
+        assert!(
+            is_padded::<T>(),
+            "A uniform's size has to be a multiple of 16 bytes, pad it"
+        )
+    };
+    bytemuck::bytes_of(value)
+}
+
+// A uniform buffer holding one `T`, with a bind group layout and bind group
+// exposing it at binding 0. Saves spelling out the three of them for every
+// camera, light or set of parameters, and keeps what's written to the
+// buffer the same type the buffer was made for.
+//
+// `T` has to be #[repr(C)] and Pod, and match the WGSL struct field for
+// field. Its size has to be padded out to a multiple of 16 bytes (checked
+// at compile time), see UNIFORM_ALIGNMENT.
+pub struct Uniform<T> {
+    buffer: wgpu::Buffer,
+    layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    _value: PhantomData<T>,
+}
+
+impl<T: bytemuck::Pod> Uniform<T> {
+    // A layout exposing a `T` at binding 0 to the `visibility` stages, for
+    // `with_layout`
+    pub fn create_layout(
+        device: &wgpu::Device,
+        label: &str,
+        visibility: wgpu::ShaderStages,
+    ) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some(&format!("{} Bind Group Layout", label)),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: wgpu::BufferSize::new(
+                        std::mem::size_of::<T>() as wgpu::BufferAddress,
+                    ),
+                },
+                count: None,
+            }],
+        })
+    }
+
+    // Starts out holding `value`. Uniforms made with the same layout can be
+    // bound by the same pipelines (e.g. the mirror's camera in place of the
+    // main one).
+    pub fn with_layout(
+        device: &wgpu::Device,
+        label: &str,
+        layout: &wgpu::BindGroupLayout,
+        value: &T,
+    ) -> Self {
+        Self::with_usage(
+            device,
+            label,
+            layout,
+            value,
+            wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        )
+    }
+
+    fn with_usage(
+        device: &wgpu::Device,
+        label: &str,
+        layout: &wgpu::BindGroupLayout,
+        value: &T,
+        usage: wgpu::BufferUsages,
+    ) -> Self {
+        let buffer =
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(&format!("{} Buffer", label)),
+                contents: bytes(value),
+                usage,
+            });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(&format!("{} Bind Group", label)),
+            layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+        Self {
+            buffer,
+            layout: layout.clone(),
+            bind_group,
+            _value: PhantomData,
+        }
+    }
+
+    // Takes effect for anything submitted after it
+    pub fn update(&self, queue: &wgpu::Queue, value: &T) {
+        queue.write_buffer(&self.buffer, 0, bytes(value));
+    }
+
+    pub fn layout(&self) -> &wgpu::BindGroupLayout {
+        &self.layout
+    }
+
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gpu::test_gpu;
+    use crate::readback::read_buffer;
+
+    // Laid out like a WGSL light: vec3 position, f32 intensity, vec4 color
+    #[repr(C)]
+    #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+    struct Light {
+        position: [f32; 3],
+        intensity: f32,
+        color: [f32; 4],
+    }
+
+    #[test]
+    fn written_bytes_follow_the_struct_layout() {
+        let Some(gpu) = test_gpu() else { return };
+        let layout = Uniform::<Light>::create_layout(
+            &gpu.device,
+            "Test Light",
+            wgpu::ShaderStages::FRAGMENT,
+        );
+        // COPY_SRC on top of what `with_layout` asks for, so read_buffer
+        // can copy the light out
+        let uniform = Uniform::with_usage(
+            &gpu.device,
+            "Test Light",
+            &layout,
+            &bytemuck::Zeroable::zeroed(),
+            wgpu::BufferUsages::UNIFORM
+                | wgpu::BufferUsages::COPY_DST
+                | wgpu::BufferUsages::COPY_SRC,
+        );
+        let light = Light {
+            position: [1.0, 2.0, 3.0],
+            intensity: 0.5,
+            color: [0.1, 0.2, 0.3, 1.0],
+        };
+        uniform.update(&gpu.queue, &light);
+
+        let written: Vec<u8> = read_buffer(
+            &gpu.device,
+            &gpu.queue,
+            &uniform.buffer,
+            std::mem::size_of::<Light>(),
+        );
+        // The shader's view of it: position in the first 12 bytes, the
+        // intensity filling out the vec3's 16, then the color
+        let expected: Vec<u8> = [1.0f32, 2.0, 3.0, 0.5, 0.1, 0.2, 0.3, 1.0]
+            .iter()
+            .flat_map(|float| float.to_ne_bytes())
+            .collect();
+        assert_eq!(written, expected);
+    }
+
+    #[test]
+    fn sizes_have_to_be_padded_to_16_bytes() {
+        assert!(is_padded::<Light>());
+        assert!(is_padded::<crate::camera::CameraUniform>());
+        assert!(is_padded::<[f32; 4]>());
+        // A bare vec3 needs padding out to a vec4
+        assert!(!is_padded::<[f32; 3]>());
+        assert!(!is_padded::<f32>());
+    }
+}
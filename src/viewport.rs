@@ -0,0 +1,216 @@
+use cgmath::Vector3;
+use winit::dpi::PhysicalSize;
+
+use crate::camera::Camera;
+
+// How far above the main camera's target the top-down camera sits
+const TOP_DOWN_HEIGHT: f32 = 6.0;
+
+// What a viewport looks at the scene through
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViewportCamera {
+    // Whichever camera the window would otherwise use, fitted to the
+    // viewport's shape
+    Main,
+    // Straight down at the main camera's target
+    TopDown,
+}
+
+// One region of the window the scene gets drawn into, see
+// `Renderer::viewports`
+#[derive(Debug, Clone, Copy)]
+pub struct Viewport {
+    // x, y, width and height as fractions of the target, from its top left
+    pub rect: [f32; 4],
+    pub camera: ViewportCamera,
+    // Index into the render pipelines to draw with instead of the active
+    // one. Entities with a pipeline of their own keep it either way.
+    pub pipeline: Option<usize>,
+}
+
+impl Viewport {
+    // `rect` in pixels of a target of `size`, clamped to fit inside it. The
+    // edges are rounded rather than the width and height, so viewports that
+    // meet in fractions also meet in pixels: split an odd width in half and
+    // one side gets the extra column, instead of both or neither.
+    pub fn pixel_rect(&self, size: PhysicalSize<u32>) -> [u32; 4] {
+        let [x, y, width, height] = self.rect;
+        let edge = |fraction: f32, extent: u32| {
+            (fraction.clamp(0.0, 1.0) * extent as f32).round() as u32
+        };
+        let (left, right) = (edge(x, size.width), edge(x + width, size.width));
+        let (top, bottom) =
+            (edge(y, size.height), edge(y + height, size.height));
+        [
+            left,
+            top,
+            right.saturating_sub(left),
+            bottom.saturating_sub(top),
+        ]
+    }
+
+    // This viewport placed inside `rect` rather than the whole target, with
+    // `rect` in the same fractions of the target
+    pub fn within(self, rect: [f32; 4]) -> Viewport {
+        let [x, y, width, height] = self.rect;
+        let [outer_x, outer_y, outer_width, outer_height] = rect;
+        Viewport {
+            rect: [
+                outer_x + x * outer_width,
+                outer_y + y * outer_height,
+                width * outer_width,
+                height * outer_height,
+            ],
+            ..self
+        }
+    }
+}
+
+// The whole target through the main camera, what no viewports at all draws
+pub const FULL: Viewport = Viewport {
+    rect: [0.0, 0.0, 1.0, 1.0],
+    camera: ViewportCamera::Main,
+    pipeline: None,
+};
+
+// The largest rect `aspect` wide that fits in a target `target_aspect` wide,
+// centered, as fractions of the target. Wider targets get bars left and
+// right (pillarboxing), taller ones above and below (letterboxing).
+pub fn letterbox(target_aspect: f32, aspect: f32) -> [f32; 4] {
+    if target_aspect > aspect {
+        let width = aspect / target_aspect;
+        [(1.0 - width) / 2.0, 0.0, width, 1.0]
+    } else {
+        let height = target_aspect / aspect;
+        [0.0, (1.0 - height) / 2.0, 1.0, height]
+    }
+}
+
+// The left half through the main camera, the right half from above
+pub fn split_screen() -> Vec<Viewport> {
+    vec![
+        Viewport {
+            rect: [0.0, 0.0, 0.5, 1.0],
+            camera: ViewportCamera::Main,
+            pipeline: None,
+        },
+        Viewport {
+            rect: [0.5, 0.0, 0.5, 1.0],
+            camera: ViewportCamera::TopDown,
+            pipeline: None,
+        },
+    ]
+}
+
+// `columns` by `rows` equal cells through the main camera, each drawing
+// with the next of `n_pipelines` render pipelines, left to right and then
+// top to bottom. Cells past the last pipeline get no viewport, so they're
+// left cleared, and pipelines past the last cell aren't shown.
+pub fn grid(columns: u32, rows: u32, n_pipelines: usize) -> Vec<Viewport> {
+    let (columns, rows) = (columns.max(1), rows.max(1));
+    let (width, height) = (1.0 / columns as f32, 1.0 / rows as f32);
+    (0..n_pipelines.min((columns * rows) as usize))
+        .map(|index| {
+            let (column, row) =
+                (index as u32 % columns, index as u32 / columns);
+            Viewport {
+                rect: [
+                    column as f32 * width,
+                    row as f32 * height,
+                    width,
+                    height,
+                ],
+                camera: ViewportCamera::Main,
+                pipeline: Some(index),
+            }
+        })
+        .collect()
+}
+
+// The squarest grid with a cell for each of `n` pipelines, wider than tall
+// when it can't be square
+pub fn grid_size(n: usize) -> (u32, u32) {
+    let columns = (n.max(1) as f32).sqrt().ceil() as u32;
+    (columns, (n.max(1) as u32).div_ceil(columns))
+}
+
+// Refits `view_proj`, made for a target `from_aspect` wide, to one
+// `to_aspect` wide. Both projections scale clip space x by 1 / aspect, so
+// this keeps the vertical field of view (or extent, for an orthographic
+// projection) and widens or narrows the horizontal one.
+pub fn refit_aspect(
+    view_proj: cgmath::Matrix4<f32>,
+    from_aspect: f32,
+    to_aspect: f32,
+) -> cgmath::Matrix4<f32> {
+    cgmath::Matrix4::from_nonuniform_scale(from_aspect / to_aspect, 1.0, 1.0)
+        * view_proj
+}
+
+// A perspective camera like `main` but looking straight down at its target,
+// `aspect` wide. Up on screen is -Z, since looking along -Y it can't be +Y.
+pub fn top_down_camera(main: &Camera, aspect: f32) -> Camera {
+    Camera {
+        eye: main.target + Vector3::new(0.0, TOP_DOWN_HEIGHT, 0.0),
+        target: main.target,
+        up: -Vector3::unit_z(),
+        aspect,
+        ..*main
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn letterbox_centers_the_largest_fitting_rect() {
+        // A square target gets bars above and below
+        let [x, y, width, height] = letterbox(1.0, 2.0);
+        assert_eq!([x, width], [0.0, 1.0]);
+        assert!((y - 0.25).abs() < 1e-6 && (height - 0.5).abs() < 1e-6);
+        // A wide one gets them at the sides
+        let [x, y, width, height] = letterbox(4.0, 2.0);
+        assert_eq!([y, height], [0.0, 1.0]);
+        assert!((x - 0.25).abs() < 1e-6 && (width - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn split_screen_stays_inside_the_letterbox() {
+        let rect = [0.0, 0.25, 1.0, 0.5];
+        let size = PhysicalSize::new(200, 200);
+        let pixels: Vec<_> = split_screen()
+            .into_iter()
+            .map(|viewport| viewport.within(rect).pixel_rect(size))
+            .collect();
+        assert_eq!(pixels, [[0, 50, 100, 100], [100, 50, 100, 100]]);
+    }
+
+    #[test]
+    fn grid_leaves_cells_without_a_pipeline_empty() {
+        let size = PhysicalSize::new(300, 200);
+        let cells: Vec<_> = grid(3, 2, 4)
+            .into_iter()
+            .map(|viewport| (viewport.pipeline, viewport.pixel_rect(size)))
+            .collect();
+        assert_eq!(
+            cells,
+            [
+                (Some(0), [0, 0, 100, 100]),
+                (Some(1), [100, 0, 100, 100]),
+                (Some(2), [200, 0, 100, 100]),
+                (Some(3), [0, 100, 100, 100]),
+            ]
+        );
+        // More pipelines than cells only shows as many as fit
+        assert_eq!(grid(2, 1, 5).len(), 2);
+    }
+
+    #[test]
+    fn grid_size_fits_every_pipeline() {
+        assert_eq!(grid_size(1), (1, 1));
+        assert_eq!(grid_size(4), (2, 2));
+        assert_eq!(grid_size(5), (3, 2));
+        assert_eq!(grid_size(10), (4, 3));
+    }
+}
@@ -0,0 +1,82 @@
+use cgmath::{Matrix4, Point3, Vector4};
+
+use crate::sdf_text::{SdfFont, SdfTextRenderer};
+
+// Em size of a label in pixels of the scene target
+const LABEL_SIZE: f32 = 18.0;
+
+// Text anchored to points in the world. Each label is projected with the
+// camera's view projection once per frame and laid out as an ordinary
+// screen-aligned line of text centered just above its point, so it stays
+// the same size and faces the camera however far away it is.
+//
+// The text renderer is drawn in a pass that shares the scene's depth buffer,
+// which is what lets geometry in front of a label hide it.
+pub struct WorldLabels {
+    text: SdfTextRenderer,
+    labels: Vec<(Point3<f32>, String)>,
+}
+
+impl WorldLabels {
+    pub fn new(text: SdfTextRenderer) -> Self {
+        Self {
+            text,
+            labels: vec![],
+        }
+    }
+
+    pub fn font(&self) -> &SdfFont {
+        self.text.font()
+    }
+
+    // With occlusion off, labels draw over everything in the scene
+    pub fn set_occluded(&mut self, occluded: bool) {
+        self.text.depth_test = occluded;
+    }
+
+    // Queued labels are only kept until the next `prepare`
+    pub fn queue(&mut self, position: Point3<f32>, text: &str) {
+        self.labels.push((position, text.to_string()));
+    }
+
+    pub fn prepare(
+        &mut self,
+        queue: &wgpu::Queue,
+        view_proj: &Matrix4<f32>,
+        screen_size: winit::dpi::PhysicalSize<u32>,
+    ) {
+        let w = screen_size.width as f32;
+        let h = screen_size.height as f32;
+
+        for (position, label) in std::mem::take(&mut self.labels) {
+            let clip = view_proj
+                * Vector4::new(position.x, position.y, position.z, 1.0);
+            // Behind the camera the divide would mirror the label onto the
+            // screen
+            if clip.w <= 0.0 {
+                continue;
+            }
+            let ndc = clip.truncate() / clip.w;
+            // Past the far plane, or in front of the near one
+            if !(0.0..=1.0).contains(&ndc.z) {
+                continue;
+            }
+
+            let x = (ndc.x + 1.0) / 2.0 * w;
+            let y = (1.0 - ndc.y) / 2.0 * h;
+            let width = self.text.measure(&label, LABEL_SIZE);
+            self.text.queue_text_at_depth(
+                &label,
+                [x - width / 2.0, y - LABEL_SIZE * 1.5],
+                LABEL_SIZE,
+                ndc.z,
+            );
+        }
+
+        self.text.prepare(queue, screen_size);
+    }
+
+    pub fn render(&self, render_pass: &mut wgpu::RenderPass) {
+        self.text.render(render_pass);
+    }
+}
@@ -0,0 +1,275 @@
+// Renders the default scene (the g25 textured pentagon) headlessly, without
+// MSAA, and compares it against a committed reference image, to catch
+// pipeline and shader changes that alter the output by accident. The same
+// scene with the default MSAA, as `run_headless` and --screenshot draw it,
+// is held to that reference everywhere but along its edges.
+//
+// When a change is meant to alter the output, regenerate the references with
+//
+//     BLESS=1 cargo test --test golden
+//
+// and commit them along with it.
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use learn_wgpu::HeadlessRenderer;
+
+const WIDTH: u32 = 256;
+const HEIGHT: u32 = 256;
+
+// How far apart a channel may be, out of 255, before the pixel counts as
+// different. Leaves room for rounding differences between adapters.
+const TOLERANCE: u8 = 2;
+
+// The share of pixels that may differ between the MSAA and non-MSAA frames.
+// Only edges should, and they're a small part of the frame, where a frame
+// that failed to draw differs nearly everywhere.
+const MSAA_EDGE_FRACTION: f32 = 0.05;
+
+// How many differing pixels a `Diff` lists by location
+const MAX_REPORTED: usize = 10;
+
+// Where `compare_images` found pixels more than its tolerance apart
+#[derive(Debug)]
+struct Diff {
+    width: u32,
+    height: u32,
+    differing: usize,
+    // The first MAX_REPORTED of them, in row order, as (x, y)
+    locations: Vec<(u32, u32)>,
+    // The largest difference in any one channel
+    max_difference: u8,
+}
+
+impl fmt::Display for Diff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} pixels differ (by up to {}), first at {:?}",
+            self.differing,
+            self.width * self.height,
+            self.max_difference,
+            self.locations
+        )
+    }
+}
+
+// Compares two RGBA8 images pixel by pixel, allowing each channel to be up to
+// `tolerance` apart
+fn compare_images(
+    actual: &image::RgbaImage,
+    expected: &image::RgbaImage,
+    tolerance: u8,
+) -> Result<(), Diff> {
+    let mut diff = Diff {
+        width: expected.width(),
+        height: expected.height(),
+        differing: 0,
+        locations: Vec::new(),
+        max_difference: 0,
+    };
+
+    if actual.dimensions() != expected.dimensions() {
+        // Every pixel differs as far as anyone looking at them is concerned
+        diff.differing = (diff.width * diff.height) as usize;
+        diff.max_difference = u8::MAX;
+        return Err(diff);
+    }
+
+    for (x, y, pixel) in actual.enumerate_pixels() {
+        let difference = pixel
+            .0
+            .iter()
+            .zip(expected.get_pixel(x, y).0)
+            .map(|(a, b)| a.abs_diff(b))
+            .max()
+            .unwrap_or(0);
+        if difference > tolerance {
+            diff.differing += 1;
+            diff.max_difference = diff.max_difference.max(difference);
+            if diff.locations.len() < MAX_REPORTED {
+                diff.locations.push((x, y));
+            }
+        }
+    }
+
+    if diff.differing == 0 {
+        Ok(())
+    } else {
+        Err(diff)
+    }
+}
+
+fn reference_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("golden")
+        .join(format!("{}.png", name))
+}
+
+fn blessing() -> bool {
+    std::env::var("BLESS").is_ok_and(|value| value == "1")
+}
+
+// Compares `actual` against tests/golden/<name>.png, or overwrites it with
+// `actual` when blessing. A failed comparison writes what was rendered next
+// to the reference as <name>.actual.png, for looking at the two side by side.
+fn assert_matches_reference(name: &str, actual: &image::RgbaImage) {
+    let path = reference_path(name);
+    if blessing() {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        actual.save(&path).unwrap();
+        return;
+    }
+
+    let expected = image::open(&path)
+        .unwrap_or_else(|e| {
+            panic!(
+                "Couldn't read {} ({}), run with BLESS=1 to create it",
+                path.display(),
+                e
+            )
+        })
+        .to_rgba8();
+    if let Err(diff) = compare_images(actual, &expected, TOLERANCE) {
+        let actual_path = path.with_extension("actual.png");
+        actual.save(&actual_path).unwrap();
+        panic!(
+            "{} doesn't match its reference: {} (wrote {})",
+            name,
+            diff,
+            actual_path.display()
+        );
+    }
+}
+
+// A WIDTH x HEIGHT frame of the default scene, after `setup` has changed
+// what it needs to and the animation has taken one more step
+fn render(setup: impl FnOnce(&mut HeadlessRenderer)) -> image::RgbaImage {
+    let mut headless = HeadlessRenderer::new(WIDTH, HEIGHT)
+        .expect("No adapter for the golden tests, not even a software one");
+    setup(&mut headless);
+    headless.update();
+    let bytes = headless.render_to_rgba().unwrap();
+    image::RgbaImage::from_raw(WIDTH, HEIGHT, bytes)
+        .expect("The headless frame isn't WIDTH x HEIGHT RGBA8")
+}
+
+#[test]
+fn default_scene_matches_reference() {
+    let actual = render(|headless| headless.set_sample_count(1));
+    assert_matches_reference("default_scene", &actual);
+}
+
+// MSAA's edges depend on where the adapter puts its samples, so rather than
+// having references per adapter this allows them to differ
+#[test]
+fn msaa_scene_matches_reference_away_from_edges() {
+    let mut sample_count = 1;
+    let actual = render(|headless| sample_count = headless.sample_count());
+    if sample_count == 1 {
+        eprintln!("Skipping the MSAA golden test, the adapter has no MSAA");
+        return;
+    }
+    let expected = image::open(reference_path("default_scene"))
+        .expect("Couldn't read the default_scene reference")
+        .to_rgba8();
+    if let Err(diff) = compare_images(&actual, &expected, TOLERANCE) {
+        let allowed = (WIDTH * HEIGHT) as f32 * MSAA_EDGE_FRACTION;
+        if diff.differing as f32 > allowed {
+            let path =
+                reference_path("msaa_scene").with_extension("actual.png");
+            actual.save(&path).unwrap();
+            panic!(
+                "The MSAA frame differs by more than its edges: {} (wrote {})",
+                diff,
+                path.display()
+            );
+        }
+    }
+}
+
+// The same scene with the pentagon's attributes read from several vertex
+// buffers instead of one interleaved one, which shouldn't change a pixel
+#[test]
+fn split_vertex_streams_match_interleaved() {
+    let actual = render(|headless| {
+        headless.set_sample_count(1);
+        headless.set_render_pipeline("split streams");
+    });
+    assert_matches_reference("default_scene", &actual);
+}
+
+// The labels are drawn with the built-in SDF font, so this also catches the
+// font going missing
+#[test]
+fn labels_match_reference() {
+    let actual = render(|headless| {
+        headless.set_sample_count(1);
+        headless.set_show_labels(true);
+    });
+    assert_matches_reference("labels", &actual);
+}
+
+// Paused or in step mode, frames that don't move the animation on are drawn
+// in between its steps. The particles must only move with the steps, and
+// end up where the same steps without those frames leave them.
+#[test]
+fn particles_only_move_with_steps() {
+    // 60 steps, the last taken by `render`
+    let frame = |show_particles: bool, redraws_between_steps: bool| {
+        render(|headless| {
+            headless.set_sample_count(1);
+            headless.set_show_particles(show_particles);
+            for _ in 0..59 {
+                headless.update();
+                if redraws_between_steps {
+                    headless.update_by(0.0);
+                    headless.render_to_rgba().unwrap();
+                }
+            }
+        })
+    };
+
+    let stepped = frame(true, false);
+    if let Err(diff) = compare_images(&frame(true, true), &stepped, 0) {
+        panic!("Frames without a step moved the particles: {}", diff);
+    }
+    assert!(
+        compare_images(&frame(false, false), &stepped, TOLERANCE).is_err(),
+        "No particles were drawn"
+    );
+}
+
+fn solid(width: u32, height: u32, rgba: [u8; 4]) -> image::RgbaImage {
+    image::RgbaImage::from_pixel(width, height, image::Rgba(rgba))
+}
+
+#[test]
+fn differences_within_tolerance_pass() {
+    let expected = solid(4, 4, [100, 100, 100, 255]);
+    let mut actual = expected.clone();
+    actual.put_pixel(1, 2, image::Rgba([102, 98, 100, 255]));
+    assert!(compare_images(&actual, &expected, 2).is_ok());
+}
+
+#[test]
+fn differences_are_counted_and_located() {
+    let expected = solid(4, 4, [100, 100, 100, 255]);
+    let mut actual = expected.clone();
+    actual.put_pixel(3, 0, image::Rgba([110, 100, 100, 255]));
+    actual.put_pixel(1, 2, image::Rgba([100, 100, 100, 0]));
+
+    let diff = compare_images(&actual, &expected, 2).unwrap_err();
+    assert_eq!(diff.differing, 2);
+    assert_eq!(diff.locations, vec![(3, 0), (1, 2)]);
+    assert_eq!(diff.max_difference, 255);
+}
+
+#[test]
+fn different_sizes_never_match() {
+    let expected = solid(4, 4, [0, 0, 0, 255]);
+    let actual = solid(4, 2, [0, 0, 0, 255]);
+    let diff = compare_images(&actual, &expected, u8::MAX).unwrap_err();
+    assert_eq!(diff.differing, 16);
+}